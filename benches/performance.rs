@@ -1,7 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use llm_wrapper::{
-    EnhancedLLMWrapper, EnhancedConfig, Template, CacheManager, TemplateEngine,
-    cache::{CacheKey, CacheConfig, ResponseMetadata},
+    EnhancedConfig, Template, CacheManager, TemplateEngine,
+    cache::{CacheKey, CacheConfig, EvictionPolicy, ResponseMetadata, SemanticCacheConfig},
     template::TemplateConfig,
     streaming::{StreamingManager, ChatRequest, Message},
 };
@@ -30,9 +30,16 @@ fn benchmark_cache_operations(c: &mut Criterion) {
                         cache_dir: None,
                         max_memory_bytes: Some(100 * 1024 * 1024),
                         memory_pressure_threshold: 0.8,
+                        access_decay_half_life: None,
+                        maintenance_interval: Duration::from_secs(60),
+                        sliding_ttl: false,
+                        eviction_policy: EvictionPolicy::Lru,
+                        compress_disk_entries: false,
+                        semantic: SemanticCacheConfig::default(),
+                        negative_ttl: None,
                     };
                     
-                    let mut cache = CacheManager::new(config);
+                    let cache = CacheManager::new(config);
                     
                     // Benchmark cache put and get operations
                     let start = Instant::now();
@@ -47,15 +54,20 @@ fn benchmark_cache_operations(c: &mut Criterion) {
                         let metadata = ResponseMetadata {
                             model: "test_model".to_string(),
                             tokens_used: Some(100),
+                            prompt_tokens: None,
+                            completion_tokens: None,
                             response_time: Duration::from_millis(500),
                             backend_type: "test".to_string(),
+                            backend: None,
+                            tags: Vec::new(),
+                            request_id: None,
                         };
                         
                         cache.put(key.clone(), format!("response_{}", i), metadata).await.unwrap();
                         
                         // Immediately get it back
                         let result = cache.get(&key).await;
-                        black_box(result);
+                        let _ = black_box(result);
                     }
                     
                     start.elapsed()
@@ -101,9 +113,10 @@ fn benchmark_template_rendering(c: &mut Criterion) {
                     max_template_size: 1024 * 1024,
                     max_render_time_ms: 5000,
                     allowed_helpers: vec!["upper".to_string(), "lower".to_string()],
+                    denied_helpers: Vec::new(),
                 };
                 
-                let mut engine = TemplateEngine::new(config);
+                let engine = TemplateEngine::new(config);
                 
                 let template = Template {
                     name: "test_template".to_string(),
@@ -114,6 +127,7 @@ fn benchmark_template_rendering(c: &mut Criterion) {
                     parent_template: None,
                     tags: Vec::new(),
                     usage_examples: Vec::new(),
+                    few_shot: Vec::new(),
                 };
                 
                 engine.register_template(template).unwrap();
@@ -142,7 +156,7 @@ fn benchmark_template_rendering(c: &mut Criterion) {
                 
                 let start = Instant::now();
                 let result = engine.render("test_template", &context);
-                black_box(result);
+                let _ = black_box(result);
                 start.elapsed()
             });
         });
@@ -158,13 +172,11 @@ fn benchmark_streaming_manager(c: &mut Criterion) {
     
     group.bench_function("create_multiple_streams", |b| {
         b.to_async(&rt).iter(|| async {
-            let mut manager = StreamingManager::new(10);
+            let manager = StreamingManager::new(10);
             
             let start = Instant::now();
             
             // Create multiple concurrent streams (mock)
-            let mut handles = Vec::new();
-            
             for i in 0..5 {
                 let request = ChatRequest {
                     model: "test_model".to_string(),
@@ -175,6 +187,8 @@ fn benchmark_streaming_manager(c: &mut Criterion) {
                     }],
                     stream: true,
                     options: None,
+                    response_format: None,
+                    tools: None,
                 };
                 
                 // Note: This would normally create real streams, but for benchmarking
@@ -182,6 +196,7 @@ fn benchmark_streaming_manager(c: &mut Criterion) {
                 black_box(request);
             }
             
+            black_box(manager);
             start.elapsed()
         });
     });
@@ -218,7 +233,7 @@ fn benchmark_end_to_end_workflow(c: &mut Criterion) {
             // Mock response generation
             tokio::time::sleep(Duration::from_micros(200)).await;
             
-            black_box((template_name, variables));
+            black_box((config, template_name, variables));
             start.elapsed()
         });
     });
@@ -239,9 +254,16 @@ fn benchmark_memory_usage(c: &mut Criterion) {
                 cache_dir: None,
                 max_memory_bytes: Some(1024 * 1024), // 1MB limit
                 memory_pressure_threshold: 0.8,
+                access_decay_half_life: None,
+                maintenance_interval: Duration::from_secs(60),
+                sliding_ttl: false,
+                eviction_policy: EvictionPolicy::Lru,
+                compress_disk_entries: false,
+                semantic: SemanticCacheConfig::default(),
+                negative_ttl: None,
             };
             
-            let mut cache = CacheManager::new(config);
+            let cache = CacheManager::new(config);
             
             let start = Instant::now();
             
@@ -258,8 +280,13 @@ fn benchmark_memory_usage(c: &mut Criterion) {
                 let metadata = ResponseMetadata {
                     model: "test_model".to_string(),
                     tokens_used: Some(100),
+                    prompt_tokens: None,
+                    completion_tokens: None,
                     response_time: Duration::from_millis(500),
                     backend_type: "test".to_string(),
+                    backend: None,
+                    tags: Vec::new(),
+                    request_id: None,
                 };
                 
                 cache.put(key, large_response, metadata).await.unwrap();
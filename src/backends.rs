@@ -3,7 +3,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
 
-use crate::streaming::{ChatRequest, StreamResponse};
+use crate::config::ConnectionConfig;
+use crate::streaming::{ChatRequest, Message, ResponseFormat, RetryPolicy, StreamResponse, StreamToken, StreamingManager, TokenKind, ToolCall};
 use crate::error::BackendError;
 
 #[derive(Debug, Error)]
@@ -50,16 +51,99 @@ pub struct ModelCapabilities {
     pub supports_vision: bool,
     pub supports_thinking: bool,
     pub supports_streaming: bool,
+    /// Whether the model turns text into embedding vectors
+    #[serde(default)]
+    pub supports_embeddings: bool,
     pub max_tokens: Option<u32>,
     pub context_length: Option<u32>,
 }
 
+/// Order for `ModelFilter` results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModelSort {
+    #[default]
+    Name,
+    /// Largest first
+    Size,
+    /// Most recently modified first
+    Modified,
+}
+
+impl std::str::FromStr for ModelSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "name" => Ok(Self::Name),
+            "size" => Ok(Self::Size),
+            "modified" | "date" => Ok(Self::Modified),
+            other => Err(format!("Unknown sort key '{}' (expected name, size or modified)", other)),
+        }
+    }
+}
+
+/// Narrows and orders a model list
+#[derive(Debug, Clone, Default)]
+pub struct ModelFilter {
+    /// Case-insensitive substring the name must contain
+    pub name: Option<String>,
+    pub vision_only: bool,
+    pub thinking_only: bool,
+    pub sort: ModelSort,
+}
+
+impl ModelFilter {
+    /// Drop the models that don't match and sort the rest. Models missing the
+    /// size or date being sorted on go last; ties are broken by name.
+    pub fn apply(&self, models: Vec<ModelInfo>) -> Vec<ModelInfo> {
+        let needle = self.name.as_ref().map(|n| n.to_lowercase());
+        let mut models: Vec<ModelInfo> = models
+            .into_iter()
+            .filter(|m| needle.as_ref().is_none_or(|n| m.name.to_lowercase().contains(n)))
+            .filter(|m| !self.vision_only || m.capabilities.supports_vision)
+            .filter(|m| !self.thinking_only || m.capabilities.supports_thinking)
+            .collect();
+
+        match self.sort {
+            ModelSort::Name => models.sort_by(|a, b| a.name.cmp(&b.name)),
+            ModelSort::Size => models.sort_by(|a, b| {
+                b.size.is_some().cmp(&a.size.is_some())
+                    .then(b.size.cmp(&a.size))
+                    .then_with(|| a.name.cmp(&b.name))
+            }),
+            ModelSort::Modified => models.sort_by(|a, b| {
+                b.modified_at.is_some().cmp(&a.modified_at.is_some())
+                    .then(b.modified_at.cmp(&a.modified_at))
+                    .then_with(|| a.name.cmp(&b.name))
+            }),
+        }
+        models
+    }
+}
+
+/// What Ollama's `/api/show` reports about an installed model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelDetails {
+    pub family: Option<String>,
+    /// Human-readable size such as "8.0B"
+    pub parameter_size: Option<String>,
+    pub quantization_level: Option<String>,
+    /// Context window the model was trained with
+    pub context_length: Option<u32>,
+    /// Capabilities the server lists for the model, e.g. "vision"; empty on
+    /// servers too old to report them
+    pub capabilities: Vec<String>,
+    /// Prompt template from the Modelfile
+    pub template: Option<String>,
+}
+
 impl Default for ModelCapabilities {
     fn default() -> Self {
         Self {
             supports_vision: false,
             supports_thinking: false,
             supports_streaming: true,
+            supports_embeddings: false,
             max_tokens: Some(4096),
             context_length: Some(4096),
         }
@@ -71,6 +155,16 @@ pub trait Backend: Send + Sync {
     /// Send a chat request and get a complete response
     async fn chat(&self, request: ChatRequest) -> Result<String, BackendError>;
     
+    /// Send a chat request and get the response along with token usage, when
+    /// the backend reports it
+    async fn chat_with_usage(&self, request: ChatRequest) -> Result<ChatCompletion, BackendError> {
+        Ok(ChatCompletion {
+            content: self.chat(request).await?,
+            usage: None,
+            tool_calls: Vec::new(),
+        })
+    }
+    
     /// Send a chat request and get a streaming response
     async fn chat_stream(&self, request: ChatRequest) -> Result<StreamResponse, BackendError>;
     
@@ -88,6 +182,346 @@ pub trait Backend: Send + Sync {
     
     /// Health check
     async fn health_check(&self) -> Result<(), BackendError>;
+    
+    /// Download a model so it becomes available for chat
+    async fn pull_model(&self, _name: &str) -> Result<(), BackendError> {
+        Err(BackendError::Unsupported(format!("{} backend cannot pull models", self.backend_type())))
+    }
+    
+    /// Remove a locally installed model
+    async fn delete_model(&self, _name: &str) -> Result<(), BackendError> {
+        Err(BackendError::Unsupported(format!("{} backend cannot delete models", self.backend_type())))
+    }
+    
+    /// Compute one embedding vector per input string
+    async fn embed(&self, _model: &str, _input: &[String]) -> Result<Vec<Vec<f32>>, BackendError> {
+        Err(BackendError::InvalidResponse)
+    }
+    
+    /// State of the circuit breaker guarding this backend; unguarded backends are always closed
+    fn circuit_state(&self) -> CircuitState {
+        CircuitState::Closed
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Requests flow normally
+    Closed,
+    /// The backend is failing; requests fail fast until the cooldown passes
+    Open,
+    /// The cooldown has passed; the next request probes whether the backend recovered
+    HalfOpen,
+}
+
+impl std::fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open => write!(f, "open"),
+            CircuitState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+/// Counts consecutive connection failures and trips open after `failure_threshold`
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: std::time::Duration,
+    inner: std::sync::Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<std::time::Instant>,
+    probe_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: std::time::Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            inner: std::sync::Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let inner = self.inner.lock().unwrap();
+        match inner.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.cooldown => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Admit a request, or fail fast while open. Half-open admits a single probe.
+    pub fn try_acquire(&self) -> Result<BreakerPermit<'_>, BackendError> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(opened_at) = inner.opened_at else {
+            return Ok(BreakerPermit { breaker: self, probe: false });
+        };
+
+        if opened_at.elapsed() < self.cooldown || inner.probe_in_flight {
+            return Err(BackendError::Connection(format!(
+                "circuit breaker open after {} consecutive failures",
+                inner.consecutive_failures
+            )));
+        }
+
+        inner.probe_in_flight = true;
+        Ok(BreakerPermit { breaker: self, probe: true })
+    }
+
+    pub fn record_success(&self) {
+        *self.inner.lock().unwrap() = BreakerState::default();
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures += 1;
+        // A failed probe re-opens the circuit for another cooldown
+        if inner.probe_in_flight || inner.consecutive_failures >= self.failure_threshold {
+            inner.opened_at = Some(std::time::Instant::now());
+        }
+        inner.probe_in_flight = false;
+    }
+
+    /// Feed a call's outcome into the breaker. Only errors suggesting the
+    /// backend is unreachable count; any other reply proves it is alive.
+    fn observe<T>(&self, result: &Result<T, BackendError>) {
+        match result {
+            Err(e) if e.is_unreachable() => self.record_failure(),
+            _ => self.record_success(),
+        }
+    }
+}
+
+/// A request admitted by `CircuitBreaker::try_acquire`. A half-open probe
+/// dropped before its outcome is observed, say because the caller's future
+/// was cancelled, frees the probe slot for the next request.
+#[must_use]
+pub struct BreakerPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl BreakerPermit<'_> {
+    /// Record the admitted call's outcome
+    pub fn observe<T>(mut self, result: &Result<T, BackendError>) {
+        self.probe = false;
+        self.breaker.observe(result);
+    }
+}
+
+impl Drop for BreakerPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.inner.lock().unwrap().probe_in_flight = false;
+        }
+    }
+}
+
+/// Wraps any backend with a circuit breaker
+pub struct CircuitBreakerBackend {
+    inner: Box<dyn Backend>,
+    breaker: CircuitBreaker,
+}
+
+impl CircuitBreakerBackend {
+    pub fn new(inner: Box<dyn Backend>, breaker: CircuitBreaker) -> Self {
+        Self { inner, breaker }
+    }
+}
+
+#[async_trait]
+impl Backend for CircuitBreakerBackend {
+    async fn chat(&self, request: ChatRequest) -> Result<String, BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.chat(request).await;
+        permit.observe(&result);
+        result
+    }
+
+    async fn chat_with_usage(&self, request: ChatRequest) -> Result<ChatCompletion, BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.chat_with_usage(request).await;
+        permit.observe(&result);
+        result
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> Result<StreamResponse, BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.chat_stream(request).await;
+        permit.observe(&result);
+        result
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.list_models().await;
+        permit.observe(&result);
+        result
+    }
+
+    async fn get_model_capabilities(&self, model_name: &str) -> Result<ModelCapabilities, BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.get_model_capabilities(model_name).await;
+        permit.observe(&result);
+        result
+    }
+
+    fn capabilities(&self) -> &BackendCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn backend_type(&self) -> BackendType {
+        self.inner.backend_type()
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.health_check().await;
+        permit.observe(&result);
+        result
+    }
+
+    async fn pull_model(&self, name: &str) -> Result<(), BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.pull_model(name).await;
+        permit.observe(&result);
+        result
+    }
+
+    async fn delete_model(&self, name: &str) -> Result<(), BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.delete_model(name).await;
+        permit.observe(&result);
+        result
+    }
+
+    async fn embed(&self, model: &str, input: &[String]) -> Result<Vec<Vec<f32>>, BackendError> {
+        let permit = self.breaker.try_acquire()?;
+        let result = self.inner.embed(model, input).await;
+        permit.observe(&result);
+        result
+    }
+
+    fn circuit_state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+}
+
+/// Wraps any backend, retrying chat requests that could not reach it with
+/// the jittered, capped backoff of its `RetryPolicy`
+pub struct RetryBackend {
+    inner: Box<dyn Backend>,
+    policy: RetryPolicy,
+}
+
+impl RetryBackend {
+    pub fn new(inner: Box<dyn Backend>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    async fn retry<T, F, Fut>(&self, mut call: F) -> Result<T, BackendError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, BackendError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match call().await {
+                Err(e) if e.is_unreachable() && attempt < self.policy.max_retries => {
+                    tracing::debug!(attempt = attempt + 1, error = %e, "Retrying backend request");
+                    tokio::time::sleep(self.policy.delay_for(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for RetryBackend {
+    async fn chat(&self, request: ChatRequest) -> Result<String, BackendError> {
+        self.retry(|| self.inner.chat(request.clone())).await
+    }
+
+    async fn chat_with_usage(&self, request: ChatRequest) -> Result<ChatCompletion, BackendError> {
+        self.retry(|| self.inner.chat_with_usage(request.clone())).await
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> Result<StreamResponse, BackendError> {
+        self.retry(|| self.inner.chat_stream(request.clone())).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, BackendError> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model_capabilities(&self, model_name: &str) -> Result<ModelCapabilities, BackendError> {
+        self.inner.get_model_capabilities(model_name).await
+    }
+
+    fn capabilities(&self) -> &BackendCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn backend_type(&self) -> BackendType {
+        self.inner.backend_type()
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        self.inner.health_check().await
+    }
+
+    async fn pull_model(&self, name: &str) -> Result<(), BackendError> {
+        self.inner.pull_model(name).await
+    }
+
+    async fn delete_model(&self, name: &str) -> Result<(), BackendError> {
+        self.inner.delete_model(name).await
+    }
+
+    async fn embed(&self, model: &str, input: &[String]) -> Result<Vec<Vec<f32>>, BackendError> {
+        self.inner.embed(model, input).await
+    }
+
+    fn circuit_state(&self) -> CircuitState {
+        self.inner.circuit_state()
+    }
+}
+
+/// Token counts reported by a backend for a single request
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    /// Time spent generating the completion
+    #[serde(default, with = "humantime_serde")]
+    pub eval_duration: Option<std::time::Duration>,
+}
+
+impl TokenUsage {
+    pub fn total_tokens(&self) -> Option<u32> {
+        match (self.prompt_tokens, self.completion_tokens) {
+            (None, None) => None,
+            (prompt, completion) => Some(prompt.unwrap_or(0) + completion.unwrap_or(0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatCompletion {
+    pub content: String,
+    pub usage: Option<TokenUsage>,
+    /// Calls the model made to the request's `tools`, if any
+    pub tool_calls: Vec<ToolCall>,
 }
 
 #[derive(Debug, Clone)]
@@ -109,123 +543,641 @@ impl Default for BackendCapabilities {
     }
 }
 
+/// The error for a non-2xx reply. Only a gateway or overload status says
+/// the model server can't be reached; any other reply proves it is up.
+fn status_error(status: reqwest::StatusCode, model: Option<&str>) -> BackendError {
+    match (status, model) {
+        (reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN, _) => BackendError::Authentication,
+        (reqwest::StatusCode::TOO_MANY_REQUESTS, _) => BackendError::RateLimit,
+        (reqwest::StatusCode::NOT_FOUND, Some(model)) => BackendError::ModelNotFound(model.to_string()),
+        (
+            reqwest::StatusCode::BAD_GATEWAY
+            | reqwest::StatusCode::SERVICE_UNAVAILABLE
+            | reqwest::StatusCode::GATEWAY_TIMEOUT,
+            _,
+        ) => BackendError::Connection(format!("HTTP error: {}", status)),
+        _ => {
+            tracing::warn!(%status, "Backend replied with an HTTP error");
+            BackendError::InvalidResponse
+        }
+    }
+}
+
 /// Ollama backend implementation
 pub struct OllamaBackend {
     client: reqwest::Client,
     base_url: String,
     capabilities: BackendCapabilities,
-    streaming_manager: crate::streaming::StreamingManager,
 }
 
 impl OllamaBackend {
+    /// Extract the content and token counts from a non-streaming `/api/chat` body
+    fn parse_chat_response(body: &serde_json::Value) -> Result<ChatCompletion, BackendError> {
+        let content = body.get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_str())
+            .ok_or(BackendError::InvalidResponse)?;
+
+        let count = |field: &str| body.get(field).and_then(|v| v.as_u64()).map(|v| v as u32);
+        let usage = TokenUsage {
+            prompt_tokens: count("prompt_eval_count"),
+            completion_tokens: count("eval_count"),
+            // Ollama reports durations in nanoseconds
+            eval_duration: body.get("eval_duration")
+                .and_then(|v| v.as_u64())
+                .map(std::time::Duration::from_nanos),
+        };
+
+        // Ollama sends arguments as an object, but accept a JSON string as OpenAI does
+        let tool_calls = body.get("message")
+            .and_then(|m| m.get("tool_calls"))
+            .and_then(|calls| calls.as_array())
+            .map(|calls| calls.iter().map(Self::parse_tool_call).collect::<Result<Vec<_>, _>>())
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(ChatCompletion {
+            content: content.to_string(),
+            usage: Some(usage),
+            tool_calls,
+        })
+    }
+
+    fn parse_tool_call(call: &serde_json::Value) -> Result<ToolCall, BackendError> {
+        let function = call.get("function").ok_or(BackendError::InvalidResponse)?;
+        let name = function.get("name")
+            .and_then(|n| n.as_str())
+            .ok_or(BackendError::InvalidResponse)?;
+        let arguments = match function.get("arguments") {
+            Some(serde_json::Value::String(raw)) => serde_json::from_str(raw).map_err(|_| BackendError::InvalidResponse)?,
+            Some(arguments) => arguments.clone(),
+            None => serde_json::json!({}),
+        };
+        Ok(ToolCall { name: name.to_string(), arguments })
+    }
+
+    /// Extract model details from an `/api/show` body. The context length
+    /// lives under an architecture-specific key such as `llama.context_length`.
+    pub fn parse_show_response(body: &serde_json::Value) -> ModelDetails {
+        let details = body.get("details");
+        let detail = |field: &str| details
+            .and_then(|d| d.get(field))
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string);
+
+        let context_length = body.get("model_info")
+            .and_then(|info| info.as_object())
+            .and_then(|info| info.iter().find(|(key, _)| key.ends_with(".context_length")))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|length| length as u32);
+
+        ModelDetails {
+            family: detail("family"),
+            parameter_size: detail("parameter_size"),
+            quantization_level: detail("quantization_level"),
+            context_length,
+            capabilities: body.get("capabilities")
+                .and_then(|c| c.as_array())
+                .map(|c| c.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+                .unwrap_or_default(),
+            template: body.get("template").and_then(|t| t.as_str()).map(str::to_string),
+        }
+    }
+
+    /// Fetch a model's details from `/api/show`
+    pub async fn show_model(&self, name: &str) -> Result<ModelDetails, BackendError> {
+        let url = format!("{}/api/show", self.base_url);
+        let response = self.client
+            .post(&url)
+            .json(&serde_json::json!({ "model": name }))
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(Self::parse_show_response(&response.json().await?)),
+            status => Err(status_error(status, Some(name))),
+        }
+    }
+
     pub fn new(base_url: String) -> Result<Self, BackendInitError> {
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
+        Self::with_config(base_url, std::time::Duration::from_secs(30), &ConnectionConfig::default())
+    }
+
+    pub fn with_config(
+        base_url: String,
+        timeout: std::time::Duration,
+        connection: &ConnectionConfig,
+    ) -> Result<Self, BackendInitError> {
+        let client = connection
+            .build_client(Some(timeout))
             .map_err(|e| BackendInitError::Connection(e.to_string()))?;
+        Ok(Self::with_client(base_url, client))
+    }
 
-        let streaming_manager = crate::streaming::StreamingManager::new(10);
+    /// A backend using everything in its config section, headers included
+    pub fn from_config(config: &crate::config::BackendConfig) -> Result<Self, BackendInitError> {
+        Ok(Self::with_client(config.base_url.clone(), config.build_client()?))
+    }
 
-        Ok(Self {
+    fn with_client(base_url: String, client: reqwest::Client) -> Self {
+        Self {
             client,
             base_url: base_url.trim_end_matches('/').to_string(),
             capabilities: BackendCapabilities::default(),
-            streaming_manager,
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for OllamaBackend {
+    async fn chat(&self, request: ChatRequest) -> Result<String, BackendError> {
+        Ok(self.chat_with_usage(request).await?.content)
+    }
+
+    async fn chat_with_usage(&self, request: ChatRequest) -> Result<ChatCompletion, BackendError> {
+        let url = format!("{}/api/chat", self.base_url);
+        
+        // Convert to non-streaming request
+        let mut ollama_request = request;
+        ollama_request.stream = false;
+        
+        let response = self.client
+            .post(&url)
+            .json(&ollama_request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(status_error(response.status(), Some(&ollama_request.model)));
+        }
+
+        let chat_response: serde_json::Value = response.json().await?;
+        Self::parse_chat_response(&chat_response)
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> Result<StreamResponse, BackendError> {
+        let url = format!("{}/api/chat", self.base_url);
+
+        let mut ollama_request = request;
+        ollama_request.stream = true;
+
+        let response = self.client
+            .post(&url)
+            .json(&ollama_request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(status_error(response.status(), Some(&ollama_request.model)));
+        }
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let cancellation_token = tokio_util::sync::CancellationToken::new();
+        let stop_sequences = StreamingManager::stop_sequences(&ollama_request);
+        let token = cancellation_token.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = StreamingManager::forward_stream(response.bytes_stream(), stop_sequences, sender, token).await {
+                tracing::warn!(error = %e, "Ollama stream ended with an error");
+            }
+        });
+
+        Ok(StreamResponse {
+            id: rand::random(),
+            receiver,
+            cancellation_token,
         })
     }
 
-    async fn detect_capabilities(&mut self) -> Result<(), BackendError> {
-        // Try to get model list to verify connection
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, BackendError> {
+        let url = format!("{}/api/tags", self.base_url);
+        let response = self.client.get(&url).send().await?;
+
+        if !response.status().is_success() {
+            return Err(status_error(response.status(), None));
+        }
+
+        let models_response: serde_json::Value = response.json().await?;
+        Ok(self.parse_tags_response(&models_response))
+    }
+
+    async fn get_model_capabilities(&self, model_name: &str) -> Result<ModelCapabilities, BackendError> {
+        let mut capabilities = self.detect_model_capabilities(model_name);
+        match self.show_model(model_name).await {
+            Ok(details) => {
+                capabilities.context_length = details.context_length;
+                // Newer servers say outright what the model can do
+                if !details.capabilities.is_empty() {
+                    capabilities.supports_vision = details.capabilities.iter().any(|c| c == "vision");
+                    capabilities.supports_thinking = details.capabilities.iter().any(|c| c == "thinking");
+                    capabilities.supports_embeddings = details.capabilities.iter().any(|c| c == "embedding");
+                }
+            }
+            Err(e) => tracing::debug!(model = model_name, error = %e, "Falling back to name-based capabilities"),
+        }
+        Ok(capabilities)
+    }
+
+    fn capabilities(&self) -> &BackendCapabilities {
+        &self.capabilities
+    }
+
+    fn backend_type(&self) -> BackendType {
+        BackendType::Ollama
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
         let url = format!("{}/api/tags", self.base_url);
         let response = self.client.get(&url).send().await?;
         
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(status_error(response.status(), None))
+        }
+    }
+
+    async fn pull_model(&self, name: &str) -> Result<(), BackendError> {
+        let url = format!("{}/api/pull", self.base_url);
+
+        // Without streaming, Ollama replies once the download has finished
+        let response = self.client
+            .post(&url)
+            .json(&serde_json::json!({ "name": name, "stream": false }))
+            .timeout(std::time::Duration::from_secs(60 * 60))
+            .send()
+            .await?;
+
         if !response.status().is_success() {
-            return Err(BackendError::Connection(format!(
-                "Failed to connect to Ollama: {}",
-                response.status()
-            )));
+            return Err(status_error(response.status(), Some(name)));
+        }
+
+        let body: serde_json::Value = response.json().await?;
+        match body.get("error").and_then(|e| e.as_str()) {
+            Some(error) => Err(BackendError::Connection(format!("Failed to pull model: {}", error))),
+            None => Ok(()),
         }
+    }
+
+    async fn delete_model(&self, name: &str) -> Result<(), BackendError> {
+        let url = format!("{}/api/delete", self.base_url);
+        let response = self.client
+            .delete(&url)
+            .json(&serde_json::json!({ "name": name }))
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(status_error(status, Some(name))),
+        }
+    }
+
+    async fn embed(&self, model: &str, input: &[String]) -> Result<Vec<Vec<f32>>, BackendError> {
+        let url = format!("{}/api/embeddings", self.base_url);
+        let mut embeddings = Vec::with_capacity(input.len());
+
+        // The embeddings endpoint takes a single prompt per request
+        for prompt in input {
+            let response = self.client
+                .post(&url)
+                .json(&Self::embedding_request(model, prompt))
+                .send()
+                .await?;
 
-        // Ollama supports streaming by default
-        self.capabilities.supports_streaming = true;
+            if !response.status().is_success() {
+                return Err(status_error(response.status(), Some(model)));
+            }
+
+            let body: serde_json::Value = response.json().await?;
+            let embedding = body.get("embedding")
+                .and_then(|e| e.as_array())
+                .ok_or(BackendError::InvalidResponse)?
+                .iter()
+                .map(|v| v.as_f64().map(|f| f as f32).ok_or(BackendError::InvalidResponse))
+                .collect::<Result<Vec<f32>, _>>()?;
+
+            embeddings.push(embedding);
+        }
+
+        Ok(embeddings)
+    }
+}
+
+impl OllamaBackend {
+    fn embedding_request(model: &str, prompt: &str) -> serde_json::Value {
+        serde_json::json!({
+            "model": model,
+            "prompt": prompt,
+        })
+    }
+
+    /// Models listed in an `/api/tags` body
+    fn parse_tags_response(&self, body: &serde_json::Value) -> Vec<ModelInfo> {
+        let Some(models_array) = body.get("models").and_then(|m| m.as_array()) else {
+            return Vec::new();
+        };
+
+        models_array
+            .iter()
+            .filter_map(|model| {
+                let name = model.get("name")?.as_str()?.to_string();
+                let size = model.get("size").and_then(|s| s.as_u64());
+                // RFC 3339 with a local offset, e.g. "2024-05-01T12:00:00.1234-07:00"
+                let modified_at = model.get("modified_at")
+                    .and_then(|m| m.as_str())
+                    .and_then(|m| chrono::DateTime::parse_from_rfc3339(m).ok())
+                    .map(|m| m.with_timezone(&chrono::Utc));
+
+                Some(ModelInfo {
+                    capabilities: self.detect_model_capabilities(&name),
+                    name,
+                    size,
+                    modified_at,
+                })
+            })
+            .collect()
+    }
+
+    fn detect_model_capabilities(&self, model_name: &str) -> ModelCapabilities {
+        let model_lower = model_name.to_lowercase();
         
-        Ok(())
+        let supports_vision = model_lower.contains("llava") 
+            || model_lower.contains("vision")
+            || model_lower.contains("bakllava")
+            || model_lower.contains("moondream");
+            
+        let supports_thinking = model_lower.contains("o1")
+            || model_lower.contains("reasoning")
+            || model_lower.contains("thinking");
+
+        let supports_embeddings = model_lower.contains("embed")
+            || model_lower.contains("minilm");
+
+        ModelCapabilities {
+            supports_vision,
+            supports_thinking,
+            supports_streaming: true, // Ollama supports streaming for all models
+            supports_embeddings,
+            max_tokens: Some(4096),
+            // Only known once /api/show has been asked
+            context_length: None,
+        }
     }
 }
 
-#[async_trait]
-impl Backend for OllamaBackend {
-    async fn chat(&self, request: ChatRequest) -> Result<String, BackendError> {
-        let url = format!("{}/api/chat", self.base_url);
-        
-        // Convert to non-streaming request
-        let mut ollama_request = request;
-        ollama_request.stream = false;
-        
+/// OpenAI-compatible backend using the `/v1/chat/completions` API
+pub struct OpenAIBackend {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    capabilities: BackendCapabilities,
+}
+
+impl OpenAIBackend {
+    pub fn new(base_url: String, api_key: Option<String>) -> Result<Self, BackendInitError> {
+        Self::with_config(base_url, api_key, std::time::Duration::from_secs(60), &ConnectionConfig::default())
+    }
+
+    pub fn with_config(
+        base_url: String,
+        api_key: Option<String>,
+        timeout: std::time::Duration,
+        connection: &ConnectionConfig,
+    ) -> Result<Self, BackendInitError> {
+        let client = connection
+            .build_client(Some(timeout))
+            .map_err(|e| BackendInitError::Connection(e.to_string()))?;
+        Self::with_client(base_url, api_key, client)
+    }
+
+    /// A backend using everything in its config section, headers included
+    pub fn from_config(config: &crate::config::BackendConfig) -> Result<Self, BackendInitError> {
+        Self::with_client(config.base_url.clone(), config.api_key.clone(), config.build_client()?)
+    }
+
+    fn with_client(base_url: String, api_key: Option<String>, client: reqwest::Client) -> Result<Self, BackendInitError> {
+        let api_key = api_key
+            .filter(|key| !key.trim().is_empty())
+            .ok_or(BackendInitError::Authentication)?;
+
+        // Accept base URLs both with and without the trailing /v1
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v1").unwrap_or(base_url).to_string();
+
+        Ok(Self {
+            client,
+            base_url,
+            api_key,
+            capabilities: BackendCapabilities::default(),
+        })
+    }
+
+    /// The `/v1/chat/completions` body for `request`
+    fn request_body(request: &ChatRequest, stream: bool) -> serde_json::Value {
+        let messages: Vec<serde_json::Value> = request.messages.iter().map(Self::message_json).collect();
+
+        let mut body = serde_json::json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": stream,
+        });
+
+        // OpenAI takes sampling parameters at the top level, under its own names
+        if let Some(options) = &request.options {
+            for (ours, theirs) in [
+                ("temperature", "temperature"),
+                ("top_p", "top_p"),
+                ("num_predict", "max_tokens"),
+                ("seed", "seed"),
+                ("stop", "stop"),
+            ] {
+                if let Some(value) = options.get(ours) {
+                    body[theirs] = value.clone();
+                }
+            }
+        }
+
+        match &request.response_format {
+            Some(ResponseFormat::Json) => {
+                body["response_format"] = serde_json::json!({ "type": "json_object" });
+            }
+            Some(ResponseFormat::Schema(schema)) => {
+                body["response_format"] = serde_json::json!({
+                    "type": "json_schema",
+                    "json_schema": { "name": "response", "schema": schema },
+                });
+            }
+            None => {}
+        }
+
+        body
+    }
+
+    /// A message with images becomes a list of content parts, each image as a
+    /// base64 data URL
+    fn message_json(message: &Message) -> serde_json::Value {
+        let Some(images) = message.images.as_ref().filter(|images| !images.is_empty()) else {
+            return serde_json::json!({ "role": message.role, "content": message.content });
+        };
+
+        let mut parts = vec![serde_json::json!({ "type": "text", "text": message.content })];
+        parts.extend(images.iter().map(|image| {
+            serde_json::json!({
+                "type": "image_url",
+                "image_url": { "url": format!("data:{};base64,{}", Self::image_mime_type(image), image) },
+            })
+        }));
+        serde_json::json!({ "role": message.role, "content": parts })
+    }
+
+    /// Sniff the image format from the first bytes of its base64 encoding
+    fn image_mime_type(image: &str) -> &'static str {
+        if image.starts_with("iVBORw0KGgo") {
+            "image/png"
+        } else if image.starts_with("R0lGOD") {
+            "image/gif"
+        } else if image.starts_with("UklGR") {
+            "image/webp"
+        } else {
+            "image/jpeg"
+        }
+    }
+
+    fn detect_model_capabilities(&self, model_name: &str) -> ModelCapabilities {
+        let model_lower = model_name.to_lowercase();
+
+        ModelCapabilities {
+            supports_vision: model_lower.contains("gpt-4o") || model_lower.contains("vision"),
+            supports_thinking: model_lower.starts_with("o1") || model_lower.starts_with("o3"),
+            supports_streaming: true,
+            // Embeddings aren't requested over the OpenAI API yet
+            supports_embeddings: false,
+            max_tokens: Some(4096),
+            context_length: Some(128_000),
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for OpenAIBackend {
+    async fn chat(&self, request: ChatRequest) -> Result<String, BackendError> {
+        Ok(self.chat_with_usage(request).await?.content)
+    }
+
+    async fn chat_with_usage(&self, request: ChatRequest) -> Result<ChatCompletion, BackendError> {
+        if request.tools.is_some() {
+            return Err(BackendError::Unsupported("tool calling on the OpenAI backend".to_string()));
+        }
+        let url = format!("{}/v1/chat/completions", self.base_url);
+        let body = Self::request_body(&request, false);
+
+        let response = self.client
+            .post(&url)
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(status_error(response.status(), Some(&request.model)));
+        }
+
+        let chat_response: serde_json::Value = response.json().await?;
+
+        let content = chat_response.get("choices")
+            .and_then(|c| c.get(0))
+            .and_then(|c| c.get("message"))
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_str())
+            .ok_or(BackendError::InvalidResponse)?;
+
+        let usage = chat_response.get("usage").map(|usage| {
+            let count = |field: &str| usage.get(field).and_then(|v| v.as_u64()).map(|v| v as u32);
+            TokenUsage {
+                prompt_tokens: count("prompt_tokens"),
+                completion_tokens: count("completion_tokens"),
+                eval_duration: None,
+            }
+        });
+
+        Ok(ChatCompletion {
+            content: content.to_string(),
+            usage,
+            tool_calls: Vec::new(),
+        })
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> Result<StreamResponse, BackendError> {
+        if request.tools.is_some() {
+            return Err(BackendError::Unsupported("tool calling on the OpenAI backend".to_string()));
+        }
+        let url = format!("{}/v1/chat/completions", self.base_url);
+        let body = Self::request_body(&request, true);
+
         let response = self.client
             .post(&url)
-            .json(&ollama_request)
+            .bearer_auth(&self.api_key)
+            .json(&body)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            return Err(BackendError::Connection(format!(
-                "HTTP error: {}",
-                response.status()
-            )));
+            return Err(status_error(response.status(), Some(&request.model)));
         }
 
-        let chat_response: serde_json::Value = response.json().await?;
-        
-        if let Some(content) = chat_response.get("message")
-            .and_then(|m| m.get("content"))
-            .and_then(|c| c.as_str()) 
-        {
-            Ok(content.to_string())
-        } else {
-            Err(BackendError::InvalidResponse)
-        }
-    }
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let cancellation_token = tokio_util::sync::CancellationToken::new();
+        let stop_sequences = StreamingManager::stop_sequences(&request);
+        let token = cancellation_token.clone();
 
-    async fn chat_stream(&self, _request: ChatRequest) -> Result<StreamResponse, BackendError> {
-        // This would need to be implemented with proper streaming manager integration
-        // For now, return an error indicating it's not implemented
-        Err(BackendError::Connection("Streaming not yet integrated".to_string()))
+        tokio::spawn(async move {
+            if let Err(e) = StreamingManager::forward_sse_stream(response.bytes_stream(), stop_sequences, sender, token).await {
+                tracing::warn!(error = %e, "OpenAI stream ended with an error");
+            }
+        });
+
+        Ok(StreamResponse {
+            id: rand::random(),
+            receiver,
+            cancellation_token,
+        })
     }
 
     async fn list_models(&self) -> Result<Vec<ModelInfo>, BackendError> {
-        let url = format!("{}/api/tags", self.base_url);
-        let response = self.client.get(&url).send().await?;
+        let url = format!("{}/v1/models", self.base_url);
+        let response = self.client.get(&url).bearer_auth(&self.api_key).send().await?;
 
         if !response.status().is_success() {
-            return Err(BackendError::Connection(format!(
-                "HTTP error: {}",
-                response.status()
-            )));
+            return Err(status_error(response.status(), None));
         }
 
         let models_response: serde_json::Value = response.json().await?;
-        
-        if let Some(models_array) = models_response.get("models").and_then(|m| m.as_array()) {
-            let models = models_array
-                .iter()
-                .filter_map(|model| {
-                    let name = model.get("name")?.as_str()?.to_string();
-                    let size = model.get("size").and_then(|s| s.as_u64());
-                    
-                    Some(ModelInfo {
-                        name: name.clone(),
-                        size,
-                        modified_at: None, // Ollama doesn't provide this in a standard format
-                        capabilities: self.detect_model_capabilities(&name),
+
+        let models = models_response.get("data")
+            .and_then(|d| d.as_array())
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|model| {
+                        let name = model.get("id")?.as_str()?.to_string();
+                        let modified_at = model.get("created")
+                            .and_then(|c| c.as_i64())
+                            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0));
+
+                        Some(ModelInfo {
+                            capabilities: self.detect_model_capabilities(&name),
+                            name,
+                            size: None,
+                            modified_at,
+                        })
                     })
-                })
-                .collect();
-            
-            Ok(models)
-        } else {
-            Ok(Vec::new())
-        }
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(models)
     }
 
     async fn get_model_capabilities(&self, model_name: &str) -> Result<ModelCapabilities, BackendError> {
@@ -237,51 +1189,42 @@ impl Backend for OllamaBackend {
     }
 
     fn backend_type(&self) -> BackendType {
-        BackendType::Ollama
+        BackendType::OpenAI
     }
 
     async fn health_check(&self) -> Result<(), BackendError> {
-        let url = format!("{}/api/tags", self.base_url);
-        let response = self.client.get(&url).send().await?;
-        
+        let url = format!("{}/v1/models", self.base_url);
+        let response = self.client.get(&url).bearer_auth(&self.api_key).send().await?;
+
         if response.status().is_success() {
             Ok(())
         } else {
-            Err(BackendError::Connection(format!(
-                "Health check failed: {}",
-                response.status()
-            )))
+            Err(status_error(response.status(), None))
         }
     }
 }
 
-impl OllamaBackend {
-    fn detect_model_capabilities(&self, model_name: &str) -> ModelCapabilities {
-        let model_lower = model_name.to_lowercase();
-        
-        let supports_vision = model_lower.contains("llava") 
-            || model_lower.contains("vision")
-            || model_lower.contains("bakllava")
-            || model_lower.contains("moondream");
-            
-        let supports_thinking = model_lower.contains("o1")
-            || model_lower.contains("reasoning")
-            || model_lower.contains("thinking");
-
-        ModelCapabilities {
-            supports_vision,
-            supports_thinking,
-            supports_streaming: true, // Ollama supports streaming for all models
-            max_tokens: Some(4096),
-            context_length: Some(4096),
-        }
-    }
-}
+/// Builds the error an injected `MockBackend` failure returns
+type MockErrorFactory = Box<dyn Fn() -> BackendError + Send + Sync>;
 
 /// Mock backend for testing
 pub struct MockBackend {
     capabilities: BackendCapabilities,
     responses: HashMap<String, String>,
+    stream_responses: HashMap<String, Vec<String>>,
+    token_delay: std::time::Duration,
+    latency: std::time::Duration,
+    failures_remaining: std::sync::atomic::AtomicUsize,
+    failure: Option<MockErrorFactory>,
+    calls: std::sync::atomic::AtomicUsize,
+    pulled_models: std::sync::Mutex<Vec<String>>,
+    deleted_models: std::sync::Mutex<Vec<String>>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MockBackend {
@@ -289,31 +1232,158 @@ impl MockBackend {
         Self {
             capabilities: BackendCapabilities::default(),
             responses: HashMap::new(),
+            stream_responses: HashMap::new(),
+            token_delay: std::time::Duration::ZERO,
+            latency: std::time::Duration::ZERO,
+            failures_remaining: std::sync::atomic::AtomicUsize::new(0),
+            failure: None,
+            calls: std::sync::atomic::AtomicUsize::new(0),
+            pulled_models: std::sync::Mutex::new(Vec::new()),
+            deleted_models: std::sync::Mutex::new(Vec::new()),
         }
     }
 
     pub fn add_response(&mut self, prompt: String, response: String) {
         self.responses.insert(prompt, response);
     }
+
+    /// Stream `tokens` one at a time when `chat_stream` is asked `prompt`
+    pub fn add_stream_response(&mut self, prompt: String, tokens: Vec<String>) {
+        self.stream_responses.insert(prompt, tokens);
+    }
+
+    /// Pause between streamed tokens, to give cancellation something to interrupt
+    pub fn set_token_delay(&mut self, delay: std::time::Duration) {
+        self.token_delay = delay;
+    }
+
+    /// Delay every chat and stream call by `latency` before it answers
+    pub fn set_latency(&mut self, latency: std::time::Duration) {
+        self.latency = latency;
+    }
+
+    /// Fail the next `calls` chat or stream calls as if the server were down
+    pub fn fail_next(&mut self, calls: usize) {
+        self.fail_next_with(calls, || BackendError::Connection("injected failure".to_string()));
+    }
+
+    /// Fail the next `calls` chat or stream calls with the error `error` builds
+    pub fn fail_next_with<F>(&mut self, calls: usize, error: F)
+    where
+        F: Fn() -> BackendError + Send + Sync + 'static,
+    {
+        self.failure = Some(Box::new(error));
+        *self.failures_remaining.get_mut() = calls;
+    }
+
+    /// Chat and stream calls received so far, including failed ones
+    pub fn call_count(&self) -> usize {
+        self.calls.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Apply the configured latency, then fail if failures are still pending
+    async fn inject(&self) -> Result<(), BackendError> {
+        use std::sync::atomic::Ordering;
+
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        let failing = self.failures_remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        match &self.failure {
+            Some(error) if failing => Err(error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The canned reply for a request
+    fn reply(&self, request: &ChatRequest) -> Result<String, BackendError> {
+        // Simple mock: return first message content as key
+        let message = request.messages.first().ok_or(BackendError::InvalidResponse)?;
+        let response = self.responses
+            .get(&message.content)
+            .cloned()
+            .unwrap_or_else(|| "Mock response".to_string());
+
+        // JSON mode wraps the reply so callers always receive valid JSON
+        if request.response_format.is_some() && serde_json::from_str::<serde_json::Value>(&response).is_err() {
+            return Ok(serde_json::json!({ "response": response }).to_string());
+        }
+        Ok(response)
+    }
+
+    /// Names passed to `pull_model`, in call order
+    pub fn pulled_models(&self) -> Vec<String> {
+        self.pulled_models.lock().unwrap().clone()
+    }
+
+    /// Names passed to `delete_model`, in call order
+    pub fn deleted_models(&self) -> Vec<String> {
+        self.deleted_models.lock().unwrap().clone()
+    }
+
+    /// Length of the vectors returned by `embed`
+    pub const EMBEDDING_DIMENSIONS: usize = 8;
 }
 
 #[async_trait]
 impl Backend for MockBackend {
     async fn chat(&self, request: ChatRequest) -> Result<String, BackendError> {
-        // Simple mock: return first message content as key
-        if let Some(message) = request.messages.first() {
-            if let Some(response) = self.responses.get(&message.content) {
-                Ok(response.clone())
-            } else {
-                Ok("Mock response".to_string())
-            }
-        } else {
-            Err(BackendError::InvalidResponse)
-        }
+        self.inject().await?;
+        self.reply(&request)
     }
 
-    async fn chat_stream(&self, _request: ChatRequest) -> Result<StreamResponse, BackendError> {
-        Err(BackendError::Connection("Mock streaming not implemented".to_string()))
+    async fn chat_stream(&self, request: ChatRequest) -> Result<StreamResponse, BackendError> {
+        self.inject().await?;
+
+        // Scripted prompts stream token by token; anything else streams the
+        // canned reply as a single, complete token
+        let scripted = request.messages.first().and_then(|m| self.stream_responses.get(&m.content)).cloned();
+        let tokens = match scripted {
+            // An empty script still ends with a completion marker
+            Some(tokens) if tokens.is_empty() => vec![String::new()],
+            Some(tokens) => tokens,
+            None => vec![self.reply(&request)?],
+        };
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let cancellation_token = tokio_util::sync::CancellationToken::new();
+        let token = cancellation_token.clone();
+        let delay = self.token_delay;
+
+        tokio::spawn(async move {
+            let count = tokens.len();
+            for (i, content) in tokens.into_iter().enumerate() {
+                if i > 0 && !delay.is_zero() {
+                    tokio::select! {
+                        biased;
+                        _ = token.cancelled() => return,
+                        _ = tokio::time::sleep(delay) => {}
+                    }
+                }
+                if token.is_cancelled() {
+                    return;
+                }
+                let stream_token = StreamToken {
+                    content,
+                    is_complete: i + 1 == count,
+                    kind: TokenKind::Answer,
+                    metadata: None,
+                };
+                if sender.send(stream_token).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(StreamResponse {
+            id: rand::random(),
+            receiver,
+            cancellation_token,
+        })
     }
 
     async fn list_models(&self) -> Result<Vec<ModelInfo>, BackendError> {
@@ -328,7 +1398,10 @@ impl Backend for MockBackend {
     }
 
     async fn get_model_capabilities(&self, _model_name: &str) -> Result<ModelCapabilities, BackendError> {
-        Ok(ModelCapabilities::default())
+        Ok(ModelCapabilities {
+            supports_embeddings: true,
+            ..ModelCapabilities::default()
+        })
     }
 
     fn capabilities(&self) -> &BackendCapabilities {
@@ -342,6 +1415,31 @@ impl Backend for MockBackend {
     async fn health_check(&self) -> Result<(), BackendError> {
         Ok(())
     }
+
+    async fn pull_model(&self, name: &str) -> Result<(), BackendError> {
+        self.pulled_models.lock().unwrap().push(name.to_string());
+        Ok(())
+    }
+
+    async fn delete_model(&self, name: &str) -> Result<(), BackendError> {
+        self.deleted_models.lock().unwrap().push(name.to_string());
+        Ok(())
+    }
+
+    async fn embed(&self, _model: &str, input: &[String]) -> Result<Vec<Vec<f32>>, BackendError> {
+        // Deterministic vectors derived from the input bytes
+        Ok(input
+            .iter()
+            .map(|text| {
+                (0..Self::EMBEDDING_DIMENSIONS)
+                    .map(|i| {
+                        let sum: u32 = text.bytes().skip(i).step_by(Self::EMBEDDING_DIMENSIONS).map(u32::from).sum();
+                        (sum % 1000) as f32 / 1000.0
+                    })
+                    .collect()
+            })
+            .collect())
+    }
 }
 
 #[cfg(test)]
@@ -362,6 +1460,8 @@ mod tests {
             }],
             stream: false,
             options: None,
+            response_format: None,
+            tools: None,
         };
 
         let response = backend.chat(request).await.unwrap();
@@ -381,4 +1481,479 @@ mod tests {
         assert_eq!(models.len(), 1);
         assert_eq!(models[0].name, "mock-model");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_openai_backend_requires_api_key() {
+        let result = OpenAIBackend::new("https://api.openai.com".to_string(), None);
+        assert!(matches!(result, Err(BackendInitError::Authentication)));
+
+        let result = OpenAIBackend::new("https://api.openai.com".to_string(), Some("  ".to_string()));
+        assert!(matches!(result, Err(BackendInitError::Authentication)));
+
+        let backend = OpenAIBackend::new("https://api.openai.com/v1/".to_string(), Some("sk-test".to_string())).unwrap();
+        assert_eq!(backend.base_url, "https://api.openai.com");
+        assert!(matches!(backend.backend_type(), BackendType::OpenAI));
+    }
+
+    #[test]
+    fn test_openai_request_body_sends_images() {
+        let request = ChatRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: "What is this?".to_string(),
+                images: Some(vec!["iVBORw0KGgoAAAA".to_string()]),
+            }],
+            stream: true,
+            options: None,
+            response_format: None,
+            tools: None,
+        };
+
+        let body = OpenAIBackend::request_body(&request, true);
+        assert_eq!(body["stream"], true);
+        let content = &body["messages"][0]["content"];
+        assert_eq!(content[0]["text"], "What is this?");
+        assert_eq!(content[1]["image_url"]["url"], "data:image/png;base64,iVBORw0KGgoAAAA");
+    }
+
+    #[test]
+    fn test_parse_ollama_usage() {
+        let body = serde_json::json!({
+            "model": "llama3.2",
+            "created_at": "2024-05-01T12:00:00.000000Z",
+            "message": { "role": "assistant", "content": "Hi there!" },
+            "done": true,
+            "total_duration": 5191566416u64,
+            "load_duration": 2154458u64,
+            "prompt_eval_count": 26,
+            "prompt_eval_duration": 383809000u64,
+            "eval_count": 298,
+            "eval_duration": 4799921000u64
+        });
+
+        let completion = OllamaBackend::parse_chat_response(&body).unwrap();
+        assert_eq!(completion.content, "Hi there!");
+
+        let usage = completion.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, Some(26));
+        assert_eq!(usage.completion_tokens, Some(298));
+        assert_eq!(usage.total_tokens(), Some(324));
+        assert_eq!(usage.eval_duration, Some(std::time::Duration::from_nanos(4799921000)));
+    }
+
+    #[test]
+    fn test_parse_ollama_tool_calls() {
+        let body = serde_json::json!({
+            "model": "llama3.1",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    { "function": { "name": "get_weather", "arguments": { "city": "Paris", "unit": "celsius" } } },
+                    { "function": { "name": "get_time", "arguments": "{\"zone\": \"CET\"}" } }
+                ]
+            },
+            "done": true
+        });
+
+        let completion = OllamaBackend::parse_chat_response(&body).unwrap();
+        assert_eq!(completion.content, "");
+        assert_eq!(
+            completion.tool_calls,
+            vec![
+                ToolCall { name: "get_weather".to_string(), arguments: serde_json::json!({ "city": "Paris", "unit": "celsius" }) },
+                ToolCall { name: "get_time".to_string(), arguments: serde_json::json!({ "zone": "CET" }) },
+            ]
+        );
+
+        // Tools go out in Ollama's request format
+        let request = ChatRequest {
+            model: "llama3.1".to_string(),
+            messages: Vec::new(),
+            stream: false,
+            options: None,
+            response_format: None,
+            tools: Some(vec![crate::streaming::Tool::function(
+                "get_weather",
+                "Current weather for a city",
+                serde_json::json!({ "type": "object", "properties": { "city": { "type": "string" } } }),
+            )]),
+        };
+        let sent = serde_json::to_value(&request).unwrap();
+        assert_eq!(sent["tools"][0]["type"], "function");
+        assert_eq!(sent["tools"][0]["function"]["name"], "get_weather");
+    }
+
+    #[test]
+    fn test_parse_ollama_show_response() {
+        let body = serde_json::json!({
+            "modelfile": "FROM llama3.2:latest",
+            "parameters": "stop \"<|eot_id|>\"",
+            "template": "{{ if .System }}<|start_header_id|>system<|end_header_id|>{{ .System }}{{ end }}",
+            "details": {
+                "parent_model": "",
+                "format": "gguf",
+                "family": "llama",
+                "families": ["llama"],
+                "parameter_size": "3.2B",
+                "quantization_level": "Q4_K_M"
+            },
+            "model_info": {
+                "general.architecture": "llama",
+                "general.parameter_count": 3212749888u64,
+                "llama.attention.head_count": 24,
+                "llama.context_length": 131072,
+                "llama.embedding_length": 3072
+            },
+            "capabilities": ["completion", "tools"],
+            "modified_at": "2024-09-25T10:14:31.553279342+02:00"
+        });
+
+        let details = OllamaBackend::parse_show_response(&body);
+        assert_eq!(details.family.as_deref(), Some("llama"));
+        assert_eq!(details.parameter_size.as_deref(), Some("3.2B"));
+        assert_eq!(details.quantization_level.as_deref(), Some("Q4_K_M"));
+        assert_eq!(details.context_length, Some(131072));
+        assert_eq!(details.capabilities, vec!["completion", "tools"]);
+        assert!(details.template.unwrap().starts_with("{{ if .System }}"));
+
+        // Older servers omit model_info entirely
+        let details = OllamaBackend::parse_show_response(&serde_json::json!({ "details": {} }));
+        assert_eq!(details, ModelDetails::default());
+    }
+
+    #[test]
+    fn test_parse_ollama_tags_modified_at() {
+        let backend = OllamaBackend::new("http://localhost:11434".to_string()).unwrap();
+        let body = serde_json::json!({
+            "models": [
+                {
+                    "name": "llama3.2:latest",
+                    "modified_at": "2024-09-25T10:14:31.553279342+02:00",
+                    "size": 2019393189u64,
+                    "digest": "a80c4f17acd55265feec403c7aef86be0c25983ab279d83f3bcd3abbcb5b8b72"
+                },
+                { "name": "mistral:latest", "modified_at": "last tuesday", "size": 4109865159u64 },
+                { "name": "phi3:latest" }
+            ]
+        });
+
+        let models = backend.parse_tags_response(&body);
+        assert_eq!(models.len(), 3);
+        let expected = chrono::DateTime::parse_from_rfc3339("2024-09-25T08:14:31.553279342Z").unwrap();
+        assert_eq!(models[0].modified_at, Some(expected.with_timezone(&chrono::Utc)));
+        assert_eq!(models[0].size, Some(2019393189));
+        assert_eq!(models[1].modified_at, None);
+        assert_eq!(models[2].modified_at, None);
+    }
+
+    #[test]
+    fn test_model_filter_matches_and_orders() {
+        let model = |name: &str, size: Option<u64>, days_ago: Option<i64>, vision: bool| ModelInfo {
+            name: name.to_string(),
+            size,
+            modified_at: days_ago.map(|d| chrono::Utc::now() - chrono::Duration::days(d)),
+            capabilities: ModelCapabilities {
+                supports_vision: vision,
+                supports_thinking: name.contains("r1"),
+                ..ModelCapabilities::default()
+            },
+        };
+        let models = vec![
+            model("mistral:7b", Some(4_100), Some(30), false),
+            model("llava:13b", Some(8_000), Some(2), true),
+            model("deepseek-r1:8b", None, Some(1), false),
+            model("llama3.2-vision:11b", Some(7_900), None, true),
+        ];
+        let names = |filter: ModelFilter| -> Vec<String> {
+            filter.apply(models.clone()).into_iter().map(|m| m.name).collect()
+        };
+
+        assert_eq!(
+            names(ModelFilter::default()),
+            vec!["deepseek-r1:8b", "llama3.2-vision:11b", "llava:13b", "mistral:7b"]
+        );
+        assert_eq!(
+            names(ModelFilter { sort: ModelSort::Size, ..Default::default() }),
+            vec!["llava:13b", "llama3.2-vision:11b", "mistral:7b", "deepseek-r1:8b"]
+        );
+        assert_eq!(
+            names(ModelFilter { sort: ModelSort::Modified, ..Default::default() }),
+            vec!["deepseek-r1:8b", "llava:13b", "mistral:7b", "llama3.2-vision:11b"]
+        );
+        assert_eq!(
+            names(ModelFilter { vision_only: true, sort: ModelSort::Size, ..Default::default() }),
+            vec!["llava:13b", "llama3.2-vision:11b"]
+        );
+        assert_eq!(names(ModelFilter { thinking_only: true, ..Default::default() }), vec!["deepseek-r1:8b"]);
+        assert_eq!(names(ModelFilter { name: Some("LLA".to_string()), ..Default::default() }), vec!["llama3.2-vision:11b", "llava:13b"]);
+        assert_eq!("modified".parse::<ModelSort>(), Ok(ModelSort::Modified));
+        assert!("newest".parse::<ModelSort>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mock_backend_scripted_stream() {
+        let mut backend = MockBackend::new();
+        backend.add_stream_response(
+            "Count to three".to_string(),
+            vec!["One, ".to_string(), "two, ".to_string(), "three.".to_string()],
+        );
+        backend.set_token_delay(std::time::Duration::from_millis(5));
+        let request = |prompt: &str| ChatRequest {
+            model: "mock-model".to_string(),
+            messages: vec![crate::streaming::Message {
+                role: "user".to_string(),
+                content: prompt.to_string(),
+                images: None,
+            }],
+            stream: true,
+            options: None,
+            response_format: None,
+            tools: None,
+        };
+
+        let mut stream = backend.chat_stream(request("Count to three")).await.unwrap();
+        let mut tokens = Vec::new();
+        while let Some(token) = stream.receiver.recv().await {
+            tokens.push(token);
+        }
+        let content: String = tokens.iter().map(|t| t.content.as_str()).collect();
+        assert_eq!(content, "One, two, three.");
+        assert_eq!(tokens.len(), 3);
+        assert!(tokens.last().unwrap().is_complete);
+        assert!(tokens[..2].iter().all(|t| !t.is_complete));
+
+        // Cancelling after the first token ends the stream without completing it
+        let mut stream = backend.chat_stream(request("Count to three")).await.unwrap();
+        assert_eq!(stream.receiver.recv().await.unwrap().content, "One, ");
+        stream.cancellation_token.cancel();
+        assert!(stream.receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mock_backend_injected_failures_and_latency() {
+        let request = || ChatRequest {
+            model: "mock-model".to_string(),
+            messages: vec![crate::streaming::Message {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                images: None,
+            }],
+            stream: false,
+            options: None,
+            response_format: None,
+            tools: None,
+        };
+
+        // Failures stop after the requested count, and trip a wrapping breaker
+        let mut mock = MockBackend::new();
+        mock.fail_next(2);
+        let backend = CircuitBreakerBackend::new(Box::new(mock), CircuitBreaker::new(2, std::time::Duration::from_millis(20)));
+        assert!(matches!(backend.chat(request()).await, Err(BackendError::Connection(_))));
+        assert!(matches!(backend.chat(request()).await, Err(BackendError::Connection(_))));
+        assert_eq!(backend.circuit_state(), CircuitState::Open);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(backend.chat(request()).await.unwrap(), "Mock response");
+
+        let mut backend = MockBackend::new();
+        backend.fail_next_with(1, || BackendError::RateLimit);
+        assert!(matches!(backend.chat_stream(request()).await, Err(BackendError::RateLimit)));
+        assert!(backend.chat_stream(request()).await.is_ok());
+        assert_eq!(backend.call_count(), 2);
+
+        backend.set_latency(std::time::Duration::from_millis(40));
+        let start = std::time::Instant::now();
+        backend.chat(request()).await.unwrap();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_retry_backend_retries_only_unreachable_errors() {
+        let request = || ChatRequest {
+            model: "mock-model".to_string(),
+            messages: vec![crate::streaming::Message {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                images: None,
+            }],
+            stream: false,
+            options: None,
+            response_format: None,
+            tools: None,
+        };
+        let policy = RetryPolicy {
+            max_retries: 2,
+            base_delay: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(5),
+        };
+
+        let mut mock = MockBackend::new();
+        mock.fail_next(2);
+        let backend = RetryBackend::new(Box::new(mock), policy);
+        assert_eq!(backend.chat(request()).await.unwrap(), "Mock response");
+
+        // Gives up once the policy's retries are used
+        let mut mock = MockBackend::new();
+        mock.fail_next(3);
+        let backend = RetryBackend::new(Box::new(mock), policy);
+        assert!(matches!(backend.chat(request()).await, Err(BackendError::Connection(_))));
+        assert_eq!(backend.chat(request()).await.unwrap(), "Mock response");
+
+        // Errors from a reachable backend are returned straight away
+        let mut mock = MockBackend::new();
+        mock.fail_next_with(1, || BackendError::RateLimit);
+        let backend = RetryBackend::new(Box::new(mock), policy);
+        assert!(matches!(backend.chat_stream(request()).await, Err(BackendError::RateLimit)));
+    }
+
+    #[test]
+    fn test_ollama_embedding_request_body() {
+        let body = OllamaBackend::embedding_request("nomic-embed-text", "hello world");
+        assert_eq!(body["model"], "nomic-embed-text");
+        assert_eq!(body["prompt"], "hello world");
+    }
+
+    #[tokio::test]
+    async fn test_mock_embeddings_are_deterministic() {
+        let backend = MockBackend::new();
+        let input = vec!["hello".to_string(), "world".to_string()];
+
+        let first = backend.embed("mock-model", &input).await.unwrap();
+        let second = backend.embed("mock-model", &input).await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 2);
+        assert!(first.iter().all(|v| v.len() == MockBackend::EMBEDDING_DIMENSIONS));
+        assert_ne!(first[0], first[1]);
+    }
+
+    #[tokio::test]
+    async fn test_mock_model_management() {
+        let backend = MockBackend::new();
+        backend.pull_model("llama3.2").await.unwrap();
+        backend.pull_model("mistral").await.unwrap();
+        backend.delete_model("llama3.2").await.unwrap();
+
+        assert_eq!(backend.pulled_models(), vec!["llama3.2", "mistral"]);
+        assert_eq!(backend.deleted_models(), vec!["llama3.2"]);
+
+        let openai = OpenAIBackend::new("https://api.openai.com".to_string(), Some("sk-test".to_string())).unwrap();
+        assert!(matches!(openai.pull_model("gpt-4o").await, Err(BackendError::Unsupported(_))));
+    }
+    /// Health checks fail with a connection error while `down` is set
+    struct FlakyBackend {
+        down: std::sync::Arc<std::sync::atomic::AtomicBool>,
+        calls: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+        capabilities: BackendCapabilities,
+    }
+
+    #[async_trait]
+    impl Backend for FlakyBackend {
+        async fn chat(&self, _request: ChatRequest) -> Result<String, BackendError> {
+            Err(BackendError::InvalidResponse)
+        }
+
+        async fn chat_stream(&self, _request: ChatRequest) -> Result<StreamResponse, BackendError> {
+            Err(BackendError::InvalidResponse)
+        }
+
+        async fn list_models(&self) -> Result<Vec<ModelInfo>, BackendError> {
+            Ok(Vec::new())
+        }
+
+        async fn get_model_capabilities(&self, _model_name: &str) -> Result<ModelCapabilities, BackendError> {
+            Ok(ModelCapabilities::default())
+        }
+
+        fn capabilities(&self) -> &BackendCapabilities {
+            &self.capabilities
+        }
+
+        fn backend_type(&self) -> BackendType {
+            BackendType::Mock
+        }
+
+        async fn health_check(&self) -> Result<(), BackendError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if self.down.load(std::sync::atomic::Ordering::SeqCst) {
+                Err(BackendError::Connection("connection refused".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_and_recovers() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let down = Arc::new(AtomicBool::new(true));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flaky = FlakyBackend {
+            down: down.clone(),
+            calls: calls.clone(),
+            capabilities: BackendCapabilities::default(),
+        };
+        let cooldown = std::time::Duration::from_millis(50);
+        let backend = CircuitBreakerBackend::new(Box::new(flaky), CircuitBreaker::new(3, cooldown));
+
+        for _ in 0..3 {
+            assert!(backend.health_check().await.is_err());
+        }
+        assert_eq!(backend.circuit_state(), CircuitState::Open);
+
+        // While open, calls fail fast without reaching the backend
+        assert!(matches!(backend.health_check().await, Err(BackendError::Connection(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // A failed probe after the cooldown re-opens the circuit
+        tokio::time::sleep(cooldown).await;
+        assert_eq!(backend.circuit_state(), CircuitState::HalfOpen);
+        assert!(backend.health_check().await.is_err());
+        assert_eq!(backend.circuit_state(), CircuitState::Open);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // A successful probe closes it again
+        down.store(false, Ordering::SeqCst);
+        tokio::time::sleep(cooldown).await;
+        assert!(backend.health_check().await.is_ok());
+        assert_eq!(backend.circuit_state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_ignores_non_connection_errors() {
+        let breaker = CircuitBreaker::new(1, std::time::Duration::from_secs(60));
+        breaker.observe::<()>(&Err(BackendError::ModelNotFound("missing".to_string())));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // A reply with an error status proves the backend is up, unless it
+        // says the server is unavailable
+        breaker.observe::<()>(&Err(status_error(reqwest::StatusCode::NOT_FOUND, None)));
+        breaker.observe::<()>(&Err(status_error(reqwest::StatusCode::INTERNAL_SERVER_ERROR, Some("llama3.2"))));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(status_error(reqwest::StatusCode::SERVICE_UNAVAILABLE, None).is_unreachable());
+
+        breaker.observe::<()>(&Err(BackendError::Timeout));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.try_acquire().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_frees_abandoned_probe() {
+        let cooldown = std::time::Duration::from_millis(20);
+        let breaker = CircuitBreaker::new(1, cooldown);
+        breaker.record_failure();
+        tokio::time::sleep(cooldown).await;
+
+        // The probe's future is dropped before it reports back
+        let probe = breaker.try_acquire().unwrap();
+        assert!(breaker.try_acquire().is_err());
+        drop(probe);
+
+        let probe = breaker.try_acquire().unwrap();
+        probe.observe::<()>(&Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
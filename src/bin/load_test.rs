@@ -68,6 +68,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             parent_template: None,
             tags: vec!["test".to_string()],
             usage_examples: Vec::new(),
+            few_shot: Vec::new(),
         };
         
         wrapper.save_template(template).await?;
@@ -86,7 +87,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             if args.test_templates {
                 // Test template rendering
-                let variables = json!({
+                let _variables = json!({
                     "name": format!("User{}", request_id),
                     "request_id": request_id
                 });
@@ -115,7 +116,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 batch_handles.push(handle);
             } else {
                 // Test basic chat operations
-                let message = format!("Test message {}", request_id);
+                let _message = format!("Test message {}", request_id);
                 
                 let handle = tokio::spawn(async move {
                     let start = Instant::now();
@@ -188,7 +189,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Max Response Time: {:.2}ms", max_duration.as_millis());
     
     // Get performance metrics
-    let performance_metrics = wrapper.get_performance_metrics();
     let performance_report = wrapper.get_performance_report();
     
     println!("\n🎯 Performance Targets");
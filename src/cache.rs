@@ -1,15 +1,18 @@
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use lru::LruCache;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
 use std::num::NonZeroUsize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
 use std::time::{Duration, Instant};
 use thiserror::Error;
 use sha2::{Digest, Sha256};
 use tokio::fs;
-use tokio::sync::mpsc;
-use crate::streaming::{StreamToken, StreamResponse, StreamId};
+use tokio::sync::{mpsc, oneshot};
+use crate::streaming::{StreamToken, StreamResponse, StreamId, TokenKind};
 
 #[derive(Debug, Error)]
 pub enum CacheError {
@@ -25,7 +28,7 @@ pub enum CacheError {
     Persistence(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CacheStats {
     pub hits: u64,
     pub misses: u64,
@@ -34,6 +37,12 @@ pub struct CacheStats {
     pub evictions: u64,
     pub disk_writes: u64,
     pub disk_reads: u64,
+    /// Exact-key misses answered by a semantically similar entry
+    #[serde(default)]
+    pub semantic_hits: u64,
+    /// Bytes taken by the cache files this manager has written or loaded
+    #[serde(default)]
+    pub disk_usage_bytes: u64,
 }
 
 impl CacheStats {
@@ -55,8 +64,11 @@ impl CacheStats {
     }
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct CacheKey {
+    /// Full SHA-256 digest of the prompt (and system prompt, if any)
+    pub prompt_digest: [u8; 32],
+    /// Truncated digest, kept for logging hooks that expect a `u64`
     pub prompt_hash: u64,
     pub model: String,
     pub parameters: ParameterHash,
@@ -64,19 +76,46 @@ pub struct CacheKey {
 
 impl CacheKey {
     pub fn new(prompt: &str, model: &str, parameters: &HashMap<String, serde_json::Value>) -> Self {
+        Self::with_system_prompt(prompt, None, model, parameters)
+    }
+
+    /// Build a key that also distinguishes requests by their system prompt
+    pub fn with_system_prompt(
+        prompt: &str,
+        system_prompt: Option<&str>,
+        model: &str,
+        parameters: &HashMap<String, serde_json::Value>,
+    ) -> Self {
         let mut hasher = Sha256::new();
+        if let Some(system) = system_prompt {
+            // Length prefix keeps the system/user boundary unambiguous
+            hasher.update((system.len() as u64).to_le_bytes());
+            hasher.update(system.as_bytes());
+        }
         hasher.update(prompt.as_bytes());
-        let prompt_hash = u64::from_le_bytes(hasher.finalize()[..8].try_into().unwrap());
+        let prompt_digest: [u8; 32] = hasher.finalize().into();
+        let prompt_hash = u64::from_le_bytes(prompt_digest[..8].try_into().unwrap());
 
         Self {
+            prompt_digest,
             prompt_hash,
             model: model.to_string(),
             parameters: ParameterHash::new(parameters),
         }
     }
+
+    /// Hex file stem used for the persisted entry, unique per prompt, model and parameters
+    pub fn file_stem(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.prompt_digest);
+        hasher.update(self.model.as_bytes());
+        hasher.update(self.parameters.0.to_le_bytes());
+
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct ParameterHash(u64);
 
 impl ParameterHash {
@@ -101,18 +140,36 @@ impl ParameterHash {
 pub struct CacheEntry {
     pub response: String,
     pub created_at: Instant,
+    pub last_accessed: Instant,
     pub access_count: u32,
+    /// Access count with exponential decay applied, so it reflects recent usage
+    pub access_frequency: f64,
     pub metadata: ResponseMetadata,
     pub is_streaming: bool,
     pub stream_tokens: Option<Vec<StreamToken>>,
+    /// Prompt embedding, present when the semantic cache is enabled
+    pub embedding: Option<Vec<f32>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseMetadata {
     pub model: String,
     pub tokens_used: Option<u32>,
+    #[serde(default)]
+    pub prompt_tokens: Option<u32>,
+    #[serde(default)]
+    pub completion_tokens: Option<u32>,
     pub response_time: Duration,
     pub backend_type: String,
+    /// Name of the configured backend that served the response
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// Labels for grouping entries, e.g. a tenant, for `invalidate_by_tag`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Correlation id of the request that produced the response
+    #[serde(default)]
+    pub request_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -125,6 +182,68 @@ pub struct CacheConfig {
     pub cache_dir: Option<PathBuf>,
     pub max_memory_bytes: Option<usize>,
     pub memory_pressure_threshold: f64, // 0.0 to 1.0
+    /// Half-life for decaying entry access frequency; `None` disables decay
+    #[serde(default = "default_access_decay_half_life", with = "humantime_serde")]
+    pub access_decay_half_life: Option<Duration>,
+    /// How often expired entries are purged and access frequencies decayed
+    #[serde(default = "default_maintenance_interval", with = "humantime_serde")]
+    pub maintenance_interval: Duration,
+    /// Measure `ttl` from the last access rather than from creation, so entries
+    /// in active use never expire
+    #[serde(default)]
+    pub sliding_ttl: bool,
+    /// Which entry to give up when the cache is full or under memory pressure
+    #[serde(default)]
+    pub eviction_policy: EvictionPolicy,
+    /// Gzip persisted entries, writing `.json.gz` instead of `.json` files
+    #[serde(default)]
+    pub compress_disk_entries: bool,
+    /// Reuse answers to paraphrased prompts on an exact-key miss
+    #[serde(default)]
+    pub semantic: SemanticCacheConfig,
+    /// How long an unreachable-backend failure is remembered per key, failing
+    /// identical requests fast instead of retrying; `None` disables it
+    #[serde(default, with = "humantime_serde")]
+    pub negative_ttl: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticCacheConfig {
+    pub enabled: bool,
+    /// Minimum cosine similarity between prompt embeddings to count as a hit
+    pub similarity_threshold: f32,
+    /// Model used to embed prompts; defaults to the chat model
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+}
+
+impl Default for SemanticCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            similarity_threshold: 0.95,
+            embedding_model: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvictionPolicy {
+    /// Evict the least recently used entry
+    #[default]
+    Lru,
+    /// Evict the least frequently used entry (lowest `access_count`), which
+    /// keeps a stable hot set resident through bursts of one-off prompts
+    Lfu,
+}
+
+fn default_access_decay_half_life() -> Option<Duration> {
+    Some(Duration::from_secs(3600))
+}
+
+fn default_maintenance_interval() -> Duration {
+    Duration::from_secs(60)
 }
 
 impl Default for CacheConfig {
@@ -137,55 +256,160 @@ impl Default for CacheConfig {
             cache_dir: Some(PathBuf::from(".cache")),
             max_memory_bytes: Some(100 * 1024 * 1024), // 100MB
             memory_pressure_threshold: 0.8, // 80%
+            access_decay_half_life: default_access_decay_half_life(),
+            maintenance_interval: default_maintenance_interval(),
+            sliding_ttl: false,
+            eviction_policy: EvictionPolicy::Lru,
+            compress_disk_entries: false,
+            semantic: SemanticCacheConfig::default(),
+            negative_ttl: None,
         }
     }
 }
 
+const ENTRY_EXTENSION: &str = "json";
+const COMPRESSED_ENTRY_EXTENSION: &str = "json.gz";
+
+/// Writes queued for the background persister before `put` has to wait
+const PERSIST_QUEUE_CAPACITY: usize = 1024;
+
+enum PersistJob {
+    Write(CacheKey, Box<PersistentCacheEntry>),
+    /// Acknowledged once every job queued before it has been handled
+    Flush(oneshot::Sender<()>),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct PersistentCacheEntry {
+    /// Full key, so entries can be restored into memory on startup
+    #[serde(default)]
+    key: Option<CacheKey>,
     response: String,
     created_at: std::time::SystemTime,
     access_count: u32,
     metadata: ResponseMetadata,
     is_streaming: bool,
     stream_tokens: Option<Vec<StreamToken>>,
+    #[serde(default)]
+    embedding: Option<Vec<f32>>,
 }
 
 impl From<&CacheEntry> for PersistentCacheEntry {
     fn from(entry: &CacheEntry) -> Self {
         Self {
+            key: None,
             response: entry.response.clone(),
             created_at: std::time::SystemTime::now() - entry.created_at.elapsed(),
             access_count: entry.access_count,
             metadata: entry.metadata.clone(),
             is_streaming: entry.is_streaming,
             stream_tokens: entry.stream_tokens.clone(),
+            embedding: entry.embedding.clone(),
         }
     }
 }
 
 impl From<PersistentCacheEntry> for CacheEntry {
     fn from(entry: PersistentCacheEntry) -> Self {
-        let created_at = entry.created_at
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| Instant::now() - d)
-            .unwrap_or_else(|_| Instant::now());
+        // Translate the wall-clock creation time back into an age
+        let created_at = std::time::SystemTime::now()
+            .duration_since(entry.created_at)
+            .ok()
+            .and_then(|age| Instant::now().checked_sub(age))
+            .unwrap_or_else(Instant::now);
 
         Self {
             response: entry.response,
             created_at,
+            last_accessed: created_at,
             access_count: entry.access_count,
+            access_frequency: entry.access_count as f64,
             metadata: entry.metadata,
             is_streaming: entry.is_streaming,
             stream_tokens: entry.stream_tokens,
+            embedding: entry.embedding,
         }
     }
 }
 
-pub struct CacheManager {
+/// Mutable cache state, guarded by a single lock so `CacheManager` can be
+/// shared across tasks. The lock is never held across an `.await`.
+struct CacheState {
     memory_cache: LruCache<CacheKey, CacheEntry>,
-    config: CacheConfig,
     stats: CacheStats,
+    last_maintenance: Instant,
+    /// Size of each cache file on disk, by file stem
+    disk_files: HashMap<String, u64>,
+    /// Recent backend failures by key, with when they happened
+    failures: HashMap<CacheKey, (Instant, String)>,
+    /// Lookup counters by model; entry counts are filled in when read
+    model_stats: HashMap<String, CacheStats>,
+}
+
+/// The counters for `model`, created on first use
+fn model_stats<'a>(stats: &'a mut HashMap<String, CacheStats>, model: &str) -> &'a mut CacheStats {
+    if !stats.contains_key(model) {
+        stats.insert(model.to_string(), CacheStats::default());
+    }
+    stats.get_mut(model).unwrap()
+}
+
+impl CacheState {
+    /// Track the size of a cache file, or its removal when `size` is `None`
+    fn record_disk_file(&mut self, stem: String, size: Option<u64>) {
+        let previous = match size {
+            Some(size) => self.disk_files.insert(stem, size),
+            None => self.disk_files.remove(&stem),
+        };
+        self.stats.disk_usage_bytes = self.stats.disk_usage_bytes - previous.unwrap_or(0) + size.unwrap_or(0);
+    }
+
+    /// Remove the entry `policy` gives up first
+    fn pop_victim(&mut self, policy: EvictionPolicy) -> Option<(CacheKey, CacheEntry)> {
+        match policy {
+            EvictionPolicy::Lru => self.memory_cache.pop_lru(),
+            EvictionPolicy::Lfu => {
+                // Walk from least to most recently used so ties evict the staler
+                // entry. The decayed frequency lets once-hot entries age out.
+                let key = self.memory_cache.iter()
+                    .rev()
+                    .min_by(|(_, a), (_, b)| a.access_frequency.total_cmp(&b.access_frequency))
+                    .map(|(key, _)| key.clone())?;
+                self.memory_cache.pop_entry(&key)
+            }
+        }
+    }
+
+    /// Insert an entry, returning whatever was pushed out to make room for it
+    fn insert(&mut self, key: CacheKey, entry: CacheEntry, policy: EvictionPolicy) -> Option<(CacheKey, CacheEntry)> {
+        if policy == EvictionPolicy::Lfu
+            && !self.memory_cache.contains(&key)
+            && self.memory_cache.len() >= self.memory_cache.cap().get()
+        {
+            let victim = self.pop_victim(policy);
+            self.memory_cache.put(key, entry);
+            return victim;
+        }
+        self.memory_cache.push(key, entry)
+    }
+}
+
+pub struct CacheManager {
+    state: Arc<Mutex<CacheState>>,
+    config: CacheConfig,
+    /// Feeds the background disk writer, started on the first persisted put.
+    /// Dropping the manager closes the queue; the writer drains it, then exits.
+    persist_queue: OnceLock<mpsc::Sender<PersistJob>>,
+    /// Periodic `run_maintenance`, stopped when the manager is dropped
+    maintenance_task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for CacheManager {
+    fn drop(&mut self) {
+        if let Some(task) = self.maintenance_task.take() {
+            task.abort();
+        }
+    }
 }
 
 impl CacheManager {
@@ -193,23 +417,47 @@ impl CacheManager {
         let capacity = NonZeroUsize::new(config.max_memory_entries)
             .unwrap_or(NonZeroUsize::new(1000).unwrap());
         
-        Self {
-            memory_cache: LruCache::new(capacity),
+        let mut manager = Self {
+            state: Arc::new(Mutex::new(CacheState {
+                memory_cache: LruCache::new(capacity),
+                stats: CacheStats {
+                    hits: 0,
+                    misses: 0,
+                    total_entries: 0,
+                    memory_usage_bytes: 0,
+                    evictions: 0,
+                    disk_writes: 0,
+                    disk_reads: 0,
+                    semantic_hits: 0,
+                    disk_usage_bytes: 0,
+                },
+                last_maintenance: Instant::now(),
+                disk_files: HashMap::new(),
+                failures: HashMap::new(),
+                model_stats: HashMap::new(),
+            })),
             config,
-            stats: CacheStats {
-                hits: 0,
-                misses: 0,
-                total_entries: 0,
-                memory_usage_bytes: 0,
-                evictions: 0,
-                disk_writes: 0,
-                disk_reads: 0,
-            },
-        }
+            persist_queue: OnceLock::new(),
+            maintenance_task: None,
+        };
+        manager.start_maintenance();
+        manager
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.config.ttl
+    }
+
+    /// Change how long entries live. Applies to entries already cached too,
+    /// since expiry is checked against the current TTL on every read.
+    pub fn set_ttl(&mut self, ttl: Duration, sliding: bool) {
+        self.config.ttl = ttl;
+        self.config.sliding_ttl = sliding;
+        self.start_maintenance();
     }
 
     pub async fn new_with_persistence(config: CacheConfig) -> Result<Self, CacheError> {
-        let mut cache_manager = Self::new(config);
+        let cache_manager = Self::new(config);
         
         if cache_manager.config.enable_persistence {
             cache_manager.load_from_disk().await?;
@@ -218,142 +466,416 @@ impl CacheManager {
         Ok(cache_manager)
     }
 
-    pub async fn get(&mut self, key: &CacheKey) -> Option<String> {
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap()
+    }
+
+    pub async fn get(&self, key: &CacheKey) -> Option<String> {
         // First check memory cache
-        if let Some(entry) = self.memory_cache.get_mut(key) {
-            // Check TTL
-            if entry.created_at.elapsed() > self.config.ttl {
-                self.memory_cache.pop(key);
-                self.stats.misses += 1;
-                return None;
-            }
+        {
+            let mut state = self.state();
+            let state = &mut *state;
+            if let Some(entry) = state.memory_cache.get_mut(key) {
+                // Check TTL
+                if self.is_expired(entry) {
+                    state.memory_cache.pop(key);
+                    state.stats.misses += 1;
+                    model_stats(&mut state.model_stats, &key.model).misses += 1;
+                    return None;
+                }
 
-            // Update access count
-            entry.access_count += 1;
-            self.stats.hits += 1;
-            return Some(entry.response.clone());
+                // Update access count
+                entry.last_accessed = Instant::now();
+                entry.access_count += 1;
+                entry.access_frequency += 1.0;
+                state.stats.hits += 1;
+                model_stats(&mut state.model_stats, &key.model).hits += 1;
+                return Some(entry.response.clone());
+            }
         }
 
         // If not in memory and persistence is enabled, try disk
         if self.config.enable_persistence {
             if let Ok(Some(entry)) = self.load_from_disk_by_key(key).await {
                 // Check TTL for disk entry
-                if entry.created_at.elapsed() <= self.config.ttl {
+                if !self.is_expired(&entry) {
                     let response = entry.response.clone();
                     
                     // Put back in memory cache
                     let mut updated_entry = entry;
                     updated_entry.access_count += 1;
-                    self.memory_cache.put(key.clone(), updated_entry);
+                    updated_entry.access_frequency += 1.0;
+                    let mut state = self.state();
+                    state.insert(key.clone(), updated_entry, self.config.eviction_policy);
                     
-                    self.stats.hits += 1;
-                    self.stats.disk_reads += 1;
+                    state.stats.hits += 1;
+                    state.stats.disk_reads += 1;
+                    let model = model_stats(&mut state.model_stats, &key.model);
+                    model.hits += 1;
+                    model.disk_reads += 1;
                     return Some(response);
                 }
             }
         }
 
-        self.stats.misses += 1;
+        let mut state = self.state();
+        state.stats.misses += 1;
+        model_stats(&mut state.model_stats, &key.model).misses += 1;
         None
     }
 
+    /// Find the closest entry for the same model and parameters whose prompt
+    /// embedding is at least `similarity_threshold` similar to `embedding`.
+    /// Always misses unless the semantic cache is enabled.
+    pub fn get_similar(&self, key: &CacheKey, embedding: &[f32]) -> Option<String> {
+        if !self.config.semantic.enabled {
+            return None;
+        }
+
+        let mut state = self.state();
+        let state = &mut *state;
+        let now = Instant::now();
+        let best = state.memory_cache.iter()
+            .filter(|(candidate, entry)| {
+                candidate.model == key.model
+                    && candidate.parameters == key.parameters
+                    && !entry.is_streaming
+                    && !self.is_expired_at(entry, now)
+            })
+            .filter_map(|(candidate, entry)| {
+                let similarity = cosine_similarity(entry.embedding.as_deref()?, embedding);
+                (similarity >= self.config.semantic.similarity_threshold).then_some((candidate, similarity))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(candidate, _)| candidate.clone())?;
+
+        let entry = state.memory_cache.get_mut(&best)?;
+        entry.last_accessed = now;
+        entry.access_count += 1;
+        entry.access_frequency += 1.0;
+        state.stats.semantic_hits += 1;
+        model_stats(&mut state.model_stats, &key.model).semantic_hits += 1;
+        Some(entry.response.clone())
+    }
+
     pub async fn put(
-        &mut self,
+        &self,
+        key: CacheKey,
+        value: String,
+        metadata: ResponseMetadata,
+    ) -> Result<(), CacheError> {
+        self.put_with_embedding(key, value, metadata, None).await
+    }
+
+    /// Store a response along with its prompt embedding, making it available
+    /// to `get_similar`
+    pub async fn put_with_embedding(
+        &self,
         key: CacheKey,
         value: String,
         metadata: ResponseMetadata,
+        embedding: Option<Vec<f32>>,
     ) -> Result<(), CacheError> {
         let entry = CacheEntry {
             response: value.clone(),
             created_at: Instant::now(),
+            last_accessed: Instant::now(),
             access_count: 1,
+            access_frequency: 1.0,
             metadata: metadata.clone(),
             is_streaming: false,
             stream_tokens: None,
+            embedding,
         };
 
-        // Check memory pressure before adding
-        self.handle_memory_pressure().await?;
+        self.store_entry(key, entry).await
+    }
 
-        // Store in memory cache
-        if let Some(evicted) = self.memory_cache.push(key.clone(), entry.clone()) {
-            self.stats.evictions += 1;
-            
-            // If persistence is enabled, save evicted entry to disk
-            if self.config.enable_persistence {
-                self.save_to_disk(&evicted.0, &evicted.1).await?;
+    /// Insert `entry`, evicting under memory pressure, and write everything
+    /// that left memory (plus the entry itself) to disk when persistence is on
+    async fn store_entry(&self, key: CacheKey, entry: CacheEntry) -> Result<(), CacheError> {
+        let evicted = {
+            let mut state = self.state();
+
+            // Check memory pressure before adding
+            let mut evicted = self.handle_memory_pressure(&mut state);
+
+            // Store in memory cache
+            if let Some(pushed_out) = state.insert(key.clone(), entry.clone(), self.config.eviction_policy) {
+                state.stats.evictions += 1;
+                evicted.push(pushed_out);
             }
-        }
 
-        // Also save to disk if persistence is enabled
+            self.update_stats(&mut state);
+            evicted
+        };
+
+        // If persistence is enabled, queue evicted entries and the new one for disk
         if self.config.enable_persistence {
-            self.save_to_disk(&key, &entry).await?;
+            for (evicted_key, evicted_entry) in &evicted {
+                self.queue_write(evicted_key, evicted_entry).await?;
+            }
+            self.queue_write(&key, &entry).await?;
         }
 
-        self.update_stats();
         Ok(())
     }
 
-    pub fn invalidate_model(&mut self, model: &str) {
-        let keys_to_remove: Vec<_> = self.memory_cache
+    pub fn invalidate_model(&self, model: &str) {
+        let mut state = self.state();
+        let keys_to_remove: Vec<_> = state.memory_cache
             .iter()
             .filter(|(key, _)| key.model == model)
             .map(|(key, _)| key.clone())
             .collect();
 
         for key in keys_to_remove {
-            self.memory_cache.pop(&key);
+            state.memory_cache.pop(&key);
+        }
+
+        self.update_stats(&mut state);
+    }
+
+    /// Drop the entry for `key` from memory and, when persistence is on, from
+    /// disk. Returns whether there was one.
+    pub async fn invalidate(&self, key: &CacheKey) -> Result<bool, CacheError> {
+        // A pending write would otherwise bring the entry back
+        self.flush().await;
+        let mut removed = {
+            let mut state = self.state();
+            let removed = state.memory_cache.pop(key).is_some();
+            self.update_stats(&mut state);
+            removed
+        };
+
+        if self.config.enable_persistence {
+            let cache_dir = self.get_cache_dir()?;
+            for extension in [ENTRY_EXTENSION, COMPRESSED_ENTRY_EXTENSION] {
+                let path = cache_dir.join(format!("{}.{}", key.file_stem(), extension));
+                if fs::remove_file(&path).await.is_ok() {
+                    self.state().record_disk_file(key.file_stem(), None);
+                    removed = true;
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Drop every entry tagged `tag` from memory and, when persistence is on,
+    /// from disk. Returns the number of entries removed.
+    pub async fn invalidate_by_tag(&self, tag: &str) -> Result<usize, CacheError> {
+        // Pending writes would otherwise land after the scan below
+        self.flush().await;
+        let has_tag = |metadata: &ResponseMetadata| metadata.tags.iter().any(|t| t == tag);
+
+        let removed_keys: HashSet<CacheKey> = {
+            let mut state = self.state();
+            let keys: HashSet<_> = state.memory_cache
+                .iter()
+                .filter(|(_, entry)| has_tag(&entry.metadata))
+                .map(|(key, _)| key.clone())
+                .collect();
+
+            for key in &keys {
+                state.memory_cache.pop(key);
+            }
+
+            self.update_stats(&mut state);
+            keys
+        };
+        let mut removed = removed_keys.len();
+
+        if self.config.enable_persistence {
+            let cache_dir = self.get_cache_dir()?;
+            if cache_dir.exists() {
+                let mut entries = fs::read_dir(&cache_dir).await
+                    .map_err(|e| CacheError::Persistence(format!("Failed to read cache directory: {}", e)))?;
+
+                while let Some(entry) = entries.next_entry().await
+                    .map_err(|e| CacheError::Persistence(format!("Failed to read directory entry: {}", e)))? {
+                    let path = entry.path();
+                    if !path.extension().is_some_and(|extension| extension == "json" || extension == "gz") {
+                        continue;
+                    }
+
+                    let Ok(persistent_entry) = Self::read_persisted_entry(&path).await else {
+                        continue;
+                    };
+                    if has_tag(&persistent_entry.metadata) {
+                        fs::remove_file(&path).await
+                            .map_err(|e| CacheError::Persistence(format!("Failed to remove cache file: {}", e)))?;
+                        if let Some(stem) = Self::entry_file_stem(&path) {
+                            self.state().record_disk_file(stem, None);
+                        }
+                        // Entries also held in memory were already counted
+                        if !persistent_entry.key.is_some_and(|key| removed_keys.contains(&key)) {
+                            removed += 1;
+                        }
+                    }
+                }
+            }
         }
 
-        self.update_stats();
+        Ok(removed)
     }
 
-    pub fn invalidate_by_parameters(&mut self, model: &str, parameters: &HashMap<String, serde_json::Value>) {
+    pub fn invalidate_by_parameters(&self, model: &str, parameters: &HashMap<String, serde_json::Value>) {
         let target_param_hash = ParameterHash::new(parameters);
         
-        let keys_to_remove: Vec<_> = self.memory_cache
+        let mut state = self.state();
+        let keys_to_remove: Vec<_> = state.memory_cache
             .iter()
             .filter(|(key, _)| key.model == model && key.parameters == target_param_hash)
             .map(|(key, _)| key.clone())
             .collect();
 
         for key in keys_to_remove {
-            self.memory_cache.pop(&key);
+            state.memory_cache.pop(&key);
         }
 
-        self.update_stats();
+        self.update_stats(&mut state);
     }
 
-    pub fn invalidate_expired(&mut self) {
+    pub fn invalidate_expired(&self) {
+        self.invalidate_expired_locked(&mut self.state());
+    }
+
+    fn invalidate_expired_locked(&self, state: &mut CacheState) {
+        Self::purge_expired(&self.config, state);
+    }
+
+    fn purge_expired(config: &CacheConfig, state: &mut CacheState) {
         let now = Instant::now();
-        let keys_to_remove: Vec<_> = self.memory_cache
+        let keys_to_remove: Vec<_> = state.memory_cache
             .iter()
-            .filter(|(_, entry)| now.duration_since(entry.created_at) > self.config.ttl)
+            .filter(|(_, entry)| Self::expired_at(config, entry, now))
             .map(|(key, _)| key.clone())
             .collect();
 
         for key in keys_to_remove {
-            self.memory_cache.pop(&key);
+            state.memory_cache.pop(&key);
         }
 
-        self.update_stats();
+        let negative_ttl = config.negative_ttl.unwrap_or_default();
+        state.failures.retain(|_, (failed_at, _)| now.duration_since(*failed_at) < negative_ttl);
+
+        Self::refresh_stats(state);
     }
 
-    pub fn get_stats(&self) -> &CacheStats {
-        &self.stats
+    /// The error from a failure remembered for `key` within `negative_ttl`
+    pub fn get_failure(&self, key: &CacheKey) -> Option<String> {
+        let negative_ttl = self.config.negative_ttl?;
+        let mut state = self.state();
+        match state.failures.get(key) {
+            Some((failed_at, error)) if failed_at.elapsed() < negative_ttl => Some(error.clone()),
+            Some(_) => {
+                state.failures.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Remember that `key` failed, if the negative cache is enabled
+    pub fn put_failure(&self, key: CacheKey, error: String) {
+        if self.config.negative_ttl.is_some() {
+            self.state().failures.insert(key, (Instant::now(), error));
+        }
+    }
+
+    /// Scale every entry's access frequency by the decay accumulated over `elapsed`
+    pub fn decay_access_frequencies(&self, elapsed: Duration) {
+        Self::decay_locked(&self.config, &mut self.state(), elapsed);
+    }
+
+    fn decay_locked(config: &CacheConfig, state: &mut CacheState, elapsed: Duration) {
+        let Some(half_life) = config.access_decay_half_life else {
+            return;
+        };
+        if half_life.is_zero() {
+            return;
+        }
+
+        let factor = 0.5_f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64());
+        for (_, entry) in state.memory_cache.iter_mut() {
+            entry.access_frequency *= factor;
+        }
+    }
+
+    /// Purge expired entries and decay access frequencies for the time since the last run
+    pub fn run_maintenance(&self) {
+        Self::maintain(&self.config, &mut self.state());
+    }
+
+    fn maintain(config: &CacheConfig, state: &mut CacheState) {
+        let elapsed = state.last_maintenance.elapsed();
+        state.last_maintenance = Instant::now();
+
+        Self::decay_locked(config, state, elapsed);
+        Self::purge_expired(config, state);
+    }
+
+    /// Run maintenance every `maintenance_interval` in the background, so a
+    /// cache that is only read still decays and expires. Replaces any task
+    /// already running, which would otherwise keep the old config. Needs a
+    /// Tokio runtime; without one only `run_maintenance` does the work.
+    fn start_maintenance(&mut self) {
+        if let Some(task) = self.maintenance_task.take() {
+            task.abort();
+        }
+        let interval = self.config.maintenance_interval;
+        if interval.is_zero() || tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+
+        let state = Arc::downgrade(&self.state);
+        let config = self.config.clone();
+        self.maintenance_task = Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(state) = state.upgrade() else {
+                    return;
+                };
+                Self::maintain(&config, &mut state.lock().unwrap());
+            }
+        }));
     }
 
-    pub async fn persist_to_disk(&mut self) -> Result<(), CacheError> {
+    /// Snapshot of the current statistics
+    pub fn get_stats(&self) -> CacheStats {
+        self.state().stats.clone()
+    }
+
+    /// Statistics broken down by the model in each key. Covers every model
+    /// looked up or currently cached; aggregate-only counters such as
+    /// evictions and disk writes stay at zero.
+    pub fn get_stats_by_model(&self) -> HashMap<String, CacheStats> {
+        let state = self.state();
+        let mut by_model = state.model_stats.clone();
+        for (key, entry) in state.memory_cache.iter() {
+            let stats = model_stats(&mut by_model, &key.model);
+            stats.total_entries += 1;
+            stats.memory_usage_bytes += Self::estimate_entry_size(key, entry);
+        }
+        by_model
+    }
+
+    /// Write every in-memory entry to disk now, after any queued writes
+    pub async fn persist_to_disk(&self) -> Result<(), CacheError> {
         if !self.config.enable_persistence {
             return Ok(());
         }
+        self.flush().await;
 
         let cache_dir = self.get_cache_dir()?;
         fs::create_dir_all(&cache_dir).await
             .map_err(|e| CacheError::Persistence(format!("Failed to create cache directory: {}", e)))?;
 
         // Save all memory cache entries to disk
-        let entries: Vec<_> = self.memory_cache.iter()
+        let entries: Vec<_> = self.state().memory_cache.iter()
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect();
             
@@ -364,92 +886,234 @@ impl CacheManager {
         Ok(())
     }
 
-    pub fn clear(&mut self) {
-        self.memory_cache.clear();
-        self.stats.total_entries = 0;
-        self.stats.memory_usage_bytes = 0;
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.memory_cache.clear();
+        state.failures.clear();
+        state.stats.total_entries = 0;
+        state.stats.memory_usage_bytes = 0;
     }
 
-    async fn handle_memory_pressure(&mut self) -> Result<(), CacheError> {
+    /// Evict entries per the eviction policy when over the memory threshold,
+    /// returning them so the caller can persist them once the lock is released
+    fn handle_memory_pressure(&self, state: &mut CacheState) -> Vec<(CacheKey, CacheEntry)> {
+        let mut evicted = Vec::new();
         if let Some(max_bytes) = self.config.max_memory_bytes {
-            let current_usage = self.estimate_memory_usage();
+            let current_usage = Self::estimate_memory_usage(state);
             let threshold = (max_bytes as f64 * self.config.memory_pressure_threshold) as usize;
             
             if current_usage > threshold {
                 // Reduce cache size by 25%
-                let target_size = (self.memory_cache.len() as f64 * 0.75) as usize;
+                let target_size = (state.memory_cache.len() as f64 * 0.75) as usize;
                 
-                while self.memory_cache.len() > target_size {
-                    if let Some((key, entry)) = self.memory_cache.pop_lru() {
-                        self.stats.evictions += 1;
-                        
-                        // Save to disk if persistence is enabled
-                        if self.config.enable_persistence {
-                            self.save_to_disk(&key, &entry).await?;
-                        }
+                while state.memory_cache.len() > target_size {
+                    if let Some(popped) = state.pop_victim(self.config.eviction_policy) {
+                        state.stats.evictions += 1;
+                        evicted.push(popped);
                     } else {
                         break;
                     }
                 }
                 
-                self.update_stats();
+                self.update_stats(state);
             }
         }
         
-        Ok(())
+        evicted
     }
 
-    fn estimate_memory_usage(&self) -> usize {
+    fn estimate_memory_usage(state: &CacheState) -> usize {
         // Rough estimation: each entry is approximately the size of the response plus overhead
-        self.memory_cache.iter()
-            .map(|(key, entry)| {
-                key.model.len() + 
-                entry.response.len() + 
-                entry.metadata.model.len() + 
-                entry.metadata.backend_type.len() + 
-                200 // overhead estimate
-            })
+        state.memory_cache.iter()
+            .map(|(key, entry)| Self::estimate_entry_size(key, entry))
             .sum()
     }
 
-    fn update_stats(&mut self) {
-        self.stats.total_entries = self.memory_cache.len();
-        self.stats.memory_usage_bytes = self.estimate_memory_usage();
+    fn estimate_entry_size(key: &CacheKey, entry: &CacheEntry) -> usize {
+        key.model.len() +
+        entry.response.len() +
+        entry.metadata.model.len() +
+        entry.metadata.backend_type.len() +
+        200 // overhead estimate
     }
 
-    async fn save_to_disk(&mut self, key: &CacheKey, entry: &CacheEntry) -> Result<(), CacheError> {
-        let cache_dir = self.get_cache_dir()?;
+    fn update_stats(&self, state: &mut CacheState) {
+        Self::refresh_stats(state);
+    }
+
+    fn refresh_stats(state: &mut CacheState) {
+        state.stats.total_entries = state.memory_cache.len();
+        state.stats.memory_usage_bytes = Self::estimate_memory_usage(state);
+    }
+
+    /// Wait until every write queued so far has reached disk. Call before
+    /// shutdown so no entries are lost with the runtime.
+    pub async fn flush(&self) {
+        let Some(queue) = self.persist_queue.get() else {
+            return;
+        };
+
+        let (done, acknowledged) = oneshot::channel();
+        if queue.send(PersistJob::Flush(done)).await.is_ok() {
+            let _ = acknowledged.await;
+        }
+    }
+
+    /// Hand an entry to the background writer, waiting only if its queue is full
+    async fn queue_write(&self, key: &CacheKey, entry: &CacheEntry) -> Result<(), CacheError> {
+        let persistent_entry = PersistentCacheEntry {
+            key: Some(key.clone()),
+            ..PersistentCacheEntry::from(entry)
+        };
+
+        self.persist_queue()
+            .send(PersistJob::Write(key.clone(), Box::new(persistent_entry)))
+            .await
+            .map_err(|_| CacheError::Persistence("Background cache writer stopped".to_string()))
+    }
+
+    fn persist_queue(&self) -> &mpsc::Sender<PersistJob> {
+        self.persist_queue.get_or_init(|| {
+            let (sender, mut receiver) = mpsc::channel(PERSIST_QUEUE_CAPACITY);
+            let state = self.state.clone();
+            let config = self.config.clone();
+
+            tokio::spawn(async move {
+                while let Some(job) = receiver.recv().await {
+                    match job {
+                        PersistJob::Write(key, entry) => match Self::write_entry_file(&config, &key, &entry).await {
+                            Ok(size) => {
+                                let mut state = state.lock().unwrap();
+                                state.stats.disk_writes += 1;
+                                state.record_disk_file(key.file_stem(), Some(size));
+                            }
+                            Err(e) => tracing::warn!(error = %e, "Failed to persist cache entry"),
+                        },
+                        PersistJob::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            });
+
+            sender
+        })
+    }
+
+    async fn save_to_disk(&self, key: &CacheKey, entry: &CacheEntry) -> Result<(), CacheError> {
+        let persistent_entry = PersistentCacheEntry {
+            key: Some(key.clone()),
+            ..PersistentCacheEntry::from(entry)
+        };
+        let size = Self::write_entry_file(&self.config, key, &persistent_entry).await?;
+
+        let mut state = self.state();
+        state.stats.disk_writes += 1;
+        state.record_disk_file(key.file_stem(), Some(size));
+        Ok(())
+    }
+
+    /// Write an entry file, returning its size in bytes
+    async fn write_entry_file(
+        config: &CacheConfig,
+        key: &CacheKey,
+        persistent_entry: &PersistentCacheEntry,
+    ) -> Result<u64, CacheError> {
+        let cache_dir = config.cache_dir.clone()
+            .ok_or_else(|| CacheError::Persistence("Cache directory not configured".to_string()))?;
         fs::create_dir_all(&cache_dir).await
             .map_err(|e| CacheError::Persistence(format!("Failed to create cache directory: {}", e)))?;
             
-        let file_path = cache_dir.join(format!("{:x}.json", key.prompt_hash));
+        let (extension, stale_extension) = if config.compress_disk_entries {
+            (COMPRESSED_ENTRY_EXTENSION, ENTRY_EXTENSION)
+        } else {
+            (ENTRY_EXTENSION, COMPRESSED_ENTRY_EXTENSION)
+        };
+        let file_path = cache_dir.join(format!("{}.{}", key.file_stem(), extension));
         
-        let persistent_entry = PersistentCacheEntry::from(entry);
-        let serialized = serde_json::to_string(&persistent_entry)?;
+        let serialized = serde_json::to_vec(persistent_entry)?;
+        let contents = if config.compress_disk_entries {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&serialized)?;
+            encoder.finish()?
+        } else {
+            serialized
+        };
         
-        fs::write(&file_path, serialized).await
+        let size = contents.len() as u64;
+        fs::write(&file_path, contents).await
             .map_err(|e| CacheError::Persistence(format!("Failed to write cache file: {}", e)))?;
-        
-        self.stats.disk_writes += 1;
-        Ok(())
+
+        // Don't leave a copy in the other format behind to shadow this one
+        let _ = fs::remove_file(cache_dir.join(format!("{}.{}", key.file_stem(), stale_extension))).await;
+        Ok(size)
+    }
+
+    /// File stem shared by both the plain and compressed forms of an entry file
+    fn entry_file_stem(path: &Path) -> Option<String> {
+        let name = path.file_name()?.to_str()?;
+        let stem = name.strip_suffix(".json.gz").or_else(|| name.strip_suffix(".json"))?;
+        Some(stem.to_string())
     }
 
     async fn load_from_disk_by_key(&self, key: &CacheKey) -> Result<Option<CacheEntry>, CacheError> {
         let cache_dir = self.get_cache_dir()?;
-        let file_path = cache_dir.join(format!("{:x}.json", key.prompt_hash));
+        // Either format is readable, whatever the current setting
+        let Some(file_path) = [COMPRESSED_ENTRY_EXTENSION, ENTRY_EXTENSION]
+            .iter()
+            .map(|extension| cache_dir.join(format!("{}.{}", key.file_stem(), extension)))
+            .find(|path| path.exists())
+        else {
+            return Ok(None);
+        };
         
-        if !file_path.exists() {
+        let persistent_entry = Self::read_persisted_entry(&file_path).await?;
+        if self.is_persisted_entry_expired(&persistent_entry) {
             return Ok(None);
         }
-        
-        let content = fs::read_to_string(&file_path).await
-            .map_err(|e| CacheError::Persistence(format!("Failed to read cache file: {}", e)))?;
-        
-        let persistent_entry: PersistentCacheEntry = serde_json::from_str(&content)?;
         Ok(Some(persistent_entry.into()))
     }
 
-    async fn load_from_disk(&mut self) -> Result<(), CacheError> {
+    /// Read an entry file, gunzipping it first if it is compressed
+    async fn read_persisted_entry(path: &Path) -> Result<PersistentCacheEntry, CacheError> {
+        let content = fs::read(path).await
+            .map_err(|e| CacheError::Persistence(format!("Failed to read cache file: {}", e)))?;
+
+        if path.extension().is_some_and(|extension| extension == "gz") {
+            let mut json = Vec::new();
+            GzDecoder::new(content.as_slice()).read_to_end(&mut json)?;
+            Ok(serde_json::from_slice(&json)?)
+        } else {
+            Ok(serde_json::from_slice(&content)?)
+        }
+    }
+
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        self.is_expired_at(entry, Instant::now())
+    }
+
+    fn is_expired_at(&self, entry: &CacheEntry, now: Instant) -> bool {
+        Self::expired_at(&self.config, entry, now)
+    }
+
+    fn expired_at(config: &CacheConfig, entry: &CacheEntry, now: Instant) -> bool {
+        let since = if config.sliding_ttl {
+            entry.last_accessed
+        } else {
+            entry.created_at
+        };
+        now.saturating_duration_since(since) > config.ttl
+    }
+
+    /// TTL check against wall-clock time, which survives process restarts
+    fn is_persisted_entry_expired(&self, entry: &PersistentCacheEntry) -> bool {
+        entry.created_at
+            .elapsed()
+            .map(|age| age > self.config.ttl)
+            .unwrap_or(false)
+    }
+
+    async fn load_from_disk(&self) -> Result<(), CacheError> {
         let cache_dir = self.get_cache_dir()?;
         
         if !cache_dir.exists() {
@@ -463,23 +1127,29 @@ impl CacheManager {
             .map_err(|e| CacheError::Persistence(format!("Failed to read directory entry: {}", e)))? {
             
             if let Some(extension) = entry.path().extension() {
-                if extension == "json" {
-                    if let Ok(content) = fs::read_to_string(entry.path()).await {
-                        if let Ok(persistent_entry) = serde_json::from_str::<PersistentCacheEntry>(&content) {
-                            let cache_entry: CacheEntry = persistent_entry.into();
-                            
-                            // Check TTL before loading
-                            if cache_entry.created_at.elapsed() <= self.config.ttl {
-                                // Create a dummy key for loading - in practice, we'd need to store the key
-                                // For now, we'll skip loading from disk on startup to avoid this complexity
-                                // This would be improved in a production implementation
-                            }
+                if extension == "json" || extension == "gz" {
+                    if let (Some(stem), Ok(file_metadata)) = (Self::entry_file_stem(&entry.path()), entry.metadata().await) {
+                        self.state().record_disk_file(stem, Some(file_metadata.len()));
+                    }
+
+                    if let Ok(mut persistent_entry) = Self::read_persisted_entry(&entry.path()).await {
+                        // Entries written before keys were persisted can only be
+                        // found lazily through `get`
+                        let Some(key) = persistent_entry.key.take() else {
+                            continue;
+                        };
+
+                        if !self.is_persisted_entry_expired(&persistent_entry) {
+                            let mut state = self.state();
+                            state.insert(key, persistent_entry.into(), self.config.eviction_policy);
+                            state.stats.disk_reads += 1;
                         }
                     }
                 }
             }
         }
-        
+
+        self.update_stats(&mut self.state());
         Ok(())
     }
 
@@ -488,54 +1158,56 @@ impl CacheManager {
             .ok_or_else(|| CacheError::Persistence("Cache directory not configured".to_string()))
     }
 
-    pub fn reduce_cache_size(&mut self, target_ratio: f64) {
-        let target_size = (self.memory_cache.len() as f64 * target_ratio) as usize;
+    pub fn reduce_cache_size(&self, target_ratio: f64) {
+        let mut state = self.state();
+        let target_size = (state.memory_cache.len() as f64 * target_ratio) as usize;
         
-        while self.memory_cache.len() > target_size {
-            if self.memory_cache.pop_lru().is_some() {
-                self.stats.evictions += 1;
+        while state.memory_cache.len() > target_size {
+            if state.pop_victim(self.config.eviction_policy).is_some() {
+                state.stats.evictions += 1;
             } else {
                 break;
             }
         }
         
-        self.update_stats();
+        self.update_stats(&mut state);
     }
 
     pub fn get_detailed_stats(&self) -> DetailedCacheStats {
+        let state = self.state();
         DetailedCacheStats {
-            basic_stats: self.stats.clone(),
-            memory_pressure_ratio: self.get_memory_pressure_ratio(),
-            average_entry_size: self.get_average_entry_size(),
-            cache_efficiency: self.calculate_cache_efficiency(),
+            basic_stats: state.stats.clone(),
+            memory_pressure_ratio: self.get_memory_pressure_ratio(&state),
+            average_entry_size: Self::get_average_entry_size(&state),
+            cache_efficiency: Self::calculate_cache_efficiency(&state.stats),
         }
     }
 
-    fn get_memory_pressure_ratio(&self) -> f64 {
+    fn get_memory_pressure_ratio(&self, state: &CacheState) -> f64 {
         if let Some(max_bytes) = self.config.max_memory_bytes {
-            self.stats.memory_usage_bytes as f64 / max_bytes as f64
+            state.stats.memory_usage_bytes as f64 / max_bytes as f64
         } else {
             0.0
         }
     }
 
-    fn get_average_entry_size(&self) -> usize {
-        if self.memory_cache.is_empty() {
+    fn get_average_entry_size(state: &CacheState) -> usize {
+        if state.memory_cache.is_empty() {
             0
         } else {
-            self.stats.memory_usage_bytes / self.memory_cache.len()
+            state.stats.memory_usage_bytes / state.memory_cache.len()
         }
     }
 
-    fn calculate_cache_efficiency(&self) -> f64 {
-        let total_requests = self.stats.hits + self.stats.misses;
+    fn calculate_cache_efficiency(stats: &CacheStats) -> f64 {
+        let total_requests = stats.hits + stats.misses;
         if total_requests == 0 {
             0.0
         } else {
             // Efficiency considers both hit ratio and eviction ratio
-            let hit_ratio = self.stats.hit_ratio();
-            let eviction_penalty = if self.stats.total_entries > 0 {
-                self.stats.evictions as f64 / self.stats.total_entries as f64
+            let hit_ratio = stats.hit_ratio();
+            let eviction_penalty = if stats.total_entries > 0 {
+                stats.evictions as f64 / stats.total_entries as f64
             } else {
                 0.0
             };
@@ -545,7 +1217,7 @@ impl CacheManager {
     }
 
     pub async fn put_streaming(
-        &mut self,
+        &self,
         key: CacheKey,
         tokens: Vec<StreamToken>,
         metadata: ResponseMetadata,
@@ -556,6 +1228,7 @@ impl CacheManager {
 
         // Combine all tokens into a single response
         let response = tokens.iter()
+            .filter(|token| token.kind == TokenKind::Answer)
             .map(|token| token.content.as_str())
             .collect::<Vec<_>>()
             .join("");
@@ -563,49 +1236,41 @@ impl CacheManager {
         let entry = CacheEntry {
             response,
             created_at: Instant::now(),
+            last_accessed: Instant::now(),
             access_count: 1,
+            access_frequency: 1.0,
             metadata: metadata.clone(),
             is_streaming: true,
             stream_tokens: Some(tokens),
+            embedding: None,
         };
 
-        // Check memory pressure before adding
-        self.handle_memory_pressure().await?;
-
-        // Store in memory cache
-        if let Some(evicted) = self.memory_cache.push(key.clone(), entry.clone()) {
-            self.stats.evictions += 1;
-            
-            // If persistence is enabled, save evicted entry to disk
-            if self.config.enable_persistence {
-                self.save_to_disk(&evicted.0, &evicted.1).await?;
-            }
-        }
-
-        // Also save to disk if persistence is enabled
-        if self.config.enable_persistence {
-            self.save_to_disk(&key, &entry).await?;
-        }
-
-        self.update_stats();
-        Ok(())
+        self.store_entry(key, entry).await
     }
 
-    pub async fn get_streaming(&mut self, key: &CacheKey) -> Option<Vec<StreamToken>> {
-        if let Some(entry) = self.memory_cache.get_mut(key) {
-            // Check TTL
-            if entry.created_at.elapsed() > self.config.ttl {
-                self.memory_cache.pop(key);
-                self.stats.misses += 1;
-                return None;
-            }
+    pub async fn get_streaming(&self, key: &CacheKey) -> Option<Vec<StreamToken>> {
+        {
+            let mut state = self.state();
+            let state = &mut *state;
+            if let Some(entry) = state.memory_cache.get_mut(key) {
+                // Check TTL
+                if self.is_expired(entry) {
+                    state.memory_cache.pop(key);
+                    state.stats.misses += 1;
+                    model_stats(&mut state.model_stats, &key.model).misses += 1;
+                    return None;
+                }
 
-            // Update access count
-            entry.access_count += 1;
-            self.stats.hits += 1;
-            
-            if entry.is_streaming {
-                return entry.stream_tokens.clone();
+                // Update access count
+                entry.last_accessed = Instant::now();
+                entry.access_count += 1;
+                entry.access_frequency += 1.0;
+                state.stats.hits += 1;
+                model_stats(&mut state.model_stats, &key.model).hits += 1;
+                
+                if entry.is_streaming {
+                    return entry.stream_tokens.clone();
+                }
             }
         }
 
@@ -613,27 +1278,34 @@ impl CacheManager {
         if self.config.enable_persistence {
             if let Ok(Some(entry)) = self.load_from_disk_by_key(key).await {
                 // Check TTL for disk entry
-                if entry.created_at.elapsed() <= self.config.ttl && entry.is_streaming {
+                if !self.is_expired(&entry) && entry.is_streaming {
                     let tokens = entry.stream_tokens.clone();
                     
                     // Put back in memory cache
                     let mut updated_entry = entry;
                     updated_entry.access_count += 1;
-                    self.memory_cache.put(key.clone(), updated_entry);
+                    updated_entry.access_frequency += 1.0;
+                    let mut state = self.state();
+                    state.insert(key.clone(), updated_entry, self.config.eviction_policy);
                     
-                    self.stats.hits += 1;
-                    self.stats.disk_reads += 1;
+                    state.stats.hits += 1;
+                    state.stats.disk_reads += 1;
+                    let model = model_stats(&mut state.model_stats, &key.model);
+                    model.hits += 1;
+                    model.disk_reads += 1;
                     return tokens;
                 }
             }
         }
 
-        self.stats.misses += 1;
+        let mut state = self.state();
+        state.stats.misses += 1;
+        model_stats(&mut state.model_stats, &key.model).misses += 1;
         None
     }
 
     pub async fn create_cached_stream(
-        &mut self,
+        &self,
         key: &CacheKey,
         stream_id: StreamId,
     ) -> Option<StreamResponse> {
@@ -668,28 +1340,46 @@ impl CacheManager {
         }
     }
 
-    pub async fn warm_cache(&mut self, keys: Vec<CacheKey>) -> Result<(), CacheError> {
+    pub async fn warm_cache(&self, keys: Vec<CacheKey>) -> Result<(), CacheError> {
         if !self.config.enable_persistence {
             return Ok(());
         }
 
         for key in keys {
-            if !self.memory_cache.contains(&key) {
-                if let Ok(Some(entry)) = self.load_from_disk_by_key(&key).await {
-                    // Check TTL before warming
-                    if entry.created_at.elapsed() <= self.config.ttl {
-                        self.memory_cache.put(key, entry);
-                        self.stats.disk_reads += 1;
-                    }
+            if self.state().memory_cache.contains(&key) {
+                continue;
+            }
+            if let Ok(Some(entry)) = self.load_from_disk_by_key(&key).await {
+                // Check TTL before warming
+                if !self.is_expired(&entry) {
+                    let mut state = self.state();
+                    state.insert(key, entry, self.config.eviction_policy);
+                    state.stats.disk_reads += 1;
                 }
             }
         }
 
-        self.update_stats();
+        self.update_stats(&mut self.state());
         Ok(())
     }
 }
 
+/// Cosine similarity of two vectors; 0.0 when their lengths differ or either is zero
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetailedCacheStats {
     pub basic_stats: CacheStats,
@@ -713,6 +1403,24 @@ mod tests {
             cache_dir: Some(PathBuf::from("test_cache")),
             max_memory_bytes: Some(1024),
             memory_pressure_threshold: 0.8,
+            access_decay_half_life: None,
+            maintenance_interval: Duration::from_secs(60),
+            sliding_ttl: false,
+            eviction_policy: EvictionPolicy::Lru,
+            compress_disk_entries: false,
+            semantic: SemanticCacheConfig::default(),
+            negative_ttl: None,
+        }
+    }
+
+    /// Long-lived entries persisted under `temp_dir`, with no memory cap
+    fn persistent_test_config(temp_dir: &tempfile::TempDir) -> CacheConfig {
+        CacheConfig {
+            ttl: Duration::from_secs(3600),
+            enable_persistence: true,
+            cache_dir: Some(temp_dir.path().to_path_buf()),
+            max_memory_bytes: None,
+            ..create_test_config()
         }
     }
 
@@ -720,14 +1428,19 @@ mod tests {
         ResponseMetadata {
             model: "test-model".to_string(),
             tokens_used: Some(100),
+            prompt_tokens: None,
+            completion_tokens: None,
             response_time: Duration::from_millis(500),
             backend_type: "test".to_string(),
+            backend: None,
+            tags: Vec::new(),
+            request_id: None,
         }
     }
 
     #[tokio::test]
     async fn test_cache_put_and_get() {
-        let mut cache = CacheManager::new(create_test_config());
+        let cache = CacheManager::new(create_test_config());
         let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
         let response = "test response".to_string();
         let metadata = create_test_metadata();
@@ -736,24 +1449,24 @@ mod tests {
         
         let retrieved = cache.get(&key).await;
         assert_eq!(retrieved, Some(response));
-        assert_eq!(cache.stats.hits, 1);
-        assert_eq!(cache.stats.misses, 0);
+        assert_eq!(cache.get_stats().hits, 1);
+        assert_eq!(cache.get_stats().misses, 0);
     }
 
     #[tokio::test]
     async fn test_cache_miss() {
-        let mut cache = CacheManager::new(create_test_config());
+        let cache = CacheManager::new(create_test_config());
         let key = CacheKey::new("nonexistent prompt", "test-model", &HashMap::new());
         
         let retrieved = cache.get(&key).await;
         assert_eq!(retrieved, None);
-        assert_eq!(cache.stats.hits, 0);
-        assert_eq!(cache.stats.misses, 1);
+        assert_eq!(cache.get_stats().hits, 0);
+        assert_eq!(cache.get_stats().misses, 1);
     }
 
     #[tokio::test]
     async fn test_lru_eviction() {
-        let mut cache = CacheManager::new(create_test_config());
+        let cache = CacheManager::new(create_test_config());
         let metadata = create_test_metadata();
 
         // Fill cache to capacity
@@ -776,9 +1489,65 @@ mod tests {
         assert_eq!(retrieved, Some("response 3".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_lfu_eviction_keeps_hot_entry() {
+        let config = CacheConfig {
+            eviction_policy: EvictionPolicy::Lfu,
+            ..create_test_config()
+        };
+        let cache = CacheManager::new(config);
+        let metadata = create_test_metadata();
+        let key = |i: usize| CacheKey::new(&format!("prompt {}", i), "test-model", &HashMap::new());
+
+        // The hot entry is popular but the least recently used once the others arrive
+        cache.put(key(0), "response 0".to_string(), metadata.clone()).await.unwrap();
+        for _ in 0..5 {
+            cache.get(&key(0)).await;
+        }
+        cache.put(key(1), "response 1".to_string(), metadata.clone()).await.unwrap();
+        cache.put(key(2), "response 2".to_string(), metadata.clone()).await.unwrap();
+
+        cache.put(key(3), "response 3".to_string(), metadata).await.unwrap();
+
+        assert_eq!(cache.get(&key(0)).await, Some("response 0".to_string()));
+        assert_eq!(cache.get(&key(1)).await, None);
+        assert_eq!(cache.get(&key(3)).await, Some("response 3".to_string()));
+        assert_eq!(cache.get_stats().evictions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_lfu_eviction_uses_decayed_frequency() {
+        let config = CacheConfig {
+            eviction_policy: EvictionPolicy::Lfu,
+            ttl: Duration::from_secs(3600),
+            access_decay_half_life: Some(Duration::from_secs(3600)),
+            ..create_test_config()
+        };
+        let cache = CacheManager::new(config);
+        let metadata = create_test_metadata();
+        let key = |i: usize| CacheKey::new(&format!("prompt {}", i), "test-model", &HashMap::new());
+
+        // Entry 0 was popular a day ago; entries 1 and 2 are in use now
+        cache.put(key(0), "response 0".to_string(), metadata.clone()).await.unwrap();
+        for _ in 0..5 {
+            cache.get(&key(0)).await;
+        }
+        cache.decay_access_frequencies(Duration::from_secs(24 * 3600));
+        for i in 1..3 {
+            cache.put(key(i), format!("response {}", i), metadata.clone()).await.unwrap();
+            cache.get(&key(i)).await;
+        }
+
+        cache.put(key(3), "response 3".to_string(), metadata).await.unwrap();
+
+        assert_eq!(cache.get(&key(0)).await, None);
+        assert_eq!(cache.get(&key(1)).await, Some("response 1".to_string()));
+        assert_eq!(cache.get(&key(2)).await, Some("response 2".to_string()));
+    }
+
     #[tokio::test]
     async fn test_ttl_expiration() {
-        let mut cache = CacheManager::new(create_test_config());
+        let cache = CacheManager::new(create_test_config());
         let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
         let response = "test response".to_string();
         let metadata = create_test_metadata();
@@ -795,12 +1564,36 @@ mod tests {
         // Should be expired now
         let retrieved = cache.get(&key).await;
         assert_eq!(retrieved, None);
-        assert_eq!(cache.stats.misses, 1);
+        assert_eq!(cache.get_stats().misses, 1);
+    }
+
+    #[tokio::test]
+    async fn test_sliding_ttl_refreshes_on_access() {
+        let config = CacheConfig {
+            ttl: Duration::from_millis(300),
+            sliding_ttl: true,
+            ..create_test_config()
+        };
+        let cache = CacheManager::new(config);
+        let busy = CacheKey::new("busy prompt", "test-model", &HashMap::new());
+        let idle = CacheKey::new("idle prompt", "test-model", &HashMap::new());
+
+        cache.put(busy.clone(), "busy".to_string(), create_test_metadata()).await.unwrap();
+        cache.put(idle.clone(), "idle".to_string(), create_test_metadata()).await.unwrap();
+
+        // Keep touching one entry well past the original TTL
+        for _ in 0..5 {
+            sleep(Duration::from_millis(150)).await;
+            assert_eq!(cache.get(&busy).await, Some("busy".to_string()));
+        }
+
+        assert_eq!(cache.get(&idle).await, None);
+        assert_eq!(cache.get(&busy).await, Some("busy".to_string()));
     }
 
     #[tokio::test]
     async fn test_model_invalidation() {
-        let mut cache = CacheManager::new(create_test_config());
+        let cache = CacheManager::new(create_test_config());
         let metadata = create_test_metadata();
 
         // Add entries for different models
@@ -822,14 +1615,43 @@ mod tests {
         assert_eq!(retrieved, Some("response 2".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_tag_invalidation() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = persistent_test_config(&temp_dir);
+        let cache = CacheManager::new(config);
+        let tagged = |tag: &str| ResponseMetadata {
+            tags: vec![tag.to_string()],
+            request_id: None,
+            ..create_test_metadata()
+        };
+
+        let key1 = CacheKey::new("prompt 1", "test-model", &HashMap::new());
+        let key2 = CacheKey::new("prompt 2", "test-model", &HashMap::new());
+        let key3 = CacheKey::new("prompt 3", "test-model", &HashMap::new());
+        cache.put(key1.clone(), "response 1".to_string(), tagged("tenant-a")).await.unwrap();
+        cache.put(key2.clone(), "response 2".to_string(), tagged("tenant-b")).await.unwrap();
+        cache.put(key3.clone(), "response 3".to_string(), tagged("tenant-a")).await.unwrap();
+
+        assert_eq!(cache.invalidate_by_tag("tenant-a").await.unwrap(), 2);
+
+        // Gone from memory and disk alike
+        assert_eq!(cache.get(&key1).await, None);
+        assert_eq!(cache.get(&key3).await, None);
+        assert_eq!(cache.get(&key2).await, Some("response 2".to_string()));
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        assert_eq!(cache.invalidate_by_tag("tenant-a").await.unwrap(), 0);
+    }
+
     #[tokio::test]
     async fn test_cache_stats() {
-        let mut cache = CacheManager::new(create_test_config());
+        let cache = CacheManager::new(create_test_config());
         let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
         let metadata = create_test_metadata();
 
         // Initial stats
-        assert_eq!(cache.stats.hit_ratio(), 0.0);
+        assert_eq!(cache.get_stats().hit_ratio(), 0.0);
 
         // Add entry and access it
         cache.put(key.clone(), "response".to_string(), metadata).await.unwrap();
@@ -840,9 +1662,39 @@ mod tests {
         let key2 = CacheKey::new("other prompt", "test-model", &HashMap::new());
         cache.get(&key2).await;
 
-        assert_eq!(cache.stats.hits, 2);
-        assert_eq!(cache.stats.misses, 1);
-        assert_eq!(cache.stats.hit_ratio(), 2.0 / 3.0);
+        assert_eq!(cache.get_stats().hits, 2);
+        assert_eq!(cache.get_stats().misses, 1);
+        assert_eq!(cache.get_stats().hit_ratio(), 2.0 / 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_cache_stats_by_model() {
+        let cache = CacheManager::new(create_test_config());
+        let metadata = create_test_metadata();
+        let key_a = CacheKey::new("prompt", "model-a", &HashMap::new());
+        let key_b = CacheKey::new("prompt", "model-b", &HashMap::new());
+
+        cache.put(key_a.clone(), "a".to_string(), metadata.clone()).await.unwrap();
+        cache.put(key_b.clone(), "b".to_string(), metadata).await.unwrap();
+        cache.get(&key_a).await;
+        cache.get(&key_a).await;
+        cache.get(&key_b).await;
+        cache.get(&CacheKey::new("other", "model-b", &HashMap::new())).await;
+        cache.get(&CacheKey::new("other", "model-c", &HashMap::new())).await;
+
+        let by_model = cache.get_stats_by_model();
+        let counts = |model: &str| {
+            let stats = &by_model[model];
+            (stats.hits, stats.misses, stats.total_entries)
+        };
+        assert_eq!(counts("model-a"), (2, 0, 1));
+        assert_eq!(counts("model-b"), (1, 1, 1));
+        assert_eq!(counts("model-c"), (0, 1, 0));
+        assert!(by_model["model-a"].memory_usage_bytes > 0);
+
+        // The aggregate still covers every model
+        let stats = cache.get_stats();
+        assert_eq!((stats.hits, stats.misses, stats.total_entries), (3, 2, 2));
     }
 
     #[tokio::test]
@@ -878,9 +1730,16 @@ mod tests {
             cache_dir: Some(PathBuf::from("test_cache")),
             max_memory_bytes: Some(100), // Very small limit to trigger pressure
             memory_pressure_threshold: 0.5,
+            access_decay_half_life: None,
+            maintenance_interval: Duration::from_secs(60),
+            sliding_ttl: false,
+            eviction_policy: EvictionPolicy::Lru,
+            compress_disk_entries: false,
+            semantic: SemanticCacheConfig::default(),
+            negative_ttl: None,
         };
 
-        let mut cache = CacheManager::new(config);
+        let cache = CacheManager::new(config);
         let metadata = create_test_metadata();
 
         // Add several large entries
@@ -891,13 +1750,13 @@ mod tests {
         }
 
         // Cache should have been reduced due to memory pressure
-        assert!(cache.memory_cache.len() < 5);
-        assert!(cache.stats.evictions > 0);
+        assert!(cache.state().memory_cache.len() < 5);
+        assert!(cache.get_stats().evictions > 0);
     }
 
     #[tokio::test]
     async fn test_streaming_cache() {
-        let mut cache = CacheManager::new(create_test_config());
+        let cache = CacheManager::new(create_test_config());
         let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
         let metadata = create_test_metadata();
 
@@ -905,11 +1764,13 @@ mod tests {
             StreamToken {
                 content: "Hello".to_string(),
                 is_complete: false,
+                kind: TokenKind::Answer,
                 metadata: None,
             },
             StreamToken {
                 content: " world!".to_string(),
                 is_complete: true,
+                kind: TokenKind::Answer,
                 metadata: None,
             },
         ];
@@ -922,12 +1783,12 @@ mod tests {
         assert_eq!(retrieved.len(), 2);
         assert_eq!(retrieved[0].content, "Hello");
         assert_eq!(retrieved[1].content, " world!");
-        assert_eq!(cache.stats.hits, 1);
+        assert_eq!(cache.get_stats().hits, 1);
     }
 
     #[tokio::test]
     async fn test_cached_stream_creation() {
-        let mut cache = CacheManager::new(create_test_config());
+        let cache = CacheManager::new(create_test_config());
         let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
         let metadata = create_test_metadata();
 
@@ -935,11 +1796,13 @@ mod tests {
             StreamToken {
                 content: "Test".to_string(),
                 is_complete: false,
+                kind: TokenKind::Answer,
                 metadata: None,
             },
             StreamToken {
                 content: " response".to_string(),
                 is_complete: true,
+                kind: TokenKind::Answer,
                 metadata: None,
             },
         ];
@@ -964,7 +1827,7 @@ mod tests {
             enable_persistence: false, // Disable persistence for this test
             ..create_test_config()
         };
-        let mut cache = CacheManager::new(config);
+        let cache = CacheManager::new(config);
         
         let key1 = CacheKey::new("prompt 1", "test-model", &HashMap::new());
         let key2 = CacheKey::new("prompt 2", "test-model", &HashMap::new());
@@ -974,4 +1837,256 @@ mod tests {
         let result = cache.warm_cache(keys).await;
         assert!(result.is_ok());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_truncated_hash_collision_uses_separate_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = persistent_test_config(&temp_dir);
+        let cache = CacheManager::new(config);
+
+        // Simulate two prompts whose digests agree in the first 64 bits
+        let key_a = CacheKey::new("prompt a", "test-model", &HashMap::new());
+        let mut key_b = key_a.clone();
+        key_b.prompt_digest[31] ^= 0xff;
+        assert_eq!(key_a.prompt_hash, key_b.prompt_hash);
+        assert_ne!(key_a, key_b);
+        assert_ne!(key_a.file_stem(), key_b.file_stem());
+
+        cache.put(key_a.clone(), "response a".to_string(), create_test_metadata()).await.unwrap();
+        cache.put(key_b.clone(), "response b".to_string(), create_test_metadata()).await.unwrap();
+        cache.flush().await;
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+
+        // Both entries survive a round trip through disk
+        cache.state().memory_cache.clear();
+        assert_eq!(cache.get(&key_a).await, Some("response a".to_string()));
+        assert_eq!(cache.get(&key_b).await, Some("response b".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_load_from_disk_restores_entries() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = persistent_test_config(&temp_dir);
+
+        let mut params = HashMap::new();
+        params.insert("temperature".to_string(), serde_json::json!(0.7));
+        let key1 = CacheKey::new("prompt 1", "model-a", &params);
+        let key2 = CacheKey::new("prompt 2", "model-b", &HashMap::new());
+
+        let cache = CacheManager::new(config.clone());
+        cache.put(key1.clone(), "response 1".to_string(), create_test_metadata()).await.unwrap();
+        cache.put(key2.clone(), "response 2".to_string(), create_test_metadata()).await.unwrap();
+        cache.flush().await;
+        drop(cache);
+
+        let restored = CacheManager::new_with_persistence(config).await.unwrap();
+        assert_eq!(restored.get_stats().total_entries, 2);
+        assert!(restored.state().memory_cache.contains(&key1));
+
+        assert_eq!(restored.get(&key1).await, Some("response 1".to_string()));
+        assert_eq!(restored.get(&key2).await, Some("response 2".to_string()));
+        assert_eq!(restored.get_stats().hits, 2);
+    }
+
+    #[tokio::test]
+    async fn test_put_persists_in_background() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = persistent_test_config(&temp_dir);
+        let cache = CacheManager::new(config);
+        let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
+
+        let start = std::time::Instant::now();
+        cache.put(key.clone(), "test response".to_string(), create_test_metadata()).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        cache.flush().await;
+        assert!(temp_dir.path().join(format!("{}.json", key.file_stem())).exists());
+        assert_eq!(cache.get_stats().disk_writes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_single_key() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = persistent_test_config(&temp_dir);
+        let cache = CacheManager::new(config);
+        let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
+        let other = CacheKey::new("other prompt", "test-model", &HashMap::new());
+        cache.put(key.clone(), "test response".to_string(), create_test_metadata()).await.unwrap();
+        cache.put(other.clone(), "other response".to_string(), create_test_metadata()).await.unwrap();
+
+        assert!(cache.invalidate(&key).await.unwrap());
+        assert!(!temp_dir.path().join(format!("{}.json", key.file_stem())).exists());
+        // Neither memory nor disk can answer for it any more
+        assert!(cache.get(&key).await.is_none());
+        assert_eq!(cache.get(&other).await, Some("other response".to_string()));
+        assert!(!cache.invalidate(&key).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_disk_usage_reflects_cache_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = persistent_test_config(&temp_dir);
+        let cache = CacheManager::new(config.clone());
+        let key1 = CacheKey::new("prompt 1", "test-model", &HashMap::new());
+        let key2 = CacheKey::new("prompt 2", "test-model", &HashMap::new());
+        cache.put(key1, "response 1".to_string(), create_test_metadata()).await.unwrap();
+        cache.put(key2, "response 2".repeat(50), create_test_metadata()).await.unwrap();
+        cache.flush().await;
+
+        let on_disk: u64 = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .sum();
+        assert!(on_disk > 0);
+        assert_eq!(cache.get_stats().disk_usage_bytes, on_disk);
+
+        // Existing files are counted when the cache is reloaded
+        drop(cache);
+        let restored = CacheManager::new_with_persistence(config).await.unwrap();
+        assert_eq!(restored.get_stats().disk_usage_bytes, on_disk);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_entry_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = CacheConfig {
+            compress_disk_entries: true,
+            ..persistent_test_config(&temp_dir)
+        };
+        let key = CacheKey::new("long prompt", "test-model", &HashMap::new());
+        let response = "a long, repetitive response ".repeat(200);
+
+        let cache = CacheManager::new(config);
+        cache.put(key.clone(), response.clone(), create_test_metadata()).await.unwrap();
+        cache.flush().await;
+
+        let file_path = temp_dir.path().join(format!("{}.json.gz", key.file_stem()));
+        let bytes = std::fs::read(&file_path).unwrap();
+        assert_eq!(&bytes[..2], &[0x1f, 0x8b]);
+        assert!(bytes.len() < response.len());
+
+        // Read back through the disk path rather than memory
+        cache.state().memory_cache.clear();
+        assert_eq!(cache.get(&key).await, Some(response));
+        assert_eq!(cache.get_stats().disk_reads, 1);
+    }
+
+    /// Stand-in embedder: one axis per topic word, so paraphrases land close together
+    fn mock_embed(prompt: &str) -> Vec<f32> {
+        let prompt = prompt.to_lowercase();
+        ["weather", "today", "capital", "france"]
+            .iter()
+            .map(|word| if prompt.contains(word) { 1.0 } else { 0.05 })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_semantic_lookup_matches_paraphrases() {
+        let config = CacheConfig {
+            ttl: Duration::from_secs(3600),
+            max_memory_bytes: None,
+            semantic: SemanticCacheConfig {
+                enabled: true,
+                similarity_threshold: 0.9,
+                embedding_model: None,
+            },
+            ..create_test_config()
+        };
+        let cache = CacheManager::new(config);
+        let prompt = "What's the weather like today?";
+        let key = CacheKey::new(prompt, "test-model", &HashMap::new());
+        cache.put_with_embedding(key, "Sunny".to_string(), create_test_metadata(), Some(mock_embed(prompt))).await.unwrap();
+
+        let paraphrase = "How is the weather today?";
+        let paraphrase_key = CacheKey::new(paraphrase, "test-model", &HashMap::new());
+        assert_eq!(cache.get(&paraphrase_key).await, None);
+        assert_eq!(cache.get_similar(&paraphrase_key, &mock_embed(paraphrase)), Some("Sunny".to_string()));
+
+        let unrelated = "What is the capital of France?";
+        let unrelated_key = CacheKey::new(unrelated, "test-model", &HashMap::new());
+        assert_eq!(cache.get_similar(&unrelated_key, &mock_embed(unrelated)), None);
+
+        // Entries for another model never match
+        let other_model_key = CacheKey::new(paraphrase, "other-model", &HashMap::new());
+        assert_eq!(cache.get_similar(&other_model_key, &mock_embed(paraphrase)), None);
+
+        assert_eq!(cache.get_stats().semantic_hits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_shared_cache_concurrent_access() {
+        let config = CacheConfig {
+            max_memory_entries: 100,
+            ttl: Duration::from_secs(3600),
+            max_memory_bytes: None,
+            ..create_test_config()
+        };
+        let cache = std::sync::Arc::new(CacheManager::new(config));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|task| {
+                let cache = cache.clone();
+                tokio::spawn(async move {
+                    for i in 0..10 {
+                        let key = CacheKey::new(&format!("prompt {} {}", task, i), "test-model", &HashMap::new());
+                        cache.put(key.clone(), format!("response {} {}", task, i), create_test_metadata()).await.unwrap();
+                        assert_eq!(cache.get(&key).await, Some(format!("response {} {}", task, i)));
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let stats = cache.get_stats();
+        assert_eq!(stats.total_entries, 80);
+        assert_eq!(stats.hits, 80);
+        assert_eq!(stats.misses, 0);
+    }
+
+    #[tokio::test]
+    async fn test_access_frequency_decay() {
+        let config = CacheConfig {
+            ttl: Duration::from_secs(3600),
+            access_decay_half_life: Some(Duration::from_secs(3600)),
+            ..create_test_config()
+        };
+        let cache = CacheManager::new(config);
+        let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
+
+        cache.put(key.clone(), "response".to_string(), create_test_metadata()).await.unwrap();
+        for _ in 0..7 {
+            cache.get(&key).await;
+        }
+        assert_eq!(cache.state().memory_cache.peek(&key).unwrap().access_frequency, 8.0);
+
+        // One half-life later the entry is half as popular
+        cache.decay_access_frequencies(Duration::from_secs(3600));
+        assert!((cache.state().memory_cache.peek(&key).unwrap().access_frequency - 4.0).abs() < 1e-9);
+
+        // A week of inactivity leaves it effectively cold, but the raw count is kept
+        cache.decay_access_frequencies(Duration::from_secs(7 * 24 * 3600));
+        let entry = cache.state().memory_cache.peek(&key).unwrap().clone();
+        assert!(entry.access_frequency < 1e-9);
+        assert_eq!(entry.access_count, 8);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_runs_without_writes() {
+        let config = CacheConfig {
+            ttl: Duration::from_secs(3600),
+            access_decay_half_life: Some(Duration::from_millis(10)),
+            maintenance_interval: Duration::from_millis(20),
+            ..create_test_config()
+        };
+        let cache = CacheManager::new(config);
+        let key = CacheKey::new("test prompt", "test-model", &HashMap::new());
+        cache.put(key.clone(), "response".to_string(), create_test_metadata()).await.unwrap();
+        cache.get(&key).await;
+
+        // Only reads and idle time from here on; the background task still decays
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(cache.state().memory_cache.peek(&key).unwrap().access_frequency < 1.0);
+    }
+}
@@ -0,0 +1,240 @@
+//! Splitting long documents into overlapping chunks, e.g. to embed them.
+//!
+//! Chunks are sized with the token estimator in [`crate::tokenize`] and cut
+//! between sentences, preferring paragraph breaks, so no sentence is split
+//! unless it alone is over budget. Sentence detection is a heuristic on
+//! terminal punctuation, so abbreviations like "e.g." can end a sentence early.
+
+use crate::tokenize::estimate_tokens;
+use std::ops::Range;
+
+/// A piece of a larger text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    pub text: &'a str,
+    /// Byte range of `text` within the source, so `&source[range] == text`
+    pub range: Range<usize>,
+}
+
+/// A sentence, or a run of words from a sentence too long for one chunk
+struct Unit {
+    range: Range<usize>,
+    paragraph_end: bool,
+}
+
+/// Split `text` into chunks of at most `max_tokens` estimated tokens, each
+/// repeating up to `overlap` tokens of whole sentences from the end of the one
+/// before it. A single word over the budget becomes its own oversized chunk.
+pub fn chunk_text(text: &str, max_tokens: usize, overlap: usize) -> Vec<Chunk<'_>> {
+    let max_tokens = max_tokens.max(1);
+    let units = units(text, max_tokens);
+    let span_tokens = |first: usize, last: usize| {
+        estimate_tokens(&text[units[first].range.start..units[last].range.end])
+    };
+
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    while first < units.len() {
+        let mut last = first;
+        while last + 1 < units.len() && span_tokens(first, last + 1) <= max_tokens {
+            last += 1;
+        }
+        // Prefer ending on a paragraph break, as long as the chunk stays at least half full
+        if last + 1 < units.len() && !units[last].paragraph_end {
+            if let Some(end) = (first..last).rev().find(|&i| units[i].paragraph_end && span_tokens(first, i) * 2 >= max_tokens) {
+                last = end;
+            }
+        }
+
+        let range = units[first].range.start..units[last].range.end;
+        chunks.push(Chunk { text: &text[range.clone()], range });
+        if last + 1 == units.len() {
+            break;
+        }
+
+        // Step back over the trailing sentences that fit in the overlap, always moving forward
+        let mut next = last + 1;
+        while next - 1 > first && span_tokens(next - 1, last) <= overlap {
+            next -= 1;
+        }
+        first = next;
+    }
+    chunks
+}
+
+fn units(text: &str, max_tokens: usize) -> Vec<Unit> {
+    let mut units = Vec::new();
+    for paragraph in paragraphs(text) {
+        for sentence in sentences(text, paragraph) {
+            if estimate_tokens(&text[sentence.clone()]) <= max_tokens {
+                units.push(Unit { range: sentence, paragraph_end: false });
+            } else {
+                units.extend(pack_words(text, sentence, max_tokens).into_iter().map(|range| Unit { range, paragraph_end: false }));
+            }
+        }
+        if let Some(unit) = units.last_mut() {
+            unit.paragraph_end = true;
+        }
+    }
+    units
+}
+
+/// Ranges of the blank-line separated paragraphs, without surrounding whitespace
+fn paragraphs(text: &str) -> Vec<Range<usize>> {
+    let mut paragraphs = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        if line.trim().is_empty() {
+            paragraphs.extend(current.take());
+            continue;
+        }
+        let end = line_start + line.trim_end().len();
+        match &mut current {
+            Some(paragraph) => paragraph.end = end,
+            None => current = Some(line_start + line.len() - line.trim_start().len()..end),
+        }
+    }
+    paragraphs.extend(current);
+    paragraphs
+}
+
+/// Ranges of the sentences in `paragraph`, without surrounding whitespace
+fn sentences(text: &str, paragraph: Range<usize>) -> Vec<Range<usize>> {
+    let base = paragraph.start;
+    let paragraph = &text[paragraph];
+    let mut sentences = Vec::new();
+    let mut push = |range: Range<usize>| {
+        let sentence = &paragraph[range.clone()];
+        let trimmed = sentence.trim_start();
+        if !trimmed.is_empty() {
+            let start = base + range.start + sentence.len() - trimmed.len();
+            sentences.push(start..base + range.end);
+        }
+    };
+
+    let mut start = 0;
+    let mut chars = paragraph.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if !matches!(c, '.' | '!' | '?') {
+            continue;
+        }
+        // Keep repeated punctuation and closing quotes or brackets with their sentence
+        let mut end = i + c.len_utf8();
+        while let Some(&(j, next)) = chars.peek() {
+            if !matches!(next, '.' | '!' | '?' | '"' | '\'' | ')' | ']' | '”' | '’') {
+                break;
+            }
+            end = j + next.len_utf8();
+            chars.next();
+        }
+        if chars.peek().is_none_or(|&(_, next)| next.is_whitespace()) {
+            push(start..end);
+            start = end;
+        }
+    }
+    push(start..paragraph.len());
+    sentences
+}
+
+/// Split an oversized sentence into runs of whole words that fit the budget
+fn pack_words(text: &str, sentence: Range<usize>, max_tokens: usize) -> Vec<Range<usize>> {
+    let base = sentence.start;
+    let mut words = Vec::new();
+    let mut word_start = None;
+    for (i, c) in text[sentence.clone()].char_indices() {
+        match (c.is_whitespace(), word_start) {
+            (false, None) => word_start = Some(base + i),
+            (true, Some(start)) => {
+                words.push(start..base + i);
+                word_start = None;
+            }
+            _ => {}
+        }
+    }
+    words.extend(word_start.map(|start| start..sentence.end));
+
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for word in words {
+        match runs.last_mut() {
+            Some(run) if estimate_tokens(&text[run.start..word.end]) <= max_tokens => run.end = word.end,
+            _ => runs.push(word),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "Rust is a systems programming language. It focuses on safety and speed. \
+Ownership rules are checked at compile time.\n\n\
+The borrow checker prevents data races! Lifetimes describe how long references live. \
+Traits let types share behaviour.\n\n\
+Cargo builds the code and fetches dependencies. Crates are published to crates.io (the registry). \
+Tests run with cargo test.\n";
+
+    fn is_sentence_start(text: &str, offset: usize) -> bool {
+        text[..offset].trim_end().is_empty() || text[..offset].trim_end().ends_with(['.', '!', '?'])
+    }
+
+    #[test]
+    fn test_chunks_keep_sentences_whole_and_within_budget() {
+        let chunks = chunk_text(DOCUMENT, 25, 0);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert_eq!(&DOCUMENT[chunk.range.clone()], chunk.text);
+            assert!(estimate_tokens(chunk.text) <= 25, "{:?} is over budget", chunk.text);
+            assert!(chunk.text.ends_with(['.', '!']), "{:?} ends mid-sentence", chunk.text);
+            assert!(is_sentence_start(DOCUMENT, chunk.range.start), "{:?} starts mid-sentence", chunk.text);
+        }
+
+        // Without overlap the chunks cover every sentence once, in order
+        for pair in chunks.windows(2) {
+            assert!(pair[0].range.end < pair[1].range.start);
+            assert!(DOCUMENT[pair[0].range.end..pair[1].range.start].trim().is_empty());
+        }
+        assert_eq!(chunks[0].range.start, 0);
+        assert_eq!(chunks.last().unwrap().range.end, DOCUMENT.trim_end().len());
+    }
+
+    #[test]
+    fn test_chunks_prefer_paragraph_breaks() {
+        // Each paragraph fits on its own but no two fit together
+        let chunks = chunk_text(DOCUMENT, 30, 0);
+        let paragraphs: Vec<&str> = DOCUMENT.split("\n\n").map(str::trim).collect();
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text).collect();
+        assert_eq!(texts, paragraphs);
+    }
+
+    #[test]
+    fn test_chunks_overlap_by_whole_sentences() {
+        let overlap = 12;
+        let chunks = chunk_text(DOCUMENT, 25, overlap);
+        assert!(chunks.len() > 1);
+        for pair in chunks.windows(2) {
+            let (previous, next) = (&pair[0], &pair[1]);
+            assert!(next.range.start > previous.range.start, "chunks must move forward");
+            assert!(next.range.start < previous.range.end, "{:?} doesn't overlap {:?}", next.text, previous.text);
+            assert!(is_sentence_start(DOCUMENT, next.range.start));
+
+            let repeated = &DOCUMENT[next.range.start..previous.range.end];
+            assert!(previous.text.ends_with(repeated));
+            assert!(estimate_tokens(repeated) <= overlap, "{:?} is more than {} tokens", repeated, overlap);
+        }
+    }
+
+    #[test]
+    fn test_oversized_sentence_splits_between_words() {
+        let text = "one two three four five six seven eight nine ten eleven twelve thirteen fourteen";
+        let chunks = chunk_text(text, 5, 0);
+        assert!(chunks.len() > 1);
+        let rejoined: Vec<&str> = chunks.iter().map(|chunk| chunk.text).collect();
+        assert_eq!(rejoined.join(" "), text);
+        assert!(chunks.iter().all(|chunk| estimate_tokens(chunk.text) <= 5));
+        assert!(chunk_text("  \n\n ", 5, 0).is_empty());
+    }
+}
@@ -4,16 +4,171 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use crate::cache::CacheConfig;
+use crate::context::TruncationStrategy;
 use crate::error::ConfigError;
+use crate::streaming::GenerationOptions;
+
+/// Re-reads a config file whenever it changes on disk. Each change that
+/// loads and validates is queued for `take_update`; invalid edits are logged
+/// and dropped, so the previous config stays in effect.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    // Behind a mutex so the wrapper holding the watcher stays `Sync`
+    updates: std::sync::Mutex<std::sync::mpsc::Receiver<EnhancedConfig>>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn new<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
+        use notify::Watcher;
+
+        let path = path.as_ref().to_path_buf();
+        if !path.is_file() {
+            return Err(ConfigError::FileNotFound(path.display().to_string()));
+        }
+        // Editors often save by replacing the file, which ends a watch on the
+        // file itself, so watch the directory and filter by name
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file_name = path.file_name().map(|name| name.to_os_string());
+
+        let (sender, updates) = std::sync::mpsc::channel();
+        let watched = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else { return };
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                return;
+            }
+            if !event.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == file_name) {
+                return;
+            }
+            match EnhancedConfig::load(&watched) {
+                Ok(config) => {
+                    tracing::info!(path = %watched.display(), "Config file changed, reloading");
+                    let _ = sender.send(config);
+                }
+                Err(e) => tracing::warn!(path = %watched.display(), error = %e, "Ignoring invalid config reload"),
+            }
+        })
+        .map_err(|e| ConfigError::Invalid(format!("Cannot watch {}: {}", path.display(), e)))?;
+        watcher
+            .watch(&dir, notify::RecursiveMode::NonRecursive)
+            .map_err(|e| ConfigError::Invalid(format!("Cannot watch {}: {}", path.display(), e)))?;
+
+        Ok(Self { path, updates: std::sync::Mutex::new(updates), _watcher: watcher })
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// The most recent valid config read since the last call, if any
+    pub fn take_update(&self) -> Option<EnhancedConfig> {
+        self.updates.lock().ok()?.try_iter().last()
+    }
+}
+
+/// Expand `${VAR}`, `${VAR:-default}` and `$VAR` from the process
+/// environment. `$$` is a literal `$`; a `$` not followed by a name is kept.
+pub fn expand_env_vars(input: &str) -> Result<String, ConfigError> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(pos) = rest.find('$') {
+        output.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(stripped) = after.strip_prefix('$') {
+            output.push('$');
+            rest = stripped;
+        } else if let Some(braced) = after.strip_prefix('{') {
+            let end = braced.find('}').ok_or_else(|| {
+                ConfigError::Parse(format!("Unterminated '${{' in \"{}\"", input))
+            })?;
+            let (name, default) = match braced[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&braced[..end], None),
+            };
+            if !is_env_name(name) {
+                return Err(ConfigError::Parse(format!("Invalid environment variable name '{}'", name)));
+            }
+            // Like the shell, `:-` also replaces a variable that is set but empty
+            match (std::env::var(name), default) {
+                (Ok(value), Some(default)) if value.is_empty() => output.push_str(default),
+                (Ok(value), _) => output.push_str(&value),
+                (Err(_), Some(default)) => output.push_str(default),
+                (Err(_), None) => return Err(ConfigError::UndefinedEnvVar(name.to_string())),
+            }
+            rest = &braced[end + 1..];
+        } else {
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            let name = &after[..len];
+            if is_env_name(name) {
+                let value = std::env::var(name).map_err(|_| ConfigError::UndefinedEnvVar(name.to_string()))?;
+                output.push_str(&value);
+            } else {
+                output.push('$');
+                output.push_str(name);
+            }
+            rest = &after[len..];
+        }
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn is_env_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn expand_env_in_value(value: &mut toml::Value) -> Result<(), ConfigError> {
+    match value {
+        toml::Value::String(s) => *s = expand_env_vars(s)?,
+        toml::Value::Array(items) => {
+            for item in items {
+                expand_env_in_value(item)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, item) in table.iter_mut() {
+                expand_env_in_value(item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnhancedConfig {
+    /// Backends tried in order when the current one is unreachable; empty disables failover
+    #[serde(default)]
+    pub fallback_backends: Vec<String>,
+    /// How often every backend is health-checked in the background; `None`
+    /// only checks backends when they are used
+    #[serde(default, with = "humantime_serde")]
+    pub health_check_interval: Option<Duration>,
+    /// Shortcut names for models, e.g. `fast = "llama3.2:3b"`
+    #[serde(default)]
+    pub model_aliases: HashMap<String, String>,
+    /// Sampling parameters used for a model unless the request sets them,
+    /// keyed by the model name aliases resolve to
+    #[serde(default)]
+    pub model_defaults: HashMap<String, GenerationOptions>,
     pub backends: HashMap<String, BackendConfig>,
     pub cache: CacheConfig,
     pub ui: UIConfig,
     pub templates: TemplateConfig,
     pub logging: LoggingConfig,
     pub streaming: StreamingConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
 }
 
 impl Default for EnhancedConfig {
@@ -22,12 +177,17 @@ impl Default for EnhancedConfig {
         backends.insert("ollama".to_string(), BackendConfig::default());
         
         Self {
+            fallback_backends: Vec::new(),
+            health_check_interval: None,
+            model_aliases: HashMap::new(),
+            model_defaults: HashMap::new(),
             backends,
             cache: CacheConfig::default(),
             ui: UIConfig::default(),
             templates: TemplateConfig::default(),
             logging: LoggingConfig::default(),
             streaming: StreamingConfig::default(),
+            context: ContextConfig::default(),
         }
     }
 }
@@ -37,8 +197,12 @@ impl EnhancedConfig {
         let content = std::fs::read_to_string(&path)
             .map_err(|_| ConfigError::FileNotFound(path.as_ref().display().to_string()))?;
         
-        let config: EnhancedConfig = toml::from_str(&content)
+        // Expand after parsing so `$` in comments or keys is left alone
+        let mut value: toml::Value = toml::from_str(&content)
             .map_err(|e| ConfigError::Parse(e.to_string()))?;
+        expand_env_in_value(&mut value)?;
+        let config: EnhancedConfig = value.try_into()
+            .map_err(|e: toml::de::Error| ConfigError::Parse(e.to_string()))?;
         
         config.validate()?;
         Ok(config)
@@ -58,6 +222,16 @@ impl EnhancedConfig {
             return Err(ConfigError::Validation("At least one backend must be configured".to_string()));
         }
 
+        if self.health_check_interval.is_some_and(|interval| interval.is_zero()) {
+            return Err(ConfigError::Validation("health_check_interval must be greater than 0".to_string()));
+        }
+
+        for name in &self.fallback_backends {
+            if !self.backends.contains_key(name) {
+                return Err(ConfigError::Validation(format!("Fallback backend '{}' is not configured", name)));
+            }
+        }
+
         for (name, backend) in &self.backends {
             if name.is_empty() {
                 return Err(ConfigError::Validation("Backend name cannot be empty".to_string()));
@@ -70,6 +244,30 @@ impl EnhancedConfig {
             if backend.retry_attempts > 10 {
                 return Err(ConfigError::Validation(format!("Backend '{}' retry_attempts cannot exceed 10", name)));
             }
+
+            if backend.circuit_breaker.failure_threshold == 0 {
+                return Err(ConfigError::Validation(format!("Backend '{}' circuit_breaker.failure_threshold must be greater than 0", name)));
+            }
+
+            if let Err(e) = backend.header_map() {
+                return Err(ConfigError::Validation(format!("Backend '{}' {}", name, e)));
+            }
+
+            if let Some(Err(e)) = backend.proxy.as_deref().map(proxy) {
+                return Err(ConfigError::Validation(format!("Backend '{}' proxy is not valid: {}", name, e)));
+            }
+
+            if let Err(e) = backend.ca_certificate() {
+                return Err(ConfigError::Validation(format!("Backend '{}' {}", name, e)));
+            }
+
+            for (model, limit) in &backend.model_rate_limits {
+                if limit.max_concurrent == 0 || limit.requests_per_minute == 0 {
+                    return Err(ConfigError::Validation(format!(
+                        "Backend '{}' rate limit for model '{}' must allow at least one request", name, model
+                    )));
+                }
+            }
         }
 
         // Validate cache config
@@ -86,10 +284,20 @@ impl EnhancedConfig {
             return Err(ConfigError::Validation("Streaming max_concurrent_streams must be greater than 0".to_string()));
         }
 
+        if self.streaming.max_concurrent_requests == Some(0) {
+            return Err(ConfigError::Validation("Streaming max_concurrent_requests must be greater than 0".to_string()));
+        }
+
         if self.streaming.buffer_size < 1024 {
             return Err(ConfigError::Validation("Streaming buffer_size must be at least 1024 bytes".to_string()));
         }
 
+        if let Some(max_tokens) = self.context.max_context_tokens {
+            if self.context.reserve_tokens >= max_tokens {
+                return Err(ConfigError::Validation("Context reserve_tokens must be less than max_context_tokens".to_string()));
+            }
+        }
+
         // Validate UI config
         if self.ui.max_history == 0 {
             return Err(ConfigError::Validation("UI max_history must be greater than 0".to_string()));
@@ -111,19 +319,153 @@ impl EnhancedConfig {
             return Err(ConfigError::Validation(format!("Invalid logging output '{}'. Valid outputs: {:?}", self.logging.output, valid_outputs)));
         }
 
+        let valid_rotations = ["daily", "hourly", "size"];
+        if !valid_rotations.contains(&self.logging.rotation.as_str()) {
+            return Err(ConfigError::Validation(format!("Invalid logging rotation '{}'. Valid rotations: {:?}", self.logging.rotation, valid_rotations)));
+        }
+
+        if self.logging.rotation == "size" && self.logging.max_file_size == 0 {
+            return Err(ConfigError::Validation("Logging max_file_size must be greater than 0".to_string()));
+        }
+
+        if self.logging.max_files == 0 {
+            return Err(ConfigError::Validation("Logging max_files must be at least 1".to_string()));
+        }
+
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BackendConfig {
     pub backend_type: BackendType,
     pub base_url: String,
     #[serde(with = "humantime_serde")]
     pub timeout: Duration,
     pub retry_attempts: u32,
+    /// Upper bound on the backoff between retries
+    #[serde(default = "default_max_retry_delay", with = "humantime_serde")]
+    pub max_retry_delay: Duration,
     pub rate_limit: Option<RateLimit>,
     pub default_model: Option<String>,
+    /// Per-model limits applied in addition to the backend-wide `rate_limit`
+    #[serde(default)]
+    pub model_rate_limits: HashMap<String, RateLimit>,
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+    /// API key sent as a Bearer token (required for OpenAI)
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Extra headers sent with every request, e.g. for gateways that route on them
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// HTTP or SOCKS5 proxy for requests to this backend, e.g. `socks5://127.0.0.1:1080`.
+    /// Local addresses and hosts in `NO_PROXY` always bypass it.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// PEM certificate trusted in addition to the system roots, for servers
+    /// signed by a private CA
+    #[serde(default)]
+    pub ca_cert_path: Option<PathBuf>,
+    /// INSECURE: skip certificate and hostname verification entirely, leaving
+    /// the connection open to interception. Prefer `ca_cert_path`; only use
+    /// this against a self-signed server on a network you trust.
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+}
+
+impl BackendConfig {
+    /// Backoff for requests that couldn't reach this backend
+    pub fn retry_policy(&self) -> crate::streaming::RetryPolicy {
+        crate::streaming::RetryPolicy {
+            max_retries: self.retry_attempts,
+            max_delay: self.max_retry_delay,
+            ..Default::default()
+        }
+    }
+
+    /// The certificate at `ca_cert_path`, or a description of why it can't be used
+    fn ca_certificate(&self) -> Result<Option<reqwest::Certificate>, String> {
+        let Some(path) = &self.ca_cert_path else {
+            return Ok(None);
+        };
+        let pem = std::fs::read(path)
+            .map_err(|e| format!("ca_cert_path '{}' cannot be read: {}", path.display(), e))?;
+        reqwest::Certificate::from_pem(&pem)
+            .map(Some)
+            .map_err(|e| format!("ca_cert_path '{}' is not a PEM certificate: {}", path.display(), e))
+    }
+
+    /// `headers` as a header map, or a description of the first invalid entry
+    pub fn header_map(&self) -> Result<reqwest::header::HeaderMap, String> {
+        use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+        let mut map = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("header name '{}' is not valid", name))?;
+            let mut header_value = HeaderValue::from_str(value)
+                .map_err(|_| format!("header '{}' has a value that is not valid", name))?;
+            header_value.set_sensitive(true);
+            map.insert(header_name, header_value);
+        }
+        Ok(map)
+    }
+
+    /// HTTP client for this backend, with its connection tuning, request
+    /// timeout and headers
+    pub fn build_client(&self) -> Result<reqwest::Client, crate::backends::BackendInitError> {
+        use crate::backends::BackendInitError;
+
+        let headers = self.header_map().map_err(BackendInitError::Config)?;
+        let mut builder = self.connection
+            .client_builder(Some(self.timeout))
+            .default_headers(headers);
+        if let Some(url) = &self.proxy {
+            builder = builder.proxy(proxy(url).map_err(|e| BackendInitError::Config(format!("proxy is not valid: {}", e)))?);
+        }
+        if let Some(certificate) = self.ca_certificate().map_err(BackendInitError::Config)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+        if self.danger_accept_invalid_certs {
+            tracing::warn!(base_url = %crate::logging::redact(&self.base_url), "TLS certificate verification is disabled");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        builder
+            .build()
+            .map_err(|e| BackendInitError::Connection(e.to_string()))
+    }
+}
+
+// Written by hand so the API key, and any credentials in the URL, never
+// reach logs through `{:?}`
+impl std::fmt::Debug for BackendConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackendConfig")
+            .field("backend_type", &self.backend_type)
+            .field("base_url", &crate::logging::redact(&self.base_url))
+            .field("timeout", &self.timeout)
+            .field("retry_attempts", &self.retry_attempts)
+            .field("max_retry_delay", &self.max_retry_delay)
+            .field("rate_limit", &self.rate_limit)
+            .field("default_model", &self.default_model)
+            .field("model_rate_limits", &self.model_rate_limits)
+            .field("connection", &self.connection)
+            .field("api_key", &self.api_key.as_ref().map(|_| crate::logging::REDACTED))
+            .field("circuit_breaker", &self.circuit_breaker)
+            // Header values often carry credentials, so only the names are shown
+            .field("headers", &self.headers.keys().map(|name| (name, crate::logging::REDACTED)).collect::<std::collections::BTreeMap<_, _>>())
+            .field("proxy", &self.proxy.as_deref().map(crate::logging::redact))
+            .field("ca_cert_path", &self.ca_cert_path)
+            .field("danger_accept_invalid_certs", &self.danger_accept_invalid_certs)
+            .finish()
+    }
+}
+
+fn default_max_retry_delay() -> Duration {
+    Duration::from_secs(5)
 }
 
 impl Default for BackendConfig {
@@ -133,12 +475,80 @@ impl Default for BackendConfig {
             base_url: "http://localhost:11434".to_string(),
             timeout: Duration::from_secs(30),
             retry_attempts: 3,
+            max_retry_delay: default_max_retry_delay(),
             rate_limit: Some(RateLimit::default()),
             default_model: Some("llama3.2".to_string()),
+            model_rate_limits: HashMap::new(),
+            connection: ConnectionConfig::default(),
+            api_key: None,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            headers: HashMap::new(),
+            proxy: None,
+            ca_cert_path: None,
+            danger_accept_invalid_certs: false,
         }
     }
 }
 
+/// Hosts that never go through a configured proxy
+const LOCAL_HOSTS: &str = "localhost,127.0.0.1,::1";
+
+/// A proxy for every scheme that leaves local addresses, and anything
+/// listed in `NO_PROXY`, to connect directly
+pub fn proxy(url: &str) -> Result<reqwest::Proxy, reqwest::Error> {
+    let no_proxy = match std::env::var("NO_PROXY").or_else(|_| std::env::var("no_proxy")) {
+        Ok(hosts) if !hosts.trim().is_empty() => format!("{},{}", LOCAL_HOSTS, hosts),
+        _ => LOCAL_HOSTS.to_string(),
+    };
+    Ok(reqwest::Proxy::all(url)?.no_proxy(reqwest::NoProxy::from_string(&no_proxy)))
+}
+
+/// Connection pool and keep-alive tuning shared by every HTTP client the crate builds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionConfig {
+    #[serde(with = "humantime_serde")]
+    pub connect_timeout: Duration,
+    #[serde(with = "humantime_serde")]
+    pub pool_idle_timeout: Duration,
+    pub pool_max_idle_per_host: usize,
+    #[serde(with = "humantime_serde")]
+    pub tcp_keepalive: Duration,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_idle_per_host: 10,
+            tcp_keepalive: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ConnectionConfig {
+    /// Build a `reqwest::Client` with these tunings. `request_timeout` of `None`
+    /// leaves the overall request duration unbounded.
+    pub fn build_client(&self, request_timeout: Option<Duration>) -> Result<reqwest::Client, reqwest::Error> {
+        self.client_builder(request_timeout).build()
+    }
+
+    /// A client builder with these tunings, for callers that need to set more
+    pub fn client_builder(&self, request_timeout: Option<Duration>) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .tcp_keepalive(self.tcp_keepalive);
+
+        if let Some(timeout) = request_timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        builder
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BackendType {
     Ollama,
@@ -163,6 +573,33 @@ impl Default for RateLimit {
     }
 }
 
+/// Stop calling a backend after repeated connection failures, then probe it
+/// again once `cooldown` has passed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    #[serde(with = "humantime_serde")]
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl From<&RateLimit> for crate::streaming::ModelRateLimit {
+    fn from(limit: &RateLimit) -> Self {
+        Self {
+            max_concurrent: limit.max_concurrent,
+            requests_per_second: limit.requests_per_minute as f64 / 60.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UIConfig {
     pub theme: String,
@@ -171,6 +608,13 @@ pub struct UIConfig {
     pub max_history: usize,
     pub show_timestamps: bool,
     pub show_model_info: bool,
+    /// Where interactive chat sessions are saved
+    #[serde(default = "default_sessions_dir")]
+    pub sessions_dir: PathBuf,
+}
+
+fn default_sessions_dir() -> PathBuf {
+    PathBuf::from("sessions")
 }
 
 impl Default for UIConfig {
@@ -182,6 +626,7 @@ impl Default for UIConfig {
             max_history: 1000,
             show_timestamps: true,
             show_model_info: true,
+            sessions_dir: default_sessions_dir(),
         }
     }
 }
@@ -211,6 +656,27 @@ pub struct LoggingConfig {
     pub format: String,
     pub output: String,
     pub file_path: Option<String>,
+    /// When to start a new log file: "daily", "hourly" or "size"
+    #[serde(default = "default_log_rotation")]
+    pub rotation: String,
+    /// Size in bytes at which a log file is rotated, when `rotation` is "size"
+    #[serde(default = "default_max_log_file_size")]
+    pub max_file_size: u64,
+    /// Log files kept, including the current one; older files are deleted
+    #[serde(default = "default_max_log_files")]
+    pub max_files: usize,
+}
+
+fn default_log_rotation() -> String {
+    "daily".to_string()
+}
+
+fn default_max_log_file_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_log_files() -> usize {
+    7
 }
 
 impl Default for LoggingConfig {
@@ -220,6 +686,9 @@ impl Default for LoggingConfig {
             format: "text".to_string(),
             output: "stdout".to_string(),
             file_path: None,
+            rotation: default_log_rotation(),
+            max_file_size: default_max_log_file_size(),
+            max_files: default_max_log_files(),
         }
     }
 }
@@ -229,6 +698,37 @@ pub struct StreamingConfig {
     pub max_concurrent_streams: usize,
     pub buffer_size: usize,
     pub enable_cancellation: bool,
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+    /// Cap on in-flight backend requests; defaults to `max_concurrent_streams`
+    #[serde(default)]
+    pub max_concurrent_requests: Option<usize>,
+    /// Fail with an error instead of waiting when every request slot is taken
+    #[serde(default)]
+    pub reject_when_saturated: bool,
+}
+
+/// Trimming of conversation history to fit the model's context window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    pub truncate: bool,
+    pub strategy: TruncationStrategy,
+    /// Tokens left free for the reply
+    pub reserve_tokens: u32,
+    /// Overrides the context length the backend reports for the model
+    #[serde(default)]
+    pub max_context_tokens: Option<u32>,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            truncate: true,
+            strategy: TruncationStrategy::default(),
+            reserve_tokens: 512,
+            max_context_tokens: None,
+        }
+    }
 }
 
 impl Default for StreamingConfig {
@@ -237,6 +737,9 @@ impl Default for StreamingConfig {
             max_concurrent_streams: 10,
             buffer_size: 8192,
             enable_cancellation: true,
+            connection: ConnectionConfig::default(),
+            max_concurrent_requests: None,
+            reject_when_saturated: false,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+//! Keeping conversations within a model's context window.
+//!
+//! Token counts come from `tokenize`'s estimates, which are close enough to
+//! decide when history has to be trimmed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::streaming::Message;
+use crate::tokenize::estimate_message_tokens;
+
+/// Which messages to drop when a conversation exceeds its token budget.
+/// System messages and the latest message are always kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationStrategy {
+    /// Drop the oldest messages first
+    #[default]
+    DropOldest,
+    /// Also keep the first non-system message, which usually sets up the task,
+    /// and drop from the middle of the conversation
+    KeepSystemDropMiddle,
+}
+
+/// Drop messages per `strategy` until the estimated total fits in `budget`
+/// tokens. If the protected messages alone exceed the budget they are still
+/// sent, leaving the backend to reject or truncate the request.
+pub fn truncate_messages(messages: Vec<Message>, budget: usize, strategy: TruncationStrategy) -> Vec<Message> {
+    let costs: Vec<usize> = messages.iter().map(estimate_message_tokens).collect();
+    let mut total: usize = costs.iter().sum();
+    if total <= budget {
+        return messages;
+    }
+
+    let last = messages.len() - 1;
+    let anchor = match strategy {
+        TruncationStrategy::DropOldest => None,
+        TruncationStrategy::KeepSystemDropMiddle => messages.iter().position(|m| m.role != "system"),
+    };
+
+    let mut keep = vec![true; messages.len()];
+    for (i, message) in messages.iter().enumerate() {
+        if total <= budget {
+            break;
+        }
+        if message.role == "system" || i == last || Some(i) == anchor {
+            continue;
+        }
+        keep[i] = false;
+        total -= costs[i];
+    }
+
+    messages
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(message, keep)| keep.then_some(message))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string(),
+            images: None,
+        }
+    }
+
+    fn long_history(turns: usize) -> Vec<Message> {
+        let mut messages = vec![message("system", "You are a helpful assistant.")];
+        for i in 0..turns {
+            let role = if i % 2 == 0 { "user" } else { "assistant" };
+            messages.push(message(role, &format!("turn {} {}", i, "lorem ipsum ".repeat(30))));
+        }
+        messages
+    }
+
+    #[test]
+    fn test_short_conversation_is_untouched() {
+        let messages = long_history(2);
+        assert_eq!(truncate_messages(messages.clone(), 4096, TruncationStrategy::DropOldest).len(), messages.len());
+    }
+
+    #[test]
+    fn test_drop_oldest_keeps_system_and_latest() {
+        let messages = long_history(100);
+        let latest = messages.last().unwrap().content.clone();
+
+        let truncated = truncate_messages(messages.clone(), 1000, TruncationStrategy::DropOldest);
+        assert!(truncated.len() < messages.len());
+        assert!(crate::tokenize::count_message_tokens(&truncated) <= 1000);
+        assert_eq!(truncated[0].role, "system");
+        assert_eq!(truncated.last().unwrap().content, latest);
+        // Only the most recent turns survive
+        assert!(!truncated.iter().any(|m| m.content.starts_with("turn 0 ")));
+    }
+
+    #[test]
+    fn test_keep_system_drop_middle_keeps_first_turn() {
+        let messages = long_history(100);
+
+        let truncated = truncate_messages(messages.clone(), 1000, TruncationStrategy::KeepSystemDropMiddle);
+        assert!(truncated.len() < messages.len());
+        assert_eq!(truncated[0].role, "system");
+        assert!(truncated[1].content.starts_with("turn 0 "));
+        assert_eq!(truncated.last().unwrap().content, messages.last().unwrap().content);
+    }
+}
@@ -17,6 +17,9 @@ pub enum WrapperError {
     #[error("UI error: {0}")]
     UI(#[from] crate::ui::UIError),
     
+    #[error("Session error: {0}")]
+    Session(#[from] crate::session::SessionError),
+    
     #[error("Stream error: {0}")]
     Stream(#[from] crate::streaming::StreamError),
     
@@ -28,6 +31,9 @@ pub enum WrapperError {
     
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
+    
+    #[error("Model output does not match the expected type: {message}\nRaw output: {raw}")]
+    InvalidOutput { message: String, raw: String },
 }
 
 #[derive(Debug, Error)]
@@ -52,6 +58,21 @@ pub enum BackendError {
     
     #[error("Invalid response format")]
     InvalidResponse,
+    
+    #[error("Operation not supported: {0}")]
+    Unsupported(String),
+}
+
+impl BackendError {
+    /// Whether the backend could not be reached at all, as opposed to
+    /// answering with an error
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            BackendError::Connection(_) | BackendError::Timeout => true,
+            BackendError::Http(e) => e.is_connect() || e.is_timeout(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -76,4 +97,7 @@ pub enum ConfigError {
     
     #[error("TOML parse error: {0}")]
     Toml(#[from] toml::de::Error),
+    
+    #[error("Environment variable '{0}' is not set (use ${{{0}:-default}} to give a fallback)")]
+    UndefinedEnvVar(String),
 }
\ No newline at end of file
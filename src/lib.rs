@@ -3,10 +3,12 @@ use base64::{engine::general_purpose, Engine as _};
 
 use mime_guess::from_path;
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use tokio::fs;
+use tracing::Instrument;
 
 // New modules
 pub mod streaming;
@@ -18,15 +20,25 @@ pub mod config;
 pub mod backends;
 pub mod logging;
 pub mod performance;
+pub mod context;
+pub mod tokenize;
+pub mod chunk;
+pub mod session;
+pub mod preset;
+#[cfg(feature = "server")]
+pub mod server;
 
 // Re-exports
 pub use error::{WrapperError, BackendError, ConfigError};
 pub use config::EnhancedConfig;
-pub use backends::{Backend, BackendType, ModelInfo, ModelCapabilities, OllamaBackend, MockBackend};
-pub use streaming::{StreamingManager, StreamResponse, StreamToken};
+pub use backends::{Backend, BackendType, ChatCompletion, CircuitBreaker, CircuitBreakerBackend, CircuitState, ModelDetails, ModelFilter, ModelInfo, ModelCapabilities, ModelSort, OllamaBackend, OpenAIBackend, MockBackend, RetryBackend, TokenUsage};
+pub use streaming::{StreamingManager, StreamResponse, StreamToken, GenerationOptions, RequestLimiter, ResponseFormat, TokenKind, Tool, ToolCall, ToolChatResponse};
 pub use cache::{CacheManager, CacheStats};
-pub use template::{TemplateEngine, Template};
-pub use ui::{TerminalUI, ChatMessage, MessageRole};
+pub use context::TruncationStrategy;
+pub use template::{FewShotExample, TemplateEngine, Template};
+pub use ui::{TerminalUI, ChatMessage, MessageRole, export_markdown};
+pub use session::{Session, SessionError};
+pub use preset::{PresetError, PresetStore};
 pub use performance::{PerformanceMonitor, PerformanceMetrics, PerformanceReport, PerformanceStatus};
 
 
@@ -38,6 +50,13 @@ pub struct Config {
     pub model_aliases: HashMap<String, String>,
     pub default_model: String,
     pub base_url: String,
+    /// Largest image, in bytes, that will be downloaded from an image URL
+    #[serde(default = "default_max_image_bytes")]
+    pub max_image_bytes: usize,
+}
+
+fn default_max_image_bytes() -> usize {
+    10 * 1024 * 1024
 }
 
 impl Default for Config {
@@ -57,6 +76,7 @@ impl Default for Config {
             model_aliases: HashMap::new(),
             default_model: "llama3.2".to_string(),
             base_url: "http://localhost:11434".to_string(),
+            max_image_bytes: default_max_image_bytes(),
         }
     }
 }
@@ -88,6 +108,49 @@ struct ChatRequest {
 
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
+    #[serde(default)]
+    message: MessageResponse,
+    #[serde(default)]
+    prompt_eval_count: Option<u32>,
+    #[serde(default)]
+    eval_count: Option<u32>,
+}
+
+/// Extra fields for a raw `/api/generate` request
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOptions {
+    pub system: Option<String>,
+    /// Prompt template overriding the one in the model's Modelfile
+    pub template: Option<String>,
+    /// Send the prompt as-is without applying any template
+    pub raw: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct GenerateRequest {
+    model: String,
+    prompt: String,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    raw: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<HashMap<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenerateChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatStreamChunk {
     #[serde(default)]
     message: MessageResponse,
     #[serde(default)]
@@ -102,14 +165,178 @@ struct MessageResponse {
     thinking: Option<String>,
 }
 
+/// Ollama treats an untagged model name as `name:latest`
+fn normalize_model_name(name: &str) -> String {
+    if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("{}:latest", name)
+    }
+}
+
+/// Find the installed model a requested name refers to: an exact match first,
+/// then a `name:tag` normalized match, and only then a substring match
+fn find_model<'a>(models: &'a [ModelEntry], requested: &str) -> Option<&'a ModelEntry> {
+    let normalized = normalize_model_name(requested);
+    
+    models.iter().find(|m| m.name == requested)
+        .or_else(|| models.iter().find(|m| normalize_model_name(&m.name) == normalized))
+        .or_else(|| models.iter().find(|m| m.name.contains(requested)))
+}
+
+/// Installed models whose names resemble `requested`, for error suggestions
+fn close_matches<'a>(installed: &'a [String], requested: &str) -> Vec<&'a str> {
+    let requested = requested.split(':').next().unwrap_or(requested).to_lowercase();
+    
+    installed.iter()
+        .map(String::as_str)
+        .filter(|name| {
+            let base = name.split(':').next().unwrap_or(name).to_lowercase();
+            base.contains(&requested) || requested.contains(&base) || edit_distance(&base, &requested) <= 2
+        })
+        .collect()
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    
+    previous[b.len()]
+}
+
+/// The URL of an image argument given as `http(s)://…` rather than a local path
+fn remote_image_url(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|p| p.starts_with("http://") || p.starts_with("https://"))
+}
+
+/// Download an image and base64-encode it, rejecting non-image content and
+/// anything larger than `max_bytes`
+async fn fetch_remote_image(client: &Client, url: &str, max_bytes: usize) -> Result<String> {
+    use futures_util::StreamExt;
+    
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Failed to download image {}: {}", url, response.status()));
+    }
+    
+    let content_type = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if !content_type.starts_with("image/") {
+        return Err(anyhow!("{} is not an image (content type '{}')", url, content_type));
+    }
+    
+    let too_large = || anyhow!("Image {} exceeds the {} byte limit", url, max_bytes);
+    if response.content_length().is_some_and(|len| len > max_bytes as u64) {
+        return Err(too_large());
+    }
+    
+    // Content-Length can be missing or wrong, so enforce the cap while reading
+    let mut bytes = Vec::new();
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        bytes.extend_from_slice(&chunk?);
+        if bytes.len() > max_bytes {
+            return Err(too_large());
+        }
+    }
+    
+    Ok(general_purpose::STANDARD.encode(bytes))
+}
+
+/// Apply one streamed `/api/generate` JSONL line, returning whether the
+/// response is complete
+fn push_generate_line(line: &str, result: &mut String, on_token: &mut impl FnMut(&str)) -> Result<bool> {
+    if line.trim().is_empty() {
+        return Ok(false);
+    }
+    let chunk: GenerateChunk = serde_json::from_str(line)?;
+    if !chunk.response.is_empty() {
+        result.push_str(&chunk.response);
+        on_token(&chunk.response);
+    }
+    Ok(chunk.done)
+}
+
+/// Accumulates a streamed legacy chat response in the same shape `chat`
+/// returns, forwarding each new piece to the caller as it is appended
+#[derive(Default)]
+struct StreamOutput {
+    result: String,
+    in_thinking: bool,
+}
+
+impl StreamOutput {
+    /// Apply one JSONL line, returning whether the response is complete
+    fn push_line(&mut self, line: &str, on_token: &mut impl FnMut(&str)) -> Result<bool> {
+        if line.trim().is_empty() {
+            return Ok(false);
+        }
+        let chunk: ChatStreamChunk = serde_json::from_str(line)?;
+        
+        if let Some(thinking) = chunk.message.thinking.filter(|t| !t.is_empty()) {
+            if !self.in_thinking && self.result.is_empty() {
+                self.in_thinking = true;
+                self.append("🤔 Thinking: ", on_token);
+            }
+            if self.in_thinking {
+                self.append(&thinking, on_token);
+            }
+        }
+        if !chunk.message.content.is_empty() {
+            if self.in_thinking {
+                self.in_thinking = false;
+                self.append("\n\n", on_token);
+            }
+            self.append(&chunk.message.content, on_token);
+        }
+        
+        Ok(chunk.done)
+    }
+    
+    fn append(&mut self, piece: &str, on_token: &mut impl FnMut(&str)) {
+        self.result.push_str(piece);
+        on_token(piece);
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct OllamaModelInfo {
     models: Vec<ModelEntry>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct ModelEntry {
     name: String,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_timestamp")]
+    modified_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// An RFC 3339 timestamp, or `None` when it's missing or doesn't parse
+fn lenient_timestamp<'de, D>(deserializer: D) -> std::result::Result<Option<chrono::DateTime<chrono::Utc>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(value
+        .as_str()
+        .and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok())
+        .map(|v| v.with_timezone(&chrono::Utc)))
 }
 
 pub struct LLMWrapper {
@@ -118,11 +345,12 @@ pub struct LLMWrapper {
     model: String,
     capabilities: crate::backends::ModelCapabilities,
     config: Config,
+    generation_options: GenerationOptions,
 }
 
 impl LLMWrapper {
     pub async fn new(base_url: &str, model: &str, config: Config) -> Result<Self> {
-        let client = Client::new();
+        let client = crate::config::ConnectionConfig::default().build_client(None)?;
         let base_url = base_url.trim_end_matches('/').to_string();
         
         let mut wrapper = Self {
@@ -131,6 +359,7 @@ impl LLMWrapper {
             model: model.to_string(),
             capabilities: crate::backends::ModelCapabilities::default(),
             config,
+            generation_options: GenerationOptions::default(),
         };
         
         wrapper.detect_capabilities().await?;
@@ -144,30 +373,47 @@ impl LLMWrapper {
         
         if response.status().is_success() {
             let model_info: OllamaModelInfo = response.json().await?;
-            
-            if let Some(current_model) = model_info.models.iter().find(|m| m.name.contains(&self.model)) {
-                // Note: The new ModelCapabilities doesn't have model_name field
-                // We'll need to track this separately or modify the structure
-                let model_name_lower = current_model.name.to_lowercase();
-                
-                // Check for vision capabilities
-                self.capabilities.supports_vision = self.config.vision_models
-                    .iter()
-                    .any(|indicator| model_name_lower.contains(indicator));
-                
-                // Check for thinking capabilities
-                self.capabilities.supports_thinking = self.config.thinking_models
-                    .iter()
-                    .any(|indicator| model_name_lower.contains(indicator));
-            }
+            self.apply_capabilities(&model_info.models);
         }
         
         Ok(())
     }
     
+    fn apply_capabilities(&mut self, models: &[ModelEntry]) {
+        if let Some(current_model) = find_model(models, &self.model) {
+            let detected = self.capabilities_for(&current_model.name);
+            self.capabilities.supports_vision = detected.supports_vision;
+            self.capabilities.supports_thinking = detected.supports_thinking;
+        }
+    }
+
+    /// Capabilities implied by the configured vision and thinking model name patterns
+    fn capabilities_for(&self, model_name: &str) -> crate::backends::ModelCapabilities {
+        let model_name_lower = model_name.to_lowercase();
+        crate::backends::ModelCapabilities {
+            supports_vision: self.config.vision_models
+                .iter()
+                .any(|indicator| model_name_lower.contains(indicator)),
+            supports_thinking: self.config.thinking_models
+                .iter()
+                .any(|indicator| model_name_lower.contains(indicator)),
+            ..Default::default()
+        }
+    }
+    
     pub fn capabilities(&self) -> &crate::backends::ModelCapabilities {
         &self.capabilities
     }
+
+    /// The model in use, with any alias resolved
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+    
+    /// Sampling parameters sent with every subsequent chat request
+    pub fn set_generation_options(&mut self, options: GenerationOptions) {
+        self.generation_options = options;
+    }
     
     pub async fn list_models(&self) -> Result<Vec<String>> {
         let url = format!("{}/api/tags", self.base_url);
@@ -181,6 +427,41 @@ impl LLMWrapper {
         }
     }
     
+    /// Parameter size, context length, quantization and template of an
+    /// installed model, from `/api/show`
+    pub async fn show_model(&self, model_name: &str) -> Result<ModelDetails> {
+        let url = format!("{}/api/show", self.base_url);
+        let response = self.client.post(&url).json(&serde_json::json!({ "model": model_name })).send().await?;
+
+        if response.status().is_success() {
+            Ok(OllamaBackend::parse_show_response(&response.json().await?))
+        } else {
+            Err(anyhow!("Failed to show model '{}': {}", model_name, response.status()))
+        }
+    }
+    
+    /// Installed models matching `filter`, in its sort order
+    pub async fn list_models_filtered(&self, filter: ModelFilter) -> Result<Vec<ModelInfo>> {
+        let url = format!("{}/api/tags", self.base_url);
+        let response = self.client.get(&url).send().await?;
+        
+        if response.status().is_success() {
+            let model_info: OllamaModelInfo = response.json().await?;
+            let models = model_info.models
+                .into_iter()
+                .map(|m| ModelInfo {
+                    capabilities: self.capabilities_for(&m.name),
+                    name: m.name,
+                    size: m.size,
+                    modified_at: m.modified_at,
+                })
+                .collect();
+            Ok(filter.apply(models))
+        } else {
+            Err(anyhow!("Failed to fetch models"))
+        }
+    }
+    
     pub async fn switch_model(&mut self, model_name: &str) -> Result<()> {
         // Check if it's an alias
         let actual_model = self.config.model_aliases
@@ -188,6 +469,18 @@ impl LLMWrapper {
             .map(|s| s.as_str())
             .unwrap_or(model_name);
         
+        // Only switch to models that are actually installed
+        let installed = self.list_models().await?;
+        let normalized = normalize_model_name(actual_model);
+        if !installed.iter().any(|name| normalize_model_name(name) == normalized) {
+            let suggestions = close_matches(&installed, actual_model);
+            return Err(if suggestions.is_empty() {
+                anyhow!("Model '{}' is not installed", actual_model)
+            } else {
+                anyhow!("Model '{}' is not installed. Did you mean: {}?", actual_model, suggestions.join(", "))
+            });
+        }
+        
         self.model = actual_model.to_string();
         self.detect_capabilities().await?;
         Ok(())
@@ -206,7 +499,7 @@ impl LLMWrapper {
         }
     }
     
-    pub async fn chat(&self, message: &str, images: &[PathBuf], system_prompt: Option<&str>) -> Result<String> {
+    async fn build_chat_request(&self, message: &str, images: &[PathBuf], system_prompt: Option<&str>, stream: bool) -> Result<ChatRequest> {
         let mut messages = Vec::new();
         
         // Add system message if provided
@@ -229,7 +522,9 @@ impl LLMWrapper {
         if !images.is_empty() && self.capabilities.supports_vision {
             let mut image_data = Vec::new();
             for img_path in images {
-                if img_path.exists() && self.is_image_file(img_path) {
+                if let Some(url) = remote_image_url(img_path) {
+                    image_data.push(fetch_remote_image(&self.client, url, self.config.max_image_bytes).await?);
+                } else if img_path.exists() && self.is_image_file(img_path) {
                     match self.encode_image(img_path).await {
                         Ok(encoded) => image_data.push(encoded),
                         Err(e) => eprintln!("⚠️  Failed to encode image {}: {}", img_path.display(), e),
@@ -248,17 +543,33 @@ impl LLMWrapper {
         let mut request = ChatRequest {
             model: self.model.clone(),
             messages,
-            stream: false, // For now, let's use non-streaming for simplicity
-            options: None,
+            stream,
+            options: self.generation_options.to_options(),
         };
         
         // Handle thinking models
         if self.capabilities.supports_thinking {
-            let mut options = HashMap::new();
-            options.insert("thinking".to_string(), serde_json::Value::Bool(true));
-            request.options = Some(options);
+            request.options
+                .get_or_insert_with(HashMap::new)
+                .insert("thinking".to_string(), serde_json::Value::Bool(true));
         }
         
+        Ok(request)
+    }
+    
+    pub async fn chat(&self, message: &str, images: &[PathBuf], system_prompt: Option<&str>) -> Result<String> {
+        Ok(self.chat_with_usage(message, images, system_prompt).await?.content)
+    }
+
+    /// Like `chat`, along with the token counts the server reported
+    pub async fn chat_with_usage(
+        &self,
+        message: &str,
+        images: &[PathBuf],
+        system_prompt: Option<&str>,
+    ) -> Result<ChatCompletion> {
+        let request = self.build_chat_request(message, images, system_prompt, false).await?;
+        
         let url = format!("{}/api/chat", self.base_url);
         let response = self.client.post(&url).json(&request).send().await?;
         
@@ -270,12 +581,124 @@ impl LLMWrapper {
                 result = format!("🤔 Thinking: {}\n\n{}", thinking, result);
             }
             
-            Ok(result)
+            Ok(ChatCompletion {
+                content: result,
+                usage: Some(TokenUsage {
+                    prompt_tokens: chat_response.prompt_eval_count,
+                    completion_tokens: chat_response.eval_count,
+                    eval_duration: None,
+                }),
+                tool_calls: Vec::new(),
+            })
         } else {
             Err(anyhow!("Chat request failed: {}", response.status()))
         }
     }
     
+    /// Stream a chat response, calling `on_token` with each piece of output as
+    /// it arrives. The pieces concatenate to the returned string, including
+    /// the thinking prefix that `chat` adds for thinking models.
+    pub async fn chat_stream<F>(
+        &self,
+        message: &str,
+        images: &[PathBuf],
+        system_prompt: Option<&str>,
+        mut on_token: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        use futures_util::StreamExt;
+        
+        let request = self.build_chat_request(message, images, system_prompt, true).await?;
+        
+        let url = format!("{}/api/chat", self.base_url);
+        let response = self.client.post(&url).json(&request).send().await?;
+        
+        if !response.status().is_success() {
+            return Err(anyhow!("Chat request failed: {}", response.status()));
+        }
+        
+        let mut output = StreamOutput::default();
+        let mut lines = streaming::LineBuffer::default();
+        let mut body = response.bytes_stream();
+        
+        while let Some(chunk) = body.next().await {
+            for line in lines.push(&chunk?) {
+                if output.push_line(&line, &mut on_token)? {
+                    return Ok(output.result);
+                }
+            }
+        }
+        if let Some(line) = lines.finish() {
+            output.push_line(&line, &mut on_token)?;
+        }
+        
+        Ok(output.result)
+    }
+    
+    fn build_generate_request(&self, prompt: &str, options: &GenerateOptions, stream: bool) -> GenerateRequest {
+        GenerateRequest {
+            model: self.model.clone(),
+            prompt: prompt.to_string(),
+            stream,
+            system: options.system.clone(),
+            template: options.template.clone(),
+            raw: options.raw,
+            options: self.generation_options.to_options(),
+        }
+    }
+    
+    /// Complete a raw prompt through `/api/generate` rather than `/api/chat`
+    pub async fn generate(&self, prompt: &str, options: &GenerateOptions) -> Result<String> {
+        let request = self.build_generate_request(prompt, options, false);
+        
+        let url = format!("{}/api/generate", self.base_url);
+        let response = self.client.post(&url).json(&request).send().await?;
+        
+        if response.status().is_success() {
+            let generated: GenerateChunk = response.json().await?;
+            Ok(generated.response)
+        } else {
+            Err(anyhow!("Generate request failed: {}", response.status()))
+        }
+    }
+    
+    /// Stream a raw prompt completion, calling `on_token` for each piece as it
+    /// arrives and returning the full response
+    pub async fn generate_stream<F>(&self, prompt: &str, options: &GenerateOptions, mut on_token: F) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        use futures_util::StreamExt;
+        
+        let request = self.build_generate_request(prompt, options, true);
+        
+        let url = format!("{}/api/generate", self.base_url);
+        let response = self.client.post(&url).json(&request).send().await?;
+        
+        if !response.status().is_success() {
+            return Err(anyhow!("Generate request failed: {}", response.status()));
+        }
+        
+        let mut result = String::new();
+        let mut lines = streaming::LineBuffer::default();
+        let mut body = response.bytes_stream();
+        
+        while let Some(chunk) = body.next().await {
+            for line in lines.push(&chunk?) {
+                if push_generate_line(&line, &mut result, &mut on_token)? {
+                    return Ok(result);
+                }
+            }
+        }
+        if let Some(line) = lines.finish() {
+            push_generate_line(&line, &mut result, &mut on_token)?;
+        }
+        
+        Ok(result)
+    }
+    
     pub async fn pull_model(&self, model_name: &str) -> Result<()> {
         let url = format!("{}/api/pull", self.base_url);
         let request = serde_json::json!({
@@ -309,19 +732,214 @@ impl LLMWrapper {
     }
 }
 
+/// Effective capabilities of one model on one configured backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityReport {
+    pub backend: String,
+    pub model: String,
+    pub capabilities: ModelCapabilities,
+}
+
+/// Machine-readable snapshot of wrapper and cache statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsReport {
+    pub metrics: MetricsCollector,
+    pub cache: cache::DetailedCacheStats,
+}
+
+/// A stream ready to open, detached from the wrapper. Send it, then hand the
+/// result to `EnhancedLLMWrapper::finish_stream`.
+pub struct PendingStream {
+    backend: Arc<dyn Backend>,
+    backend_name: String,
+    rate_limits: streaming::RequestRateLimits,
+    request_limiter: streaming::RequestLimiter,
+    request: streaming::ChatRequest,
+    span: tracing::Span,
+}
+
+/// A `PendingStream` after the backend has been asked for the stream
+pub struct SentStream {
+    result: Result<StreamResponse, WrapperError>,
+    backend_name: String,
+    model: String,
+    elapsed: std::time::Duration,
+    span: tracing::Span,
+}
+
+impl PendingStream {
+    /// Open the stream under the concurrency cap and rate limits, without
+    /// needing the wrapper
+    pub async fn send(self) -> SentStream {
+        let PendingStream { backend, backend_name, rate_limits, request_limiter, request, span } = self;
+        let model = request.model.clone();
+        let started = std::time::Instant::now();
+
+        let result = async {
+            let _permit = request_limiter.acquire().await?;
+            let slots = rate_limits.acquire()?;
+            let response = backend.chat_stream(request).await?;
+            // The rate limit slots stay taken until the stream ends
+            Ok(response.holding(slots))
+        }
+        .instrument(span.clone())
+        .await;
+
+        SentStream {
+            result,
+            backend_name,
+            model,
+            elapsed: started.elapsed(),
+            span,
+        }
+    }
+}
+
+/// One backend a detached chat may be sent to
+struct FailoverCandidate {
+    name: String,
+    backend: Arc<dyn Backend>,
+    rate_limits: streaming::RequestRateLimits,
+    is_primary: bool,
+}
+
+/// Send `request` to each candidate in turn while backends are unreachable.
+/// Returns the name of the backend that answered along with its reply.
+async fn send_with_failover(
+    candidates: &[FailoverCandidate],
+    request: &streaming::ChatRequest,
+) -> Result<(String, ChatCompletion), WrapperError> {
+    let mut last_error: Option<WrapperError> = None;
+    for candidate in candidates {
+        // The primary fails fast on its own; fallbacks known to be down are skipped
+        if !candidate.is_primary && candidate.backend.circuit_state() == CircuitState::Open {
+            continue;
+        }
+
+        // A backend over its configured rate limit is passed over like an unreachable one
+        let slots = match candidate.rate_limits.acquire() {
+            Ok(slots) => slots,
+            Err(e) => {
+                crate::logging::log_error(&e, "Backend rate limited, trying next fallback");
+                last_error = Some(e.into());
+                continue;
+            }
+        };
+        let result = candidate.backend.chat_with_usage(request.clone()).await;
+        drop(slots);
+
+        match result {
+            Ok(completion) => return Ok((candidate.name.clone(), completion)),
+            Err(e) if e.is_unreachable() => {
+                crate::logging::log_backend_event("failover", &candidate.name, false, None);
+                crate::logging::log_error(&e, "Backend unreachable, trying next fallback");
+                last_error = Some(e.into());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(last_error
+        .unwrap_or_else(|| BackendError::Connection("No reachable backend".to_string()).into()))
+}
+
+/// How a chat started with `begin_chat_with_history` continues
+pub enum ChatStep {
+    /// Served from the cache; nothing left to do
+    Answered(String),
+    /// Needs a backend: send it, then pass the result to `finish_chat`
+    Pending(Box<PendingChat>),
+}
+
+/// A chat that missed the cache, detached from the wrapper so the backend
+/// call can run without borrowing it
+pub struct PendingChat {
+    cache_key: cache::CacheKey,
+    request: streaming::ChatRequest,
+    candidates: Vec<FailoverCandidate>,
+    request_limiter: streaming::RequestLimiter,
+    embedding: Option<Vec<f32>>,
+    prompt_tokens_estimate: usize,
+    start_time: std::time::Instant,
+    /// Correlation id of the request this chat belongs to
+    request_id: Option<String>,
+    span: tracing::Span,
+}
+
+/// A request of `chat_batch` once its backend call, if any, has been made
+enum BatchReply {
+    Done(Result<String, WrapperError>),
+    Sent(Box<SentChat>),
+}
+
+/// A `PendingChat` after its backend call
+pub struct SentChat {
+    pending: PendingChat,
+    result: Result<(String, ChatCompletion), WrapperError>,
+    backend_ms: f64,
+}
+
+impl PendingChat {
+    /// Send the request under the concurrency cap, failing over between
+    /// backends, without needing the wrapper
+    pub async fn send(self) -> SentChat {
+        let span = self.span.clone();
+        async move {
+            let backend_start = std::time::Instant::now();
+            let result = match self.request_limiter.acquire().await {
+                Ok(_permit) => send_with_failover(&self.candidates, &self.request).await,
+                Err(e) => Err(e.into()),
+            };
+            SentChat {
+                result,
+                backend_ms: backend_start.elapsed().as_secs_f64() * 1000.0,
+                pending: self,
+            }
+        }
+        .instrument(span)
+        .await
+    }
+}
+
 // Enhanced LLM Wrapper that orchestrates all components
 pub struct EnhancedLLMWrapper {
-    backends: HashMap<String, Box<dyn Backend>>,
+    backends: HashMap<String, Arc<dyn Backend>>,
+    /// Availability seen by the last health check, by backend name
+    backend_health: Arc<RwLock<HashMap<String, bool>>>,
+    health_task: Option<tokio::task::JoinHandle<()>>,
     cache_manager: CacheManager,
     template_engine: TemplateEngine,
     streaming_manager: StreamingManager,
+    request_limiter: streaming::RequestLimiter,
     config: EnhancedConfig,
     metrics: MetricsCollector,
     performance_monitor: performance::PerformanceMonitor,
+    monitoring_task: tokio::task::JoinHandle<()>,
     current_backend: String,
+    sessions: HashMap<String, Vec<streaming::Message>>,
+    generation_options: GenerationOptions,
+    last_usage: Option<TokenUsage>,
+    last_backend: Option<String>,
+    /// Correlation id of the most recent chat request
+    last_request_id: Option<String>,
+    cache_tags: Vec<String>,
+    /// Whether `chat_typed` asks the model once to fix output that doesn't parse
+    repair_typed_output: bool,
+    /// Context lengths reported by the current backend, by model
+    context_lengths: HashMap<String, u32>,
+    config_watcher: Option<config::ConfigWatcher>,
+}
+
+impl Drop for EnhancedLLMWrapper {
+    fn drop(&mut self) {
+        self.monitoring_task.abort();
+        if let Some(task) = &self.health_task {
+            task.abort();
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetricsCollector {
     pub requests_total: u64,
     pub cache_hits: u64,
@@ -330,6 +948,8 @@ pub struct MetricsCollector {
     pub active_streams: u64,
     pub errors_total: u64,
     pub average_response_time_ms: f64,
+    pub prompt_tokens_total: u64,
+    pub completion_tokens_total: u64,
 }
 
 impl Default for MetricsCollector {
@@ -342,6 +962,8 @@ impl Default for MetricsCollector {
             active_streams: 0,
             errors_total: 0,
             average_response_time_ms: 0.0,
+            prompt_tokens_total: 0,
+            completion_tokens_total: 0,
         }
     }
 }
@@ -377,6 +999,11 @@ impl MetricsCollector {
         self.errors_total += 1;
     }
 
+    pub fn record_token_usage(&mut self, usage: &TokenUsage) {
+        self.prompt_tokens_total += usage.prompt_tokens.unwrap_or(0) as u64;
+        self.completion_tokens_total += usage.completion_tokens.unwrap_or(0) as u64;
+    }
+
     pub fn record_response_time(&mut self, duration_ms: f64) {
         // Simple moving average
         let total_requests = self.requests_total as f64;
@@ -388,6 +1015,15 @@ impl MetricsCollector {
         }
     }
 
+    /// Zero every counter to start a fresh measurement window. Streams still
+    /// in flight stay counted, since they will report their end later.
+    pub fn reset(&mut self) {
+        *self = Self {
+            active_streams: self.active_streams,
+            ..Self::default()
+        };
+    }
+
     pub fn cache_hit_ratio(&self) -> f64 {
         let total = self.cache_hits + self.cache_misses;
         if total == 0 {
@@ -398,6 +1034,14 @@ impl MetricsCollector {
     }
 }
 
+fn write_prometheus_metric(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    use std::fmt::Write;
+
+    let _ = writeln!(out, "# HELP llm_wrapper_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE llm_wrapper_{} {}", name, kind);
+    let _ = writeln!(out, "llm_wrapper_{} {}", name, value);
+}
+
 impl EnhancedLLMWrapper {
     pub async fn new(config: EnhancedConfig) -> Result<Self, WrapperError> {
         // Initialize logging first
@@ -420,11 +1064,31 @@ impl EnhancedLLMWrapper {
             max_template_size: 1024 * 1024,
             max_render_time_ms: 5000,
             allowed_helpers: config.templates.custom_helpers.clone(),
+            ..Default::default()
         };
         let template_engine = TemplateEngine::new(template_config);
 
-        // Initialize streaming manager
-        let streaming_manager = StreamingManager::new(config.streaming.max_concurrent_streams);
+        // Initialize streaming manager with the backend and per-model rate limits
+        let mut streaming_manager = StreamingManager::with_config(streaming::StreamingConfig {
+            max_concurrent_streams: config.streaming.max_concurrent_streams,
+            requests_per_second: 10.0,
+            request_timeout: std::time::Duration::from_secs(30),
+            connection: config.streaming.connection.clone(),
+            model_rate_limits: HashMap::new(),
+            retry: streaming::RetryPolicy::default(),
+        });
+        let request_limiter = streaming::RequestLimiter::new(
+            config.streaming.max_concurrent_requests.unwrap_or(config.streaming.max_concurrent_streams),
+            config.streaming.reject_when_saturated,
+        );
+        for (name, backend_config) in &config.backends {
+            streaming_manager.set_backend_rate_limit(name, backend_config.rate_limit.as_ref().map(Into::into));
+            streaming_manager.set_backend_retry_policy(name, backend_config.retry_policy());
+            streaming_manager.set_backend_client(name, backend_config.build_client()?);
+            for (model, limit) in &backend_config.model_rate_limits {
+                streaming_manager.set_model_rate_limit(name, model, limit.into());
+            }
+        }
 
         // Initialize backends
         let mut backends: HashMap<String, Box<dyn Backend>> = HashMap::new();
@@ -432,7 +1096,7 @@ impl EnhancedLLMWrapper {
         for (name, backend_config) in &config.backends {
             match backend_config.backend_type {
                 config::BackendType::Ollama => {
-                    let backend = OllamaBackend::new(backend_config.base_url.clone())?;
+                    let backend = OllamaBackend::from_config(backend_config)?;
                     backends.insert(name.clone(), Box::new(backend));
                 }
                 config::BackendType::LMStudio => {
@@ -440,8 +1104,8 @@ impl EnhancedLLMWrapper {
                     eprintln!("Warning: LMStudio backend not yet implemented");
                 }
                 config::BackendType::OpenAI => {
-                    // TODO: Implement OpenAI backend
-                    eprintln!("Warning: OpenAI backend not yet implemented");
+                    let backend = OpenAIBackend::from_config(backend_config)?;
+                    backends.insert(name.clone(), Box::new(backend));
                 }
                 config::BackendType::Custom => {
                     // TODO: Implement Custom backend
@@ -454,6 +1118,21 @@ impl EnhancedLLMWrapper {
             }
         }
 
+        // Guard every backend with its circuit breaker, which sees a request
+        // only once its own retries are used up
+        let backends: HashMap<String, Arc<dyn Backend>> = backends
+            .into_iter()
+            .map(|(name, backend)| {
+                let backend_config = &config.backends[&name];
+                let breaker = &backend_config.circuit_breaker;
+                let guarded = CircuitBreakerBackend::new(
+                    Box::new(RetryBackend::new(backend, backend_config.retry_policy())),
+                    CircuitBreaker::new(breaker.failure_threshold, breaker.cooldown),
+                );
+                (name, Arc::new(guarded) as Arc<dyn Backend>)
+            })
+            .collect();
+
         if backends.is_empty() {
             let error = WrapperError::Config(ConfigError::Validation(
                 "No valid backends configured".to_string()
@@ -472,19 +1151,41 @@ impl EnhancedLLMWrapper {
 
         let performance_monitor = performance::PerformanceMonitor::new();
         
-        // Start background performance monitoring
-        let _monitoring_task = performance_monitor.start_monitoring_task();
+        // Start background performance monitoring; aborted when the wrapper is dropped
+        let monitoring_task = performance_monitor.start_monitoring_task();
+
+        // Backends count as up until a health check says otherwise
+        let backend_health = Arc::new(RwLock::new(
+            backends.keys().map(|name| (name.clone(), true)).collect(),
+        ));
 
-        Ok(Self {
+        let mut wrapper = Self {
             backends,
+            backend_health,
+            health_task: None,
             cache_manager,
             template_engine,
             streaming_manager,
+            request_limiter,
             config,
             metrics: MetricsCollector::default(),
             performance_monitor,
+            monitoring_task,
             current_backend,
-        })
+            sessions: HashMap::new(),
+            generation_options: GenerationOptions::default(),
+            last_usage: None,
+            last_backend: None,
+            last_request_id: None,
+            cache_tags: Vec::new(),
+            repair_typed_output: false,
+            context_lengths: HashMap::new(),
+            config_watcher: None,
+        };
+        if let Some(interval) = wrapper.config.health_check_interval {
+            wrapper.start_health_checks(interval);
+        }
+        Ok(wrapper)
     }
 
     pub async fn chat_with_template(
@@ -494,8 +1195,20 @@ impl EnhancedLLMWrapper {
         model: Option<&str>,
     ) -> Result<StreamResponse, WrapperError> {
         let start_time = std::time::Instant::now();
-        self.metrics.record_request();
-        
+        let span = self.start_request();
+        let model = model.map(|m| self.resolve_model(m));
+        self.template_stream(template_name, variables, model.as_deref(), start_time)
+            .instrument(span)
+            .await
+    }
+
+    async fn template_stream(
+        &mut self,
+        template_name: &str,
+        variables: serde_json::Value,
+        model: Option<&str>,
+        start_time: std::time::Instant,
+    ) -> Result<StreamResponse, WrapperError> {
         tracing::info!(
             template_name = template_name,
             model = model,
@@ -514,7 +1227,7 @@ impl EnhancedLLMWrapper {
             }
             Err(e) => {
                 let duration = template_start.elapsed();
-                self.metrics.record_error();
+                self.record_error();
                 self.performance_monitor.record_template_render(duration, false);
                 crate::logging::log_template_event("render", template_name, false);
                 crate::logging::log_error(&e, "Template rendering");
@@ -522,11 +1235,17 @@ impl EnhancedLLMWrapper {
             }
         };
 
-        // Create cache key
+        // Create cache key; few-shot examples change the request, so they are part of it
+        let options = self.effective_options(model, self.generation_options.to_options());
+        let few_shot = self.template_engine.few_shot_examples(template_name);
+        let mut key_params = options.clone().unwrap_or_default();
+        if !few_shot.is_empty() {
+            key_params.insert("few_shot".to_string(), serde_json::to_value(&few_shot)?);
+        }
         let cache_key = cache::CacheKey::new(
             &rendered_prompt,
             model.unwrap_or("default"),
-            &std::collections::HashMap::new(),
+            &key_params,
         );
 
         // Check cache first with error handling and performance monitoring
@@ -548,6 +1267,7 @@ impl EnhancedLLMWrapper {
                 let _ = sender.send(StreamToken {
                     content: cached_response,
                     is_complete: true,
+                    kind: streaming::TokenKind::Answer,
                     metadata: Some(streaming::TokenMetadata {
                         timestamp: chrono::Utc::now(),
                         token_count: None,
@@ -569,42 +1289,28 @@ impl EnhancedLLMWrapper {
             }
         }
 
-        // Get backend with error handling
-        let backend = self.backends.get(&self.current_backend)
-            .ok_or_else(|| {
-                let error = WrapperError::Config(ConfigError::Validation(
-                    format!("Backend '{}' not found", self.current_backend)
-                ));
-                crate::logging::log_error(&error, "Backend lookup");
-                error
-            })?;
-
-        // Create chat request
-        let request = streaming::ChatRequest {
-            model: model.unwrap_or("default").to_string(),
-            messages: vec![streaming::Message {
+        // Templates that render to a message array supply the whole conversation;
+        // otherwise the rendered prompt becomes a single user message
+        let messages = template::parse_rendered_messages(&rendered_prompt).unwrap_or_else(|| {
+            vec![streaming::Message {
                 role: "user".to_string(),
                 content: rendered_prompt.clone(),
                 images: None,
-            }],
+            }]
+        });
+        let messages = self.template_engine.with_few_shot(template_name, messages);
+
+        // Create chat request
+        let request = streaming::ChatRequest {
+            model: model.unwrap_or("default").to_string(),
+            messages,
             stream: true,
-            options: None,
+            options,
+            response_format: None,
+            tools: None,
         };
 
-        // Create stream with error handling and retry logic
-        let stream_response = match backend.chat_stream(request).await {
-            Ok(response) => {
-                self.metrics.record_stream_start();
-                crate::logging::log_stream_event("start", response.id, model.unwrap_or("default"));
-                response
-            }
-            Err(e) => {
-                self.metrics.record_error();
-                crate::logging::log_backend_event("stream_error", &self.current_backend, false, None);
-                crate::logging::log_error(&e, "Stream creation");
-                return Err(WrapperError::Backend(e));
-            }
-        };
+        let stream_response = self.open_stream(request).await?;
 
         // Record response time
         let duration = start_time.elapsed();
@@ -621,86 +1327,1035 @@ impl EnhancedLLMWrapper {
         Ok(stream_response)
     }
 
+    /// Stream a conversation from the current backend, using the default
+    /// generation options. Streams are not cached.
+    pub async fn chat_stream(
+        &mut self,
+        messages: Vec<streaming::Message>,
+        model: Option<&str>,
+    ) -> Result<StreamResponse, WrapperError> {
+        let options = self.generation_options.clone();
+        let pending = self.begin_chat_stream(messages, model, &options).await?;
+        let sent = pending.send().await;
+        self.finish_stream(sent)
+    }
+
+    /// `chat_stream` split around the backend call, for callers sharing the
+    /// wrapper behind a lock: hold it for this and for `finish_stream`, but not
+    /// while `PendingStream::send` runs. `options` apply to this request only.
+    pub async fn begin_chat_stream(
+        &mut self,
+        messages: Vec<streaming::Message>,
+        model: Option<&str>,
+        options: &GenerationOptions,
+    ) -> Result<PendingStream, WrapperError> {
+        let span = self.start_request();
+        let model = model.map(|m| self.resolve_model(m));
+        let model = model.as_deref();
+
+        let request = streaming::ChatRequest {
+            model: model.unwrap_or("default").to_string(),
+            messages,
+            stream: true,
+            options: self.effective_options(model, options.to_options()),
+            response_format: None,
+            tools: None,
+        };
+
+        self.prepare_stream(request, span.clone()).instrument(span).await
+    }
+
+    /// Open a backend stream under the concurrency cap and per-model rate limit
+    async fn open_stream(&mut self, request: streaming::ChatRequest) -> Result<StreamResponse, WrapperError> {
+        let pending = self.prepare_stream(request, tracing::Span::current()).await?;
+        let sent = pending.send().await;
+        self.finish_stream(sent)
+    }
+
+    /// Everything about opening a stream that needs the wrapper: context
+    /// fitting and picking the backend and its rate limits
+    async fn prepare_stream(
+        &mut self,
+        mut request: streaming::ChatRequest,
+        span: tracing::Span,
+    ) -> Result<PendingStream, WrapperError> {
+        request.messages = self.fit_to_context(request.messages, &request.model).await;
+
+        // Get backend with error handling
+        let backend = self.backends.get(&self.current_backend)
+            .cloned()
+            .ok_or_else(|| {
+                let error = WrapperError::Config(ConfigError::Validation(
+                    format!("Backend '{}' not found", self.current_backend)
+                ));
+                crate::logging::log_error(&error, "Backend lookup");
+                error
+            })?;
+
+        let limited_model = self.limited_model(&self.current_backend, &request.model);
+        Ok(PendingStream {
+            backend,
+            backend_name: self.current_backend.clone(),
+            rate_limits: self.streaming_manager.rate_limits_for(&self.current_backend, &limited_model),
+            request_limiter: self.request_limiter.clone(),
+            request,
+            span,
+        })
+    }
+
+    /// Record the outcome of a `PendingStream` in the wrapper's metrics
+    pub fn finish_stream(&mut self, sent: SentStream) -> Result<StreamResponse, WrapperError> {
+        let _entered = sent.span.enter();
+        match sent.result {
+            Ok(response) => {
+                self.metrics.record_stream_start();
+                self.performance_monitor.record_stream_operation("create", Some(sent.elapsed));
+                crate::logging::log_stream_event("start", response.id, &sent.model);
+                Ok(response)
+            }
+            Err(e) => {
+                self.record_error();
+                match &e {
+                    WrapperError::Backend(_) => {
+                        crate::logging::log_backend_event("stream_error", &sent.backend_name, false, None);
+                        crate::logging::log_error(&e, "Stream creation");
+                    }
+                    _ => crate::logging::log_error(&e, "Rate limit"),
+                }
+                Err(e)
+            }
+        }
+    }
+
     pub async fn chat(
         &mut self,
         message: &str,
         model: Option<&str>,
+    ) -> Result<String, WrapperError> {
+        let options = self.generation_options.to_options();
+        self.chat_with_options(message, model, None, options).await
+    }
+
+    /// The limiter bounding in-flight backend requests. Pass it to
+    /// `set_request_limiter` on other wrappers to share one budget between them.
+    pub fn request_limiter(&self) -> streaming::RequestLimiter {
+        self.request_limiter.clone()
+    }
+
+    pub fn set_request_limiter(&mut self, limiter: streaming::RequestLimiter) {
+        self.request_limiter = limiter;
+    }
+
+    pub fn generation_options(&self) -> &GenerationOptions {
+        &self.generation_options
+    }
+
+    /// Default sampling parameters for `chat`, `chat_with_history` and sessions
+    pub fn set_generation_options(&mut self, options: GenerationOptions) {
+        self.generation_options = options;
+    }
+
+    /// Chat with an optional system prompt and generation options. Both are part
+    /// of the cache key, so requests differing only in these are cached separately.
+    pub async fn chat_with_options(
+        &mut self,
+        message: &str,
+        model: Option<&str>,
+        system_prompt: Option<&str>,
+        options: Option<HashMap<String, serde_json::Value>>,
     ) -> Result<String, WrapperError> {
         let start_time = std::time::Instant::now();
-        self.metrics.record_request();
+        let span = self.start_request();
+        let model = model.map(|m| self.resolve_model(m));
+        let model = model.as_deref();
+        let options = self.effective_options(model, options);
 
         // Create cache key
-        let cache_key = cache::CacheKey::new(
+        let cache_key = cache::CacheKey::with_system_prompt(
             message,
+            system_prompt,
+            model.unwrap_or("default"),
+            options.as_ref().unwrap_or(&HashMap::new()),
+        );
+
+        let mut messages = Vec::new();
+        if let Some(system) = system_prompt {
+            messages.push(streaming::Message {
+                role: "system".to_string(),
+                content: system.to_string(),
+                images: None,
+            });
+        }
+        messages.push(streaming::Message {
+            role: "user".to_string(),
+            content: message.to_string(),
+            images: None,
+        });
+
+        self.send_chat(cache_key, messages, model, options, None, start_time)
+            .instrument(span)
+            .await
+    }
+
+    /// Answer many independent prompts concurrently, returning the replies in
+    /// input order.
+    ///
+    /// Each prompt is its own request, with the same failover, rate limits,
+    /// context fitting and metrics as `chat`. Cached prompts are answered
+    /// without waiting; the rest share the request limiter, so at most
+    /// `max_concurrent_requests` reach the backend at once.
+    pub async fn chat_batch(
+        &mut self,
+        prompts: Vec<String>,
+        model: Option<&str>,
+    ) -> Vec<Result<String, WrapperError>> {
+        let model = model.map(|m| self.resolve_model(m));
+        let model = model.as_deref();
+        let options = self.effective_options(model, self.generation_options.to_options());
+
+        let mut steps = Vec::with_capacity(prompts.len());
+        for prompt in &prompts {
+            steps.push(self.begin_batch_chat(prompt, model, options.clone()).await);
+        }
+        self.run_batch(steps).await
+    }
+
+    /// Send one prompt to several models at once, keyed by the model names
+    /// as given. Each model gets its own request, cache entry and defaults,
+    /// handled as in `chat_batch`.
+    pub async fn chat_compare(
+        &mut self,
+        prompt: &str,
+        models: &[&str],
+    ) -> HashMap<String, Result<String, WrapperError>> {
+        let mut steps = Vec::with_capacity(models.len());
+        for &name in models {
+            let model = self.resolve_model(name);
+            let options = self.effective_options(Some(&model), self.generation_options.to_options());
+            steps.push(self.begin_batch_chat(prompt, Some(&model), options).await);
+        }
+
+        let replies = self.run_batch(steps).await;
+        models.iter().map(|name| name.to_string()).zip(replies).collect()
+    }
+
+    /// Start one request of a batch: count it, give it a request id and look
+    /// it up in the cache
+    async fn begin_batch_chat(
+        &mut self,
+        prompt: &str,
+        model: Option<&str>,
+        options: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<ChatStep, WrapperError> {
+        let start_time = std::time::Instant::now();
+        let span = self.start_request();
+        let cache_key = cache::CacheKey::with_system_prompt(
+            prompt,
+            None,
+            model.unwrap_or("default"),
+            options.as_ref().unwrap_or(&HashMap::new()),
+        );
+        let messages = vec![streaming::Message {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+            images: None,
+        }];
+
+        self.begin_chat(cache_key, messages, model, options, None, start_time)
+            .instrument(span)
+            .await
+    }
+
+    /// Send the pending requests of a batch concurrently, then record each
+    /// outcome in order
+    async fn run_batch(&mut self, steps: Vec<Result<ChatStep, WrapperError>>) -> Vec<Result<String, WrapperError>> {
+        let sends = steps.into_iter().map(|step| async move {
+            match step {
+                Ok(ChatStep::Pending(pending)) => BatchReply::Sent(Box::new(pending.send().await)),
+                Ok(ChatStep::Answered(response)) => BatchReply::Done(Ok(response)),
+                Err(e) => BatchReply::Done(Err(e)),
+            }
+        });
+
+        let mut replies = Vec::new();
+        for reply in futures_util::future::join_all(sends).await {
+            replies.push(match reply {
+                BatchReply::Sent(sent) => self.finish_chat(*sent).await,
+                BatchReply::Done(result) => result,
+            });
+        }
+        replies
+    }
+
+    /// Chat in structured output mode and parse the reply into `T`.
+    ///
+    /// `format` is either `ResponseFormat::Json` for any valid JSON or a JSON
+    /// schema the reply must match. The format is part of the cache key.
+    pub async fn chat_json<T: DeserializeOwned>(
+        &mut self,
+        message: &str,
+        model: Option<&str>,
+        format: ResponseFormat,
+    ) -> Result<T, WrapperError> {
+        let start_time = std::time::Instant::now();
+        let span = self.start_request();
+        let model = model.map(|m| self.resolve_model(m));
+        let model = model.as_deref();
+
+        let options = self.effective_options(model, self.generation_options.to_options());
+        let mut key_params = options.clone().unwrap_or_default();
+        key_params.insert("format".to_string(), format.clone().into());
+        let cache_key = cache::CacheKey::new(message, model.unwrap_or("default"), &key_params);
+
+        let messages = vec![streaming::Message {
+            role: "user".to_string(),
+            content: message.to_string(),
+            images: None,
+        }];
+
+        let response = self.send_chat(cache_key, messages, model, options, Some(format), start_time)
+            .instrument(span)
+            .await?;
+        Ok(serde_json::from_str(&response)?)
+    }
+
+    /// Chat with output constrained to `schema` and deserialize it into `T`.
+    ///
+    /// Output that doesn't deserialize is dropped from the cache and reported
+    /// as `WrapperError::InvalidOutput` with the raw text. With
+    /// `set_repair_typed_output(true)` the model first gets one chance to fix it.
+    ///
+    /// This takes `&mut self` because it goes through the same path as `chat`:
+    /// it fails over, honours the per-model and per-backend rate limits, and
+    /// updates the metrics and `last_usage`.
+    pub async fn chat_typed<T: DeserializeOwned>(
+        &mut self,
+        prompt: &str,
+        schema: serde_json::Value,
+        model: Option<&str>,
+    ) -> Result<T, WrapperError> {
+        let start_time = std::time::Instant::now();
+        let span = self.start_request();
+        self.typed_chat(prompt, schema, model, start_time).instrument(span).await
+    }
+
+    async fn typed_chat<T: DeserializeOwned>(
+        &mut self,
+        prompt: &str,
+        schema: serde_json::Value,
+        model: Option<&str>,
+        start_time: std::time::Instant,
+    ) -> Result<T, WrapperError> {
+        let model = model.map(|m| self.resolve_model(m));
+        let model = model.as_deref();
+        let format = ResponseFormat::Schema(schema);
+
+        // Keyed like `chat_json`, so the two share cached answers
+        let options = self.effective_options(model, self.generation_options.to_options());
+        let mut key_params = options.clone().unwrap_or_default();
+        key_params.insert("format".to_string(), format.clone().into());
+        let cache_key = cache::CacheKey::new(prompt, model.unwrap_or("default"), &key_params);
+
+        let mut messages = vec![streaming::Message {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+            images: None,
+        }];
+        let raw = self.send_chat(cache_key.clone(), messages.clone(), model, options.clone(), Some(format.clone()), start_time).await?;
+        let error = match serde_json::from_str(&raw) {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        self.cache_manager.invalidate(&cache_key).await?;
+        if !self.repair_typed_output {
+            return Err(WrapperError::InvalidOutput { message: error.to_string(), raw });
+        }
+
+        tracing::debug!(error = %error, "Asking the model to repair output that didn't parse");
+        messages.push(streaming::Message {
+            role: "assistant".to_string(),
+            content: raw,
+            images: None,
+        });
+        messages.push(streaming::Message {
+            role: "user".to_string(),
+            content: format!(
+                "That reply could not be parsed ({}). Reply again with only the corrected JSON, matching the requested schema.",
+                error
+            ),
+            images: None,
+        });
+        let repair_key = cache::CacheKey::new(&serde_json::to_string(&messages)?, model.unwrap_or("default"), &key_params);
+        let repaired = self.send_chat(repair_key.clone(), messages, model, options, Some(format), start_time).await?;
+        match serde_json::from_str(&repaired) {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                self.cache_manager.invalidate(&repair_key).await?;
+                Err(WrapperError::InvalidOutput { message: e.to_string(), raw: repaired })
+            }
+        }
+    }
+
+    /// Send a conversation along with tools the model may call, getting back
+    /// either its text answer or the calls it wants made. Send each call's
+    /// result back as a `tool` message in a follow-up request.
+    ///
+    /// Replies aren't cached, since they depend on what the tools return.
+    pub async fn chat_with_tools(
+        &mut self,
+        messages: Vec<streaming::Message>,
+        tools: Vec<streaming::Tool>,
+        model: Option<&str>,
+    ) -> Result<streaming::ToolChatResponse, WrapperError> {
+        let start_time = std::time::Instant::now();
+        let span = self.start_request();
+        let model = model.map(|m| self.resolve_model(m)).unwrap_or_else(|| "default".to_string());
+
+        let request = streaming::ChatRequest {
+            messages: self.fit_to_context(messages, &model).await,
+            options: self.effective_options(Some(&model), self.generation_options.to_options()),
+            model,
+            stream: false,
+            response_format: None,
+            tools: Some(tools),
+        };
+
+        self.send_tool_chat(request, start_time).instrument(span).await
+    }
+
+    async fn send_tool_chat(
+        &mut self,
+        request: streaming::ChatRequest,
+        start_time: std::time::Instant,
+    ) -> Result<streaming::ToolChatResponse, WrapperError> {
+        let _permit = self.request_limiter.acquire().await?;
+        let backend_start = std::time::Instant::now();
+        let result = self.chat_with_failover(request).await;
+        let backend_ms = backend_start.elapsed().as_secs_f64() * 1000.0;
+
+        let (backend_name, completion) = match result {
+            Ok(served) => served,
+            Err(e) => {
+                self.record_error();
+                crate::logging::log_backend_event("chat", &self.current_backend, false, Some(backend_ms));
+                return Err(e);
+            }
+        };
+        crate::logging::log_backend_event("chat", &backend_name, true, Some(backend_ms));
+        let usage = completion.usage.unwrap_or_default();
+        self.metrics.record_token_usage(&usage);
+        self.last_usage = Some(usage);
+        self.last_backend = Some(backend_name);
+        self.metrics.record_response_time(start_time.elapsed().as_millis() as f64);
+
+        Ok(if completion.tool_calls.is_empty() {
+            streaming::ToolChatResponse::Text(completion.content)
+        } else {
+            streaming::ToolChatResponse::ToolCalls(completion.tool_calls)
+        })
+    }
+
+    /// Send a whole conversation to the current backend.
+    ///
+    /// The cache key hashes the full message list, so a follow-up question only
+    /// hits the cache when the entire preceding conversation matches as well.
+    /// Expect a lower hit rate for multi-turn chats than for single prompts.
+    pub async fn chat_with_history(
+        &mut self,
+        messages: Vec<streaming::Message>,
+        model: Option<&str>,
+    ) -> Result<String, WrapperError> {
+        let options = self.generation_options.clone();
+        match self.begin_chat_with_history(messages, model, &options).await? {
+            ChatStep::Answered(response) => Ok(response),
+            ChatStep::Pending(pending) => {
+                let sent = pending.send().await;
+                self.finish_chat(sent).await
+            }
+        }
+    }
+
+    /// `chat_with_history` split around the backend call, for callers sharing
+    /// the wrapper behind a lock: hold it for this and for `finish_chat`, but
+    /// not while `PendingChat::send` runs. `options` apply to this request only.
+    pub async fn begin_chat_with_history(
+        &mut self,
+        messages: Vec<streaming::Message>,
+        model: Option<&str>,
+        options: &GenerationOptions,
+    ) -> Result<ChatStep, WrapperError> {
+        let start_time = std::time::Instant::now();
+        let span = self.start_request();
+        let model = model.map(|m| self.resolve_model(m));
+        let model = model.as_deref();
+
+        let options = self.effective_options(model, options.to_options());
+        let cache_key = cache::CacheKey::new(
+            &serde_json::to_string(&messages)?,
             model.unwrap_or("default"),
-            &std::collections::HashMap::new(),
+            options.as_ref().unwrap_or(&HashMap::new()),
+        );
+
+        self.begin_chat(cache_key, messages, model, options, None, start_time)
+            .instrument(span)
+            .await
+    }
+
+    /// Chat within a named session, accumulating the user and assistant turns
+    pub async fn chat_in_session(
+        &mut self,
+        session_id: &str,
+        message: &str,
+        model: Option<&str>,
+    ) -> Result<String, WrapperError> {
+        let mut messages = self.sessions.get(session_id).cloned().unwrap_or_default();
+        messages.push(streaming::Message {
+            role: "user".to_string(),
+            content: message.to_string(),
+            images: None,
+        });
+
+        let response = self.chat_with_history(messages.clone(), model).await?;
+
+        // Only record the turn once the backend has answered
+        messages.push(streaming::Message {
+            role: "assistant".to_string(),
+            content: response.clone(),
+            images: None,
+        });
+        self.sessions.insert(session_id.to_string(), messages);
+
+        Ok(response)
+    }
+
+    /// Ask the last user message of a session again, replacing the answer it
+    /// got. Its cached answer is dropped first so the backend really is asked
+    /// again, optionally by a different model.
+    pub async fn regenerate_last(
+        &mut self,
+        session_id: &str,
+        model: Option<&str>,
+    ) -> Result<String, WrapperError> {
+        let mut messages = self.sessions.get(session_id).cloned().unwrap_or_default();
+        while messages.last().is_some_and(|m| m.role != "user") {
+            messages.pop();
+        }
+        if messages.is_empty() {
+            return Err(WrapperError::Config(ConfigError::Validation(
+                format!("Session '{}' has no message to answer again", session_id)
+            )));
+        }
+
+        // Invalidate the key chat_with_history will use, which is built from the resolved model
+        let resolved = model.map(|m| self.resolve_model(m));
+        let options = self.effective_options(resolved.as_deref(), self.generation_options.to_options());
+        let cache_key = cache::CacheKey::new(
+            &serde_json::to_string(&messages)?,
+            resolved.as_deref().unwrap_or("default"),
+            options.as_ref().unwrap_or(&HashMap::new()),
         );
+        self.cache_manager.invalidate(&cache_key).await?;
+
+        let response = self.chat_with_history(messages.clone(), model).await?;
+        messages.push(streaming::Message {
+            role: "assistant".to_string(),
+            content: response.clone(),
+            images: None,
+        });
+        self.sessions.insert(session_id.to_string(), messages);
+
+        Ok(response)
+    }
+
+    /// `options` layered over the configured `model_defaults` for `model`, so
+    /// anything the caller sets wins. Call with the resolved model name.
+    fn effective_options(
+        &self,
+        model: Option<&str>,
+        options: Option<HashMap<String, serde_json::Value>>,
+    ) -> Option<HashMap<String, serde_json::Value>> {
+        let defaults = model
+            .and_then(|m| self.config.model_defaults.get(m))
+            .and_then(|defaults| defaults.to_options());
+        match (defaults, options) {
+            (Some(mut merged), Some(options)) => {
+                merged.extend(options);
+                Some(merged)
+            }
+            (defaults, options) => options.or(defaults),
+        }
+    }
 
+    /// The model whose rate limit applies to a request for `model` on
+    /// `backend`: requests without a model count against its `default_model`
+    fn limited_model(&self, backend: &str, model: &str) -> String {
+        if model != "default" {
+            return model.to_string();
+        }
+        self.config.backends
+            .get(backend)
+            .and_then(|b| b.default_model.clone())
+            .unwrap_or_else(|| model.to_string())
+    }
+
+    /// The model an alias from `model_aliases` stands for, or `model` itself
+    pub fn resolve_model(&self, model: &str) -> String {
+        self.config.model_aliases
+            .get(model)
+            .cloned()
+            .unwrap_or_else(|| model.to_string())
+    }
+
+    pub fn session_history(&self, session_id: &str) -> Option<&[streaming::Message]> {
+        self.sessions.get(session_id).map(|m| m.as_slice())
+    }
+
+    pub fn clear_session(&mut self, session_id: &str) {
+        self.sessions.remove(session_id);
+    }
+
+    /// Open a session's conversation with `prompt` as the system message,
+    /// replacing any system message it already starts with
+    pub fn set_session_system_prompt(&mut self, session_id: &str, prompt: &str) {
+        let messages = self.sessions.entry(session_id.to_string()).or_default();
+        if messages.first().is_some_and(|m| m.role == "system") {
+            messages.remove(0);
+        }
+        messages.insert(0, streaming::Message {
+            role: "system".to_string(),
+            content: prompt.to_string(),
+            images: None,
+        });
+    }
+
+    /// Count a new request and give it a correlation id. Run the request
+    /// inside the returned span so its log events carry the id.
+    fn start_request(&mut self) -> tracing::Span {
+        self.record_request();
+        let request_id = format!("req-{:016x}", rand::random::<u64>());
+        let span = tracing::info_span!("request", request_id = %request_id);
+        self.last_request_id = Some(request_id);
+        span
+    }
+
+    fn record_request(&mut self) {
+        self.apply_config_updates();
+        self.metrics.record_request();
+        self.performance_monitor.increment_counter("total_requests");
+    }
+
+    fn record_error(&mut self) {
+        self.metrics.record_error();
+        self.performance_monitor.increment_counter("request_error");
+    }
+
+    async fn send_chat(
+        &mut self,
+        cache_key: cache::CacheKey,
+        messages: Vec<streaming::Message>,
+        model: Option<&str>,
+        options: Option<HashMap<String, serde_json::Value>>,
+        response_format: Option<ResponseFormat>,
+        start_time: std::time::Instant,
+    ) -> Result<String, WrapperError> {
+        let step = self.begin_chat(cache_key, messages, model, options, response_format, start_time).await?;
+        match step {
+            ChatStep::Answered(response) => Ok(response),
+            ChatStep::Pending(pending) => {
+                let sent = pending.send().await;
+                self.finish_chat(sent).await
+            }
+        }
+    }
+
+    /// The part of a chat before the backend call: cache lookups, then
+    /// building the request and the backends it may go to
+    async fn begin_chat(
+        &mut self,
+        cache_key: cache::CacheKey,
+        messages: Vec<streaming::Message>,
+        model: Option<&str>,
+        options: Option<HashMap<String, serde_json::Value>>,
+        response_format: Option<ResponseFormat>,
+        start_time: std::time::Instant,
+    ) -> Result<ChatStep, WrapperError> {
         // Check cache first
-        if let Some(cached_response) = self.cache_manager.get(&cache_key).await {
+        let cache_start = std::time::Instant::now();
+        let cached = self.cache_manager.get(&cache_key).await;
+        self.performance_monitor.record_cache_operation("lookup", cache_start.elapsed(), cached.is_some());
+
+        if let Some(cached_response) = cached {
             self.metrics.record_cache_hit();
-            return Ok(cached_response);
+            crate::logging::log_cache_event("hit", cache_key.prompt_hash, true);
+            return Ok(ChatStep::Answered(cached_response));
         }
+        crate::logging::log_cache_event("miss", cache_key.prompt_hash, false);
 
-        self.metrics.record_cache_miss();
+        if let Some(e) = self.remembered_failure(&cache_key) {
+            self.record_error();
+            return Err(e);
+        }
 
-        // Get backend
-        let backend = self.backends.get(&self.current_backend)
-            .ok_or_else(|| WrapperError::Config(ConfigError::Validation(
-                format!("Backend '{}' not found", self.current_backend)
-            )))?;
+        // On an exact miss, a paraphrase of an earlier prompt may still be answerable
+        let embedding = self.prompt_embedding(&messages, model).await;
+        if let Some(similar_response) = embedding.as_deref().and_then(|e| self.cache_manager.get_similar(&cache_key, e)) {
+            self.metrics.record_cache_hit();
+            return Ok(ChatStep::Answered(similar_response));
+        }
+
+        self.metrics.record_cache_miss();
 
         // Create chat request
+        let messages = self.fit_to_context(messages, model.unwrap_or("default")).await;
+        let prompt_tokens_estimate = tokenize::count_message_tokens(&messages);
         let request = streaming::ChatRequest {
             model: model.unwrap_or("default").to_string(),
-            messages: vec![streaming::Message {
-                role: "user".to_string(),
-                content: message.to_string(),
-                images: None,
-            }],
+            messages,
             stream: false,
-            options: None,
+            options,
+            response_format,
+            tools: None,
+        };
+
+        let candidates = match self.failover_candidates(&request.model) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                self.record_error();
+                return Err(e);
+            }
+        };
+
+        Ok(ChatStep::Pending(Box::new(PendingChat {
+            cache_key,
+            request,
+            candidates,
+            request_limiter: self.request_limiter.clone(),
+            embedding,
+            prompt_tokens_estimate,
+            start_time,
+            request_id: self.last_request_id.clone(),
+            span: tracing::Span::current(),
+        })))
+    }
+
+    /// Record the outcome of a `PendingChat` and cache its reply
+    pub async fn finish_chat(&mut self, sent: SentChat) -> Result<String, WrapperError> {
+        let span = sent.pending.span.clone();
+        self.store_chat_reply(sent).instrument(span).await
+    }
+
+    async fn store_chat_reply(&mut self, sent: SentChat) -> Result<String, WrapperError> {
+        let SentChat { pending, result, backend_ms } = sent;
+        let (backend_name, completion) = match result {
+            Ok(served) => served,
+            Err(e) => {
+                self.record_error();
+                crate::logging::log_backend_event("chat", &self.current_backend, false, Some(backend_ms));
+                self.remember_failure(pending.cache_key, &e);
+                return Err(e);
+            }
         };
+        crate::logging::log_backend_event("chat", &backend_name, true, Some(backend_ms));
+        let response = completion.content;
+        let usage = completion.usage.unwrap_or_default();
 
-        // Make request
-        let response = backend.chat(request).await?;
+        self.metrics.record_token_usage(&usage);
+        self.last_usage = Some(usage.clone());
 
         // Cache the response
         let metadata = cache::ResponseMetadata {
-            model: model.unwrap_or("default").to_string(),
-            tokens_used: None,
-            response_time: start_time.elapsed(),
-            backend_type: backend.backend_type().to_string(),
+            model: pending.request.model.clone(),
+            // Estimate for backends that don't report usage
+            tokens_used: usage.total_tokens()
+                .or_else(|| Some((pending.prompt_tokens_estimate + tokenize::estimate_tokens(&response)) as u32)),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            response_time: pending.start_time.elapsed(),
+            backend_type: self.backends[&backend_name].backend_type().to_string(),
+            backend: Some(backend_name.clone()),
+            tags: self.cache_tags.clone(),
+            request_id: pending.request_id.clone(),
         };
+        self.last_backend = Some(backend_name);
 
-        self.cache_manager.put(cache_key, response.clone(), metadata).await?;
+        let store_start = std::time::Instant::now();
+        let stored = self.cache_manager
+            .put_with_embedding(pending.cache_key, response.clone(), metadata, pending.embedding)
+            .await;
+        self.performance_monitor.record_cache_operation("store", store_start.elapsed(), stored.is_ok());
+        stored?;
 
         // Record response time
-        let duration = start_time.elapsed();
+        let duration = pending.start_time.elapsed();
         self.metrics.record_response_time(duration.as_millis() as f64);
 
         Ok(response)
     }
 
+    /// A recent unreachable-backend failure for `key` from the negative cache
+    fn remembered_failure(&self, key: &cache::CacheKey) -> Option<WrapperError> {
+        let error = self.cache_manager.get_failure(key)?;
+        crate::logging::log_cache_event("negative_hit", key.prompt_hash, true);
+        Some(BackendError::Connection(error).into())
+    }
+
+    /// Add `error` to the negative cache when it means the backend was unreachable
+    fn remember_failure(&self, key: cache::CacheKey, error: &WrapperError) {
+        if let WrapperError::Backend(e) = error {
+            if e.is_unreachable() {
+                let message = match e {
+                    BackendError::Connection(message) => message.clone(),
+                    other => other.to_string(),
+                };
+                self.cache_manager.put_failure(key, message);
+            }
+        }
+    }
+
+    /// Embed the conversation for the semantic cache, or `None` when it is
+    /// disabled. Embedding failures only cost the semantic lookup.
+    async fn prompt_embedding(&self, messages: &[streaming::Message], model: Option<&str>) -> Option<Vec<f32>> {
+        let semantic = &self.config.cache.semantic;
+        if !semantic.enabled {
+            return None;
+        }
+
+        let text = messages.iter()
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let embedding_model = semantic.embedding_model.as_deref().or(model);
+        match self.embed(&[text], embedding_model).await {
+            Ok(mut embeddings) => embeddings.pop(),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to embed prompt for semantic cache lookup");
+                None
+            }
+        }
+    }
+
+    /// Trim `messages` to fit the model's context window, leaving
+    /// `reserve_tokens` of it free for the reply
+    async fn fit_to_context(&mut self, messages: Vec<streaming::Message>, model: &str) -> Vec<streaming::Message> {
+        if !self.config.context.truncate {
+            return messages;
+        }
+        let Some(context_length) = self.context_length(model).await else {
+            return messages;
+        };
+
+        let budget = context_length.saturating_sub(self.config.context.reserve_tokens) as usize;
+        let original_len = messages.len();
+        let messages = context::truncate_messages(messages, budget, self.config.context.strategy);
+        if messages.len() < original_len {
+            tracing::debug!(
+                model = model,
+                dropped = original_len - messages.len(),
+                "Truncated conversation to fit the context window"
+            );
+        }
+        messages
+    }
+
+    /// Context length for `model`, from config or else the current backend
+    async fn context_length(&mut self, model: &str) -> Option<u32> {
+        if let Some(max_tokens) = self.config.context.max_context_tokens {
+            return Some(max_tokens);
+        }
+        if let Some(length) = self.context_lengths.get(model) {
+            return Some(*length);
+        }
+
+        let backend = self.backends.get(&self.current_backend)?;
+        match backend.get_model_capabilities(model).await {
+            Ok(capabilities) => {
+                let length = capabilities.context_length?;
+                self.context_lengths.insert(model.to_string(), length);
+                Some(length)
+            }
+            Err(e) => {
+                tracing::warn!(model = model, error = %e, "Failed to look up context length");
+                None
+            }
+        }
+    }
+
+    /// Send `request` to the current backend, then to each of `fallback_backends`
+    /// in order while backends are unreachable. Returns the name of the backend
+    /// that answered along with its reply.
+    async fn chat_with_failover(
+        &self,
+        request: streaming::ChatRequest,
+    ) -> Result<(String, ChatCompletion), WrapperError> {
+        let candidates = self.failover_candidates(&request.model)?;
+        send_with_failover(&candidates, &request).await
+    }
+
+    /// The current backend followed by the `fallback_backends` that passed
+    /// their last health check, each with its rate limits for `model`
+    fn failover_candidates(&self, model: &str) -> Result<Vec<FailoverCandidate>, WrapperError> {
+        if !self.backends.contains_key(&self.current_backend) {
+            return Err(WrapperError::Config(ConfigError::Validation(
+                format!("Backend '{}' not found", self.current_backend)
+            )));
+        }
+
+        let mut names = vec![self.current_backend.clone()];
+        for name in &self.config.fallback_backends {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        // A primary that failed its health check is still tried, but after the fallbacks
+        names.sort_by_key(|name| !self.is_backend_healthy(name));
+
+        Ok(names
+            .into_iter()
+            .filter_map(|name| {
+                let backend = self.backends.get(&name).cloned()?;
+                let is_primary = name == self.current_backend;
+                if !is_primary && !self.is_backend_healthy(&name) {
+                    return None;
+                }
+                let limited_model = self.limited_model(&name, model);
+                Some(FailoverCandidate {
+                    rate_limits: self.streaming_manager.rate_limits_for(&name, &limited_model),
+                    name,
+                    backend,
+                    is_primary,
+                })
+            })
+            .collect())
+    }
+
     pub async fn interactive_mode(&mut self) -> Result<(), WrapperError> {
-        let mut ui = TerminalUI::new()?;
+        let session = Session::new(&self.current_backend);
+        self.interactive_session(session).await?;
+        Ok(())
+    }
+
+    /// Run the TUI within `session`, picking up its earlier messages. The
+    /// session is saved when the UI exits and returned as it ended.
+    pub async fn interactive_session(&mut self, session: Session) -> Result<Session, WrapperError> {
+        let mut ui = TerminalUI::new(&self.config.ui)?;
         
-        // Create a channel for streaming tokens
-        let (_stream_sender, stream_receiver) = tokio::sync::mpsc::unbounded_channel();
+        // Conversations go from the UI to the backend, reply tokens come back
+        let (prompt_sender, prompt_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let (stream_sender, stream_receiver) = tokio::sync::mpsc::unbounded_channel();
         
         // Update UI with current app state
         let app_state = ui::AppState {
             current_model: self.current_backend.clone(),
+            selected_model: None,
             is_streaming: false,
-            cache_stats: self.cache_manager.get_stats().clone(),
+            cache_stats: self.cache_manager.get_stats(),
             active_template: None,
         };
         ui.update_app_state(app_state);
+        ui.resume(session);
 
-        // Run the UI
-        ui.run(stream_receiver).await?;
-        
-        Ok(())
+        // The UI owns the prompt sender, so answering only stops once it has
+        // exited; a reply still streaming at that point is dropped with it
+        tokio::select! {
+            result = ui.run(prompt_sender, stream_receiver) => result?,
+            _ = self.answer_prompts(prompt_receiver, stream_sender) => {}
+        }
+
+        if let Some(path) = ui.save_session()? {
+            tracing::info!(path = %path.display(), "Saved chat session");
+        }
+        Ok(ui.session())
+    }
+
+    /// Saved chat sessions, newest first
+    pub fn list_sessions(&self) -> Result<Vec<Session>, WrapperError> {
+        Ok(Session::list(&self.config.ui.sessions_dir)?)
+    }
+
+    pub fn load_session(&self, id: &str) -> Result<Session, WrapperError> {
+        Ok(Session::load(&self.config.ui.sessions_dir, id)?)
+    }
+
+    /// Stream a reply to each conversation received on `prompts`, forwarding
+    /// the tokens to `tokens`. Every reply ends with a complete token, even
+    /// when the backend fails. Returns once the prompt sender is dropped.
+    pub async fn answer_prompts(
+        &mut self,
+        mut prompts: tokio::sync::mpsc::UnboundedReceiver<ui::Prompt>,
+        tokens: tokio::sync::mpsc::UnboundedSender<StreamToken>,
+    ) {
+        while let Some(prompt) = prompts.recv().await {
+            let messages = match &prompt.template {
+                Some(name) => self.render_prompt_template(name, prompt.messages),
+                None => Ok(prompt.messages),
+            };
+            let opened = match messages {
+                Ok(messages) => self.chat_stream(messages, prompt.model.as_deref()).await,
+                Err(e) => Err(e),
+            };
+            let mut stream = match opened {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let _ = tokens.send(StreamToken {
+                        content: format!("Error: {}", e),
+                        is_complete: true,
+                        kind: streaming::TokenKind::Answer,
+                        metadata: None,
+                    });
+                    continue;
+                }
+            };
+
+            let mut completed = false;
+            while let Some(token) = stream.receiver.recv().await {
+                completed = token.is_complete;
+                if tokens.send(token).is_err() {
+                    // Nobody is reading the reply any more
+                    stream.cancellation_token.cancel();
+                    break;
+                }
+                if completed {
+                    break;
+                }
+            }
+            self.metrics.record_stream_end();
+
+            if !completed {
+                let _ = tokens.send(StreamToken {
+                    content: String::new(),
+                    is_complete: true,
+                    kind: streaming::TokenKind::Answer,
+                    metadata: None,
+                });
+            }
+        }
+    }
+
+    /// Replace the last user message with `template_name` rendered around it,
+    /// the message being available to the template as `{{message}}`
+    fn render_prompt_template(
+        &mut self,
+        template_name: &str,
+        mut messages: Vec<streaming::Message>,
+    ) -> Result<Vec<streaming::Message>, WrapperError> {
+        let Some(last_user) = messages.iter_mut().rev().find(|m| m.role == "user") else {
+            return Ok(messages);
+        };
+
+        let variables = serde_json::json!({ "message": last_user.content });
+        match self.template_engine.render_with_defaults(template_name, variables) {
+            Ok(rendered) => {
+                self.metrics.record_template_render();
+                crate::logging::log_template_event("render", template_name, true);
+                last_user.content = rendered;
+                Ok(messages)
+            }
+            Err(e) => {
+                self.record_error();
+                crate::logging::log_template_event("render", template_name, false);
+                Err(WrapperError::Template(e))
+            }
+        }
     }
 
     pub fn switch_backend(&mut self, backend_name: &str) -> Result<(), WrapperError> {
@@ -711,9 +2366,62 @@ impl EnhancedLLMWrapper {
         }
         
         self.current_backend = backend_name.to_string();
+        self.context_lengths.clear();
         Ok(())
     }
 
+    /// Health-check every backend each `interval` in the background, replacing
+    /// any checks already running. Backends found down are tried last, and
+    /// skipped as fallbacks, until a later check sees them recover.
+    pub fn start_health_checks(&mut self, interval: std::time::Duration) {
+        if let Some(task) = self.health_task.take() {
+            task.abort();
+        }
+
+        let backends: Vec<(String, Arc<dyn Backend>)> = self.backends
+            .iter()
+            .map(|(name, backend)| (name.clone(), backend.clone()))
+            .collect();
+        let health = self.backend_health.clone();
+        self.health_task = Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                for (name, backend) in &backends {
+                    let healthy = backend.health_check().await.is_ok();
+                    let previous = health.write().unwrap().insert(name.clone(), healthy);
+                    if previous != Some(healthy) {
+                        let event = if healthy { "backend_up" } else { "backend_down" };
+                        crate::logging::log_backend_event(event, name, healthy, None);
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Whether each backend passed its latest health check. Backends are
+    /// reported up until a check has run.
+    pub fn backend_health(&self) -> HashMap<String, bool> {
+        self.backend_health.read().unwrap().clone()
+    }
+
+    fn is_backend_healthy(&self, name: &str) -> bool {
+        self.backend_health.read().unwrap().get(name).copied().unwrap_or(true)
+    }
+
+    /// Circuit breaker state of every configured backend
+    pub fn circuit_states(&self) -> HashMap<String, CircuitState> {
+        self.backends
+            .iter()
+            .map(|(name, backend)| (name.clone(), backend.circuit_state()))
+            .collect()
+    }
+
+    pub fn current_backend(&self) -> &str {
+        &self.current_backend
+    }
+
     pub fn list_backends(&self) -> Vec<&str> {
         self.backends.keys().map(|s| s.as_str()).collect()
     }
@@ -727,14 +2435,233 @@ impl EnhancedLLMWrapper {
         Ok(backend.list_models().await?)
     }
 
-    pub fn get_cache_stats(&self) -> &CacheStats {
+    /// Models on the current backend matching `filter`, in its sort order
+    pub async fn list_models_filtered(&self, filter: ModelFilter) -> Result<Vec<ModelInfo>, WrapperError> {
+        Ok(filter.apply(self.list_models().await?))
+    }
+
+    /// Download a model on the current backend
+    pub async fn pull_model(&self, name: &str) -> Result<(), WrapperError> {
+        let backend = self.backends.get(&self.current_backend)
+            .ok_or_else(|| WrapperError::Config(ConfigError::Validation(
+                format!("Backend '{}' not found", self.current_backend)
+            )))?;
+
+        backend.pull_model(name).await?;
+        crate::logging::log_backend_event("pull_model", &self.current_backend, true, None);
+        Ok(())
+    }
+
+    /// Delete a model from the current backend and drop its cached responses
+    pub async fn delete_model(&mut self, name: &str) -> Result<(), WrapperError> {
+        let backend = self.backends.get(&self.current_backend)
+            .ok_or_else(|| WrapperError::Config(ConfigError::Validation(
+                format!("Backend '{}' not found", self.current_backend)
+            )))?;
+
+        backend.delete_model(name).await?;
+        crate::logging::log_backend_event("delete_model", &self.current_backend, true, None);
+        self.cache_manager.invalidate_model(name);
+        Ok(())
+    }
+
+    /// Compute embeddings for `input` using the current backend
+    pub async fn embed(&self, input: &[String], model: Option<&str>) -> Result<Vec<Vec<f32>>, WrapperError> {
+        let backend = self.backends.get(&self.current_backend)
+            .ok_or_else(|| WrapperError::Config(ConfigError::Validation(
+                format!("Backend '{}' not found", self.current_backend)
+            )))?;
+
+        let _permit = self.request_limiter.acquire().await?;
+        Ok(backend.embed(model.unwrap_or("default"), input).await?)
+    }
+
+    /// Query model capabilities across every configured backend. When `model` is
+    /// `None`, every model each backend reports is included.
+    pub async fn get_capabilities(&self, model: Option<&str>) -> Result<Vec<CapabilityReport>, WrapperError> {
+        let mut backend_names: Vec<&String> = self.backends.keys().collect();
+        backend_names.sort();
+
+        let mut reports = Vec::new();
+        for name in backend_names {
+            let backend = &self.backends[name];
+
+            let models = match model {
+                Some(m) => vec![m.to_string()],
+                None => match backend.list_models().await {
+                    Ok(models) => models.into_iter().map(|m| m.name).collect(),
+                    Err(e) => {
+                        tracing::warn!(backend = %name, error = %e, "Failed to list models for capability query");
+                        continue;
+                    }
+                },
+            };
+
+            for model_name in models {
+                // One model that can't be queried shouldn't hide the rest
+                let capabilities = match backend.get_model_capabilities(&model_name).await {
+                    Ok(capabilities) => capabilities,
+                    Err(e) => {
+                        tracing::warn!(backend = %name, model = %model_name, error = %e, "Failed to get model capabilities");
+                        continue;
+                    }
+                };
+                reports.push(CapabilityReport {
+                    backend: name.clone(),
+                    model: model_name,
+                    capabilities,
+                });
+            }
+        }
+
+        Ok(reports)
+    }
+
+    pub fn get_cache_stats(&self) -> CacheStats {
         self.cache_manager.get_stats()
     }
 
+    /// Cache hits, misses and entries for each model
+    pub fn cache_stats_by_model(&self) -> HashMap<String, CacheStats> {
+        self.cache_manager.get_stats_by_model()
+    }
+
     pub fn get_metrics(&self) -> &MetricsCollector {
         &self.metrics
     }
 
+    pub fn reset_metrics(&mut self) {
+        self.metrics.reset();
+    }
+
+    /// How long cached responses currently live
+    pub fn cache_ttl(&self) -> std::time::Duration {
+        self.cache_manager.ttl()
+    }
+
+    pub fn get_detailed_cache_stats(&self) -> cache::DetailedCacheStats {
+        self.cache_manager.get_detailed_stats()
+    }
+
+    /// Current metrics and cache statistics in one serializable report
+    pub fn stats_report(&self) -> StatsReport {
+        StatsReport {
+            metrics: self.metrics.clone(),
+            cache: self.get_detailed_cache_stats(),
+        }
+    }
+
+    /// Render wrapper, cache and performance metrics in the Prometheus text format
+    pub fn prometheus_metrics(&self) -> String {
+        let metrics = &self.metrics;
+        let cache_stats = self.cache_manager.get_stats();
+        let performance = self.performance_monitor.get_metrics();
+
+        let mut out = String::new();
+        write_prometheus_metric(&mut out, "requests_total", "counter", "Chat requests received", metrics.requests_total as f64);
+        write_prometheus_metric(&mut out, "cache_hits_total", "counter", "Requests answered from the cache", metrics.cache_hits as f64);
+        write_prometheus_metric(&mut out, "cache_misses_total", "counter", "Requests that missed the cache", metrics.cache_misses as f64);
+        write_prometheus_metric(&mut out, "errors_total", "counter", "Failed requests", metrics.errors_total as f64);
+        write_prometheus_metric(&mut out, "template_renders_total", "counter", "Templates rendered", metrics.template_renders as f64);
+        write_prometheus_metric(&mut out, "prompt_tokens_total", "counter", "Prompt tokens reported by backends", metrics.prompt_tokens_total as f64);
+        write_prometheus_metric(&mut out, "completion_tokens_total", "counter", "Completion tokens reported by backends", metrics.completion_tokens_total as f64);
+        write_prometheus_metric(&mut out, "active_streams", "gauge", "Streams currently open", metrics.active_streams as f64);
+        write_prometheus_metric(&mut out, "cache_hit_ratio", "gauge", "Fraction of lookups served from the cache", metrics.cache_hit_ratio());
+        write_prometheus_metric(&mut out, "average_response_time_ms", "gauge", "Mean request latency in milliseconds", metrics.average_response_time_ms);
+        write_prometheus_metric(&mut out, "cache_entries", "gauge", "Entries held in the memory cache", cache_stats.total_entries as f64);
+        write_prometheus_metric(&mut out, "cache_memory_bytes", "gauge", "Estimated memory used by the cache", cache_stats.memory_usage_bytes as f64);
+        write_prometheus_metric(&mut out, "cache_evictions_total", "counter", "Entries evicted from the memory cache", cache_stats.evictions as f64);
+        write_prometheus_metric(&mut out, "process_memory_mb", "gauge", "Resident memory of the process", performance.system_metrics.memory_usage_mb);
+        write_prometheus_metric(&mut out, "process_cpu_percent", "gauge", "CPU usage of the process", performance.system_metrics.cpu_usage_percent);
+        write_prometheus_metric(&mut out, "uptime_seconds", "gauge", "Seconds since the wrapper started", performance.system_metrics.uptime_seconds as f64);
+        out
+    }
+
+    /// Token usage of the most recent backend call (cache hits leave it unchanged)
+    pub fn last_usage(&self) -> Option<&TokenUsage> {
+        self.last_usage.as_ref()
+    }
+
+    /// Name of the backend that served the most recent uncached reply, which
+    /// differs from the current backend after a failover
+    pub fn last_backend(&self) -> Option<&str> {
+        self.last_backend.as_deref()
+    }
+
+    /// Correlation id of the most recent chat request. Every log event of
+    /// that request carries it as the `request_id` span field.
+    pub fn last_request_id(&self) -> Option<&str> {
+        self.last_request_id.as_deref()
+    }
+
+    /// Tag every response cached from now on, e.g. with a tenant or feature
+    /// name, so the group can later be dropped with `invalidate_cache_tag`
+    pub fn set_cache_tags(&mut self, tags: Vec<String>) {
+        self.cache_tags = tags;
+    }
+
+    /// Let `chat_typed` re-prompt the model once when its output doesn't parse
+    pub fn set_repair_typed_output(&mut self, repair: bool) {
+        self.repair_typed_output = repair;
+    }
+
+    pub fn get_config(&self) -> &EnhancedConfig {
+        &self.config
+    }
+
+    /// Reload `path` whenever it changes. Updates are picked up at the start of
+    /// the next request, or immediately with `apply_config_updates`.
+    pub fn watch_config<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), WrapperError> {
+        self.config_watcher = Some(config::ConfigWatcher::new(path)?);
+        Ok(())
+    }
+
+    /// Apply the settings that are safe to change at runtime (cache TTL, rate
+    /// limits and the log level) from the latest reloaded
+    /// config. Streams already running are unaffected; other settings need a
+    /// restart. Returns whether an update was applied.
+    pub fn apply_config_updates(&mut self) -> bool {
+        let Some(new_config) = self.config_watcher.as_ref().and_then(|w| w.take_update()) else {
+            return false;
+        };
+
+        self.cache_manager.set_ttl(new_config.cache.ttl, new_config.cache.sliding_ttl);
+        self.config.cache.ttl = new_config.cache.ttl;
+        self.config.cache.sliding_ttl = new_config.cache.sliding_ttl;
+
+        for (name, backend) in self.config.backends.iter_mut() {
+            if let Some(updated) = new_config.backends.get(name) {
+                backend.rate_limit = updated.rate_limit.clone();
+                backend.model_rate_limits = updated.model_rate_limits.clone();
+                self.streaming_manager.set_backend_rate_limit(name, backend.rate_limit.as_ref().map(Into::into));
+            }
+        }
+        let model_limits: HashMap<(String, String), streaming::ModelRateLimit> = self.config.backends
+            .iter()
+            .flat_map(|(name, backend)| {
+                backend.model_rate_limits
+                    .iter()
+                    .map(move |(model, limit)| ((name.clone(), model.clone()), limit.into()))
+            })
+            .collect();
+        self.streaming_manager.update_model_rate_limits(&model_limits);
+
+        if new_config.logging.level != self.config.logging.level {
+            match crate::logging::set_log_level(&new_config.logging.level) {
+                Ok(()) => self.config.logging.level = new_config.logging.level,
+                Err(e) => tracing::warn!(level = %new_config.logging.level, error = %e, "Keeping previous log level"),
+            }
+        }
+
+        tracing::info!(ttl = ?self.config.cache.ttl, "Applied reloaded configuration");
+        true
+    }
+
+    /// Per-model limiter stats, keyed by backend name and model
+    pub fn get_model_rate_limiter_stats(&mut self) -> HashMap<(String, String), streaming::RateLimiterStats> {
+        self.streaming_manager.get_model_rate_limiter_stats()
+    }
+
     pub fn get_performance_metrics(&self) -> performance::PerformanceMetrics {
         self.performance_monitor.get_metrics()
     }
@@ -745,18 +2672,57 @@ impl EnhancedLLMWrapper {
 
     pub async fn export_performance_metrics(&self, path: &str) -> Result<(), WrapperError> {
         self.performance_monitor.export_metrics_to_file(path).await
-            .map_err(|e| WrapperError::Io(e))
+            .map_err(WrapperError::Io)
     }
 
-    pub fn list_templates(&self) -> Vec<&Template> {
+    pub fn list_templates(&self) -> Vec<Template> {
         self.template_engine.list_templates()
     }
 
+    /// Check a template renders with `example_vars` without sending anything
+    /// to a model
+    pub fn validate_template_render(
+        &self,
+        name: &str,
+        example_vars: &serde_json::Value,
+    ) -> Result<template::RenderValidation, WrapperError> {
+        Ok(self.template_engine.validate_render(name, example_vars)?)
+    }
+
+    /// Render a template with its variable defaults filled in, without
+    /// sending it to a model
+    pub fn render_template(&self, name: &str, vars: serde_json::Value) -> Result<String, WrapperError> {
+        Ok(self.template_engine.render_with_defaults(name, vars)?)
+    }
+
+    /// Render a template as `render_template` does and write it to `path`
+    pub async fn render_template_to_file<P: AsRef<std::path::Path>>(
+        &self,
+        name: &str,
+        vars: serde_json::Value,
+        path: P,
+    ) -> Result<(), WrapperError> {
+        let rendered = self.render_template(name, vars)?;
+        tokio::fs::write(path, rendered).await?;
+        Ok(())
+    }
+
     pub async fn save_template(&mut self, template: Template) -> Result<(), WrapperError> {
-        self.template_engine.register_template(template)?;
+        self.template_engine.save_template(template).await?;
         Ok(())
     }
 
+    pub async fn delete_template(&mut self, name: &str) -> Result<(), WrapperError> {
+        self.template_engine.delete_template(name).await?;
+        crate::logging::log_template_event("delete", name, true);
+        Ok(())
+    }
+
+    /// Wait for queued cache writes to reach disk; call before shutting down
+    pub async fn flush_cache(&self) {
+        self.cache_manager.flush().await;
+    }
+
     pub async fn clear_cache(&mut self) -> Result<(), WrapperError> {
         self.cache_manager.clear();
         Ok(())
@@ -766,4 +2732,190 @@ impl EnhancedLLMWrapper {
         self.cache_manager.invalidate_model(model);
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Drop cached responses tagged `tag` from memory and disk, returning how many were removed
+    pub async fn invalidate_cache_tag(&mut self, tag: &str) -> Result<usize, WrapperError> {
+        Ok(self.cache_manager.invalidate_by_tag(tag).await?)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve the same canned HTTP response to every request on a local port,
+    /// returning the server's base URL
+    async fn serve_fixed(content_type: &'static str, body: Vec<u8>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    content_type,
+                    body.len()
+                );
+                let _ = socket.write_all(header.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_switch_model_requires_installed_model() {
+        let tags = br#"{"models":[{"name":"llama3.2:latest"},{"name":"llava:latest"}]}"#.to_vec();
+        let base_url = serve_fixed("application/json", tags).await;
+
+        let mut config = Config::default();
+        config.model_aliases.insert("vision".to_string(), "llava".to_string());
+        let mut wrapper = LLMWrapper::new(&base_url, "llama3.2", config).await.unwrap();
+
+        wrapper.switch_model("vision").await.unwrap();
+        assert_eq!(wrapper.model, "llava");
+        assert!(wrapper.capabilities().supports_vision);
+
+        let err = wrapper.switch_model("llama3.3").await.unwrap_err();
+        assert!(err.to_string().contains("'llama3.3' is not installed"));
+        assert!(err.to_string().contains("llama3.2:latest"));
+        assert_eq!(wrapper.model, "llava");
+
+        let err = wrapper.switch_model("mistral").await.unwrap_err();
+        assert_eq!(err.to_string(), "Model 'mistral' is not installed");
+    }
+
+    fn model_entries(names: &[&str]) -> Vec<ModelEntry> {
+        names.iter().map(|name| ModelEntry { name: name.to_string(), ..Default::default() }).collect()
+    }
+
+    #[test]
+    fn test_find_model_prefers_exact_match() {
+        let models = model_entries(&["llama3-vision:latest", "llama3:latest", "llama3:70b"]);
+
+        assert_eq!(find_model(&models, "llama3").unwrap().name, "llama3:latest");
+        assert_eq!(find_model(&models, "llama3:70b").unwrap().name, "llama3:70b");
+        assert_eq!(find_model(&models, "llama3-vision").unwrap().name, "llama3-vision:latest");
+        // Substring matching remains as a last resort
+        assert_eq!(find_model(&models, "vision").unwrap().name, "llama3-vision:latest");
+        assert!(find_model(&models, "mistral").is_none());
+    }
+
+    fn offline_wrapper(model: &str) -> LLMWrapper {
+        LLMWrapper {
+            client: Client::new(),
+            base_url: "http://localhost:11434".to_string(),
+            model: model.to_string(),
+            capabilities: crate::backends::ModelCapabilities::default(),
+            config: Config::default(),
+            generation_options: GenerationOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_generate_request_shape() {
+        let mut wrapper = offline_wrapper("codellama");
+        let minimal = serde_json::to_value(
+            wrapper.build_generate_request("def fib(", &GenerateOptions::default(), false),
+        ).unwrap();
+        assert_eq!(minimal, serde_json::json!({
+            "model": "codellama",
+            "prompt": "def fib(",
+            "stream": false
+        }));
+
+        wrapper.set_generation_options(GenerationOptions {
+            temperature: Some(0.2),
+            ..Default::default()
+        });
+        let options = GenerateOptions {
+            system: Some("Complete the code".to_string()),
+            template: Some("{{ .System }} {{ .Prompt }}".to_string()),
+            raw: true,
+        };
+        let full = serde_json::to_value(wrapper.build_generate_request("def fib(", &options, true)).unwrap();
+        assert_eq!(full, serde_json::json!({
+            "model": "codellama",
+            "prompt": "def fib(",
+            "stream": true,
+            "system": "Complete the code",
+            "template": "{{ .System }} {{ .Prompt }}",
+            "raw": true,
+            "options": {"temperature": 0.2}
+        }));
+
+        let mut result = String::new();
+        let mut pieces = Vec::new();
+        assert!(!push_generate_line(r#"{"response":"n):","done":false}"#, &mut result, &mut |p: &str| pieces.push(p.to_string())).unwrap());
+        assert!(push_generate_line(r#"{"response":"","done":true}"#, &mut result, &mut |p: &str| pieces.push(p.to_string())).unwrap());
+        assert_eq!(result, "n):");
+        assert_eq!(pieces, vec!["n):"]);
+    }
+
+    #[test]
+    fn test_capabilities_attributed_to_requested_model() {
+        let mut wrapper = offline_wrapper("llama3");
+        let models = model_entries(&["llama3-vision", "llama3"]);
+
+        wrapper.apply_capabilities(&models);
+        assert!(!wrapper.capabilities().supports_vision);
+
+        wrapper.model = "llama3-vision".to_string();
+        wrapper.apply_capabilities(&models);
+        assert!(wrapper.capabilities().supports_vision);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_remote_image() {
+        let client = Client::new();
+        let png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 1, 2, 3];
+
+        let url = format!("{}/image.png", serve_fixed("image/png", png.clone()).await);
+        assert_eq!(remote_image_url(Path::new(&url)), Some(url.as_str()));
+        let encoded = fetch_remote_image(&client, &url, 1024).await.unwrap();
+        assert_eq!(general_purpose::STANDARD.decode(encoded).unwrap(), png);
+
+        let url = format!("{}/image.png", serve_fixed("image/png", vec![0; 64]).await);
+        let err = fetch_remote_image(&client, &url, 32).await.unwrap_err();
+        assert!(err.to_string().contains("exceeds the 32 byte limit"));
+
+        let url = format!("{}/page.html", serve_fixed("text/html", b"<html></html>".to_vec()).await);
+        let err = fetch_remote_image(&client, &url, 1024).await.unwrap_err();
+        assert!(err.to_string().contains("is not an image"));
+
+        assert!(remote_image_url(Path::new("photos/cat.png")).is_none());
+    }
+
+    #[test]
+    fn test_stream_output_matches_chat_format() {
+        let mut output = StreamOutput::default();
+        let mut pieces = Vec::new();
+        let lines = [
+            r#"{"message":{"content":"","thinking":"Let me "},"done":false}"#,
+            r#"{"message":{"content":"","thinking":"think."},"done":false}"#,
+            r#"{"message":{"content":"Hello"},"done":false}"#,
+            r#"{"message":{"content":" there"},"done":false}"#,
+            "",
+            r#"{"message":{"content":""},"done":true}"#,
+        ];
+
+        let mut done = false;
+        for line in lines {
+            done = output.push_line(line, &mut |piece: &str| pieces.push(piece.to_string())).unwrap();
+        }
+
+        assert!(done);
+        assert_eq!(output.result, "🤔 Thinking: Let me think.\n\nHello there");
+        assert_eq!(pieces.concat(), output.result);
+
+        // Plain models stream content only
+        let mut output = StreamOutput::default();
+        output.push_line(r#"{"message":{"content":"Hi"},"done":true}"#, &mut |_: &str| {}).unwrap();
+        assert_eq!(output.result, "Hi");
+        assert!(output.push_line("not json", &mut |_: &str| {}).is_err());
+    }
+}
@@ -1,6 +1,9 @@
 use clap::{Parser, Subcommand};
-use llm_wrapper::{LLMWrapper, Config, EnhancedLLMWrapper, EnhancedConfig, Template};
-use std::path::PathBuf;
+use llm_wrapper::{LLMWrapper, Config, EnhancedLLMWrapper, EnhancedConfig, GenerateOptions, GenerationOptions, Template};
+use llm_wrapper::{ChatCompletion, ChatMessage, MessageRole, ModelFilter, ModelSort, PresetStore, Session, export_markdown};
+use std::io::IsTerminal;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
 use serde_json::json;
 
 #[derive(Parser)]
@@ -21,6 +24,10 @@ struct Cli {
     /// System prompt
     #[arg(short, long)]
     system: Option<String>,
+
+    /// Saved preset to use as the system prompt (see `preset list`)
+    #[arg(long, conflicts_with = "system")]
+    preset: Option<String>,
     
     /// Image files to include
     #[arg(short, long)]
@@ -28,31 +35,142 @@ struct Cli {
     
     /// Single message mode
     message: Option<String>,
+    
+    /// Sampling temperature
+    #[arg(long, global = true)]
+    temperature: Option<f64>,
+    
+    /// Nucleus sampling probability
+    #[arg(long, global = true)]
+    top_p: Option<f64>,
+    
+    /// Maximum number of tokens to generate
+    #[arg(long, global = true)]
+    num_predict: Option<i32>,
+    
+    /// Context window size
+    #[arg(long, global = true)]
+    num_ctx: Option<u32>,
+    
+    /// Random seed for reproducible output
+    #[arg(long, global = true)]
+    seed: Option<i64>,
+    
+    /// Stop sequence (repeatable)
+    #[arg(long, global = true)]
+    stop: Vec<String>,
+
+    /// How single-message responses are printed
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Config file to use instead of config.toml (or enhanced-config.toml in enhanced mode)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// The response text alone
+    Text,
+    /// A JSON object with the response, model, token counts and latency
+    Json,
+}
+
+impl Cli {
+    fn generation_options(&self) -> GenerationOptions {
+        GenerationOptions {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            num_predict: self.num_predict,
+            num_ctx: self.num_ctx,
+            seed: self.seed,
+            stop: self.stop.clone(),
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// List available models
-    List,
+    List {
+        /// Only models whose name contains this text
+        #[arg(long)]
+        filter: Option<String>,
+        /// Only models that accept images
+        #[arg(long)]
+        vision: bool,
+        /// Only reasoning models
+        #[arg(long)]
+        thinking: bool,
+        /// Sort by name, size or modified
+        #[arg(long, default_value = "name")]
+        sort: ModelSort,
+    },
     /// Pull a model
     Pull { model: String },
     /// Delete a model
     Delete { model: String },
     /// Interactive chat mode with enhanced TUI
     Chat,
-    /// Show model capabilities
+    /// Show model capabilities and details
+    #[command(visible_alias = "show")]
     Info { model: Option<String> },
+    /// Complete a raw prompt with the generate endpoint
+    Generate {
+        /// Prompt to complete
+        prompt: String,
+        /// Prompt template overriding the model's own
+        #[arg(long)]
+        template: Option<String>,
+        /// Send the prompt without applying any template
+        #[arg(long)]
+        raw: bool,
+        /// Wait for the full response instead of streaming it
+        #[arg(long)]
+        no_stream: bool,
+    },
     /// Enhanced mode with all features
     Enhanced {
         #[command(subcommand)]
         command: Option<EnhancedCommands>,
     },
+    /// Saved system prompts, applied with --preset
+    Preset {
+        #[command(subcommand)]
+        action: PresetAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum PresetAction {
+    /// List saved presets
+    List,
+    /// Save a preset, replacing any with the same name
+    Add {
+        name: String,
+        /// System prompt text
+        prompt: String,
+    },
+    /// Delete a preset
+    Remove { name: String },
 }
 
 #[derive(Subcommand)]
 enum EnhancedCommands {
     /// Interactive mode with TUI
     Interactive,
+    /// Saved interactive sessions
+    Session {
+        #[command(subcommand)]
+        action: SessionAction,
+    },
+    /// Line-based chat that keeps the conversation history
+    Chat {
+        /// Model to use
+        #[arg(short, long)]
+        model: Option<String>,
+    },
     /// Template management
     Template {
         #[command(subcommand)]
@@ -63,6 +181,11 @@ enum EnhancedCommands {
         #[command(subcommand)]
         action: CacheAction,
     },
+    /// Model management
+    Models {
+        #[command(subcommand)]
+        action: ModelAction,
+    },
     /// Chat with template
     ChatTemplate {
         /// Template name
@@ -74,8 +197,39 @@ enum EnhancedCommands {
         #[arg(short, long)]
         model: Option<String>,
     },
+    /// Send one prompt to several models and show the replies side by side
+    Compare {
+        /// Comma-separated models to compare
+        #[arg(long, value_delimiter = ',', required = true)]
+        models: Vec<String>,
+        /// Prompt sent to every model
+        prompt: String,
+    },
     /// Show metrics and statistics
-    Stats,
+    Stats {
+        /// Clear the counters after printing them
+        #[arg(long)]
+        reset: bool,
+        /// Print metrics and cache statistics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show a capability matrix for every configured backend
+    Capabilities {
+        /// Only query this model
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Serve an OpenAI-compatible /v1/chat/completions endpoint
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -96,6 +250,41 @@ enum TemplateAction {
     Show { name: String },
     /// Delete a template
     Delete { name: String },
+    /// Render a template to text, with variable defaults applied
+    Render {
+        name: String,
+        /// Variables as JSON
+        #[arg(short, long)]
+        vars: Option<String>,
+        /// Write the result here instead of stdout
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Render a template with sample variables without calling a model
+    Validate {
+        name: String,
+        /// Variables as JSON
+        #[arg(short, long)]
+        vars: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionAction {
+    /// List saved sessions
+    List,
+    /// Continue a saved session in the TUI
+    Load { id: String },
+    /// Start a new session in the TUI
+    New,
+}
+
+#[derive(Subcommand)]
+enum ModelAction {
+    /// Download a model
+    Pull { name: String },
+    /// Delete a model
+    Delete { name: String },
 }
 
 #[derive(Subcommand)]
@@ -106,28 +295,55 @@ enum CacheAction {
     Clear,
     /// Clear cache for specific model
     ClearModel { model: String },
+    /// Clear cached entries carrying a tag
+    ClearTag { tag: String },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let generation_options = cli.generation_options();
+    let system_prompt = match &cli.preset {
+        Some(name) => Some(PresetStore::load(presets_path(cli.config.as_deref()))?.get(name)?.to_string()),
+        None => cli.system.clone(),
+    };
     
     match cli.command {
         Some(Commands::Enhanced { command }) => {
             // Use enhanced wrapper with all features
-            let enhanced_config = load_enhanced_config().await?;
+            let enhanced_config = load_enhanced_config(cli.config.as_deref()).await?;
             let mut enhanced_wrapper = EnhancedLLMWrapper::new(enhanced_config).await?;
+            enhanced_wrapper.set_generation_options(generation_options.clone());
             
             match command {
                 Some(EnhancedCommands::Interactive) => {
                     enhanced_wrapper.interactive_mode().await?;
                 }
+                Some(EnhancedCommands::Session { action }) => {
+                    handle_session_command(&mut enhanced_wrapper, action).await?;
+                }
+                Some(EnhancedCommands::Chat { model }) => {
+                    session_chat(&mut enhanced_wrapper, model.as_deref(), system_prompt.as_deref()).await?;
+                }
                 Some(EnhancedCommands::Template { action }) => {
                     handle_template_command(&mut enhanced_wrapper, action).await?;
                 }
                 Some(EnhancedCommands::Cache { action }) => {
                     handle_cache_command(&mut enhanced_wrapper, action).await?;
                 }
+                Some(EnhancedCommands::Models { action }) => {
+                    match action {
+                        ModelAction::Pull { name } => {
+                            println!("⬇️  Pulling {}...", name);
+                            enhanced_wrapper.pull_model(&name).await?;
+                            println!("✅ Model {} pulled successfully", name);
+                        }
+                        ModelAction::Delete { name } => {
+                            enhanced_wrapper.delete_model(&name).await?;
+                            println!("🗑️ Model {} deleted", name);
+                        }
+                    }
+                }
                 Some(EnhancedCommands::ChatTemplate { template, vars, model }) => {
                     let variables = if let Some(vars_str) = vars {
                         serde_json::from_str(&vars_str)?
@@ -136,12 +352,33 @@ async fn main() -> anyhow::Result<()> {
                     };
                     
                     let stream_response = enhanced_wrapper.chat_with_template(&template, variables, model.as_deref()).await?;
-                    println!("🤖 Response (streaming):");
-                    // For CLI, we'll just collect the stream and print it
-                    // In a real implementation, you'd want to handle the stream properly
-                    println!("Stream created with ID: {}", stream_response.id);
+                    println!("🤖 Response:");
+                    print_stream(stream_response).await?;
+                }
+                Some(EnhancedCommands::Compare { models, prompt }) => {
+                    let names: Vec<&str> = models.iter().map(String::as_str).collect();
+                    let mut results = enhanced_wrapper.chat_compare(&prompt, &names).await;
+                    let columns: Vec<(String, String)> = models
+                        .iter()
+                        .map(|model| {
+                            let reply = match results.remove(model) {
+                                Some(Ok(reply)) => reply,
+                                Some(Err(e)) => format!("❌ {}", e),
+                                None => String::new(),
+                            };
+                            (model.clone(), reply)
+                        })
+                        .collect();
+                    print_side_by_side(&columns);
+                }
+                Some(EnhancedCommands::Stats { reset, json: true }) => {
+                    println!("{}", serde_json::to_string_pretty(&enhanced_wrapper.stats_report())?);
+                    if reset {
+                        enhanced_wrapper.reset_metrics();
+                    }
                 }
-                Some(EnhancedCommands::Stats) => {
+                Some(EnhancedCommands::Stats { reset, json: false }) => {
+                    let model_limits = enhanced_wrapper.get_model_rate_limiter_stats();
                     let metrics = enhanced_wrapper.get_metrics();
                     let cache_stats = enhanced_wrapper.get_cache_stats();
                     
@@ -155,6 +392,10 @@ async fn main() -> anyhow::Result<()> {
                     println!("📝 Template Renders: {}", metrics.template_renders);
                     println!("🌊 Active Streams: {}", metrics.active_streams);
                     println!("⚠️  Total Errors: {}", metrics.errors_total);
+                    println!("🔤 Tokens: {} prompt / {} completion",
+                        metrics.prompt_tokens_total,
+                        metrics.completion_tokens_total
+                    );
                     println!();
                     println!("💾 Cache Details:");
                     println!("  Total Entries: {}", cache_stats.total_entries);
@@ -162,24 +403,107 @@ async fn main() -> anyhow::Result<()> {
                     println!("  Evictions: {}", cache_stats.evictions);
                     println!("  Disk Reads: {}", cache_stats.disk_reads);
                     println!("  Disk Writes: {}", cache_stats.disk_writes);
+
+                    println!();
+                    println!("🔌 Backend Circuits:");
+                    let mut circuits: Vec<_> = enhanced_wrapper.circuit_states().into_iter().collect();
+                    circuits.sort_by(|a, b| a.0.cmp(&b.0));
+                    for (backend, state) in circuits {
+                        println!("  {}: {}", backend, state);
+                    }
+
+                    if !model_limits.is_empty() {
+                        println!();
+                        println!("🚦 Model Rate Limits:");
+                        for ((backend, model), stats) in &model_limits {
+                            println!("  {}/{}: {}/{} concurrent, {:.1}/{:.1} tokens available",
+                                backend,
+                                model,
+                                stats.current_concurrent,
+                                stats.max_concurrent,
+                                stats.available_tokens,
+                                stats.max_tokens
+                            );
+                        }
+                    }
+
+                    if reset {
+                        enhanced_wrapper.reset_metrics();
+                        println!();
+                        println!("🔄 Metrics reset");
+                    }
+                }
+                Some(EnhancedCommands::Capabilities { model, json }) => {
+                    let reports = enhanced_wrapper.get_capabilities(model.as_deref()).await?;
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&reports)?);
+                    } else if reports.is_empty() {
+                        println!("No models found");
+                    } else {
+                        let mark = |b: bool| if b { "✅" } else { "❌" };
+                        println!("🧩 Capability Matrix");
+                        println!("═══════════════════");
+                        println!("{:<12} {:<28} {:^8} {:^8} {:^9} {:^8} {:>8}",
+                            "Backend", "Model", "Vision", "Think", "Stream", "Embed", "Context");
+                        for report in &reports {
+                            let caps = &report.capabilities;
+                            println!("{:<12} {:<28} {:^8} {:^8} {:^9} {:^8} {:>8}",
+                                report.backend,
+                                report.model,
+                                mark(caps.supports_vision),
+                                mark(caps.supports_thinking),
+                                mark(caps.supports_streaming),
+                                mark(caps.supports_embeddings),
+                                caps.context_length.map_or("-".to_string(), |c| c.to_string())
+                            );
+                        }
+                    }
+                }
+                #[cfg(feature = "server")]
+                Some(EnhancedCommands::Serve { addr }) => {
+                    let listener = tokio::net::TcpListener::bind(&addr).await?;
+                    println!("🌐 Serving OpenAI-compatible API on http://{}/v1", listener.local_addr()?);
+                    // The server flushes the cache itself once Ctrl-C has drained it
+                    let shutdown = async {
+                        let _ = tokio::signal::ctrl_c().await;
+                    };
+                    llm_wrapper::server::serve_with_shutdown(listener, enhanced_wrapper, shutdown).await?;
+                    return Ok(());
                 }
                 None => {
                     // Default to interactive mode
                     enhanced_wrapper.interactive_mode().await?;
                 }
             }
+
+            enhanced_wrapper.flush_cache().await;
+        }
+        Some(Commands::Preset { action }) => {
+            handle_preset_command(&presets_path(cli.config.as_deref()), action)?;
         }
         _ => {
             // Legacy mode - use original wrapper
-            let config = Config::load("config.toml").unwrap_or_default();
+            let config = match &cli.config {
+                Some(path) => Config::load(path)
+                    .with_context(|| format!("Failed to load config file {}", path.display()))?,
+                None => Config::load("config.toml").unwrap_or_default(),
+            };
             let mut wrapper = LLMWrapper::new(&cli.url, &cli.model, config).await?;
+            wrapper.set_generation_options(generation_options.clone());
             
             match cli.command {
-                Some(Commands::List) => {
-                    let models = wrapper.list_models().await?;
+                Some(Commands::List { filter, vision, thinking, sort }) => {
+                    let models = wrapper.list_models_filtered(ModelFilter {
+                        name: filter,
+                        vision_only: vision,
+                        thinking_only: thinking,
+                        sort,
+                    }).await?;
                     println!("Available models:");
                     for model in models {
-                        println!("  - {}", model);
+                        let size = model.size.map_or("-".to_string(), |s| format!("{:.1} GB", s as f64 / 1e9));
+                        let modified = model.modified_at.map_or("-".to_string(), |m| m.format("%Y-%m-%d").to_string());
+                        println!("  - {:<32} {:>8}  {}", model.name, size, modified);
                     }
                 }
                 Some(Commands::Pull { model }) => {
@@ -189,7 +513,7 @@ async fn main() -> anyhow::Result<()> {
                     wrapper.delete_model(&model).await?;
                 }
                 Some(Commands::Chat) => {
-                    interactive_mode(wrapper, cli.model.clone()).await?;
+                    interactive_mode(wrapper, cli.model.clone(), system_prompt.as_deref()).await?;
                 }
                 Some(Commands::Info { model }) => {
                     let model_name = model.as_deref().unwrap_or(&cli.model);
@@ -199,15 +523,64 @@ async fn main() -> anyhow::Result<()> {
                     println!("Vision: {}", if caps.supports_vision { "✅" } else { "❌" });
                     println!("Thinking: {}", if caps.supports_thinking { "✅" } else { "❌" });
                     println!("Streaming: {}", if caps.supports_streaming { "✅" } else { "❌" });
+
+                    let details = wrapper.show_model(wrapper.model()).await?;
+                    let unknown = || "unknown".to_string();
+                    println!("Parameters: {}", details.parameter_size.unwrap_or_else(unknown));
+                    println!("Quantization: {}", details.quantization_level.unwrap_or_else(unknown));
+                    println!("Context length: {}", details.context_length.map_or_else(unknown, |c| c.to_string()));
+                    if let Some(template) = details.template.filter(|t| !t.is_empty()) {
+                        println!("Template:");
+                        println!("{}", template);
+                    }
+                }
+                Some(Commands::Generate { prompt, template, raw, no_stream }) => {
+                    let options = GenerateOptions {
+                        system: system_prompt.clone(),
+                        template,
+                        raw,
+                    };
+                    if no_stream {
+                        println!("{}", wrapper.generate(&prompt, &options).await?);
+                    } else {
+                        use std::io::Write;
+                        let generation = wrapper.generate_stream(&prompt, &options, |token| {
+                            print!("{}", token);
+                            let _ = std::io::stdout().flush();
+                        });
+                        // Dropping the generation on Ctrl-C closes the connection,
+                        // which stops the backend from generating further
+                        tokio::select! {
+                            result = generation => {
+                                result?;
+                                println!();
+                            }
+                            _ = tokio::signal::ctrl_c() => {
+                                println!();
+                                println!("⏹️  Cancelled");
+                            }
+                        }
+                    }
                 }
                 None => {
-                    if let Some(message) = cli.message {
+                    let stdin = std::io::stdin();
+                    let message = single_message(cli.message, stdin.is_terminal(), stdin.lock())?;
+                    if let Some(message) = message {
                         // Single message mode
-                        let response = wrapper.chat(&message, &cli.image, cli.system.as_deref()).await?;
-                        println!("{}", response);
+                        match cli.format {
+                            OutputFormat::Text => {
+                                let response = wrapper.chat(&message, &cli.image, system_prompt.as_deref()).await?;
+                                println!("{}", response);
+                            }
+                            OutputFormat::Json => {
+                                let start = std::time::Instant::now();
+                                let completion = wrapper.chat_with_usage(&message, &cli.image, system_prompt.as_deref()).await?;
+                                println!("{}", json_response(&cli.model, &completion, start.elapsed()));
+                            }
+                        }
                     } else {
                         // Interactive mode
-                        interactive_mode(wrapper, cli.model.clone()).await?;
+                        interactive_mode(wrapper, cli.model.clone(), system_prompt.as_deref()).await?;
                     }
                 }
                 _ => unreachable!(),
@@ -218,7 +591,7 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn interactive_mode(mut wrapper: LLMWrapper, model_name: String) -> anyhow::Result<()> {
+async fn interactive_mode(mut wrapper: LLMWrapper, model_name: String, system_prompt: Option<&str>) -> anyhow::Result<()> {
     use std::io::{self, Write};
     
     let caps = wrapper.capabilities();
@@ -253,7 +626,8 @@ async fn interactive_mode(mut wrapper: LLMWrapper, model_name: String) -> anyhow
                 "/image" => {
                     if parts.len() > 1 {
                         let path = PathBuf::from(parts[1]);
-                        if path.exists() {
+                        let is_url = parts[1].starts_with("http://") || parts[1].starts_with("https://");
+                        if is_url || path.exists() {
                             current_images.push(path.clone());
                             println!("📷 Added: {}", path.display());
                         } else {
@@ -283,9 +657,14 @@ async fn interactive_mode(mut wrapper: LLMWrapper, model_name: String) -> anyhow
             print!("🤖 Assistant: ");
             io::stdout().flush()?;
             
-            match wrapper.chat(input, &current_images, None).await {
-                Ok(response) => {
-                    println!("{}", response);
+            let streamed = wrapper.chat_stream(input, &current_images, system_prompt, |token| {
+                print!("{}", token);
+                let _ = io::stdout().flush();
+            }).await;
+            
+            match streamed {
+                Ok(_) => {
+                    println!();
                 }
                 Err(e) => {
                     println!("❌ Error: {}", e);
@@ -299,28 +678,306 @@ async fn interactive_mode(mut wrapper: LLMWrapper, model_name: String) -> anyhow
     Ok(())
 }
 
-async fn load_enhanced_config() -> anyhow::Result<EnhancedConfig> {
-    // Try to load from enhanced-config.toml, fall back to defaults
-    match EnhancedConfig::load("enhanced-config.toml") {
-        Ok(config) => {
-            println!("✅ Loaded configuration from enhanced-config.toml");
-            Ok(config)
+/// The prompt for single-message mode: the `message` argument, or all of
+/// stdin when it is piped in. `None` means there is nothing to send and the
+/// CLI should go interactive.
+fn single_message<R: std::io::Read>(
+    message: Option<String>,
+    stdin_is_terminal: bool,
+    mut stdin: R,
+) -> std::io::Result<Option<String>> {
+    if message.is_some() || stdin_is_terminal {
+        return Ok(message);
+    }
+
+    let mut input = String::new();
+    stdin.read_to_string(&mut input)?;
+    let input = input.trim_end();
+    Ok((!input.is_empty()).then(|| input.to_string()))
+}
+
+/// The `--format json` output for a single-message response. Legacy mode has
+/// no cache, so `cached` is always false there.
+fn json_response(model: &str, completion: &ChatCompletion, latency: std::time::Duration) -> serde_json::Value {
+    let usage = completion.usage.clone().unwrap_or_default();
+    json!({
+        "response": completion.content,
+        "model": model,
+        "prompt_tokens": usage.prompt_tokens,
+        "completion_tokens": usage.completion_tokens,
+        "total_tokens": usage.total_tokens(),
+        "cached": false,
+        "latency_ms": latency.as_millis() as u64,
+    })
+}
+
+/// Print tokens as they arrive until the stream completes or Ctrl-C is pressed
+async fn print_stream(mut stream_response: llm_wrapper::StreamResponse) -> anyhow::Result<()> {
+    use crossterm::style::Stylize;
+    use llm_wrapper::streaming::TokenKind;
+    use std::io::{self, Write};
+
+    let mut in_reasoning = false;
+    loop {
+        tokio::select! {
+            token = stream_response.receiver.recv() => {
+                let Some(token) = token else { break };
+                match token.kind {
+                    // Reasoning is dimmed and separated from the answer
+                    TokenKind::Reasoning => {
+                        in_reasoning = true;
+                        print!("{}", token.content.as_str().dim());
+                    }
+                    TokenKind::Answer => {
+                        if std::mem::take(&mut in_reasoning) {
+                            println!();
+                            println!();
+                        }
+                        print!("{}", token.content);
+                    }
+                }
+                io::stdout().flush()?;
+                if token.is_complete {
+                    break;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                stream_response.cancellation_token.cancel();
+                println!();
+                println!("⏹️  Cancelled");
+                return Ok(());
+            }
         }
-        Err(e) => {
-            println!("⚠️  Failed to load enhanced-config.toml: {}", e);
-            println!("ℹ️  Using default configuration");
-            let default_config = EnhancedConfig::default();
-            
-            // Save default config for future reference
-            if let Err(save_err) = default_config.save("enhanced-config.toml") {
-                println!("⚠️  Failed to save default config: {}", save_err);
-            } else {
-                println!("💾 Saved default configuration to enhanced-config.toml");
+    }
+
+    println!();
+    Ok(())
+}
+
+/// Print `(heading, text)` pairs as columns that share the terminal width
+fn print_side_by_side(columns: &[(String, String)]) {
+    if columns.is_empty() {
+        return;
+    }
+    let total_width = crossterm::terminal::size().map_or(120, |(width, _)| width as usize);
+    let width = (total_width.saturating_sub(3 * (columns.len() - 1)) / columns.len()).max(16);
+
+    let wrapped: Vec<Vec<String>> = columns.iter().map(|(_, text)| wrap_text(text, width)).collect();
+    let row = |cells: Vec<String>| {
+        let cells: Vec<String> = cells.iter().map(|cell| format!("{:<width$}", cell, width = width)).collect();
+        println!("{}", cells.join(" │ ").trim_end());
+    };
+
+    row(columns.iter().map(|(heading, _)| heading.chars().take(width).collect()).collect());
+    println!("{}", vec!["─".repeat(width); columns.len()].join("─┼─"));
+    let height = wrapped.iter().map(Vec::len).max().unwrap_or(0);
+    for line in 0..height {
+        row(wrapped.iter().map(|lines| lines.get(line).cloned().unwrap_or_default()).collect());
+    }
+}
+
+/// Greedy word wrap, splitting words longer than `width`
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            while word.len() > width {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                lines.push(word.drain(..width).collect());
             }
-            
-            Ok(default_config)
+            if word.is_empty() {
+                continue;
+            }
+            let word: String = word.into_iter().collect();
+            let line_len = line.chars().count();
+            if line_len > 0 && line_len + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
         }
+        lines.push(line);
+    }
+    lines
+}
+
+async fn handle_session_command(wrapper: &mut EnhancedLLMWrapper, action: SessionAction) -> anyhow::Result<()> {
+    let session = match action {
+        SessionAction::List => {
+            let sessions = wrapper.list_sessions()?;
+            if sessions.is_empty() {
+                println!("No saved sessions");
+            }
+            for session in sessions {
+                println!(
+                    "{}  {}  {:<16} {} messages",
+                    session.id,
+                    session.created_at.format("%Y-%m-%d %H:%M"),
+                    session.model,
+                    session.messages.len()
+                );
+            }
+            return Ok(());
+        }
+        SessionAction::Load { id } => wrapper.load_session(&id)?,
+        SessionAction::New => Session::new(wrapper.current_backend()),
+    };
+
+    let session = wrapper.interactive_session(session).await?;
+    if !session.messages.is_empty() {
+        println!("💾 Session {} saved", session.id);
     }
+    Ok(())
+}
+
+async fn session_chat(wrapper: &mut EnhancedLLMWrapper, model: Option<&str>, system_prompt: Option<&str>) -> anyhow::Result<()> {
+    use std::io::{self, Write};
+
+    const SESSION_ID: &str = "cli";
+
+    // Kept alongside the session so the conversation can be exported
+    let mut transcript: Vec<ChatMessage> = Vec::new();
+    let transcript_message = |role: MessageRole, content: &str| ChatMessage {
+        role,
+        content: content.to_string(),
+        timestamp: chrono::Utc::now(),
+        model: model.unwrap_or("default").to_string(),
+        template_used: None,
+        cached: false,
+        reasoning: None,
+    };
+
+    if let Some(prompt) = system_prompt {
+        wrapper.set_session_system_prompt(SESSION_ID, prompt);
+    }
+
+    println!("💬 Chat session started (history is kept between turns)");
+    println!("Commands: /clear, /retry [model], /save <path>, /quit");
+    println!("{}", "-".repeat(50));
+
+    loop {
+        print!("💬 You: ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            break;
+        }
+        let input = input.trim();
+
+        match input {
+            "" => continue,
+            "/quit" | "/q" => break,
+            "/clear" => {
+                wrapper.clear_session(SESSION_ID);
+                if let Some(prompt) = system_prompt {
+                    wrapper.set_session_system_prompt(SESSION_ID, prompt);
+                }
+                transcript.clear();
+                println!("🗑️ Cleared conversation history");
+            }
+            _ if input == "/retry" || input.starts_with("/retry ") => {
+                let retry_model = input.strip_prefix("/retry").map(str::trim).filter(|m| !m.is_empty());
+                print!("🤖 Assistant: ");
+                io::stdout().flush()?;
+
+                match wrapper.regenerate_last(SESSION_ID, retry_model.or(model)).await {
+                    Ok(response) => {
+                        println!("{}", response);
+                        if transcript.last().is_some_and(|m| m.role == MessageRole::Assistant) {
+                            transcript.pop();
+                        }
+                        transcript.push(transcript_message(MessageRole::Assistant, &response));
+                    }
+                    Err(e) => println!("❌ Error: {}", e),
+                }
+            }
+            _ if input.starts_with("/save") => match input.strip_prefix("/save").map(str::trim) {
+                Some(path) if !path.is_empty() => match std::fs::write(path, export_markdown(&transcript)) {
+                    Ok(()) => println!("💾 Saved {} messages to {}", transcript.len(), path),
+                    Err(e) => println!("❌ Could not save to {}: {}", path, e),
+                },
+                _ => println!("Usage: /save <path>"),
+            },
+            _ => {
+                print!("🤖 Assistant: ");
+                io::stdout().flush()?;
+
+                match wrapper.chat_in_session(SESSION_ID, input, model).await {
+                    Ok(response) => {
+                        println!("{}", response);
+                        transcript.push(transcript_message(MessageRole::User, input));
+                        transcript.push(transcript_message(MessageRole::Assistant, &response));
+                    }
+                    Err(e) => println!("❌ Error: {}", e),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Presets live next to the config file, or in the working directory by default
+fn presets_path(config: Option<&Path>) -> PathBuf {
+    config.and_then(Path::parent).unwrap_or(Path::new("")).join("presets.json")
+}
+
+fn handle_preset_command(path: &Path, action: PresetAction) -> anyhow::Result<()> {
+    let mut store = PresetStore::load(path)?;
+    match action {
+        PresetAction::List => {
+            println!("📚 Presets:");
+            for (name, prompt) in store.iter() {
+                let first_line = prompt.lines().next().unwrap_or_default();
+                println!("  - {}: {}", name, first_line);
+            }
+        }
+        PresetAction::Add { name, prompt } => {
+            let replaced = store.add(&name, &prompt)?.is_some();
+            store.save()?;
+            println!("✅ Preset '{}' {}", name, if replaced { "updated" } else { "saved" });
+        }
+        PresetAction::Remove { name } => {
+            store.remove(&name)?;
+            store.save()?;
+            println!("🗑️ Preset '{}' removed", name);
+        }
+    }
+    Ok(())
+}
+
+async fn load_enhanced_config(path: Option<&Path>) -> anyhow::Result<EnhancedConfig> {
+    // A file asked for by name must exist; only the default may fall back
+    if let Some(path) = path {
+        let config = EnhancedConfig::load(path)?;
+        println!("✅ Loaded configuration from {}", path.display());
+        return Ok(config);
+    }
+
+    // Write out the defaults on first run, but never over an existing file
+    let default_path = Path::new("enhanced-config.toml");
+    if default_path.exists() {
+        let config = EnhancedConfig::load(default_path)
+            .with_context(|| format!("Failed to load {}", default_path.display()))?;
+        println!("✅ Loaded configuration from enhanced-config.toml");
+        return Ok(config);
+    }
+
+    println!("ℹ️  Using default configuration");
+    let default_config = EnhancedConfig::default();
+    if let Err(save_err) = default_config.save(default_path) {
+        println!("⚠️  Failed to save default config: {}", save_err);
+    } else {
+        println!("💾 Saved default configuration to enhanced-config.toml");
+    }
+    Ok(default_config)
 }
 
 async fn handle_template_command(
@@ -356,11 +1013,12 @@ async fn handle_template_command(
                 name: name.clone(),
                 content,
                 description,
-                variables: Vec::new(), // TODO: Parse variables from template
+                variables: Vec::new(), // inferred from the content on registration
                 created_at: std::time::SystemTime::now(),
                 parent_template: None,
                 tags: Vec::new(),
                 usage_examples: Vec::new(),
+                few_shot: Vec::new(),
             };
             
             wrapper.save_template(template).await?;
@@ -390,8 +1048,53 @@ async fn handle_template_command(
             }
         }
         TemplateAction::Delete { name } => {
-            // TODO: Implement template deletion
-            println!("❌ Template deletion not yet implemented");
+            match wrapper.delete_template(&name).await {
+                Ok(()) => println!("🗑️ Template '{}' deleted", name),
+                Err(llm_wrapper::WrapperError::Template(llm_wrapper::template::TemplateError::NotFound(_))) => {
+                    println!("❌ Template '{}' not found", name);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        TemplateAction::Render { name, vars, out } => {
+            let variables = match vars {
+                Some(vars) => serde_json::from_str(&vars)?,
+                None => json!({}),
+            };
+            match out {
+                Some(path) => {
+                    wrapper.render_template_to_file(&name, variables, &path).await?;
+                    println!("✅ Rendered '{}' to {}", name, path.display());
+                }
+                None => println!("{}", wrapper.render_template(&name, variables)?),
+            }
+        }
+        TemplateAction::Validate { name, vars } => {
+            let variables = match vars {
+                Some(vars) => serde_json::from_str(&vars)?,
+                None => json!({}),
+            };
+            let report = wrapper.validate_template_render(&name, &variables)?;
+            if let Some(rendered) = &report.rendered {
+                println!("✅ Template '{}' renders cleanly:", name);
+                println!("{}", rendered);
+                return Ok(());
+            }
+
+            println!("❌ Template '{}' has problems:", name);
+            for variable in &report.missing_variables {
+                println!("  Missing required variable: {}", variable);
+            }
+            for mismatch in &report.type_mismatches {
+                println!("  {}", mismatch);
+            }
+            if let Some(error) = &report.syntax_error {
+                println!("  {}", error.trim_end().replace('\n', "\n  "));
+            }
+            if let Some(error) = &report.render_error {
+                println!("  {}", error);
+            }
+            anyhow::bail!("template '{}' failed validation", name);
         }
     }
     Ok(())
@@ -414,6 +1117,25 @@ async fn handle_cache_command(
             println!("Evictions: {}", stats.evictions);
             println!("Disk Reads: {}", stats.disk_reads);
             println!("Disk Writes: {}", stats.disk_writes);
+            println!("Disk Usage: {} bytes", stats.disk_usage_bytes);
+
+            let mut by_model: Vec<_> = wrapper.cache_stats_by_model().into_iter().collect();
+            if !by_model.is_empty() {
+                by_model.sort_by(|(a, _), (b, _)| a.cmp(b));
+                println!();
+                println!("By Model:");
+                for (model, stats) in by_model {
+                    println!(
+                        "  {}: {:.1}% hit ratio, {} hits, {} misses, {} entries, {} bytes",
+                        model,
+                        stats.hit_ratio() * 100.0,
+                        stats.hits,
+                        stats.misses,
+                        stats.total_entries,
+                        stats.memory_usage_bytes
+                    );
+                }
+            }
         }
         CacheAction::Clear => {
             wrapper.clear_cache().await?;
@@ -423,6 +1145,66 @@ async fn handle_cache_command(
             wrapper.invalidate_cache_for_model(&model).await?;
             println!("✅ Cache cleared for model: {}", model);
         }
+        CacheAction::ClearTag { tag } => {
+            let removed = wrapper.invalidate_cache_tag(&tag).await?;
+            println!("✅ Removed {} cache entries tagged: {}", removed, tag);
+        }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use llm_wrapper::TokenUsage;
+
+    #[test]
+    fn test_piped_stdin_becomes_the_message() {
+        let piped = "Summarize this file\nline two\n".as_bytes();
+        assert_eq!(
+            single_message(None, false, piped).unwrap().as_deref(),
+            Some("Summarize this file\nline two")
+        );
+
+        // An explicit message wins, and a terminal is never read from
+        assert_eq!(single_message(Some("Hi".to_string()), false, piped).unwrap().as_deref(), Some("Hi"));
+        assert_eq!(single_message(None, true, piped).unwrap(), None);
+        assert_eq!(single_message(None, false, "\n".as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_json_response_format() {
+        let completion = ChatCompletion {
+            content: "Hello there".to_string(),
+            usage: Some(TokenUsage {
+                prompt_tokens: Some(12),
+                completion_tokens: Some(3),
+                eval_duration: None,
+            }),
+            tool_calls: Vec::new(),
+        };
+        let output = json_response("llama3.2", &completion, std::time::Duration::from_millis(250)).to_string();
+
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed["response"], "Hello there");
+        assert_eq!(parsed["model"], "llama3.2");
+        assert_eq!(parsed["total_tokens"], 15);
+        assert_eq!(parsed["latency_ms"], 250);
+    }
+
+    #[tokio::test]
+    async fn test_load_enhanced_config_from_custom_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("work-profile.toml");
+        let mut config = EnhancedConfig::default();
+        config.model_aliases.insert("fast".to_string(), "llama3.2:3b".to_string());
+        config.save(&path).unwrap();
+
+        let loaded = load_enhanced_config(Some(&path)).await.unwrap();
+        assert_eq!(loaded.model_aliases["fast"], "llama3.2:3b");
+
+        let missing = dir.path().join("missing.toml");
+        let error = load_enhanced_config(Some(&missing)).await.unwrap_err();
+        assert!(error.to_string().contains("missing.toml"), "{}", error);
+    }
+}
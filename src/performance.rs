@@ -20,6 +20,7 @@ pub struct CachePerformanceMetrics {
     pub memory_usage_bytes: usize,
     pub eviction_rate: f64,
     pub total_operations: u64,
+    pub lookup_latency: LatencyPercentiles,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +29,7 @@ pub struct TemplatePerformanceMetrics {
     pub render_success_rate: f64,
     pub total_renders: u64,
     pub cache_hit_ratio: f64,
+    pub render_latency: LatencyPercentiles,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +39,38 @@ pub struct StreamingPerformanceMetrics {
     pub active_streams: u64,
     pub total_streams_created: u64,
     pub stream_success_rate: f64,
+    pub first_token_latency: LatencyPercentiles,
+}
+
+/// Tail latencies over the most recent samples of an operation
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyPercentiles {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+}
+
+impl LatencyPercentiles {
+    /// Nearest-rank percentiles; all zero when there are no samples
+    pub fn from_samples(samples: &[Duration]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+
+        let mut sorted: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let rank = |p: f64| {
+            let index = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+            sorted[index.clamp(1, sorted.len()) - 1]
+        };
+
+        Self {
+            p50_ms: rank(50.0),
+            p95_ms: rank(95.0),
+            p99_ms: rank(99.0),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,12 +100,14 @@ impl Default for PerformanceMetrics {
                 memory_usage_bytes: 0,
                 eviction_rate: 0.0,
                 total_operations: 0,
+                lookup_latency: LatencyPercentiles::default(),
             },
             template_metrics: TemplatePerformanceMetrics {
                 average_render_time_ms: 0.0,
                 render_success_rate: 0.0,
                 total_renders: 0,
                 cache_hit_ratio: 0.0,
+                render_latency: LatencyPercentiles::default(),
             },
             streaming_metrics: StreamingPerformanceMetrics {
                 average_first_token_time_ms: 0.0,
@@ -79,6 +115,7 @@ impl Default for PerformanceMetrics {
                 active_streams: 0,
                 total_streams_created: 0,
                 stream_success_rate: 0.0,
+                first_token_latency: LatencyPercentiles::default(),
             },
             system_metrics: SystemPerformanceMetrics {
                 memory_usage_mb: 0.0,
@@ -91,6 +128,12 @@ impl Default for PerformanceMetrics {
     }
 }
 
+impl Default for PerformanceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PerformanceMonitor {
     pub fn new() -> Self {
         Self {
@@ -103,7 +146,7 @@ impl PerformanceMonitor {
 
     pub fn record_operation_time(&self, operation: &str, duration: Duration) {
         let mut times = self.operation_times.lock().unwrap();
-        times.entry(operation.to_string()).or_insert_with(Vec::new).push(duration);
+        times.entry(operation.to_string()).or_default().push(duration);
         
         // Keep only the last 1000 measurements to prevent memory growth
         if let Some(vec) = times.get_mut(operation) {
@@ -152,6 +195,7 @@ impl PerformanceMonitor {
         if let Some(cache_lookup_times) = times.get("cache_lookup") {
             metrics.cache_metrics.average_lookup_time_ms = 
                 cache_lookup_times.iter().map(|d| d.as_millis() as f64).sum::<f64>() / cache_lookup_times.len() as f64;
+            metrics.cache_metrics.lookup_latency = LatencyPercentiles::from_samples(cache_lookup_times);
         }
 
         if let Some(cache_store_times) = times.get("cache_store") {
@@ -166,12 +210,15 @@ impl PerformanceMonitor {
         if total_cache_ops > 0 {
             metrics.cache_metrics.hit_ratio = *cache_hits as f64 / total_cache_ops as f64;
         }
-        metrics.cache_metrics.total_operations = total_cache_ops;
+        let cache_stores = counters.get("cache_store_success").unwrap_or(&0)
+            + counters.get("cache_store_error").unwrap_or(&0);
+        metrics.cache_metrics.total_operations = total_cache_ops + cache_stores;
 
         // Update template metrics
         if let Some(template_times) = times.get("template_render") {
             metrics.template_metrics.average_render_time_ms = 
                 template_times.iter().map(|d| d.as_millis() as f64).sum::<f64>() / template_times.len() as f64;
+            metrics.template_metrics.render_latency = LatencyPercentiles::from_samples(template_times);
         }
 
         let template_success = counters.get("template_render_success").unwrap_or(&0);
@@ -187,16 +234,17 @@ impl PerformanceMonitor {
         if let Some(first_token_times) = times.get("stream_first_token") {
             metrics.streaming_metrics.average_first_token_time_ms = 
                 first_token_times.iter().map(|d| d.as_millis() as f64).sum::<f64>() / first_token_times.len() as f64;
+            metrics.streaming_metrics.first_token_latency = LatencyPercentiles::from_samples(first_token_times);
         }
 
-        metrics.streaming_metrics.total_streams_created = counters.get("stream_create").unwrap_or(&0).clone();
-        metrics.streaming_metrics.active_streams = counters.get("stream_active").unwrap_or(&0).clone();
+        metrics.streaming_metrics.total_streams_created = *counters.get("stream_create").unwrap_or(&0);
+        metrics.streaming_metrics.active_streams = *counters.get("stream_active").unwrap_or(&0);
 
         // Update system metrics
         metrics.system_metrics.uptime_seconds = self.start_time.elapsed().as_secs();
-        metrics.system_metrics.total_requests = counters.get("total_requests").unwrap_or(&0).clone();
+        metrics.system_metrics.total_requests = *counters.get("total_requests").unwrap_or(&0);
 
-        let total_errors = counters.values().filter(|&v| v > &0).count() as u64;
+        let total_errors = *counters.get("request_error").unwrap_or(&0);
         if metrics.system_metrics.total_requests > 0 {
             metrics.system_metrics.error_rate = total_errors as f64 / metrics.system_metrics.total_requests as f64;
         }
@@ -205,21 +253,31 @@ impl PerformanceMonitor {
     }
 
     pub fn start_monitoring_task(&self) -> tokio::task::JoinHandle<()> {
+        self.start_monitoring_task_with_interval(Duration::from_secs(60)) // Update every minute
+    }
+
+    /// Spawn a task that samples process memory and CPU every `period`. Abort the
+    /// returned handle to stop it.
+    pub fn start_monitoring_task_with_interval(&self, period: Duration) -> tokio::task::JoinHandle<()> {
         let metrics_clone = Arc::clone(&self.metrics);
         let start_time = self.start_time;
         
         tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(60)); // Update every minute
+            let mut interval = interval(period);
+            let mut sampler = SystemSampler::new();
             
             loop {
                 interval.tick().await;
                 
-                // Update system metrics
+                // Sample before taking the lock so readers are not blocked on sysinfo
+                let sample = sampler.sample();
+                
                 let mut metrics = metrics_clone.lock().unwrap();
                 metrics.system_metrics.uptime_seconds = start_time.elapsed().as_secs();
-                
-                // In a real implementation, you would collect actual system metrics here
-                // For now, we'll just update the uptime
+                if let Some((memory_mb, cpu_percent)) = sample {
+                    metrics.system_metrics.memory_usage_mb = memory_mb;
+                    metrics.system_metrics.cpu_usage_percent = cpu_percent;
+                }
                 
                 tracing::debug!("Performance metrics updated: {:?}", *metrics);
             }
@@ -260,11 +318,11 @@ impl PerformanceMonitor {
             report.overall_status = PerformanceStatus::Warning;
         }
 
-        // Check streaming performance targets
-        if metrics.streaming_metrics.average_first_token_time_ms > 200.0 {
+        // Check streaming performance targets against the tail, not the mean
+        if metrics.streaming_metrics.first_token_latency.p95_ms > 200.0 {
             report.issues.push(format!(
-                "First token time ({:.2}ms) exceeds target (200ms)",
-                metrics.streaming_metrics.average_first_token_time_ms
+                "First token p95 ({:.2}ms) exceeds target (200ms)",
+                metrics.streaming_metrics.first_token_latency.p95_ms
             ));
             report.overall_status = PerformanceStatus::Critical;
         }
@@ -292,6 +350,34 @@ impl PerformanceMonitor {
     }
 }
 
+/// Samples resident memory and CPU usage of the current process
+struct SystemSampler {
+    system: sysinfo::System,
+    pid: Option<sysinfo::Pid>,
+}
+
+impl SystemSampler {
+    fn new() -> Self {
+        Self {
+            system: sysinfo::System::new(),
+            pid: sysinfo::get_current_pid().ok(),
+        }
+    }
+
+    /// Returns `(memory_mb, cpu_percent)`, or `None` on unsupported platforms.
+    /// CPU usage is measured between consecutive samples, so the first reads 0.
+    fn sample(&mut self) -> Option<(f64, f64)> {
+        let pid = self.pid?;
+        if !self.system.refresh_process(pid) {
+            return None;
+        }
+
+        let process = self.system.process(pid)?;
+        let memory_mb = process.memory() as f64 / (1024.0 * 1024.0);
+        Some((memory_mb, process.cpu_usage() as f64))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PerformanceReport {
     pub overall_status: PerformanceStatus,
@@ -319,7 +405,7 @@ impl std::fmt::Display for PerformanceStatus {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::time::{sleep, Duration};
+    use tokio::time::Duration;
 
     #[tokio::test]
     async fn test_performance_monitor() {
@@ -350,4 +436,44 @@ mod tests {
         assert_eq!(report.overall_status, PerformanceStatus::Warning);
         assert!(!report.issues.is_empty());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_monitoring_task_samples_system_metrics() {
+        if !sysinfo::IS_SUPPORTED_SYSTEM {
+            return;
+        }
+
+        let monitor = PerformanceMonitor::new();
+        let handle = monitor.start_monitoring_task_with_interval(Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let metrics = monitor.get_metrics();
+        assert!(metrics.system_metrics.memory_usage_mb > 0.0);
+
+        handle.abort();
+        assert!(handle.await.unwrap_err().is_cancelled());
+    }
+
+    #[test]
+    fn test_percentiles_expose_tail_latency() {
+        let monitor = PerformanceMonitor::new();
+
+        // 90 fast first tokens and a slow tail of 10
+        for _ in 0..90 {
+            monitor.record_stream_operation("first_token", Some(Duration::from_millis(10)));
+        }
+        for _ in 0..10 {
+            monitor.record_stream_operation("first_token", Some(Duration::from_millis(1000)));
+        }
+
+        let metrics = monitor.get_metrics();
+        let latency = &metrics.streaming_metrics.first_token_latency;
+        assert!(metrics.streaming_metrics.average_first_token_time_ms < 200.0);
+        assert_eq!(latency.p50_ms, 10.0);
+        assert_eq!(latency.p95_ms, 1000.0);
+        assert_eq!(latency.p99_ms, 1000.0);
+
+        let report = monitor.check_performance_targets();
+        assert_eq!(report.overall_status, PerformanceStatus::Critical);
+    }
+}
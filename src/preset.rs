@@ -0,0 +1,106 @@
+//! Named system prompts ("presets") such as personas, applied to a chat by name.
+//!
+//! Unlike templates they take no variables. The whole library is one JSON
+//! object of name to prompt text, kept next to the config file.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PresetError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Preset '{0}' not found")]
+    NotFound(String),
+    #[error("Invalid preset name '{0}'")]
+    InvalidName(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PresetStore {
+    path: PathBuf,
+    presets: BTreeMap<String, String>,
+}
+
+impl PresetStore {
+    /// The presets saved at `path`; a missing file is an empty library
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, PresetError> {
+        let path = path.into();
+        let presets = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, presets })
+    }
+
+    /// Write the library back to the file it was loaded from, creating its directory if needed
+    pub fn save(&self) -> Result<(), PresetError> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.presets)?)?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, name: &str) -> Result<&str, PresetError> {
+        self.presets
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| PresetError::NotFound(name.to_string()))
+    }
+
+    /// Add or replace a preset, returning the prompt it replaced
+    pub fn add(&mut self, name: &str, prompt: &str) -> Result<Option<String>, PresetError> {
+        if name.trim().is_empty() {
+            return Err(PresetError::InvalidName(name.to_string()));
+        }
+        Ok(self.presets.insert(name.to_string(), prompt.to_string()))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<String, PresetError> {
+        self.presets
+            .remove(name)
+            .ok_or_else(|| PresetError::NotFound(name.to_string()))
+    }
+
+    /// Every preset as `(name, prompt)`, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.presets.iter().map(|(name, prompt)| (name.as_str(), prompt.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("presets.json");
+
+        let mut store = PresetStore::load(&path).unwrap();
+        assert_eq!(store.iter().count(), 0);
+        store.add("reviewer", "You review Rust code.").unwrap();
+        store.add("pirate", "Talk like a pirate.").unwrap();
+        assert_eq!(store.add("pirate", "Always talk like a pirate.").unwrap().as_deref(), Some("Talk like a pirate."));
+        assert!(matches!(store.add(" ", "blank"), Err(PresetError::InvalidName(_))));
+        store.save().unwrap();
+
+        let mut loaded = PresetStore::load(&path).unwrap();
+        let names: Vec<&str> = loaded.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["pirate", "reviewer"]);
+        assert_eq!(loaded.get("pirate").unwrap(), "Always talk like a pirate.");
+
+        assert_eq!(loaded.remove("reviewer").unwrap(), "You review Rust code.");
+        assert!(matches!(loaded.remove("reviewer"), Err(PresetError::NotFound(_))));
+        assert!(matches!(loaded.get("missing"), Err(PresetError::NotFound(_))));
+    }
+}
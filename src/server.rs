@@ -0,0 +1,297 @@
+//! OpenAI-compatible HTTP gateway in front of `EnhancedLLMWrapper`.
+//!
+//! Exposes `POST /v1/chat/completions` and a Prometheus `GET /metrics`.
+//! Requests go through the wrapper, so responses are cached and counted in
+//! its metrics like any other chat.
+
+use axum::{
+    extract::State,
+    http::{HeaderValue, StatusCode},
+    response::{sse::Event, IntoResponse, Response, Sse},
+    routing::{get, post},
+    Json, Router,
+};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::streaming::{Message, StreamError, StreamResponse, TokenKind};
+use crate::{ChatStep, EnhancedLLMWrapper, GenerationOptions, WrapperError};
+
+pub type SharedWrapper = Arc<Mutex<EnhancedLLMWrapper>>;
+
+/// Response header carrying the wrapper's correlation id for the request
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatCompletionRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub messages: Vec<ChatCompletionMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    #[serde(default)]
+    pub max_tokens: Option<i32>,
+    #[serde(default)]
+    pub seed: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCompletionMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCompletionResponse {
+    pub id: String,
+    pub object: String,
+    pub created: i64,
+    pub model: String,
+    pub choices: Vec<ChatCompletionChoice>,
+    /// Omitted when the reply was served from the cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCompletionChoice {
+    pub index: u32,
+    pub message: ChatCompletionMessage,
+    pub finish_reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCompletionUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl ChatCompletionRequest {
+    fn generation_options(&self, defaults: &GenerationOptions) -> GenerationOptions {
+        GenerationOptions {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            num_predict: self.max_tokens.or(defaults.num_predict),
+            seed: self.seed.or(defaults.seed),
+            ..defaults.clone()
+        }
+    }
+
+    fn messages(&self) -> Vec<Message> {
+        self.messages
+            .iter()
+            .map(|m| Message {
+                role: m.role.clone(),
+                content: m.content.clone(),
+                images: None,
+            })
+            .collect()
+    }
+}
+
+/// Build the router, for embedding the gateway in a larger application
+pub fn router(wrapper: SharedWrapper) -> Router {
+    Router::new()
+        .route("/v1/chat/completions", post(chat_completions))
+        .route("/metrics", get(metrics))
+        .with_state(wrapper)
+}
+
+/// Serve the gateway on `listener` until the process is stopped
+pub async fn serve(listener: TcpListener, wrapper: EnhancedLLMWrapper) -> std::io::Result<()> {
+    serve_with_shutdown(listener, wrapper, std::future::pending()).await
+}
+
+/// Serve the gateway until `shutdown` completes, then let in-flight requests
+/// finish and flush queued cache writes to disk
+pub async fn serve_with_shutdown<F>(listener: TcpListener, wrapper: EnhancedLLMWrapper, shutdown: F) -> std::io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let wrapper = Arc::new(Mutex::new(wrapper));
+    axum::serve(listener, router(wrapper.clone()))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    wrapper.lock().await.flush_cache().await;
+    Ok(())
+}
+
+async fn chat_completions(
+    State(wrapper): State<SharedWrapper>,
+    Json(request): Json<ChatCompletionRequest>,
+) -> Result<Response, ApiError> {
+    let id = format!("chatcmpl-{:016x}", rand::random::<u64>());
+    let created = chrono::Utc::now().timestamp();
+    let model = request.model.clone().unwrap_or_else(|| "default".to_string());
+
+    // The lock is only held to start and finish the request, never across the
+    // backend call, so requests run concurrently
+    let mut request_id = None;
+    let result = if request.stream {
+        let pending = {
+            let mut wrapper = wrapper.lock().await;
+            let options = request.generation_options(wrapper.generation_options());
+            let pending = wrapper.begin_chat_stream(request.messages(), request.model.as_deref(), &options).await;
+            request_id = wrapper.last_request_id().map(String::from);
+            pending
+        };
+        let opened = match pending {
+            Ok(pending) => {
+                let sent = pending.send().await;
+                wrapper.lock().await.finish_stream(sent)
+            }
+            Err(e) => Err(e),
+        };
+
+        opened.map(|stream| sse_response(stream, id, created, model))
+    } else {
+        let options = request.generation_options(wrapper.lock().await.generation_options());
+        complete_chat(&wrapper, &request, &options, &mut request_id).await.map(|(content, usage)| {
+            Json(ChatCompletionResponse {
+                id,
+                object: "chat.completion".to_string(),
+                created,
+                model,
+                choices: vec![ChatCompletionChoice {
+                    index: 0,
+                    message: ChatCompletionMessage {
+                        role: "assistant".to_string(),
+                        content,
+                    },
+                    finish_reason: "stop".to_string(),
+                }],
+                usage,
+            })
+            .into_response()
+        })
+    };
+
+    // Errors carry the correlation id too, so failures can be traced in the logs
+    let mut response = result.unwrap_or_else(|e| ApiError(e).into_response());
+    if let Some(request_id) = request_id.and_then(|id| HeaderValue::from_str(&id).ok()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+    }
+    Ok(response)
+}
+
+/// Answer `request` without streaming, along with its token usage unless the
+/// reply came from the cache. The request's correlation id goes in `request_id`.
+async fn complete_chat(
+    wrapper: &SharedWrapper,
+    request: &ChatCompletionRequest,
+    options: &GenerationOptions,
+    request_id: &mut Option<String>,
+) -> Result<(String, Option<ChatCompletionUsage>), WrapperError> {
+    let step = {
+        let mut wrapper = wrapper.lock().await;
+        let step = wrapper.begin_chat_with_history(request.messages(), request.model.as_deref(), options).await;
+        *request_id = wrapper.last_request_id().map(String::from);
+        step?
+    };
+    let pending = match step {
+        ChatStep::Answered(content) => return Ok((content, None)),
+        ChatStep::Pending(pending) => pending,
+    };
+
+    let sent = pending.send().await;
+    let mut wrapper = wrapper.lock().await;
+    let content = wrapper.finish_chat(sent).await?;
+    let usage = wrapper.last_usage().map(|usage| {
+        let prompt_tokens = usage.prompt_tokens.unwrap_or(0);
+        let completion_tokens = usage.completion_tokens.unwrap_or(0);
+        ChatCompletionUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    });
+    Ok((content, usage))
+}
+
+async fn metrics(State(wrapper): State<SharedWrapper>) -> impl IntoResponse {
+    let body = wrapper.lock().await.prometheus_metrics();
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+/// Relay stream tokens as `chat.completion.chunk` events, ending with `[DONE]`.
+/// Reasoning tokens have no OpenAI equivalent and are dropped.
+fn sse_response(mut stream: StreamResponse, id: String, created: i64, model: String) -> Response {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Event>();
+    let chunk = move |delta: serde_json::Value, finish_reason: Option<&str>| {
+        Event::default().data(
+            json!({
+                "id": id,
+                "object": "chat.completion.chunk",
+                "created": created,
+                "model": model,
+                "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+            })
+            .to_string(),
+        )
+    };
+
+    tokio::spawn(async move {
+        let mut sent_role = false;
+        while let Some(token) = stream.receiver.recv().await {
+            let mut delta = json!({});
+            if !sent_role {
+                delta["role"] = json!("assistant");
+                sent_role = true;
+            }
+            if token.kind == TokenKind::Answer && !token.content.is_empty() {
+                delta["content"] = json!(token.content);
+            }
+
+            let is_complete = token.is_complete;
+            let finish_reason = is_complete.then_some("stop");
+            let has_delta = delta.as_object().is_some_and(|d| !d.is_empty());
+            if (has_delta || is_complete) && sender.send(chunk(delta, finish_reason)).is_err() {
+                // Client went away
+                stream.cancellation_token.cancel();
+                return;
+            }
+            if is_complete {
+                break;
+            }
+        }
+        let _ = sender.send(Event::default().data("[DONE]"));
+    });
+
+    let events = futures_util::stream::poll_fn(move |cx| receiver.poll_recv(cx))
+        .map(Ok::<_, Infallible>);
+    Sse::new(events).into_response()
+}
+
+/// Error body in the OpenAI `{"error": {...}}` shape
+struct ApiError(WrapperError);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, kind) = match &self.0 {
+            WrapperError::Stream(StreamError::ConcurrencyLimit(_))
+            | WrapperError::Stream(StreamError::RateLimit)
+            | WrapperError::Stream(StreamError::ModelRateLimit(_))
+            | WrapperError::Stream(StreamError::BackendRateLimit(_)) => (StatusCode::TOO_MANY_REQUESTS, "rate_limit_error"),
+            _ => (StatusCode::BAD_GATEWAY, "api_error"),
+        };
+
+        let body = json!({
+            "error": {
+                "message": self.0.to_string(),
+                "type": kind,
+            }
+        });
+        (status, Json(body)).into_response()
+    }
+}
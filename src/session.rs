@@ -0,0 +1,147 @@
+//! Saved interactive chat sessions.
+//!
+//! Each session is one JSON file named after its id in the sessions
+//! directory, so resuming a chat only needs the id.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::ui::ChatMessage;
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Session '{0}' not found")]
+    NotFound(String),
+    #[error("Invalid session id '{0}'")]
+    InvalidId(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+}
+
+impl Session {
+    /// An empty session, with an id derived from the current time
+    pub fn new(model: &str) -> Self {
+        let created_at = Utc::now();
+        Self {
+            id: format!("{}-{:04x}", created_at.format("%Y%m%d-%H%M%S"), rand::random::<u16>()),
+            created_at,
+            model: model.to_string(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Write the session to `<dir>/<id>.json`, creating `dir` if needed
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, SessionError> {
+        let path = Self::path(dir, &self.id)?;
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    pub fn load(dir: &Path, id: &str) -> Result<Self, SessionError> {
+        let path = Self::path(dir, id)?;
+        let contents = std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SessionError::NotFound(id.to_string()),
+            _ => SessionError::Io(e),
+        })?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Every session saved in `dir`, newest first. Files that don't parse
+    /// as sessions are skipped.
+    pub fn list(dir: &Path) -> Result<Vec<Self>, SessionError> {
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut sessions = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match std::fs::read_to_string(&path).map_err(SessionError::from).and_then(|c| Ok(serde_json::from_str::<Self>(&c)?)) {
+                Ok(session) => sessions.push(session),
+                Err(e) => tracing::warn!(path = %path.display(), error = %e, "Skipping unreadable session file"),
+            }
+        }
+
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+        Ok(sessions)
+    }
+
+    /// Ids become file names, so anything that could leave `dir` is refused
+    fn path(dir: &Path, id: &str) -> Result<PathBuf, SessionError> {
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(SessionError::InvalidId(id.to_string()));
+        }
+        Ok(dir.join(format!("{}.json", id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::MessageRole;
+
+    fn message(role: MessageRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            model: "llama3".to_string(),
+            template_used: None,
+            cached: false,
+            reasoning: None,
+        }
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session = Session::new("llama3");
+        session.messages = vec![
+            message(MessageRole::User, "first"),
+            message(MessageRole::Assistant, "second"),
+            message(MessageRole::User, "third"),
+        ];
+        session.save(dir.path()).unwrap();
+
+        let loaded = Session::load(dir.path(), &session.id).unwrap();
+        assert_eq!(loaded.id, session.id);
+        assert_eq!(loaded.model, "llama3");
+        let turns: Vec<(&MessageRole, &str)> = loaded.messages.iter().map(|m| (&m.role, m.content.as_str())).collect();
+        assert_eq!(
+            turns,
+            vec![
+                (&MessageRole::User, "first"),
+                (&MessageRole::Assistant, "second"),
+                (&MessageRole::User, "third"),
+            ]
+        );
+
+        let listed = Session::list(dir.path()).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, session.id);
+    }
+
+    #[test]
+    fn test_load_rejects_missing_and_unsafe_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(Session::load(dir.path(), "nope"), Err(SessionError::NotFound(_))));
+        assert!(matches!(Session::load(dir.path(), "../secrets"), Err(SessionError::InvalidId(_))));
+    }
+}
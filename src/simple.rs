@@ -1,9 +1,6 @@
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
 use std::process::Command;
 
 #[derive(Parser)]
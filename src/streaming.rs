@@ -4,15 +4,62 @@ use tokio_util::sync::CancellationToken;
 use thiserror::Error;
 use serde::{Deserialize, Serialize};
 
+use crate::config::ConnectionConfig;
+
 pub type StreamId = u64;
 
 #[derive(Debug, Clone)]
 pub struct StreamingConfig {
     pub max_concurrent_streams: usize,
     pub requests_per_second: f64,
-    pub connection_timeout: std::time::Duration,
     pub request_timeout: std::time::Duration,
-    pub pool_max_idle_per_host: usize,
+    pub connection: ConnectionConfig,
+    /// Optional per-model limits layered on top of the backend-wide limiter,
+    /// keyed by backend name and model
+    pub model_rate_limits: HashMap<(String, String), ModelRateLimit>,
+    pub retry: RetryPolicy,
+}
+
+/// Exponential backoff with full jitter for retried stream requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: std::time::Duration,
+    pub max_delay: std::time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: std::time::Duration::from_millis(100),
+            max_delay: std::time::Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Upper bound for the delay before retry number `attempt` (zero-based):
+    /// `base_delay * 2^attempt`, capped at `max_delay`
+    pub fn backoff_ceiling(&self, attempt: u32) -> std::time::Duration {
+        self.base_delay
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    /// A random delay between zero and the ceiling, so concurrent streams
+    /// don't retry in lockstep
+    pub fn delay_for(&self, attempt: u32) -> std::time::Duration {
+        let ceiling = self.backoff_ceiling(attempt);
+        ceiling.mul_f64(rand::random::<f64>())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelRateLimit {
+    pub max_concurrent: usize,
+    pub requests_per_second: f64,
 }
 
 #[derive(Debug, Error)]
@@ -23,6 +70,12 @@ pub enum StreamError {
     StreamNotFound(StreamId),
     #[error("Rate limit exceeded")]
     RateLimit,
+    #[error("Rate limit exceeded for model: {0}")]
+    ModelRateLimit(String),
+    #[error("Rate limit exceeded for backend: {0}")]
+    BackendRateLimit(String),
+    #[error("Too many concurrent requests (limit {0})")]
+    ConcurrencyLimit(usize),
     #[error("Request timeout")]
     Timeout,
     #[error("Serialization error: {0}")]
@@ -36,6 +89,18 @@ pub struct StreamToken {
     pub content: String,
     pub is_complete: bool,
     pub metadata: Option<TokenMetadata>,
+    #[serde(default)]
+    pub kind: TokenKind,
+}
+
+/// Whether a streamed token is part of the answer or the model's reasoning
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    #[default]
+    Answer,
+    /// Output from a thinking model's `message.thinking` field
+    Reasoning,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,12 +115,163 @@ pub struct StreamResponse {
     pub cancellation_token: CancellationToken,
 }
 
+impl StreamResponse {
+    /// Keep `slots` reserved until the stream delivers its final token, is
+    /// cancelled, or its receiver is dropped
+    pub fn holding(self, slots: RateLimitSlots) -> StreamResponse {
+        let StreamResponse { id, mut receiver, cancellation_token } = self;
+        let (sender, forwarded) = mpsc::unbounded_channel();
+        let token = cancellation_token.clone();
+
+        tokio::spawn(async move {
+            let _slots = slots;
+            loop {
+                tokio::select! {
+                    biased;
+                    _ = token.cancelled() => break,
+                    _ = sender.closed() => break,
+                    next = receiver.recv() => match next {
+                        Some(stream_token) => {
+                            let is_complete = stream_token.is_complete;
+                            if sender.send(stream_token).is_err() || is_complete {
+                                break;
+                            }
+                        }
+                        None => break,
+                    },
+                }
+            }
+        });
+
+        StreamResponse {
+            id,
+            receiver: forwarded,
+            cancellation_token,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatRequest {
     pub model: String,
     pub messages: Vec<Message>,
     pub stream: bool,
     pub options: Option<HashMap<String, serde_json::Value>>,
+    /// Constrains the output shape; sent as Ollama's top-level `format`
+    #[serde(rename = "format", default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Functions the model may call instead of answering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+}
+
+/// A function the model may call, in Ollama's `tools` format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tool {
+    /// Always `"function"`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: ToolFunction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolFunction {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments object
+    pub parameters: serde_json::Value,
+}
+
+impl Tool {
+    pub fn function(name: &str, description: &str, parameters: serde_json::Value) -> Self {
+        Self {
+            kind: "function".to_string(),
+            function: ToolFunction {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters,
+            },
+        }
+    }
+}
+
+/// A call the model asked for, with its arguments parsed from JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// The reply to a chat offered tools: plain text, or the calls to make
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolChatResponse {
+    Text(String),
+    ToolCalls(Vec<ToolCall>),
+}
+
+/// Structured output mode: any valid JSON, or JSON matching a schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "serde_json::Value", from = "serde_json::Value")]
+pub enum ResponseFormat {
+    /// `"format": "json"`
+    Json,
+    /// `"format": { ...JSON schema... }`
+    Schema(serde_json::Value),
+}
+
+impl From<ResponseFormat> for serde_json::Value {
+    fn from(format: ResponseFormat) -> Self {
+        match format {
+            ResponseFormat::Json => serde_json::Value::String("json".to_string()),
+            ResponseFormat::Schema(schema) => schema,
+        }
+    }
+}
+
+impl From<serde_json::Value> for ResponseFormat {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(mode) if mode == "json" => ResponseFormat::Json,
+            schema => ResponseFormat::Schema(schema),
+        }
+    }
+}
+
+/// Sampling parameters, serialized into the Ollama `options` object
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    /// Maximum number of tokens to generate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i32>,
+    /// Context window size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+}
+
+impl GenerationOptions {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Convert into a request `options` map, or `None` when nothing is set
+    pub fn to_options(&self) -> Option<HashMap<String, serde_json::Value>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(map)) => Some(map.into_iter().collect()),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,11 +282,114 @@ pub struct Message {
     pub images: Option<Vec<String>>,
 }
 
+type SharedRateLimiter = std::sync::Arc<std::sync::Mutex<RateLimiter>>;
+
 pub struct StreamingManager {
+    /// Used for backends without a client of their own
     client: reqwest::Client,
+    /// Built from each backend's config, with its proxy, headers and TLS
+    /// settings, by backend name
+    backend_clients: HashMap<String, reqwest::Client>,
     active_streams: HashMap<StreamId, CancellationToken>,
-    rate_limiter: RateLimiter,
+    rate_limiter: SharedRateLimiter,
+    /// Limits from each backend's `model_rate_limits`, by backend name and model
+    model_rate_limiters: HashMap<(String, String), SharedRateLimiter>,
+    /// Limits from each backend's `rate_limit` config, by backend name
+    backend_rate_limiters: HashMap<String, SharedRateLimiter>,
     next_stream_id: StreamId,
+    /// Used for backends without a policy of their own
+    retry: RetryPolicy,
+    /// From each backend's `retry_attempts` and `max_retry_delay`, by backend name
+    backend_retry_policies: HashMap<String, RetryPolicy>,
+}
+
+/// Accumulates raw bytes across network chunks and yields complete lines.
+///
+/// Bytes are only decoded once a full line is available, so multi-byte UTF-8
+/// sequences split across chunks are carried forward instead of being replaced.
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    buffer: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Append a chunk and return every newline-terminated line now available
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let mut line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            line.truncate(pos);
+            lines.push(Self::decode(line).trim_end_matches('\r').to_string());
+        }
+        lines
+    }
+
+    /// Take any remaining partial line at end of stream
+    pub fn finish(&mut self) -> Option<String> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        Some(Self::decode(std::mem::take(&mut self.buffer)))
+    }
+
+    fn decode(bytes: Vec<u8>) -> String {
+        String::from_utf8(bytes).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Invalid UTF-8 in streamed line");
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        })
+    }
+}
+
+/// Detects stop sequences across token boundaries. Text that could be the start
+/// of a stop sequence is held back until it is known not to match.
+#[derive(Debug, Default)]
+pub struct StopSequenceFilter {
+    stop_sequences: Vec<String>,
+    pending: String,
+    holdback: usize,
+}
+
+impl StopSequenceFilter {
+    pub fn new(stop_sequences: Vec<String>) -> Self {
+        let stop_sequences: Vec<String> = stop_sequences.into_iter().filter(|s| !s.is_empty()).collect();
+        let holdback = stop_sequences.iter().map(|s| s.len()).max().unwrap_or(1) - 1;
+
+        Self {
+            stop_sequences,
+            pending: String::new(),
+            holdback,
+        }
+    }
+
+    /// Feed new content. Returns the text that is safe to emit and whether a
+    /// stop sequence was hit; anything after the stop sequence is discarded.
+    pub fn push(&mut self, content: &str) -> (String, bool) {
+        self.pending.push_str(content);
+
+        let earliest_stop = self.stop_sequences
+            .iter()
+            .filter_map(|stop| self.pending.find(stop.as_str()))
+            .min();
+
+        if let Some(index) = earliest_stop {
+            self.pending.truncate(index);
+            return (std::mem::take(&mut self.pending), true);
+        }
+
+        let mut split = self.pending.len().saturating_sub(self.holdback);
+        while !self.pending.is_char_boundary(split) {
+            split -= 1;
+        }
+        let held = self.pending.split_off(split);
+        (std::mem::replace(&mut self.pending, held), false)
+    }
+
+    /// Release any held-back text at the natural end of the stream
+    pub fn finish(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
 }
 
 pub struct RateLimiter {
@@ -85,11 +404,13 @@ pub struct RateLimiter {
 
 impl RateLimiter {
     pub fn new(max_concurrent: usize, requests_per_second: f64) -> Self {
+        // Rates under one request per second still allow a single request at a time
+        let max_tokens = requests_per_second.max(1.0);
         Self {
             max_concurrent,
             current_count: 0,
-            tokens: requests_per_second,
-            max_tokens: requests_per_second,
+            tokens: max_tokens,
+            max_tokens,
             refill_rate: requests_per_second,
             last_refill: std::time::Instant::now(),
         }
@@ -110,6 +431,15 @@ impl RateLimiter {
         }
     }
 
+    /// Change the limits in place, keeping track of requests already in flight
+    pub fn reconfigure(&mut self, max_concurrent: usize, requests_per_second: f64) {
+        self.refill_tokens();
+        self.max_concurrent = max_concurrent;
+        self.max_tokens = requests_per_second.max(1.0);
+        self.refill_rate = requests_per_second;
+        self.tokens = self.tokens.min(self.max_tokens);
+    }
+
     pub fn release(&mut self) {
         if self.current_count > 0 {
             self.current_count -= 1;
@@ -137,6 +467,68 @@ impl RateLimiter {
     }
 }
 
+/// Slots reserved in one or more rate limiters, released when dropped. Keep
+/// it alive for as long as the request or stream it was acquired for.
+#[derive(Default)]
+pub struct RateLimitSlots {
+    limiters: Vec<SharedRateLimiter>,
+}
+
+impl RateLimitSlots {
+    /// Reserve a slot in `limiter`, or `None` if it is at its limit
+    fn acquire(limiter: &SharedRateLimiter) -> Option<Self> {
+        limiter.lock().unwrap().acquire().then(|| Self {
+            limiters: vec![limiter.clone()],
+        })
+    }
+
+    /// Hold `other`'s slots as well as these
+    pub fn merge(mut self, mut other: RateLimitSlots) -> Self {
+        self.limiters.append(&mut other.limiters);
+        self
+    }
+}
+
+impl Drop for RateLimitSlots {
+    fn drop(&mut self) {
+        for limiter in &self.limiters {
+            limiter.lock().unwrap().release();
+        }
+    }
+}
+
+/// The backend and model limiters that apply to one request, detached from
+/// the `StreamingManager` so they can be used without borrowing it
+#[derive(Clone, Default)]
+pub struct RequestRateLimits {
+    backend: Option<(String, SharedRateLimiter)>,
+    model: Option<(String, SharedRateLimiter)>,
+}
+
+impl RequestRateLimits {
+    /// Reserve a slot in both limiters, or in neither. The model limiter is
+    /// checked first so a model at its limit doesn't spend a backend token.
+    pub fn acquire(&self) -> Result<RateLimitSlots, StreamError> {
+        if let Some((model, limiter)) = &self.model {
+            if !limiter.lock().unwrap().can_proceed() {
+                return Err(StreamError::ModelRateLimit(model.clone()));
+            }
+        }
+
+        let mut slots = RateLimitSlots::default();
+        if let Some((backend, limiter)) = &self.backend {
+            slots = RateLimitSlots::acquire(limiter)
+                .ok_or_else(|| StreamError::BackendRateLimit(backend.clone()))?;
+        }
+        if let Some((model, limiter)) = &self.model {
+            let model_slot = RateLimitSlots::acquire(limiter)
+                .ok_or_else(|| StreamError::ModelRateLimit(model.clone()))?;
+            slots = slots.merge(model_slot);
+        }
+        Ok(slots)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RateLimiterStats {
     pub current_concurrent: usize,
@@ -145,43 +537,190 @@ pub struct RateLimiterStats {
     pub max_tokens: f64,
 }
 
+/// Bounds the number of backend requests in flight. Clones share the same
+/// permits, so one limiter can be handed to several wrappers.
+#[derive(Debug, Clone)]
+pub struct RequestLimiter {
+    semaphore: std::sync::Arc<tokio::sync::Semaphore>,
+    max_concurrent: usize,
+    reject_when_saturated: bool,
+}
+
+impl RequestLimiter {
+    pub fn new(max_concurrent: usize, reject_when_saturated: bool) -> Self {
+        Self {
+            semaphore: std::sync::Arc::new(tokio::sync::Semaphore::new(max_concurrent)),
+            max_concurrent,
+            reject_when_saturated,
+        }
+    }
+
+    /// Wait for a free slot, or fail immediately with `ConcurrencyLimit` when
+    /// the limiter is configured to reject instead of queueing.
+    pub async fn acquire(&self) -> Result<tokio::sync::OwnedSemaphorePermit, StreamError> {
+        let semaphore = self.semaphore.clone();
+        if self.reject_when_saturated {
+            semaphore.try_acquire_owned()
+                .map_err(|_| StreamError::ConcurrencyLimit(self.max_concurrent))
+        } else {
+            semaphore.acquire_owned()
+                .await
+                .map_err(|_| StreamError::ConcurrencyLimit(self.max_concurrent))
+        }
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.max_concurrent - self.semaphore.available_permits()
+    }
+}
+
 impl StreamingManager {
     pub fn new(max_concurrent_streams: usize) -> Self {
         Self::with_config(StreamingConfig {
             max_concurrent_streams,
             requests_per_second: 10.0,
-            connection_timeout: std::time::Duration::from_secs(10),
             request_timeout: std::time::Duration::from_secs(30),
-            pool_max_idle_per_host: 10,
+            connection: ConnectionConfig::default(),
+            model_rate_limits: HashMap::new(),
+            retry: RetryPolicy::default(),
         })
     }
 
     pub fn with_config(config: StreamingConfig) -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(config.request_timeout)
-            .connect_timeout(config.connection_timeout)
-            .pool_max_idle_per_host(config.pool_max_idle_per_host)
-            .pool_idle_timeout(std::time::Duration::from_secs(90))
-            .tcp_keepalive(std::time::Duration::from_secs(60))
-            .build()
+        let client = config.connection
+            .build_client(Some(config.request_timeout))
             .expect("Failed to create HTTP client");
 
+        let model_rate_limiters = config.model_rate_limits
+            .iter()
+            .map(|(key, limit)| (key.clone(), Self::shared_limiter(limit)))
+            .collect();
+
         Self {
             client,
+            backend_clients: HashMap::new(),
             active_streams: HashMap::new(),
-            rate_limiter: RateLimiter::new(config.max_concurrent_streams, config.requests_per_second),
+            rate_limiter: std::sync::Arc::new(std::sync::Mutex::new(
+                RateLimiter::new(config.max_concurrent_streams, config.requests_per_second),
+            )),
+            model_rate_limiters,
+            backend_rate_limiters: HashMap::new(),
             next_stream_id: 1,
+            retry: config.retry,
+            backend_retry_policies: HashMap::new(),
         }
     }
 
+    fn shared_limiter(limit: &ModelRateLimit) -> SharedRateLimiter {
+        std::sync::Arc::new(std::sync::Mutex::new(
+            RateLimiter::new(limit.max_concurrent, limit.requests_per_second),
+        ))
+    }
+
+    pub fn set_model_rate_limit(&mut self, backend: &str, model: &str, limit: ModelRateLimit) {
+        self.model_rate_limiters.insert(
+            (backend.to_string(), model.to_string()),
+            Self::shared_limiter(&limit),
+        );
+    }
+
+    pub fn remove_model_rate_limit(&mut self, backend: &str, model: &str) {
+        self.model_rate_limiters.remove(&(backend.to_string(), model.to_string()));
+    }
+
+    /// Replace the per-model limits. Limiters for models that keep a limit are
+    /// reconfigured in place so slots held by running requests are still released.
+    pub fn update_model_rate_limits(&mut self, limits: &HashMap<(String, String), ModelRateLimit>) {
+        self.model_rate_limiters.retain(|key, _| limits.contains_key(key));
+        for (key, limit) in limits {
+            match self.model_rate_limiters.get(key) {
+                Some(limiter) => limiter.lock().unwrap().reconfigure(limit.max_concurrent, limit.requests_per_second),
+                None => {
+                    self.model_rate_limiters.insert(key.clone(), Self::shared_limiter(limit));
+                }
+            }
+        }
+    }
+
+    /// Limit requests sent to `backend`, or lift its limit with `None`. An
+    /// existing limiter is reconfigured in place, keeping its in-flight count.
+    pub fn set_backend_rate_limit(&mut self, backend: &str, limit: Option<ModelRateLimit>) {
+        match (limit, self.backend_rate_limiters.get(backend)) {
+            (Some(limit), Some(limiter)) => limiter.lock().unwrap().reconfigure(limit.max_concurrent, limit.requests_per_second),
+            (Some(limit), None) => {
+                self.backend_rate_limiters.insert(backend.to_string(), Self::shared_limiter(&limit));
+            }
+            (None, _) => {
+                self.backend_rate_limiters.remove(backend);
+            }
+        }
+    }
+
+    /// The limiters a request for `model` on `backend` must get past
+    pub fn rate_limits_for(&self, backend: &str, model: &str) -> RequestRateLimits {
+        RequestRateLimits {
+            backend: self.backend_rate_limiters
+                .get(backend)
+                .map(|limiter| (backend.to_string(), limiter.clone())),
+            model: self.model_rate_limiters
+                .get(&(backend.to_string(), model.to_string()))
+                .map(|limiter| (model.to_string(), limiter.clone())),
+        }
+    }
+
+    /// Retry stream requests to `backend` with `policy` instead of the default
+    pub fn set_backend_retry_policy(&mut self, backend: &str, policy: RetryPolicy) {
+        self.backend_retry_policies.insert(backend.to_string(), policy);
+    }
+
+    /// Send stream requests to `backend` through `client` instead of the default
+    pub fn set_backend_client(&mut self, backend: &str, client: reqwest::Client) {
+        self.backend_clients.insert(backend.to_string(), client);
+    }
+
+    /// Reserve a slot in `backend`'s limiter, if it has one
+    pub fn acquire_backend_slot(&self, backend: &str) -> Result<RateLimitSlots, StreamError> {
+        match self.backend_rate_limiters.get(backend) {
+            Some(limiter) => RateLimitSlots::acquire(limiter)
+                .ok_or_else(|| StreamError::BackendRateLimit(backend.to_string())),
+            None => Ok(RateLimitSlots::default()),
+        }
+    }
+
+    /// Reserve a slot in the limiter for `model` on `backend`, if one is
+    /// configured. Models without a configured limit always succeed.
+    pub fn acquire_model_slot(&self, backend: &str, model: &str) -> Result<RateLimitSlots, StreamError> {
+        match self.model_rate_limiters.get(&(backend.to_string(), model.to_string())) {
+            Some(limiter) => RateLimitSlots::acquire(limiter)
+                .ok_or_else(|| StreamError::ModelRateLimit(model.to_string())),
+            None => Ok(RateLimitSlots::default()),
+        }
+    }
+
+    /// Reserve a request slot for `model` in both the manager-wide limiter and
+    /// the model's own limiter. Nothing is consumed unless both allow the request.
+    pub fn acquire_request_slot(&self, backend: &str, model: &str) -> Result<RateLimitSlots, StreamError> {
+        if let Some(limiter) = self.model_rate_limiters.get(&(backend.to_string(), model.to_string())) {
+            if !limiter.lock().unwrap().can_proceed() {
+                return Err(StreamError::ModelRateLimit(model.to_string()));
+            }
+        }
+
+        let slots = RateLimitSlots::acquire(&self.rate_limiter).ok_or(StreamError::RateLimit)?;
+        Ok(slots.merge(self.acquire_model_slot(backend, model)?))
+    }
+
     pub async fn create_stream(
         &mut self,
+        backend: &str,
         request: ChatRequest,
         base_url: &str,
     ) -> Result<StreamResponse, StreamError> {
-        if !self.rate_limiter.acquire() {
-            return Err(StreamError::RateLimit);
-        }
+        let slots = self.acquire_request_slot(backend, &request.model)?;
 
         let stream_id = self.next_stream_id;
         self.next_stream_id += 1;
@@ -193,13 +732,15 @@ impl StreamingManager {
         self.active_streams.insert(stream_id, cancellation_token.clone());
 
         // Spawn the streaming task
-        let client = self.client.clone();
+        let client = self.backend_clients.get(backend).unwrap_or(&self.client).clone();
         let url = format!("{}/api/chat", base_url);
         let token = cancellation_token.clone();
+        let retry = self.backend_retry_policies.get(backend).copied().unwrap_or(self.retry);
 
-        
+        // The task holds the slots until the stream finishes or is cancelled
         tokio::spawn(async move {
-            let result = Self::stream_chat(client, url, request, sender, token).await;
+            let _slots = slots;
+            let result = Self::stream_chat(client, url, request, retry, sender, token).await;
             if let Err(e) = result {
                 eprintln!("Stream error: {}", e);
             }
@@ -216,68 +757,217 @@ impl StreamingManager {
         client: reqwest::Client,
         url: String,
         request: ChatRequest,
+        retry: RetryPolicy,
         sender: mpsc::UnboundedSender<StreamToken>,
         cancellation_token: CancellationToken,
     ) -> Result<(), StreamError> {
-        use futures_util::StreamExt;
+        let response = Self::send_request_with_retry(&client, &url, &request, &retry).await?;
 
-        let response = Self::send_request_with_retry(&client, &url, &request, 3).await?;
+        Self::forward_stream(response.bytes_stream(), Self::stop_sequences(&request), sender, cancellation_token).await
+    }
 
-        let mut stream = response.bytes_stream();
+    /// Stop sequences requested through the `stop` option
+    pub(crate) fn stop_sequences(request: &ChatRequest) -> Vec<String> {
+        request.options
+            .as_ref()
+            .and_then(|options| options.get("stop"))
+            .and_then(|stop| stop.as_array())
+            .map(|stops| stops.iter().filter_map(|s| s.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    }
 
-        while let Some(chunk_result) = stream.next().await {
-            // Check for cancellation
-            if cancellation_token.is_cancelled() {
-                break;
-            }
+    /// Parse a raw JSONL byte stream into tokens, honouring stop sequences
+    pub(crate) async fn forward_stream<S, B, E>(
+        stream: S,
+        stop_sequences: Vec<String>,
+        sender: mpsc::UnboundedSender<StreamToken>,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), StreamError>
+    where
+        S: futures_util::Stream<Item = Result<B, E>> + Unpin,
+        B: AsRef<[u8]>,
+        StreamError: From<E>,
+    {
+        Self::forward_lines(stream, Self::parse_stream_line, stop_sequences, sender, cancellation_token).await
+    }
 
-            let chunk = chunk_result?;
-            let chunk_str = String::from_utf8_lossy(&chunk);
+    /// Like `forward_stream`, for an OpenAI-style server-sent event stream of
+    /// `chat.completion.chunk` objects
+    pub(crate) async fn forward_sse_stream<S, B, E>(
+        stream: S,
+        stop_sequences: Vec<String>,
+        sender: mpsc::UnboundedSender<StreamToken>,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), StreamError>
+    where
+        S: futures_util::Stream<Item = Result<B, E>> + Unpin,
+        B: AsRef<[u8]>,
+        StreamError: From<E>,
+    {
+        Self::forward_lines(stream, Self::parse_sse_line, stop_sequences, sender, cancellation_token).await
+    }
 
-            // Parse streaming response (assuming JSONL format)
-            for line in chunk_str.lines() {
-                if line.trim().is_empty() {
-                    continue;
-                }
+    async fn forward_lines<S, B, E>(
+        mut stream: S,
+        parse_line: fn(&str) -> Option<StreamToken>,
+        stop_sequences: Vec<String>,
+        sender: mpsc::UnboundedSender<StreamToken>,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), StreamError>
+    where
+        S: futures_util::Stream<Item = Result<B, E>> + Unpin,
+        B: AsRef<[u8]>,
+        StreamError: From<E>,
+    {
+        use futures_util::StreamExt;
 
-                if let Ok(response) = serde_json::from_str::<serde_json::Value>(line) {
-                    if let Some(content) = response.get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_str()) 
-                    {
-                        let is_complete = response.get("done")
-                            .and_then(|d| d.as_bool())
-                            .unwrap_or(false);
-
-                        let token = StreamToken {
-                            content: content.to_string(),
-                            is_complete,
-                            metadata: Some(TokenMetadata {
-                                timestamp: chrono::Utc::now(),
-                                token_count: None,
-                            }),
-                        };
-
-                        if sender.send(token).is_err() {
-                            // Receiver dropped, stop streaming
-                            break;
-                        }
+        let mut line_buffer = LineBuffer::default();
+        let mut stop_filter = StopSequenceFilter::new(stop_sequences);
 
-                        if is_complete {
-                            break;
-                        }
+        loop {
+            // Cancellation wins over pending data, and is noticed even while the
+            // backend is silent. Returning drops the body, aborting the request.
+            let chunk_result = tokio::select! {
+                biased;
+                _ = cancellation_token.cancelled() => return Ok(()),
+                chunk = stream.next() => match chunk {
+                    Some(chunk) => chunk,
+                    None => break,
+                },
+            };
+
+            let chunk = chunk_result?;
+
+            // Parse streaming response (JSONL format, lines may span chunks)
+            for line in line_buffer.push(chunk.as_ref()) {
+                if cancellation_token.is_cancelled() {
+                    return Ok(());
+                }
+                if let Some(token) = parse_line(&line) {
+                    if Self::forward_token(token, &mut stop_filter, &sender, &cancellation_token) {
+                        return Ok(());
                     }
                 }
             }
         }
 
+        // Flush a trailing line that was not newline-terminated
+        if let Some(token) = line_buffer.finish().and_then(|line| parse_line(&line)) {
+            Self::forward_token(token, &mut stop_filter, &sender, &cancellation_token);
+        }
+
         Ok(())
     }
 
+    /// Send a token through the stop filter. Returns `true` once streaming should end.
+    fn forward_token(
+        mut token: StreamToken,
+        stop_filter: &mut StopSequenceFilter,
+        sender: &mpsc::UnboundedSender<StreamToken>,
+        cancellation_token: &CancellationToken,
+    ) -> bool {
+        // Stop sequences only apply to the answer, never to reasoning
+        if token.kind == TokenKind::Reasoning && !token.is_complete {
+            return sender.send(token).is_err();
+        }
+
+        let (content, stopped) = stop_filter.push(&token.content);
+        token.content = content;
+
+        if stopped {
+            // Trim the overshoot, finish the stream and abort the request
+            token.is_complete = true;
+            let _ = sender.send(token);
+            cancellation_token.cancel();
+            return true;
+        }
+
+        if token.is_complete {
+            token.content.push_str(&stop_filter.finish());
+        } else if token.content.is_empty() {
+            // Everything is held back while a stop sequence may be forming
+            return false;
+        }
+
+        let is_complete = token.is_complete;
+        // Receiver dropped or stream finished
+        sender.send(token).is_err() || is_complete
+    }
+
+    fn parse_stream_line(line: &str) -> Option<StreamToken> {
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        let response = serde_json::from_str::<serde_json::Value>(line).ok()?;
+        let message = response.get("message");
+        let content = message
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_str())?;
+        let thinking = message
+            .and_then(|m| m.get("thinking"))
+            .and_then(|t| t.as_str())
+            .unwrap_or_default();
+
+        // Thinking models stream reasoning with empty content before the answer
+        let (content, kind) = if content.is_empty() && !thinking.is_empty() {
+            (thinking, TokenKind::Reasoning)
+        } else {
+            (content, TokenKind::Answer)
+        };
+
+        let is_complete = response.get("done")
+            .and_then(|d| d.as_bool())
+            .unwrap_or(false);
+
+        Some(StreamToken {
+            content: content.to_string(),
+            is_complete,
+            metadata: Some(TokenMetadata {
+                timestamp: chrono::Utc::now(),
+                token_count: None,
+            }),
+            kind,
+        })
+    }
+
+    /// One `data:` line of a chat completion event stream. `[DONE]` and a
+    /// chunk with a `finish_reason` both end the stream.
+    fn parse_sse_line(line: &str) -> Option<StreamToken> {
+        let data = line.strip_prefix("data:")?.trim();
+        let metadata = Some(TokenMetadata {
+            timestamp: chrono::Utc::now(),
+            token_count: None,
+        });
+        if data == "[DONE]" {
+            return Some(StreamToken {
+                content: String::new(),
+                is_complete: true,
+                metadata,
+                kind: TokenKind::Answer,
+            });
+        }
+
+        let chunk = serde_json::from_str::<serde_json::Value>(data).ok()?;
+        let choice = chunk.get("choices")?.get(0)?;
+        let content = choice
+            .get("delta")
+            .and_then(|d| d.get("content"))
+            .and_then(|c| c.as_str())
+            .unwrap_or_default();
+        let is_complete = choice.get("finish_reason").is_some_and(|r| !r.is_null());
+
+        Some(StreamToken {
+            content: content.to_string(),
+            is_complete,
+            metadata,
+            kind: TokenKind::Answer,
+        })
+    }
+
     pub async fn cancel_stream(&mut self, id: StreamId) -> Result<(), StreamError> {
         if let Some(token) = self.active_streams.remove(&id) {
             token.cancel();
-            self.rate_limiter.release();
             Ok(())
         } else {
             Err(StreamError::StreamNotFound(id))
@@ -292,21 +982,19 @@ impl StreamingManager {
         client: &reqwest::Client,
         url: &str,
         request: &ChatRequest,
-        max_retries: u32,
+        retry: &RetryPolicy,
     ) -> Result<reqwest::Response, StreamError> {
         let mut attempt = 0;
-        let mut delay = std::time::Duration::from_millis(100);
 
         loop {
             match client.post(url).json(request).send().await {
                 Ok(response) => {
                     if response.status().is_success() {
                         return Ok(response);
-                    } else if response.status().is_server_error() && attempt < max_retries {
+                    } else if response.status().is_server_error() && attempt < retry.max_retries {
                         // Retry on server errors
+                        tokio::time::sleep(retry.delay_for(attempt)).await;
                         attempt += 1;
-                        tokio::time::sleep(delay).await;
-                        delay *= 2; // Exponential backoff
                         continue;
                     } else {
                         return Err(StreamError::Connection(format!(
@@ -315,11 +1003,10 @@ impl StreamingManager {
                         )));
                     }
                 }
-                Err(_e) if attempt < max_retries => {
+                Err(_e) if attempt < retry.max_retries => {
                     // Retry on connection errors
+                    tokio::time::sleep(retry.delay_for(attempt)).await;
                     attempt += 1;
-                    tokio::time::sleep(delay).await;
-                    delay *= 2; // Exponential backoff
                     continue;
                 }
                 Err(e) => return Err(StreamError::Http(e)),
@@ -328,20 +1015,40 @@ impl StreamingManager {
     }
 
     pub fn get_rate_limiter_stats(&mut self) -> RateLimiterStats {
-        self.rate_limiter.get_stats()
+        self.rate_limiter.lock().unwrap().get_stats()
+    }
+
+    fn refreshed_stats(limiter: &SharedRateLimiter) -> RateLimiterStats {
+        let mut limiter = limiter.lock().unwrap();
+        limiter.refill_tokens();
+        limiter.get_stats()
+    }
+
+    pub fn get_backend_rate_limiter_stats(&mut self) -> HashMap<String, RateLimiterStats> {
+        self.backend_rate_limiters
+            .iter()
+            .map(|(backend, limiter)| (backend.clone(), Self::refreshed_stats(limiter)))
+            .collect()
+    }
+
+    /// Per-model limiter stats, keyed by backend name and model
+    pub fn get_model_rate_limiter_stats(&mut self) -> HashMap<(String, String), RateLimiterStats> {
+        self.model_rate_limiters
+            .iter()
+            .map(|(key, limiter)| (key.clone(), Self::refreshed_stats(limiter)))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::time::{timeout, Duration};
 
     #[tokio::test]
     async fn test_streaming_manager_creation() {
         let manager = StreamingManager::new(5);
         assert_eq!(manager.get_active_streams().len(), 0);
-        let stats = manager.rate_limiter.get_stats();
+        let stats = manager.rate_limiter.lock().unwrap().get_stats();
         assert_eq!(stats.max_concurrent, 5);
     }
 
@@ -357,6 +1064,403 @@ mod tests {
         assert!(limiter.acquire()); // Should work again
     }
 
+    #[tokio::test]
+    async fn test_backend_rate_limit_from_requests_per_minute() {
+        let mut manager = StreamingManager::new(5);
+        let limit = crate::config::RateLimit { max_concurrent: 5, requests_per_minute: 60 };
+        manager.set_backend_rate_limit("ollama", Some((&limit).into()));
+
+        let stats = &manager.get_backend_rate_limiter_stats()["ollama"];
+        assert_eq!(stats.max_tokens, 1.0);
+
+        // 60 rpm is one token per second: a burst of one, then a wait
+        drop(manager.acquire_backend_slot("ollama").unwrap());
+        assert!(matches!(manager.acquire_backend_slot("ollama"), Err(StreamError::BackendRateLimit(_))));
+        assert!(manager.acquire_backend_slot("other").is_ok());
+
+        tokio::time::sleep(std::time::Duration::from_millis(1050)).await;
+        assert!(manager.acquire_backend_slot("ollama").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_request_limiter_bounds_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let limiter = RequestLimiter::new(3, false);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..12)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let running = running.clone();
+                let peak = peak.clone();
+                tokio::spawn(async move {
+                    let _permit = limiter.acquire().await.unwrap();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_request_limiter_rejects_when_saturated() {
+        let limiter = RequestLimiter::new(1, true);
+        let permit = limiter.acquire().await.unwrap();
+        assert!(matches!(limiter.acquire().await, Err(StreamError::ConcurrencyLimit(1))));
+
+        drop(permit);
+        assert!(limiter.acquire().await.is_ok());
+    }
+
+    #[test]
+    fn test_retry_delays_stay_within_bounds() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: std::time::Duration::from_millis(100),
+            max_delay: std::time::Duration::from_secs(2),
+        };
+
+        assert_eq!(policy.backoff_ceiling(0), std::time::Duration::from_millis(100));
+        assert_eq!(policy.backoff_ceiling(3), std::time::Duration::from_millis(800));
+        assert_eq!(policy.backoff_ceiling(5), std::time::Duration::from_secs(2));
+        assert_eq!(policy.backoff_ceiling(40), std::time::Duration::from_secs(2));
+
+        for attempt in 0..policy.max_retries {
+            let ceiling = policy.backoff_ceiling(attempt);
+            for _ in 0..50 {
+                assert!(policy.delay_for(attempt) <= ceiling);
+            }
+        }
+    }
+
+    #[test]
+    fn test_request_rate_limits_spare_backend_tokens_for_limited_models() {
+        let mut manager = StreamingManager::new(10);
+        manager.set_backend_rate_limit("remote", Some(ModelRateLimit {
+            max_concurrent: 10,
+            requests_per_second: 2.0,
+        }));
+        manager.set_model_rate_limit("remote", "expensive-model", ModelRateLimit {
+            max_concurrent: 1,
+            requests_per_second: 10.0,
+        });
+
+        let limits = manager.rate_limits_for("remote", "expensive-model");
+        let _held = limits.acquire().unwrap();
+        for _ in 0..3 {
+            assert!(matches!(limits.acquire(), Err(StreamError::ModelRateLimit(_))));
+        }
+
+        // The rejected attempts left the backend's remaining token alone
+        let backend = &manager.backend_rate_limiters["remote"];
+        assert!(backend.lock().unwrap().get_stats().available_tokens >= 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_model_rate_limits_are_independent() {
+        let mut manager = StreamingManager::new(10);
+        manager.set_model_rate_limit("ollama", "expensive-model", ModelRateLimit {
+            max_concurrent: 1,
+            requests_per_second: 10.0,
+        });
+
+        let expensive = manager.acquire_request_slot("ollama", "expensive-model").unwrap();
+        assert!(matches!(
+            manager.acquire_request_slot("ollama", "expensive-model"),
+            Err(StreamError::ModelRateLimit(_))
+        ));
+
+        // Other models, and the same model on other backends, are unaffected
+        let cheap = manager.acquire_request_slot("ollama", "cheap-model").unwrap();
+        let other_backend = manager.acquire_request_slot("remote", "expensive-model").unwrap();
+
+        drop(expensive);
+        let _expensive = manager.acquire_request_slot("ollama", "expensive-model").unwrap();
+
+        let stats = manager.get_model_rate_limiter_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[&("ollama".to_string(), "expensive-model".to_string())].current_concurrent, 1);
+        assert_eq!(manager.get_rate_limiter_stats().current_concurrent, 3);
+
+        drop((cheap, other_backend));
+        assert_eq!(manager.get_rate_limiter_stats().current_concurrent, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_holds_slots_until_final_token() {
+        let mut manager = StreamingManager::new(10);
+        manager.set_backend_rate_limit("ollama", Some(ModelRateLimit {
+            max_concurrent: 1,
+            requests_per_second: 10.0,
+        }));
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let stream = StreamResponse {
+            id: 1,
+            receiver,
+            cancellation_token: CancellationToken::new(),
+        };
+        let mut stream = stream.holding(manager.acquire_backend_slot("ollama").unwrap());
+
+        let token = |content: &str, is_complete| StreamToken {
+            content: content.to_string(),
+            is_complete,
+            metadata: None,
+            kind: TokenKind::Answer,
+        };
+        sender.send(token("Hello", false)).unwrap();
+        assert_eq!(stream.receiver.recv().await.unwrap().content, "Hello");
+        assert!(manager.acquire_backend_slot("ollama").is_err());
+
+        sender.send(token(" world", true)).unwrap();
+        assert!(stream.receiver.recv().await.unwrap().is_complete);
+        assert!(stream.receiver.recv().await.is_none());
+        assert_eq!(manager.get_backend_rate_limiter_stats()["ollama"].current_concurrent, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_stream_releases_slots() {
+        let mut manager = StreamingManager::new(10);
+        manager.set_backend_rate_limit("ollama", Some(ModelRateLimit {
+            max_concurrent: 1,
+            requests_per_second: 10.0,
+        }));
+
+        let (_sender, receiver) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let mut stream = StreamResponse { id: 1, receiver, cancellation_token: cancellation_token.clone() }
+            .holding(manager.acquire_backend_slot("ollama").unwrap());
+
+        cancellation_token.cancel();
+        assert!(stream.receiver.recv().await.is_none());
+        assert_eq!(manager.get_backend_rate_limiter_stats()["ollama"].current_concurrent, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_uses_backend_client() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = "{\"message\":{\"role\":\"assistant\",\"content\":\"ok\"},\"done\":true}\n";
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_lowercase()
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-route", reqwest::header::HeaderValue::from_static("team-a"));
+        let mut manager = StreamingManager::new(10);
+        manager.set_backend_client("gateway", reqwest::Client::builder().default_headers(headers).build().unwrap());
+
+        let request = ChatRequest {
+            model: "llama3".to_string(),
+            messages: Vec::new(),
+            stream: true,
+            options: None,
+            response_format: None,
+            tools: None,
+        };
+        let mut stream = manager.create_stream("gateway", request, &base_url).await.unwrap();
+        assert_eq!(stream.receiver.recv().await.unwrap().content, "ok");
+        assert!(server.await.unwrap().contains("\r\nx-route: team-a\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_forwards_deltas_until_done() {
+        let events = [
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\"},\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hel\"},\"finish_reason\":null}]}\n\ndata: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},",
+            "\"finish_reason\":null}]}\n\n: keep-alive\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n",
+        ];
+        let stream = futures_util::stream::iter(events.map(|e| Ok::<_, StreamError>(e.as_bytes())));
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        StreamingManager::forward_sse_stream(stream, Vec::new(), sender, CancellationToken::new()).await.unwrap();
+
+        let mut content = String::new();
+        let mut completed = 0;
+        while let Some(token) = receiver.recv().await {
+            content.push_str(&token.content);
+            completed += token.is_complete as usize;
+        }
+        assert_eq!(content, "Hello");
+        assert_eq!(completed, 1);
+    }
+
+    #[test]
+    fn test_line_buffer_reassembles_split_json() {
+        let line = "{\"message\":{\"role\":\"assistant\",\"content\":\"Hello world\"},\"done\":false}\n";
+        let (first, second) = line.as_bytes().split_at(23);
+
+        let mut buffer = LineBuffer::default();
+        assert!(buffer.push(first).is_empty());
+
+        let lines = buffer.push(second);
+        assert_eq!(lines.len(), 1);
+
+        let token = StreamingManager::parse_stream_line(&lines[0]).unwrap();
+        assert_eq!(token.content, "Hello world");
+        assert!(!token.is_complete);
+        assert!(buffer.finish().is_none());
+    }
+
+    #[test]
+    fn test_line_buffer_flushes_trailing_line() {
+        let mut buffer = LineBuffer::default();
+        let lines = buffer.push(b"{\"message\":{\"content\":\"a\"}}\n{\"message\":{\"content\":\"b\"},\"done\":true}");
+        assert_eq!(lines.len(), 1);
+
+        let trailing = buffer.finish().unwrap();
+        let token = StreamingManager::parse_stream_line(&trailing).unwrap();
+        assert_eq!(token.content, "b");
+        assert!(token.is_complete);
+    }
+
+    #[test]
+    fn test_line_buffer_handles_split_utf8() {
+        let line = "{\"message\":{\"content\":\"hi 🦀\"},\"done\":true}\n";
+        let emoji_start = line.find('🦀').unwrap();
+        let (first, second) = line.as_bytes().split_at(emoji_start + 2);
+
+        let mut buffer = LineBuffer::default();
+        assert!(buffer.push(first).is_empty());
+        let lines = buffer.push(second);
+
+        let token = StreamingManager::parse_stream_line(&lines[0]).unwrap();
+        assert_eq!(token.content, "hi 🦀");
+        assert!(!token.content.contains('\u{FFFD}'));
+    }
+
+    #[tokio::test]
+    async fn test_stop_sequence_truncates_stream() {
+        let lines = [
+            "{\"message\":{\"content\":\"Hello wo\"},\"done\":false}\n",
+            "{\"message\":{\"content\":\"rld#\"},\"done\":false}\n",
+            "{\"message\":{\"content\":\"## and more\"},\"done\":false}\n",
+            "{\"message\":{\"content\":\" never sent\"},\"done\":true}\n",
+        ];
+        let chunks: Vec<Result<Vec<u8>, StreamError>> = lines.iter().map(|l| Ok(l.as_bytes().to_vec())).collect();
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        StreamingManager::forward_stream(
+            futures_util::stream::iter(chunks),
+            vec!["###".to_string()],
+            sender,
+            cancellation_token.clone(),
+        ).await.unwrap();
+
+        let mut tokens = Vec::new();
+        while let Some(token) = receiver.recv().await {
+            tokens.push(token);
+        }
+
+        let content: String = tokens.iter().map(|t| t.content.as_str()).collect();
+        assert_eq!(content, "Hello world");
+        assert!(tokens.last().unwrap().is_complete);
+        assert!(cancellation_token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_stream_mid_flight() {
+        let (chunk_sender, chunk_receiver) = mpsc::unbounded_channel::<Result<Vec<u8>, StreamError>>();
+        let chunks = Box::pin(futures_util::stream::unfold(chunk_receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        }));
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        let task = tokio::spawn(StreamingManager::forward_stream(chunks, Vec::new(), sender, cancellation_token.clone()));
+
+        chunk_sender.send(Ok(b"{\"message\":{\"content\":\"first\"},\"done\":false}\n".to_vec())).unwrap();
+        assert_eq!(receiver.recv().await.unwrap().content, "first");
+
+        // The backend goes quiet; cancelling must still end the stream right away
+        cancellation_token.cancel();
+        tokio::time::timeout(std::time::Duration::from_secs(1), task)
+            .await
+            .expect("stream did not stop after cancellation")
+            .unwrap()
+            .unwrap();
+
+        let _ = chunk_sender.send(Ok(b"{\"message\":{\"content\":\"second\"},\"done\":true}\n".to_vec()));
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_stop_sequence_filter_passthrough() {
+        let mut filter = StopSequenceFilter::new(vec![]);
+        assert_eq!(filter.push("abc"), ("abc".to_string(), false));
+
+        // Held-back text is released at the end of the stream
+        let mut filter = StopSequenceFilter::new(vec!["END".to_string()]);
+        assert_eq!(filter.push("the EN"), ("the ".to_string(), false));
+        assert_eq!(filter.finish(), "EN");
+    }
+
+    #[test]
+    fn test_parse_thinking_line() {
+        let line = r#"{"message":{"role":"assistant","content":"","thinking":"Checking units"},"done":false}"#;
+        let token = StreamingManager::parse_stream_line(line).unwrap();
+        assert_eq!(token.kind, TokenKind::Reasoning);
+        assert_eq!(token.content, "Checking units");
+
+        let line = r#"{"message":{"role":"assistant","content":"42"},"done":false}"#;
+        let token = StreamingManager::parse_stream_line(line).unwrap();
+        assert_eq!(token.kind, TokenKind::Answer);
+        assert_eq!(token.content, "42");
+
+        // Tokens serialized before reasoning support default to answers
+        let token: StreamToken = serde_json::from_str(r#"{"content":"hi","is_complete":true,"metadata":null}"#).unwrap();
+        assert_eq!(token.kind, TokenKind::Answer);
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_bypasses_stop_sequences() {
+        let lines = concat!(
+            r#"{"message":{"content":"","thinking":"maybe END here"},"done":false}"#, "\n",
+            r#"{"message":{"content":"answer END ignored"},"done":false}"#, "\n",
+        );
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let chunks = vec![Ok::<_, StreamError>(lines.as_bytes().to_vec())];
+
+        StreamingManager::forward_stream(
+            futures_util::stream::iter(chunks),
+            vec!["END".to_string()],
+            sender,
+            CancellationToken::new(),
+        ).await.unwrap();
+
+        let reasoning = receiver.recv().await.unwrap();
+        assert_eq!(reasoning.kind, TokenKind::Reasoning);
+        assert_eq!(reasoning.content, "maybe END here");
+
+        let answer = receiver.recv().await.unwrap();
+        assert_eq!(answer.kind, TokenKind::Answer);
+        assert_eq!(answer.content, "answer ");
+        assert!(answer.is_complete);
+    }
+
     #[tokio::test]
     async fn test_cancellation_token() {
         let token = CancellationToken::new();
@@ -371,6 +1475,7 @@ mod tests {
         let token = StreamToken {
             content: "Hello, world!".to_string(),
             is_complete: false,
+            kind: TokenKind::Answer,
             metadata: Some(TokenMetadata {
                 timestamp: chrono::Utc::now(),
                 token_count: Some(42),
@@ -384,6 +1489,66 @@ mod tests {
         assert_eq!(token.is_complete, deserialized.is_complete);
     }
 
+    #[test]
+    fn test_generation_options_serialization() {
+        assert!(GenerationOptions::default().to_options().is_none());
+
+        let options = GenerationOptions {
+            temperature: Some(0.2),
+            num_predict: Some(128),
+            seed: Some(42),
+            stop: vec!["###".to_string()],
+            ..Default::default()
+        };
+
+        let request = ChatRequest {
+            model: "test-model".to_string(),
+            messages: vec![],
+            stream: false,
+            options: options.to_options(),
+            response_format: None,
+            tools: None,
+        };
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["options"]["temperature"], 0.2);
+        assert_eq!(json["options"]["num_predict"], 128);
+        assert_eq!(json["options"]["seed"], 42);
+        assert_eq!(json["options"]["stop"][0], "###");
+        assert!(json["options"].get("top_p").is_none());
+        assert!(json["options"].get("num_ctx").is_none());
+    }
+
+    #[test]
+    fn test_response_format_serialization() {
+        let mut request = ChatRequest {
+            model: "test-model".to_string(),
+            messages: vec![],
+            stream: false,
+            options: None,
+            response_format: Some(ResponseFormat::Json),
+            tools: None,
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["format"], "json");
+        assert!(json.get("response_format").is_none());
+
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"city": {"type": "string"}},
+            "required": ["city"]
+        });
+        request.response_format = Some(ResponseFormat::Schema(schema.clone()));
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["format"], schema);
+
+        let parsed: ChatRequest = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.response_format, Some(ResponseFormat::Schema(schema)));
+
+        request.response_format = None;
+        assert!(serde_json::to_value(&request).unwrap().get("format").is_none());
+    }
+
     #[tokio::test]
     async fn test_chat_request_serialization() {
         let request = ChatRequest {
@@ -395,6 +1560,8 @@ mod tests {
             }],
             stream: true,
             options: None,
+            response_format: None,
+            tools: None,
         };
 
         let serialized = serde_json::to_string(&request).unwrap();
@@ -1,12 +1,16 @@
-use handlebars::{Handlebars, Helper, RenderContext, RenderError, HelperResult, Output, HelperDef};
+use handlebars::template::{HelperTemplate, Parameter, TemplateElement};
+use handlebars::{Handlebars, Helper, RenderContext, RenderError, HelperResult, Output, HelperDef, PathAndJson, ScopedJson};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Number, Value};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::time::SystemTime;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 use tokio::fs;
 
+use crate::streaming::Message;
+
 #[derive(Debug, Error)]
 pub enum TemplateError {
     #[error("Template not found: {0}")]
@@ -37,6 +41,16 @@ pub struct Template {
     pub parent_template: Option<String>,
     pub tags: Vec<String>,
     pub usage_examples: Vec<String>,
+    /// Worked examples sent as user/assistant turns ahead of the rendered prompt
+    #[serde(default)]
+    pub few_shot: Vec<FewShotExample>,
+}
+
+/// One example exchange shown to the model before the real prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FewShotExample {
+    pub input: String,
+    pub output: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +69,9 @@ pub enum VariableType {
     Boolean,
     Array,
     Object,
+    /// Accepts any JSON value; used for inferred references whose type
+    /// cannot be determined from the template alone
+    Any,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +82,13 @@ pub struct TemplateConfig {
     pub max_template_size: usize,
     pub max_render_time_ms: u64,
     pub allowed_helpers: Vec<String>,
+    /// Helpers and decorators templates may never invoke when sandboxed
+    #[serde(default = "default_denied_helpers")]
+    pub denied_helpers: Vec<String>,
+}
+
+fn default_denied_helpers() -> Vec<String> {
+    vec!["raw".to_string(), "log".to_string(), "inline".to_string()]
 }
 
 impl Default for TemplateConfig {
@@ -90,7 +114,19 @@ impl Default for TemplateConfig {
                 "contains".to_string(),
                 "eq".to_string(),
                 "gt".to_string(),
+                "lt".to_string(),
+                "gte".to_string(),
+                "lte".to_string(),
+                "add".to_string(),
+                "subtract".to_string(),
+                "multiply".to_string(),
+                "divide".to_string(),
+                "mod".to_string(),
+                "now".to_string(),
+                "date_format".to_string(),
+                "json".to_string(),
             ],
+            denied_helpers: default_denied_helpers(),
         }
     }
 }
@@ -153,12 +189,24 @@ impl TemplateStore {
         Ok(())
     }
 
+    /// Delete the persisted `{name}.json`, returning whether a file was removed
+    pub async fn delete_from_disk(&self, name: &str) -> Result<bool, TemplateError> {
+        if let Some(dir) = &self.template_dir {
+            let file_path = dir.join(format!("{}.json", name));
+            if file_path.exists() {
+                fs::remove_file(file_path).await?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     pub fn search_templates(&self, query: &str) -> Vec<&Template> {
         self.templates
             .values()
             .filter(|template| {
                 template.name.contains(query) ||
-                template.description.as_ref().map_or(false, |d| d.contains(query)) ||
+                template.description.as_ref().is_some_and(|d| d.contains(query)) ||
                 template.tags.iter().any(|tag| tag.contains(query))
             })
             .collect()
@@ -172,11 +220,45 @@ impl TemplateStore {
     }
 }
 
+/// Collects render output, failing writes once the deadline has passed so a
+/// render that was given up on stops instead of running to completion
+struct DeadlineWriter {
+    buffer: Vec<u8>,
+    deadline: Instant,
+}
+
+impl DeadlineWriter {
+    fn new(deadline: Instant) -> Self {
+        Self { buffer: Vec::new(), deadline }
+    }
+
+    fn is_expired(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+}
+
+impl std::io::Write for DeadlineWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.is_expired() {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "render time limit exceeded"));
+        }
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Template engine that can be shared across tasks. Templates and compiled
+/// Handlebars state live behind `RwLock`s so renders and listings run
+/// concurrently and only registrations take a write lock. Locks are always
+/// acquired store-first, then Handlebars.
 pub struct TemplateEngine {
-    handlebars: Handlebars<'static>,
-    template_store: TemplateStore,
+    handlebars: Arc<RwLock<Handlebars<'static>>>,
+    template_store: RwLock<TemplateStore>,
     config: TemplateConfig,
-    custom_helpers: HashMap<String, Box<dyn HelperDef + Send + Sync>>,
 }
 
 impl TemplateEngine {
@@ -211,18 +293,30 @@ impl TemplateEngine {
         if config.allowed_helpers.contains(&"contains".to_string()) {
             handlebars.register_helper("contains", Box::new(contains_helper));
         }
-        if config.allowed_helpers.contains(&"eq".to_string()) {
-            handlebars.register_helper("eq", Box::new(eq_helper));
+        if config.allowed_helpers.contains(&"now".to_string()) {
+            handlebars.register_helper("now", Box::new(now_helper));
+        }
+        if config.allowed_helpers.contains(&"date_format".to_string()) {
+            handlebars.register_helper("date_format", Box::new(date_format_helper));
         }
-        if config.allowed_helpers.contains(&"gt".to_string()) {
-            handlebars.register_helper("gt", Box::new(gt_helper));
+        if config.allowed_helpers.contains(&"json".to_string()) {
+            handlebars.register_helper("json", Box::new(json_helper));
+        }
+        for helper in comparison_helpers() {
+            if config.allowed_helpers.iter().any(|h| h == helper.name) {
+                handlebars.register_helper(helper.name, Box::new(helper));
+            }
+        }
+        for helper in arithmetic_helpers() {
+            if config.allowed_helpers.iter().any(|h| h == helper.name) {
+                handlebars.register_helper(helper.name, Box::new(helper));
+            }
         }
         
         Self {
-            handlebars,
-            template_store: TemplateStore::new(config.template_dir.clone()),
+            handlebars: Arc::new(RwLock::new(handlebars)),
+            template_store: RwLock::new(TemplateStore::new(config.template_dir.clone())),
             config,
-            custom_helpers: HashMap::new(),
         }
     }
 
@@ -230,8 +324,9 @@ impl TemplateEngine {
         Self::new(TemplateConfig::default())
     }
 
-    pub fn render(&mut self, template_name: &str, context: &Value) -> Result<String, TemplateError> {
-        let template = self.template_store
+    pub fn render(&self, template_name: &str, context: &Value) -> Result<String, TemplateError> {
+        let store = self.template_store.read().unwrap();
+        let template = store
             .get_template(template_name)
             .ok_or_else(|| TemplateError::NotFound(template_name.to_string()))?;
 
@@ -246,50 +341,97 @@ impl TemplateEngine {
         // Validate required variables
         self.validate_context(template, context)?;
 
-        // Handle template composition
-        let final_content = if let Some(parent_name) = &template.parent_template {
-            self.compose_template(template, parent_name)?
-        } else {
-            template.content.clone()
-        };
+        // Composed templates are rendered from their current parent content so
+        // edits to the parent are always picked up
+        if let Some(parent_name) = &template.parent_template {
+            let final_content = Self::compose_template(&store, template, parent_name)?;
+            if self.config.enable_sandboxing {
+                let context = context.clone();
+                return self.render_with_timeout(move |handlebars, out| {
+                    handlebars.render_template_to_write(&final_content, &context, out)
+                });
+            }
+            return self.handlebars.read().unwrap()
+                .render_template(&final_content, context)
+                .map_err(TemplateError::Rendering);
+        }
 
         // Register the template if not already registered
-        if !self.handlebars.has_template(template_name) {
-            self.handlebars
-                .register_template_string(template_name, &final_content)
+        if !self.handlebars.read().unwrap().has_template(template_name) {
+            self.handlebars.write().unwrap()
+                .register_template_string(template_name, &template.content)
                 .map_err(|e| TemplateError::Syntax(e.to_string()))?;
         }
 
         // Render with timeout if sandboxing is enabled
         let rendered = if self.config.enable_sandboxing {
-            self.render_with_timeout(template_name, context)?
+            let (name, context) = (template_name.to_string(), context.clone());
+            self.render_with_timeout(move |handlebars, out| handlebars.render_to_write(&name, &context, out))?
         } else {
-            self.handlebars.render(template_name, context)?
+            self.handlebars.read().unwrap().render(template_name, context)?
         };
 
         Ok(rendered)
     }
 
-    fn compose_template(&self, template: &Template, parent_name: &str) -> Result<String, TemplateError> {
-        let parent = self.template_store
+    fn compose_template(store: &TemplateStore, template: &Template, parent_name: &str) -> Result<String, TemplateError> {
+        let parent = store
             .get_template(parent_name)
             .ok_or_else(|| TemplateError::Composition(
                 format!("Parent template '{}' not found", parent_name)
             ))?;
 
-        // Simple composition: replace {{> content}} in parent with child content
-        let composed = parent.content.replace("{{> content}}", &template.content);
-        Ok(composed)
+        // Named child blocks fill matching `{{> name}}` slots in the parent;
+        // whatever the child has outside its blocks fills `{{> content}}`
+        let (mut blocks, remainder) = extract_blocks(&template.content)?;
+        blocks.entry("content".to_string()).or_insert(remainder);
+
+        Ok(fill_slots(&parent.content, &blocks))
     }
 
-    fn render_with_timeout(&self, template_name: &str, context: &Value) -> Result<String, TemplateError> {
-        // For now, just render normally. In a production system, you'd use tokio::time::timeout
-        // or a similar mechanism to enforce rendering timeouts
-        self.handlebars.render(template_name, context)
-            .map_err(TemplateError::Rendering)
+    /// Render on a separate thread, giving up after `max_render_time_ms` even
+    /// if the render never produces output. An abandoned render is stopped by
+    /// its `DeadlineWriter` at its next piece of output, so the registry lock
+    /// is only held beyond the limit by a helper that blocks.
+    fn render_with_timeout<F>(&self, render: F) -> Result<String, TemplateError>
+    where
+        F: FnOnce(&Handlebars<'static>, &mut DeadlineWriter) -> Result<(), RenderError> + Send + 'static,
+    {
+        let limit = Duration::from_millis(self.config.max_render_time_ms);
+        let deadline = Instant::now() + limit;
+        let timed_out = || TemplateError::Security(
+            format!("Template rendering exceeded {}ms time limit", self.config.max_render_time_ms)
+        );
+
+        let handlebars = Arc::clone(&self.handlebars);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut out = DeadlineWriter::new(deadline);
+            let result = render(&handlebars.read().unwrap(), &mut out);
+            let _ = sender.send((result, out));
+        });
+
+        let (result, out) = match receiver.recv_timeout(limit) {
+            Ok(finished) => finished,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => return Err(timed_out()),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(TemplateError::Rendering(RenderError::new("render thread panicked")));
+            }
+        };
+        if out.is_expired() {
+            return Err(timed_out());
+        }
+        result.map_err(TemplateError::Rendering)?;
+        String::from_utf8(out.buffer)
+            .map_err(|e| TemplateError::Rendering(RenderError::new(e.to_string())))
     }
 
-    pub fn register_template(&mut self, template: Template) -> Result<(), TemplateError> {
+    pub fn register_template(&self, mut template: Template) -> Result<(), TemplateError> {
+        // Templates created without declared variables get them inferred
+        if template.variables.is_empty() {
+            template.variables = self.extract_variables(&template.content);
+        }
+
         // Security validation
         if self.config.enable_sandboxing {
             self.validate_template_security(&template)?;
@@ -298,13 +440,15 @@ impl TemplateEngine {
         // Validate template syntax
         self.validate_template(&template.content)?;
         
+        let mut store = self.template_store.write().unwrap();
+
         // Register with Handlebars
-        self.handlebars
+        self.handlebars.write().unwrap()
             .register_template_string(&template.name, &template.content)
             .map_err(|e| TemplateError::Syntax(e.to_string()))?;
 
         // Store template
-        self.template_store.add_template(template);
+        store.add_template(template);
         
         Ok(())
     }
@@ -318,35 +462,81 @@ impl TemplateEngine {
             ));
         }
 
-        // Check for potentially dangerous patterns
-        let dangerous_patterns = [
-            "{{#raw}}",
-            "{{{{",
-            "}}}}",
-            "javascript:",
-            "<script",
-            "eval(",
-            "Function(",
-        ];
-
-        for pattern in &dangerous_patterns {
-            if template.content.contains(pattern) {
+        // Inspect the parsed template rather than its text, so prose that
+        // merely mentions code is fine and only real constructs are rejected
+        let compiled = handlebars::Template::compile(&template.content)
+            .map_err(|e| TemplateError::Syntax(self.format_template_error(&template.content, &e)))?;
+        self.check_elements(&compiled.elements)
+    }
+
+    fn check_elements(&self, elements: &[TemplateElement]) -> Result<(), TemplateError> {
+        for element in elements {
+            match element {
+                TemplateElement::HtmlExpression(_) => {
+                    return Err(TemplateError::Security(
+                        "Template contains unescaped triple-mustache output".to_string()
+                    ));
+                }
+                TemplateElement::Expression(helper) | TemplateElement::HelperBlock(helper) => {
+                    self.check_helper_name(&helper.name)?;
+                    self.check_parameters(helper.params.iter().chain(helper.hash.values()))?;
+                    for block in [&helper.template, &helper.inverse].into_iter().flatten() {
+                        self.check_elements(&block.elements)?;
+                    }
+                }
+                TemplateElement::DecoratorExpression(decorator)
+                | TemplateElement::DecoratorBlock(decorator) => {
+                    self.check_helper_name(&decorator.name)?;
+                    self.check_parameters(decorator.params.iter().chain(decorator.hash.values()))?;
+                    if let Some(block) = &decorator.template {
+                        self.check_elements(&block.elements)?;
+                    }
+                }
+                // Partials name other templates, not helpers
+                TemplateElement::PartialExpression(partial)
+                | TemplateElement::PartialBlock(partial) => {
+                    self.check_parameters(partial.params.iter().chain(partial.hash.values()))?;
+                    if let Some(block) = &partial.template {
+                        self.check_elements(&block.elements)?;
+                    }
+                }
+                TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn check_parameters<'a>(&self, params: impl Iterator<Item = &'a Parameter>) -> Result<(), TemplateError> {
+        for param in params {
+            if let Parameter::Subexpression(subexpression) = param {
+                self.check_elements(std::slice::from_ref(subexpression.as_element()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_helper_name(&self, name: &Parameter) -> Result<(), TemplateError> {
+        if let Some(name) = name.as_name() {
+            if self.config.denied_helpers.iter().any(|denied| denied == name) {
                 return Err(TemplateError::Security(
-                    format!("Template contains potentially dangerous pattern: {}", pattern)
+                    format!("Template invokes denied helper '{}'", name)
                 ));
             }
         }
-
         Ok(())
     }
 
-    pub fn list_templates(&self) -> Vec<&Template> {
-        self.template_store.list_templates()
+    pub fn list_templates(&self) -> Vec<Template> {
+        self.template_store.read().unwrap()
+            .list_templates()
+            .into_iter()
+            .cloned()
+            .collect()
     }
 
     pub fn validate_template(&self, content: &str) -> Result<(), TemplateError> {
         // Try to compile the template to check syntax
-        match self.handlebars.render_template(content, &Value::Object(serde_json::Map::new())) {
+        match handlebars::Template::compile(content) {
             Ok(_) => Ok(()),
             Err(e) => {
                 // Provide more detailed error information with line numbers
@@ -356,7 +546,89 @@ impl TemplateEngine {
         }
     }
 
-    fn format_template_error(&self, content: &str, error: &RenderError) -> String {
+    /// Infer the variables a template references from its parsed Handlebars
+    /// expressions.
+    ///
+    /// Plain references and helper arguments become required values of any
+    /// type, except arguments to the numeric helpers, which must be numbers.
+    /// `#each` targets are required arrays, `#with` targets required objects
+    /// and `#if`/`#unless` conditions optional. References inside `#each`/`#with`
+    /// blocks resolve against the block context and are skipped, as are
+    /// helpers, `this`, `@` data and `../` parent paths. A template that
+    /// doesn't compile yields no variables.
+    pub fn extract_variables(&self, content: &str) -> Vec<TemplateVariable> {
+        let mut variables = Vec::new();
+        if let Ok(compiled) = handlebars::Template::compile(content) {
+            self.collect_variables(&compiled.elements, &mut variables);
+        }
+        variables
+    }
+
+    fn collect_variables(&self, elements: &[TemplateElement], variables: &mut Vec<TemplateVariable>) {
+        for element in elements {
+            match element {
+                TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) => {
+                    if helper.params.is_empty() && helper.hash.is_empty() {
+                        // A bare name is a value unless it calls a helper, as `{{now}}` does
+                        let calls_helper = helper.name.as_name()
+                            .is_some_and(|name| self.config.allowed_helpers.iter().any(|h| h == name));
+                        if !calls_helper {
+                            note_parameter(&helper.name, VariableType::Any, true, variables);
+                        }
+                    } else {
+                        self.collect_arguments(helper, variables);
+                    }
+                }
+                TemplateElement::HelperBlock(helper) => {
+                    self.collect_arguments(helper, variables);
+                    // Inside #each/#with, bare names refer to the block context
+                    let scoped = matches!(helper.name.as_name(), Some("each" | "with"));
+                    if let Some(block) = helper.template.as_ref().filter(|_| !scoped) {
+                        self.collect_variables(&block.elements, variables);
+                    }
+                    if let Some(inverse) = &helper.inverse {
+                        self.collect_variables(&inverse.elements, variables);
+                    }
+                }
+                TemplateElement::DecoratorBlock(decorator) | TemplateElement::PartialBlock(decorator) => {
+                    if let Some(block) = &decorator.template {
+                        self.collect_variables(&block.elements, variables);
+                    }
+                }
+                TemplateElement::DecoratorExpression(_)
+                | TemplateElement::PartialExpression(_)
+                | TemplateElement::RawString(_)
+                | TemplateElement::Comment(_) => {}
+            }
+        }
+    }
+
+    /// Note the references among a helper's arguments, typed by the helper
+    fn collect_arguments(&self, helper: &HelperTemplate, variables: &mut Vec<TemplateVariable>) {
+        let name = helper.name.as_name().unwrap_or_default();
+        let is_numeric = comparison_helpers().iter().any(|h| h.name == name && matches!(h.cmp, Comparison::Numeric(_)))
+            || arithmetic_helpers().iter().any(|h| h.name == name);
+        let (var_type, required) = match name {
+            "if" | "unless" => (VariableType::Any, false),
+            "each" => (VariableType::Array, true),
+            "with" => (VariableType::Object, true),
+            _ if is_numeric => (VariableType::Number, true),
+            _ => (VariableType::Any, true),
+        };
+
+        for param in helper.params.iter().chain(helper.hash.values()) {
+            match param {
+                Parameter::Subexpression(subexpression) => {
+                    if let TemplateElement::Expression(inner) = subexpression.as_element() {
+                        self.collect_arguments(inner, variables);
+                    }
+                }
+                _ => note_parameter(param, var_type.clone(), required, variables),
+            }
+        }
+    }
+
+    fn format_template_error(&self, content: &str, error: &handlebars::TemplateError) -> String {
         let error_str = error.to_string();
         
         // Try to extract line information from the error
@@ -396,7 +668,7 @@ impl TemplateEngine {
         None
     }
 
-    pub fn register_helper<F>(&mut self, name: &str, helper: F) -> Result<(), TemplateError>
+    pub fn register_helper<F>(&self, name: &str, helper: F) -> Result<(), TemplateError>
     where
         F: HelperDef + Send + Sync + 'static,
     {
@@ -407,7 +679,7 @@ impl TemplateEngine {
             ));
         }
 
-        self.handlebars.register_helper(name, Box::new(helper));
+        self.handlebars.write().unwrap().register_helper(name, Box::new(helper));
         Ok(())
     }
 
@@ -426,15 +698,24 @@ impl TemplateEngine {
             parent_template: None,
             tags: Vec::new(),
             usage_examples: Vec::new(),
+            few_shot: Vec::new(),
         }
     }
 
-    pub fn search_templates(&self, query: &str) -> Vec<&Template> {
-        self.template_store.search_templates(query)
+    pub fn search_templates(&self, query: &str) -> Vec<Template> {
+        self.template_store.read().unwrap()
+            .search_templates(query)
+            .into_iter()
+            .cloned()
+            .collect()
     }
 
-    pub fn get_templates_by_tag(&self, tag: &str) -> Vec<&Template> {
-        self.template_store.get_templates_by_tag(tag)
+    pub fn get_templates_by_tag(&self, tag: &str) -> Vec<Template> {
+        self.template_store.read().unwrap()
+            .get_templates_by_tag(tag)
+            .into_iter()
+            .cloned()
+            .collect()
     }
 
     fn validate_context(&self, template: &Template, context: &Value) -> Result<(), TemplateError> {
@@ -467,6 +748,7 @@ impl TemplateEngine {
             VariableType::Boolean => value.is_boolean(),
             VariableType::Array => value.is_array(),
             VariableType::Object => value.is_object(),
+            VariableType::Any => true,
         };
 
         if !matches {
@@ -479,17 +761,66 @@ impl TemplateEngine {
         Ok(())
     }
 
-    pub fn render_with_defaults(&mut self, template_name: &str, mut context: Value) -> Result<String, TemplateError> {
-        let template = self.template_store
-            .get_template(template_name)
-            .ok_or_else(|| TemplateError::NotFound(template_name.to_string()))?;
+    /// Dry-run a template against example variables, collecting every
+    /// missing variable, type mismatch and syntax error instead of stopping
+    /// at the first. The template is only rendered when none are found.
+    pub fn validate_render(&self, template_name: &str, context: &Value) -> Result<RenderValidation, TemplateError> {
+        let mut report = RenderValidation::default();
+        {
+            let store = self.template_store.read().unwrap();
+            let template = store
+                .get_template(template_name)
+                .ok_or_else(|| TemplateError::NotFound(template_name.to_string()))?;
+            let context_obj = context.as_object()
+                .ok_or_else(|| TemplateError::Validation("Context must be an object".to_string()))?;
+
+            let content = match &template.parent_template {
+                Some(parent_name) => Self::compose_template(&store, template, parent_name)?,
+                None => template.content.clone(),
+            };
+            if let Err(TemplateError::Syntax(message)) = self.validate_template(&content) {
+                report.syntax_error = Some(message);
+            }
 
-        // Apply default values for missing variables
-        if let Some(context_obj) = context.as_object_mut() {
             for var in &template.variables {
-                if !context_obj.contains_key(&var.name) {
-                    if let Some(default_value) = &var.default_value {
-                        context_obj.insert(var.name.clone(), default_value.clone());
+                match context_obj.get(&var.name) {
+                    Some(value) => {
+                        if let Err(TemplateError::Validation(message)) = self.validate_variable_type(var, value) {
+                            report.type_mismatches.push(message);
+                        }
+                    }
+                    None if var.required && var.default_value.is_none() => {
+                        report.missing_variables.push(var.name.clone());
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if report.syntax_error.is_none() && report.missing_variables.is_empty() && report.type_mismatches.is_empty() {
+            match self.render(template_name, context) {
+                Ok(rendered) => report.rendered = Some(rendered),
+                Err(e) => report.render_error = Some(e.to_string()),
+            }
+        }
+
+        Ok(report)
+    }
+
+    pub fn render_with_defaults(&self, template_name: &str, mut context: Value) -> Result<String, TemplateError> {
+        {
+            let store = self.template_store.read().unwrap();
+            let template = store
+                .get_template(template_name)
+                .ok_or_else(|| TemplateError::NotFound(template_name.to_string()))?;
+
+            // Apply default values for missing variables
+            if let Some(context_obj) = context.as_object_mut() {
+                for var in &template.variables {
+                    if !context_obj.contains_key(&var.name) {
+                        if let Some(default_value) = &var.default_value {
+                            context_obj.insert(var.name.clone(), default_value.clone());
+                        }
                     }
                 }
             }
@@ -498,24 +829,34 @@ impl TemplateEngine {
         self.render(template_name, &context)
     }
 
-    pub async fn save_template(&mut self, template: Template) -> Result<(), TemplateError> {
-        self.template_store.save_to_disk(&template).await?;
+    pub async fn save_template(&self, template: Template) -> Result<(), TemplateError> {
+        // Disk IO goes through a detached store so no lock is held across the await
+        TemplateStore::new(self.config.template_dir.clone())
+            .save_to_disk(&template)
+            .await?;
         self.register_template(template)?;
         Ok(())
     }
 
-    pub async fn load_templates(&mut self) -> Result<(), TemplateError> {
-        self.template_store.load_from_disk().await?;
+    pub async fn load_templates(&self) -> Result<(), TemplateError> {
+        let mut loaded = TemplateStore::new(self.config.template_dir.clone());
+        loaded.load_from_disk().await?;
+
+        let mut store = self.template_store.write().unwrap();
+        for template in loaded.templates.into_values() {
+            store.add_template(template);
+        }
         
         // Re-register all loaded templates with Handlebars
-        for template in self.template_store.list_templates() {
+        let mut handlebars = self.handlebars.write().unwrap();
+        for template in store.list_templates() {
             let final_content = if let Some(parent_name) = &template.parent_template {
-                self.compose_template(template, parent_name)?
+                Self::compose_template(&store, template, parent_name)?
             } else {
                 template.content.clone()
             };
             
-            self.handlebars
+            handlebars
                 .register_template_string(&template.name, &final_content)
                 .map_err(|e| TemplateError::Syntax(e.to_string()))?;
         }
@@ -523,10 +864,10 @@ impl TemplateEngine {
         Ok(())
     }
 
-    pub async fn reload_template(&mut self, template_name: &str) -> Result<(), TemplateError> {
+    pub async fn reload_template(&self, template_name: &str) -> Result<(), TemplateError> {
         if self.config.auto_reload {
             // Remove from Handlebars
-            self.handlebars.unregister_template(template_name);
+            self.handlebars.write().unwrap().unregister_template(template_name);
             
             // Reload from disk if template directory is configured
             if let Some(dir) = &self.config.template_dir {
@@ -541,22 +882,40 @@ impl TemplateEngine {
         Ok(())
     }
 
-    pub fn remove_template(&mut self, template_name: &str) -> Option<Template> {
-        self.handlebars.unregister_template(template_name);
-        self.template_store.remove_template(template_name)
+    pub fn remove_template(&self, template_name: &str) -> Option<Template> {
+        let mut store = self.template_store.write().unwrap();
+        self.handlebars.write().unwrap().unregister_template(template_name);
+        store.remove_template(template_name)
+    }
+
+    /// Remove a template from memory and Handlebars and delete its backing file
+    pub async fn delete_template(&self, template_name: &str) -> Result<(), TemplateError> {
+        let removed = self.remove_template(template_name).is_some();
+        let deleted = TemplateStore::new(self.config.template_dir.clone())
+            .delete_from_disk(template_name)
+            .await?;
+
+        if removed || deleted {
+            Ok(())
+        } else {
+            Err(TemplateError::NotFound(template_name.to_string()))
+        }
     }
 
     pub async fn export_template(&self, template_name: &str, export_path: &PathBuf) -> Result<(), TemplateError> {
-        let template = self.template_store
-            .get_template(template_name)
-            .ok_or_else(|| TemplateError::NotFound(template_name.to_string()))?;
+        let json = {
+            let store = self.template_store.read().unwrap();
+            let template = store
+                .get_template(template_name)
+                .ok_or_else(|| TemplateError::NotFound(template_name.to_string()))?;
+            serde_json::to_string_pretty(template)?
+        };
 
-        let json = serde_json::to_string_pretty(template)?;
         fs::write(export_path, json).await?;
         Ok(())
     }
 
-    pub async fn import_template(&mut self, import_path: &PathBuf) -> Result<String, TemplateError> {
+    pub async fn import_template(&self, import_path: &PathBuf) -> Result<String, TemplateError> {
         let content = fs::read_to_string(import_path).await?;
         let template: Template = serde_json::from_str(&content)?;
         let template_name = template.name.clone();
@@ -565,12 +924,12 @@ impl TemplateEngine {
         Ok(template_name)
     }
 
-    pub fn clone_template(&mut self, source_name: &str, new_name: &str) -> Result<(), TemplateError> {
-        let source_template = self.template_store
+    pub fn clone_template(&self, source_name: &str, new_name: &str) -> Result<(), TemplateError> {
+        let mut cloned_template = self.template_store.read().unwrap()
             .get_template(source_name)
+            .cloned()
             .ok_or_else(|| TemplateError::NotFound(source_name.to_string()))?;
 
-        let mut cloned_template = source_template.clone();
         cloned_template.name = new_name.to_string();
         cloned_template.created_at = SystemTime::now();
         
@@ -579,7 +938,7 @@ impl TemplateEngine {
     }
 
     pub fn get_template_info(&self, template_name: &str) -> Option<TemplateInfo> {
-        self.template_store.get_template(template_name).map(|template| {
+        self.template_store.read().unwrap().get_template(template_name).map(|template| {
             TemplateInfo {
                 name: template.name.clone(),
                 description: template.description.clone(),
@@ -594,6 +953,181 @@ impl TemplateEngine {
     }
 }
 
+/// Root variable name of a path token, plus whether the path was dotted.
+/// `this`, `@` data and relative paths yield `None`.
+fn reference_root(token: &str) -> Option<(&str, bool)> {
+    let root = token.split(['.', '/']).next()?;
+
+    let is_identifier = root
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && root.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    if !is_identifier || root == "this" {
+        return None;
+    }
+
+    Some((root, root.len() < token.len()))
+}
+
+/// Record the variable a path parameter references; literals reference none
+fn note_parameter(param: &Parameter, var_type: VariableType, required: bool, variables: &mut Vec<TemplateVariable>) {
+    let Some((root, dotted)) = param.as_name().and_then(reference_root) else { return };
+    // A dotted path only tells us its root holds an object of some kind
+    let var_type = if dotted { VariableType::Any } else { var_type };
+    note_variable(variables, root, var_type, required);
+}
+
+/// Record a reference, letting required uses override optional ones and
+/// typed uses refine untyped ones
+fn note_variable(variables: &mut Vec<TemplateVariable>, name: &str, var_type: VariableType, required: bool) {
+    match variables.iter_mut().find(|v| v.name == name) {
+        Some(existing) => {
+            if required && !existing.required {
+                existing.var_type = var_type;
+                existing.required = true;
+            } else if matches!(existing.var_type, VariableType::Any) && required == existing.required {
+                existing.var_type = var_type;
+            }
+        }
+        None => variables.push(TemplateVariable {
+            name: name.to_string(),
+            var_type,
+            required,
+            default_value: None,
+            description: None,
+        }),
+    }
+}
+
+const BLOCK_OPEN: &str = "{{#block ";
+const BLOCK_CLOSE: &str = "{{/block}}";
+
+/// Split a child template into its `{{#block "name"}}...{{/block}}` regions
+/// and the content left outside them. Blocks do not nest.
+fn extract_blocks(content: &str) -> Result<(HashMap<String, String>, String), TemplateError> {
+    let mut blocks = HashMap::new();
+    let mut remainder = String::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find(BLOCK_OPEN) {
+        remainder.push_str(&rest[..start]);
+        let after = &rest[start + BLOCK_OPEN.len()..];
+
+        let header_end = after.find("}}").ok_or_else(|| {
+            TemplateError::Composition("Unterminated block declaration".to_string())
+        })?;
+        let name = after[..header_end].trim().trim_matches(|c| c == '"' || c == '\'');
+        if name.is_empty() {
+            return Err(TemplateError::Composition("Block declared without a name".to_string()));
+        }
+
+        let body = &after[header_end + 2..];
+        let body_end = body.find(BLOCK_CLOSE).ok_or_else(|| {
+            TemplateError::Composition(format!("Block '{}' is missing {}", name, BLOCK_CLOSE))
+        })?;
+        blocks.insert(name.to_string(), body[..body_end].to_string());
+        rest = &body[body_end + BLOCK_CLOSE.len()..];
+    }
+    remainder.push_str(rest);
+
+    // A child made only of blocks has no default content
+    if !blocks.is_empty() && remainder.trim().is_empty() {
+        remainder.clear();
+    }
+
+    Ok((blocks, remainder))
+}
+
+/// Replace each `{{> name}}` slot in a layout with the matching block in a
+/// single pass, so block bodies are never re-scanned. Slots without a block
+/// are left for Handlebars to resolve as regular partials.
+fn fill_slots(layout: &str, blocks: &HashMap<String, String>) -> String {
+    let mut composed = String::with_capacity(layout.len());
+    let mut rest = layout;
+
+    while let Some(start) = rest.find("{{>") {
+        composed.push_str(&rest[..start]);
+        let after = &rest[start + 3..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+
+        match blocks.get(after[..end].trim()) {
+            Some(body) => composed.push_str(body),
+            None => composed.push_str(&rest[start..start + 3 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    composed.push_str(rest);
+
+    composed
+}
+
+/// Roles accepted in a templated message array
+const MESSAGE_ROLES: [&str; 3] = ["system", "user", "assistant"];
+
+/// Interpret rendered template output as a full chat message list.
+///
+/// Returns `None` unless the output is a non-empty JSON array of messages with
+/// known roles, in which case callers should treat it as a single prompt.
+pub fn parse_rendered_messages(rendered: &str) -> Option<Vec<Message>> {
+    let trimmed = rendered.trim();
+    if !trimmed.starts_with('[') {
+        return None;
+    }
+
+    let messages: Vec<Message> = serde_json::from_str(trimmed).ok()?;
+    if messages.is_empty() || !messages.iter().all(|m| MESSAGE_ROLES.contains(&m.role.as_str())) {
+        return None;
+    }
+
+    Some(messages)
+}
+
+impl TemplateEngine {
+    /// Render a template into chat messages. Templates that render to a JSON
+    /// message array produce the whole conversation; anything else becomes a
+    /// single user message.
+    pub fn render_messages(&self, template_name: &str, context: &Value) -> Result<Vec<Message>, TemplateError> {
+        let rendered = self.render(template_name, context)?;
+
+        let messages = parse_rendered_messages(&rendered).unwrap_or_else(|| {
+            vec![Message {
+                role: "user".to_string(),
+                content: rendered,
+                images: None,
+            }]
+        });
+        Ok(self.with_few_shot(template_name, messages))
+    }
+
+    pub fn few_shot_examples(&self, template_name: &str) -> Vec<FewShotExample> {
+        self.template_store.read().unwrap()
+            .get_template(template_name)
+            .map(|template| template.few_shot.clone())
+            .unwrap_or_default()
+    }
+
+    /// Insert the template's few-shot examples as user/assistant pairs after
+    /// any leading system messages, so they precede the real conversation
+    pub fn with_few_shot(&self, template_name: &str, mut messages: Vec<Message>) -> Vec<Message> {
+        let examples = self.few_shot_examples(template_name);
+        let turn = |role: &str, content: &str| Message {
+            role: role.to_string(),
+            content: content.to_string(),
+            images: None,
+        };
+        let at = messages.iter().take_while(|m| m.role == "system").count();
+        let pairs = examples.iter().flat_map(|example| {
+            [turn("user", &example.input), turn("assistant", &example.output)]
+        });
+        messages.splice(at..at, pairs);
+        messages
+    }
+}
+
 // Helper functions
 fn upper_helper(
     h: &Helper,
@@ -721,46 +1255,128 @@ fn contains_helper(
     Ok(())
 }
 
-fn eq_helper(
-    h: &Helper,
-    _: &Handlebars,
-    _: &handlebars::Context,
-    _: &mut RenderContext,
-    out: &mut dyn Output,
-) -> HelperResult {
-    let left = h.param(0)
-        .ok_or_else(|| RenderError::new("eq helper requires two parameters"))?;
-    
-    let right = h.param(1)
-        .ok_or_else(|| RenderError::new("eq helper requires two parameters"))?;
-    
-    let equal = left.value() == right.value();
-    out.write(&equal.to_string())?;
-    Ok(())
+// Math and comparison helpers. These return typed values through
+// `call_inner` so they compose in subexpressions like `{{#if (lt x 10)}}`;
+// plain fn helpers would hand the caller a string there.
+struct ComparisonHelper {
+    name: &'static str,
+    cmp: Comparison,
 }
 
-fn gt_helper(
-    h: &Helper,
-    _: &Handlebars,
-    _: &handlebars::Context,
-    _: &mut RenderContext,
-    out: &mut dyn Output,
-) -> HelperResult {
+/// How a comparison helper relates its two arguments
+enum Comparison {
+    /// JSON equality, for arguments of any type
+    Equal,
+    /// An ordering between two numbers
+    Numeric(fn(f64, f64) -> bool),
+}
+
+impl HelperDef for ComparisonHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc handlebars::Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let result = match self.cmp {
+            Comparison::Equal => {
+                let (left, right) = two_params(h, self.name)?;
+                left.value() == right.value()
+            }
+            Comparison::Numeric(cmp) => {
+                let (left, right) = numeric_params(h, self.name)?;
+                cmp(left.as_f64().unwrap_or(0.0), right.as_f64().unwrap_or(0.0))
+            }
+        };
+        Ok(ScopedJson::Derived(Value::Bool(result)))
+    }
+}
+
+/// Binary arithmetic that stays in integers when both operands are integers
+/// and the result is exact, and falls back to floats otherwise
+struct ArithmeticHelper {
+    name: &'static str,
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+    checks_divisor: bool,
+}
+
+impl HelperDef for ArithmeticHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc handlebars::Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let (left, right) = numeric_params(h, self.name)?;
+        if self.checks_divisor && right.as_f64() == Some(0.0) {
+            return Err(RenderError::new(format!("{} helper cannot divide by zero", self.name)));
+        }
+
+        let integer = match (left.as_i64(), right.as_i64()) {
+            (Some(l), Some(r)) => (self.int_op)(l, r),
+            _ => None,
+        };
+        let result = match integer {
+            Some(value) => Value::from(value),
+            None => {
+                let value = (self.float_op)(left.as_f64().unwrap_or(0.0), right.as_f64().unwrap_or(0.0));
+                Number::from_f64(value).map(Value::Number).ok_or_else(|| {
+                    RenderError::new(format!("{} helper produced a non-finite result", self.name))
+                })?
+            }
+        };
+
+        Ok(ScopedJson::Derived(result))
+    }
+}
+
+fn two_params<'a, 'reg, 'rc>(
+    h: &'a Helper<'reg, 'rc>,
+    name: &str,
+) -> Result<(&'a PathAndJson<'reg, 'rc>, &'a PathAndJson<'reg, 'rc>), RenderError> {
     let left = h.param(0)
-        .ok_or_else(|| RenderError::new("gt helper requires two parameters"))?;
-    
+        .ok_or_else(|| RenderError::new(format!("{} helper requires two parameters", name)))?;
+
     let right = h.param(1)
-        .ok_or_else(|| RenderError::new("gt helper requires two parameters"))?;
-    
-    let greater = match (left.value(), right.value()) {
-        (Value::Number(l), Value::Number(r)) => {
-            l.as_f64().unwrap_or(0.0) > r.as_f64().unwrap_or(0.0)
+        .ok_or_else(|| RenderError::new(format!("{} helper requires two parameters", name)))?;
+
+    Ok((left, right))
+}
+
+fn numeric_params<'a>(h: &'a Helper, name: &str) -> Result<(&'a Number, &'a Number), RenderError> {
+    let (left, right) = two_params(h, name)?;
+    match (left.value(), right.value()) {
+        (Value::Number(l), Value::Number(r)) => Ok((l, r)),
+        _ => Err(RenderError::new(format!("{} helper parameters must be numbers", name))),
+    }
+}
+
+fn comparison_helpers() -> [ComparisonHelper; 5] {
+    [
+        ComparisonHelper { name: "eq", cmp: Comparison::Equal },
+        ComparisonHelper { name: "gt", cmp: Comparison::Numeric(|l, r| l > r) },
+        ComparisonHelper { name: "lt", cmp: Comparison::Numeric(|l, r| l < r) },
+        ComparisonHelper { name: "gte", cmp: Comparison::Numeric(|l, r| l >= r) },
+        ComparisonHelper { name: "lte", cmp: Comparison::Numeric(|l, r| l <= r) },
+    ]
+}
+
+fn arithmetic_helpers() -> [ArithmeticHelper; 5] {
+    [
+        ArithmeticHelper { name: "add", int_op: i64::checked_add, float_op: |l, r| l + r, checks_divisor: false },
+        ArithmeticHelper { name: "subtract", int_op: i64::checked_sub, float_op: |l, r| l - r, checks_divisor: false },
+        ArithmeticHelper { name: "multiply", int_op: i64::checked_mul, float_op: |l, r| l * r, checks_divisor: false },
+        ArithmeticHelper {
+            name: "divide",
+            int_op: |l, r| (l.checked_rem(r) == Some(0)).then(|| l.checked_div(r)).flatten(),
+            float_op: |l, r| l / r,
+            checks_divisor: true,
         },
-        _ => false,
-    };
-    
-    out.write(&greater.to_string())?;
-    Ok(())
+        ArithmeticHelper { name: "mod", int_op: i64::checked_rem, float_op: |l, r| l % r, checks_divisor: true },
+    ]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -775,6 +1391,26 @@ pub struct TemplateInfo {
     pub has_composition: bool,
 }
 
+/// Result of `TemplateEngine::validate_render`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RenderValidation {
+    /// Required variables with no value and no default
+    pub missing_variables: Vec<String>,
+    pub type_mismatches: Vec<String>,
+    /// Syntax error with the surrounding lines, as reported on registration
+    pub syntax_error: Option<String>,
+    /// Failure from the render itself, such as a helper error or timeout
+    pub render_error: Option<String>,
+    /// The rendered text, set only when the template rendered cleanly
+    pub rendered: Option<String>,
+}
+
+impl RenderValidation {
+    pub fn is_valid(&self) -> bool {
+        self.rendered.is_some()
+    }
+}
+
 // Additional helper functions
 fn format_helper(
     h: &Helper,
@@ -806,6 +1442,96 @@ fn format_helper(
     Ok(())
 }
 
+/// Format a timestamp with a strftime-style pattern, rejecting invalid
+/// specifiers instead of panicking the way `to_string` would
+fn format_timestamp<Tz>(timestamp: &chrono::DateTime<Tz>, format: &str) -> Result<String, RenderError>
+where
+    Tz: chrono::TimeZone,
+    Tz::Offset: std::fmt::Display,
+{
+    use std::fmt::Write;
+
+    let mut formatted = String::new();
+    write!(formatted, "{}", timestamp.format(format))
+        .map_err(|_| RenderError::new(format!("invalid date format '{}'", format)))?;
+    Ok(formatted)
+}
+
+fn now_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let now = chrono::Utc::now();
+
+    let formatted = match h.param(0) {
+        Some(param) => {
+            let format = param.value().as_str()
+                .ok_or_else(|| RenderError::new("now helper format must be a string"))?;
+            format_timestamp(&now, format)?
+        }
+        None => now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    };
+
+    out.write(&formatted)?;
+    Ok(())
+}
+
+fn date_format_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let input = h.param(0)
+        .ok_or_else(|| RenderError::new("date_format helper requires a date and a format"))?
+        .value().as_str()
+        .ok_or_else(|| RenderError::new("date_format helper date must be an RFC3339 string"))?;
+
+    let format = h.param(1)
+        .ok_or_else(|| RenderError::new("date_format helper requires a date and a format"))?
+        .value().as_str()
+        .ok_or_else(|| RenderError::new("date_format helper format must be a string"))?;
+
+    let timestamp = chrono::DateTime::parse_from_rfc3339(input)
+        .map_err(|e| RenderError::new(format!("date_format helper could not parse '{}': {}", input, e)))?;
+
+    out.write(&format_timestamp(&timestamp, format)?)?;
+    Ok(())
+}
+
+fn json_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = h.param(0)
+        .ok_or_else(|| RenderError::new("json helper requires one parameter"))?
+        .value();
+
+    // Accept either `{{json value "pretty"}}` or `{{json value true}}`
+    let pretty = h.param(1).is_some_and(|param| match param.value() {
+        Value::String(mode) => mode == "pretty",
+        Value::Bool(flag) => *flag,
+        _ => false,
+    });
+
+    let serialized = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+    .map_err(|e| RenderError::new(format!("json helper could not serialize value: {}", e)))?;
+
+    out.write(&serialized)?;
+    Ok(())
+}
+
 fn default_helper(
     h: &Helper,
     _: &Handlebars,
@@ -840,7 +1566,6 @@ test)]
 mod tests {
     use super::*;
     use serde_json::json;
-    use std::collections::HashMap;
 
     fn create_test_config() -> TemplateConfig {
         TemplateConfig {
@@ -856,6 +1581,7 @@ mod tests {
                 "if".to_string(),
                 "each".to_string(),
             ],
+            denied_helpers: default_denied_helpers(),
         }
     }
 
@@ -877,6 +1603,7 @@ mod tests {
             parent_template: None,
             tags: vec!["test".to_string()],
             usage_examples: vec!["{{name}} = 'World'".to_string()],
+            few_shot: vec![],
         }
     }
 
@@ -888,7 +1615,7 @@ mod tests {
 
     #[test]
     fn test_template_registration() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         let template = create_test_template();
         
         let result = engine.register_template(template);
@@ -898,7 +1625,7 @@ mod tests {
 
     #[test]
     fn test_template_rendering() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         let template = create_test_template();
         
         engine.register_template(template).unwrap();
@@ -914,7 +1641,7 @@ mod tests {
 
     #[test]
     fn test_template_validation_missing_required_variable() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         let template = create_test_template();
         
         engine.register_template(template).unwrap();
@@ -926,9 +1653,37 @@ mod tests {
         assert!(matches!(result.unwrap_err(), TemplateError::Validation(_)));
     }
 
+    #[test]
+    fn test_validate_render_reports_every_problem() {
+        let engine = TemplateEngine::new(create_test_config());
+        let mut template = create_test_template();
+        template.content = "Hello {{name}}, you are {{age}}!".to_string();
+        template.variables.push(TemplateVariable {
+            name: "age".to_string(),
+            var_type: VariableType::Number,
+            required: true,
+            default_value: None,
+            description: None,
+        });
+        engine.register_template(template).unwrap();
+
+        let report = engine.validate_render("test_template", &json!({ "age": "forty" })).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.missing_variables, vec!["name".to_string()]);
+        assert_eq!(report.type_mismatches.len(), 1);
+        assert!(report.type_mismatches[0].contains("'age'"));
+        assert!(report.syntax_error.is_none());
+
+        let report = engine.validate_render("test_template", &json!({ "name": "Ada", "age": 40 })).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.rendered.as_deref(), Some("Hello Ada, you are 40!"));
+
+        assert!(matches!(engine.validate_render("missing", &json!({})), Err(TemplateError::NotFound(_))));
+    }
+
     #[test]
     fn test_template_with_default_values() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         
         let mut template = create_test_template();
         template.variables[0].required = false;
@@ -946,7 +1701,7 @@ mod tests {
     #[test]
     fn test_template_security_validation() {
         let config = create_test_config();
-        let mut engine = TemplateEngine::new(config);
+        let engine = TemplateEngine::new(config);
         
         let mut dangerous_template = create_test_template();
         dangerous_template.content = "{{#raw}}<script>alert('xss')</script>{{/raw}}".to_string();
@@ -961,7 +1716,7 @@ mod tests {
         let mut config = create_test_config();
         config.max_template_size = 10; // Very small limit
         
-        let mut engine = TemplateEngine::new(config);
+        let engine = TemplateEngine::new(config);
         let template = create_test_template(); // This will exceed the limit
         
         let result = engine.register_template(template);
@@ -971,7 +1726,7 @@ mod tests {
 
     #[test]
     fn test_template_composition() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         
         // Create parent template
         let parent_template = Template {
@@ -983,6 +1738,7 @@ mod tests {
             parent_template: None,
             tags: vec!["layout".to_string()],
             usage_examples: vec![],
+            few_shot: vec![],
         };
         
         // Create child template
@@ -1002,9 +1758,55 @@ mod tests {
         assert_eq!(result.unwrap(), "Header\nHello World!\nFooter");
     }
 
+    #[test]
+    fn test_template_composition_named_blocks() {
+        let engine = TemplateEngine::new(create_test_config());
+
+        let mut layout = create_test_template();
+        layout.name = "layout".to_string();
+        layout.content = "[{{> header}}]\n{{>body}}\n({{> footer}})".to_string();
+        layout.variables.clear();
+        engine.register_template(layout).unwrap();
+
+        let mut page = create_test_template();
+        page.name = "page".to_string();
+        page.parent_template = Some("layout".to_string());
+        page.content = concat!(
+            "{{#block \"header\"}}Report for {{name}}{{/block}}\n",
+            "{{#block \"body\"}}All systems nominal{{/block}}\n",
+            "{{#block 'footer'}}end{{/block}}",
+        ).to_string();
+        engine.register_template(page).unwrap();
+
+        assert_eq!(
+            engine.render("page", &json!({"name": "Ada"})).unwrap(),
+            "[Report for Ada]\nAll systems nominal\n(end)"
+        );
+
+        // Loose child content still fills the default content slot
+        let mut mixed_layout = create_test_template();
+        mixed_layout.name = "mixed_layout".to_string();
+        mixed_layout.content = "{{> title}}: {{> content}}".to_string();
+        mixed_layout.variables.clear();
+        engine.register_template(mixed_layout).unwrap();
+
+        let mut mixed = create_test_template();
+        mixed.name = "mixed".to_string();
+        mixed.parent_template = Some("mixed_layout".to_string());
+        mixed.content = "{{#block \"title\"}}Greeting{{/block}}Hello {{name}}!".to_string();
+        engine.register_template(mixed).unwrap();
+
+        assert_eq!(engine.render("mixed", &json!({"name": "World"})).unwrap(), "Greeting: Hello World!");
+
+        assert!(matches!(
+            extract_blocks("{{#block \"open\"}}never closed"),
+            Err(TemplateError::Composition(_))
+        ));
+    }
+
     #[test]
     fn test_template_search() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         
         let mut template1 = create_test_template();
         template1.name = "greeting".to_string();
@@ -1028,7 +1830,7 @@ mod tests {
 
     #[test]
     fn test_variable_type_validation() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         
         let mut template = create_test_template();
         template.variables.push(TemplateVariable {
@@ -1063,7 +1865,7 @@ mod tests {
 
     #[test]
     fn test_helper_functions() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         
         let template = Template {
             name: "helper_test".to_string(),
@@ -1082,6 +1884,7 @@ mod tests {
             parent_template: None,
             tags: vec![],
             usage_examples: vec![],
+            few_shot: vec![],
         };
         
         engine.register_template(template).unwrap();
@@ -1106,7 +1909,7 @@ mod tests {
             "gt".to_string(),
         ]);
         
-        let mut engine = TemplateEngine::new(config);
+        let engine = TemplateEngine::new(config);
         
         let template = Template {
             name: "advanced_test".to_string(),
@@ -1132,6 +1935,7 @@ mod tests {
             parent_template: None,
             tags: vec![],
             usage_examples: vec![],
+            few_shot: vec![],
         };
         
         engine.register_template(template).unwrap();
@@ -1146,6 +1950,117 @@ mod tests {
         assert_eq!(result.unwrap(), "Length: 3, Joined: apple, banana, cherry, Contains: true");
     }
 
+    #[test]
+    fn test_math_and_comparison_helpers() {
+        let mut config = create_test_config();
+        config.allowed_helpers.extend(
+            ["add", "subtract", "multiply", "divide", "mod", "eq", "gt", "lt", "gte", "lte"]
+                .iter()
+                .map(|h| h.to_string()),
+        );
+        let engine = TemplateEngine::new(config);
+
+        let mut template = create_test_template();
+        template.name = "math".to_string();
+        template.content = "{{add a b}} {{subtract a b}} {{multiply a b}} {{divide a b}} {{mod a b}} {{add a 0.5}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+        assert_eq!(engine.render("math", &json!({"a": 7, "b": 2})).unwrap(), "9 5 14 3.5 1 7.5");
+        assert_eq!(engine.render("math", &json!({"a": 8, "b": 2})).unwrap(), "10 6 16 4 0 8.5");
+
+        // Non-numeric arguments and division by zero are errors
+        assert!(engine.render("math", &json!({"a": "7", "b": 2})).is_err());
+        assert!(engine.render("math", &json!({"a": 7, "b": 0})).is_err());
+
+        let mut template = create_test_template();
+        template.name = "budget".to_string();
+        template.content = "{{#if (lt x 10)}}low{{else}}high{{/if}} {{gte x 10}} {{lte x 10}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+        assert_eq!(engine.render("budget", &json!({"x": 3})).unwrap(), "low false true");
+        assert_eq!(engine.render("budget", &json!({"x": 10.5})).unwrap(), "high true false");
+
+        // eq and gt yield booleans, so a false result is falsy in #if
+        let mut template = create_test_template();
+        template.name = "checks".to_string();
+        template.content = "{{#if (eq kind \"bug\")}}bug{{else}}other{{/if}} {{#if (gt x 10)}}big{{else}}small{{/if}} {{eq kind \"bug\"}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+        assert_eq!(engine.render("checks", &json!({"kind": "bug", "x": 11})).unwrap(), "bug big true");
+        assert_eq!(engine.render("checks", &json!({"kind": "task", "x": 3})).unwrap(), "other small false");
+    }
+
+    #[test]
+    fn test_date_helpers() {
+        let mut config = create_test_config();
+        config.allowed_helpers.extend(["now".to_string(), "date_format".to_string()]);
+        let engine = TemplateEngine::new(config);
+
+        let mut template = create_test_template();
+        template.name = "dates".to_string();
+        template.content = "{{date_format due \"%Y-%m-%d %H:%M\"}} / {{date_format due \"%A, %B %e\"}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+
+        assert_eq!(
+            engine.render("dates", &json!({"due": "2024-03-15T09:30:00+02:00"})).unwrap(),
+            "2024-03-15 09:30 / Friday, March 15"
+        );
+        assert!(engine.render("dates", &json!({"due": "next tuesday"})).is_err());
+
+        let mut template = create_test_template();
+        template.name = "now".to_string();
+        template.content = "{{now \"%Y\"}}|{{now}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+
+        let rendered = engine.render("now", &json!({})).unwrap();
+        let (year, timestamp) = rendered.split_once('|').unwrap();
+        assert_eq!(year, chrono::Utc::now().format("%Y").to_string());
+        assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+
+        // Invalid format specifiers are render errors rather than panics
+        let mut template = create_test_template();
+        template.name = "bad_format".to_string();
+        template.content = "{{now \"%Q\"}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+        assert!(engine.render("bad_format", &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_json_helper() {
+        let mut config = create_test_config();
+        config.allowed_helpers.push("json".to_string());
+        let engine = TemplateEngine::new(config);
+
+        let mut template = create_test_template();
+        template.name = "context".to_string();
+        template.content = "Context: {{json payload}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+
+        let payload = json!({
+            "user": {"name": "Ada <admin>", "tags": ["math", "engines"]},
+            "scores": [1, 2.5, null],
+            "active": true
+        });
+        let rendered = engine.render("context", &json!({"payload": payload})).unwrap();
+        let serialized = rendered.strip_prefix("Context: ").unwrap();
+        assert!(!serialized.contains('\n'));
+        assert_eq!(serde_json::from_str::<Value>(serialized).unwrap(), payload);
+
+        let mut template = create_test_template();
+        template.name = "pretty".to_string();
+        template.content = "{{json items \"pretty\"}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+
+        let rendered = engine.render("pretty", &json!({"items": [{"id": 1}, {"id": 2}]})).unwrap();
+        assert!(rendered.contains('\n'));
+        assert_eq!(serde_json::from_str::<Value>(&rendered).unwrap(), json!([{"id": 1}, {"id": 2}]));
+    }
+
     #[test]
     fn test_template_error_formatting() {
         let engine = TemplateEngine::new(create_test_config());
@@ -1160,7 +2075,7 @@ mod tests {
 
     #[test]
     fn test_template_management_operations() {
-        let mut engine = TemplateEngine::new(create_test_config());
+        let engine = TemplateEngine::new(create_test_config());
         let template = create_test_template();
         
         // Test registration and info retrieval
@@ -1179,4 +2094,240 @@ mod tests {
         assert!(removed.is_some());
         assert_eq!(engine.list_templates().len(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_concurrent_render_and_register() {
+        let engine = TemplateEngine::new(create_test_config());
+        engine.register_template(create_test_template()).unwrap();
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        let result = engine.render("test_template", &json!({"name": "World"}));
+                        assert_eq!(result.unwrap(), "Hello World!");
+                    }
+                });
+            }
+
+            scope.spawn(|| {
+                for i in 0..50 {
+                    let mut template = create_test_template();
+                    template.name = format!("concurrent_{}", i);
+                    engine.register_template(template).unwrap();
+                }
+            });
+        });
+
+        assert_eq!(engine.list_templates().len(), 51);
+        assert_eq!(
+            engine.render("concurrent_49", &json!({"name": "Again"})).unwrap(),
+            "Hello Again!"
+        );
+    }
+
+    #[test]
+    fn test_security_validation_uses_template_structure() {
+        let engine = TemplateEngine::new(create_test_config());
+
+        // Prose that mentions code is not a security problem
+        let mut prose = create_test_template();
+        prose.name = "review".to_string();
+        prose.content = "Review this code for {{name}}: it calls eval( and builds <script> tags.".to_string();
+        assert!(engine.register_template(prose).is_ok());
+
+        // Unescaped triple-mustache output is rejected
+        let mut raw_output = create_test_template();
+        raw_output.name = "raw_output".to_string();
+        raw_output.content = "Body: {{{raw_html}}}".to_string();
+        assert!(matches!(
+            engine.register_template(raw_output),
+            Err(TemplateError::Security(_))
+        ));
+
+        // Denied helpers are rejected wherever they are invoked
+        for content in ["{{{{raw}}}}{{name}}{{{{/raw}}}}", "{{#if (log name)}}x{{/if}}"] {
+            let mut template = create_test_template();
+            template.name = "denied".to_string();
+            template.content = content.to_string();
+            assert!(
+                matches!(engine.register_template(template), Err(TemplateError::Security(_))),
+                "{} should be rejected",
+                content
+            );
+        }
+
+        // The denylist is configurable
+        let mut config = create_test_config();
+        config.denied_helpers = vec!["upper".to_string()];
+        let engine = TemplateEngine::new(config);
+        let mut template = create_test_template();
+        template.content = "{{upper name}}".to_string();
+        assert!(matches!(engine.register_template(template), Err(TemplateError::Security(_))));
+    }
+
+    #[test]
+    fn test_render_timeout() {
+        let mut config = create_test_config();
+        config.max_render_time_ms = 1;
+        let engine = TemplateEngine::new(config);
+
+        let mut template = create_test_template();
+        template.name = "slow".to_string();
+        template.content = "{{#each rows}}{{#each ../cols}}{{this}}{{/each}}{{/each}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+
+        let items: Vec<u32> = (0..500).collect();
+        let result = engine.render("slow", &json!({"rows": items, "cols": items}));
+        match result {
+            Err(TemplateError::Security(msg)) => assert!(msg.contains("time limit")),
+            other => panic!("expected timeout, got {:?}", other.map(|s| s.len())),
+        }
+
+        // The timed-out render has stopped, so registrations aren't held up by it
+        let started = std::time::Instant::now();
+        engine.register_template(create_test_template()).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        // A helper that blocks without writing is cut off at the deadline too
+        let mut config = create_test_config();
+        config.max_render_time_ms = 50;
+        config.allowed_helpers.push("stall".to_string());
+        let engine = TemplateEngine::new(config);
+        engine.register_helper("stall", |_: &Helper, _: &Handlebars, _: &handlebars::Context, _: &mut RenderContext, _: &mut dyn Output| -> HelperResult {
+            std::thread::sleep(Duration::from_millis(500));
+            Ok(())
+        }).unwrap();
+        let mut template = create_test_template();
+        template.content = "{{stall}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+
+        let started = std::time::Instant::now();
+        assert!(matches!(engine.render("test_template", &json!({})), Err(TemplateError::Security(_))));
+        assert!(started.elapsed() < Duration::from_millis(400));
+
+        // Fast renders still complete within the limit
+        let mut config = create_test_config();
+        config.max_render_time_ms = 1000;
+        let engine = TemplateEngine::new(config);
+        engine.register_template(create_test_template()).unwrap();
+        assert_eq!(engine.render("test_template", &json!({"name": "World"})).unwrap(), "Hello World!");
+    }
+
+    #[test]
+    fn test_extract_variables_simple_references() {
+        let engine = TemplateEngine::new(create_test_config());
+        let variables = engine.extract_variables(
+            "Hello {{name}}! {{{greeting}}} {{upper title}} {{this}} {{@index}} {{! note }}",
+        );
+        let names: Vec<&str> = variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["name", "greeting", "title"]);
+        assert!(variables.iter().all(|v| matches!(v.var_type, VariableType::Any)));
+        assert!(variables.iter().all(|v| v.required));
+
+        // Dotted paths infer their root variable
+        let variables = engine.extract_variables("{{user.name}} {{user.email}}");
+        assert_eq!(variables.len(), 1);
+        assert_eq!(variables[0].name, "user");
+    }
+
+    #[test]
+    fn test_extract_variables_conditionals() {
+        let engine = TemplateEngine::new(create_test_config());
+        let variables = engine.extract_variables(
+            "{{#if urgent}}URGENT {{/if}}{{#unless quiet}}!{{else}}{{#if loud}}!!{{/if}}{{/unless}}{{topic}}",
+        );
+        let names: Vec<&str> = variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["urgent", "quiet", "loud", "topic"]);
+        assert!(!variables[0].required);
+        assert!(!variables[2].required);
+        assert!(variables[3].required);
+
+        // A required use elsewhere wins over the conditional
+        let variables = engine.extract_variables("{{#if name}}Hi {{name}}{{/if}}");
+        assert_eq!(variables.len(), 1);
+        assert!(variables[0].required);
+    }
+
+    #[test]
+    fn test_extract_variables_numeric_arguments() {
+        let mut config = create_test_config();
+        config.allowed_helpers.extend(["add".to_string(), "lt".to_string()]);
+        let engine = TemplateEngine::new(config);
+
+        let variables = engine.extract_variables("{{count}} then {{add count step}}{{#if (lt count limit)}}!{{/if}}");
+        let names: Vec<&str> = variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["count", "step", "limit"]);
+        assert!(variables.iter().all(|v| matches!(v.var_type, VariableType::Number)));
+
+        // Numeric inputs pass validation rather than failing a string check
+        let mut template = create_test_template();
+        template.content = "{{count}} items".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+        assert_eq!(engine.render("test_template", &json!({"count": 3})).unwrap(), "3 items");
+    }
+
+    #[test]
+    fn test_extract_variables_iteration() {
+        let engine = TemplateEngine::new(create_test_config());
+        let variables = engine.extract_variables(
+            "{{#each users}}{{name}} {{#each permissions}}{{this}}{{/each}}{{/each}} by {{author}}",
+        );
+        let names: Vec<&str> = variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["users", "author"]);
+        assert!(matches!(variables[0].var_type, VariableType::Array));
+        assert!(variables[0].required);
+
+        // Registration fills in variables when none were declared
+        let mut template = create_test_template();
+        template.content = "{{#each items}}- {{this}}\n{{/each}}{{#if footer}}{{footer}}{{/if}}".to_string();
+        template.variables.clear();
+        engine.register_template(template).unwrap();
+
+        let info = engine.list_templates().remove(0);
+        assert_eq!(info.variables.len(), 2);
+        assert_eq!(
+            engine.render("test_template", &json!({"items": ["a", "b"], "footer": "done"})).unwrap(),
+            "- a\n- b\ndone"
+        );
+        assert!(matches!(
+            engine.render("test_template", &json!({"footer": "done"})),
+            Err(TemplateError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_render_messages() {
+        let engine = TemplateEngine::new(create_test_config());
+
+        let mut conversation = create_test_template();
+        conversation.name = "conversation".to_string();
+        conversation.content = r#"[
+            {"role": "system", "content": "You greet people."},
+            {"role": "user", "content": "Say hi to Bob"},
+            {"role": "assistant", "content": "Hi Bob!"},
+            {"role": "user", "content": "Say hi to {{name}}"}
+        ]"#.to_string();
+        engine.register_template(conversation).unwrap();
+
+        let messages = engine.render_messages("conversation", &json!({"name": "Alice"})).unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[3].content, "Say hi to Alice");
+
+        // Plain prompts fall back to a single user message
+        engine.register_template(create_test_template()).unwrap();
+        let messages = engine.render_messages("test_template", &json!({"name": "World"})).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].role, "user");
+        assert_eq!(messages[0].content, "Hello World!");
+
+        // Arrays that aren't valid messages are not treated as conversations
+        assert!(parse_rendered_messages(r#"[{"role": "narrator", "content": "x"}]"#).is_none());
+        assert!(parse_rendered_messages("[1, 2, 3]").is_none());
+        assert!(parse_rendered_messages("[]").is_none());
+    }
+}
@@ -0,0 +1,80 @@
+//! Approximate token counting.
+//!
+//! There is no model tokenizer here: estimates average a characters-per-token
+//! and a tokens-per-word ratio typical of BPE vocabularies on English text.
+//! Expect them to be within roughly 20% for prose and rougher for code or
+//! non-Latin scripts, which is fine for budgeting but not for billing.
+
+use crate::streaming::Message;
+
+/// Rough per-message cost of role markers and separators
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Estimate the number of tokens in `text`
+pub fn estimate_tokens(text: &str) -> usize {
+    let chars = text.chars().count();
+    if chars == 0 {
+        return 0;
+    }
+
+    // ~4 characters per token, and ~1 token per word
+    let by_chars = chars.div_ceil(4);
+    let by_words = text.split_whitespace().count();
+    (by_chars + by_words).div_ceil(2)
+}
+
+/// Estimate the tokens a single chat message takes, including its framing
+pub fn estimate_message_tokens(message: &Message) -> usize {
+    estimate_tokens(&message.content) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Estimate the tokens a whole conversation takes
+pub fn count_message_tokens(messages: &[Message]) -> usize {
+    messages.iter().map(estimate_message_tokens).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: usize, expected: usize) {
+        let tolerance = (expected / 5).max(1);
+        assert!(
+            actual.abs_diff(expected) <= tolerance,
+            "estimate {} not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_estimate_tokens_for_known_strings() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("a"), 1);
+        // Reference counts from a BPE tokenizer
+        assert_close(estimate_tokens("Hello, world!"), 4);
+        assert_close(estimate_tokens("The quick brown fox jumps over the lazy dog."), 10);
+        assert_close(
+            estimate_tokens("Large language models predict the next token in a sequence of text."),
+            13,
+        );
+    }
+
+    #[test]
+    fn test_count_message_tokens_includes_overhead() {
+        let messages = vec![
+            Message {
+                role: "system".to_string(),
+                content: String::new(),
+                images: None,
+            },
+            Message {
+                role: "user".to_string(),
+                content: "Hello, world!".to_string(),
+                images: None,
+            },
+        ];
+        assert_eq!(count_message_tokens(&messages), 2 * MESSAGE_OVERHEAD_TOKENS + estimate_tokens("Hello, world!"));
+    }
+}
@@ -1,24 +1,28 @@
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
-    Frame, Terminal,
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap},
+    Frame, Terminal, TerminalOptions, Viewport,
 };
+use serde::{Deserialize, Serialize};
 use std::io;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use tokio::sync::mpsc;
 
 use crate::cache::CacheStats;
-use crate::streaming::StreamToken;
+use crate::config::UIConfig;
+use crate::session::{Session, SessionError};
+use crate::streaming::{Message, StreamToken, TokenKind};
 
-use pulldown_cmark::{Parser, Event as MarkdownEvent, Tag, CodeBlockKind};
+use pulldown_cmark::{Parser, Event as MarkdownEvent, Tag, CodeBlockKind, HeadingLevel};
 
 #[derive(Debug, Error)]
 pub enum UIError {
@@ -30,7 +34,7 @@ pub enum UIError {
     Event(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: MessageRole,
     pub content: String,
@@ -38,9 +42,13 @@ pub struct ChatMessage {
     pub model: String,
     pub template_used: Option<String>,
     pub cached: bool,
+    /// Reasoning streamed by thinking models, shown dimmed before the answer
+    #[serde(default)]
+    pub reasoning: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MessageRole {
     User,
     Assistant,
@@ -76,6 +84,9 @@ impl MessageRole {
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub current_model: String,
+    /// Model picked with `/model` or `/retry`, sent with each prompt; `None`
+    /// answers with the backend's default model
+    pub selected_model: Option<String>,
     pub is_streaming: bool,
     pub cache_stats: CacheStats,
     pub active_template: Option<String>,
@@ -85,6 +96,7 @@ impl Default for AppState {
     fn default() -> Self {
         Self {
             current_model: "llama3.2".to_string(),
+            selected_model: None,
             is_streaming: false,
             cache_stats: CacheStats {
                 hits: 0,
@@ -94,6 +106,8 @@ impl Default for AppState {
                 evictions: 0,
                 disk_writes: 0,
                 disk_reads: 0,
+                semantic_hits: 0,
+                disk_usage_bytes: 0,
             },
             active_template: None,
         }
@@ -105,11 +119,113 @@ pub enum UIAction {
     ChangeModel(String),
     LoadTemplate(String),
     ClearHistory,
+    /// Write the conversation transcript to a file
+    SaveHistory(PathBuf),
+    /// Switch to a saved session, by id
+    ResumeSession(String),
+    /// Ask for the last answer again, optionally from another model
+    Regenerate(Option<String>),
+    /// A slash command that couldn't be run, with the reason
+    CommandError(String),
     Quit,
     None,
 }
 
+/// A conversation the UI wants answered
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub messages: Vec<Message>,
+    /// Model to answer with instead of the default
+    pub model: Option<String>,
+    /// Template the last user message is rendered through, as `{{message}}`
+    pub template: Option<String>,
+}
+
+/// Slash commands understood by the input box, with their descriptions
+pub const SLASH_COMMANDS: [(&str, &str); 7] = [
+    ("/model <name>", "Switch to another model"),
+    ("/template <name>", "Use a template for new messages"),
+    ("/clear", "Clear the message history"),
+    ("/save <path>", "Save the conversation as Markdown"),
+    ("/resume <id>", "Continue a saved session"),
+    ("/retry [model]", "Answer the last message again"),
+    ("/quit", "Quit"),
+];
+
+/// Render a conversation as Markdown: a header per message with its time
+/// and metadata, then the content as written, so code fences survive
+pub fn export_markdown(messages: &[ChatMessage]) -> String {
+    let mut markdown = format!(
+        "# Conversation\n\n_Exported {} · {} messages_\n",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
+        messages.len()
+    );
+
+    for message in messages {
+        let mut details = vec![message.timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string()];
+        if !message.model.is_empty() {
+            details.push(format!("model: {}", message.model));
+        }
+        if let Some(template) = &message.template_used {
+            details.push(format!("template: {}", template));
+        }
+        if message.cached {
+            details.push("cached".to_string());
+        }
+
+        markdown.push_str(&format!("\n## {}\n\n_{}_\n\n", message.role.as_str(), details.join(" · ")));
+        if let Some(reasoning) = &message.reasoning {
+            for line in reasoning.trim().lines() {
+                markdown.push_str(&format!("> {}\n", line));
+            }
+            markdown.push('\n');
+        }
+        markdown.push_str(message.content.trim_end());
+        markdown.push('\n');
+    }
+
+    markdown
+}
+
+/// The commands whose name starts with what has been typed so far. Nothing
+/// is suggested once an argument is being typed or for ordinary input.
+pub fn command_suggestions(input: &str) -> Vec<(&'static str, &'static str)> {
+    if !input.starts_with('/') || input.starts_with("//") || input.contains(char::is_whitespace) {
+        return Vec::new();
+    }
+    SLASH_COMMANDS
+        .iter()
+        .filter(|(usage, _)| usage.starts_with(input))
+        .copied()
+        .collect()
+}
+
+/// Turn a line starting with `/` into the action it names
+pub fn parse_command(input: &str) -> UIAction {
+    let mut parts = input.trim().splitn(2, char::is_whitespace);
+    let command = parts.next().unwrap_or_default();
+    let argument = parts.next().map(str::trim).filter(|argument| !argument.is_empty());
+
+    match (command, argument) {
+        ("/model", Some(name)) => UIAction::ChangeModel(name.to_string()),
+        ("/template", Some(name)) => UIAction::LoadTemplate(name.to_string()),
+        ("/save", Some(path)) => UIAction::SaveHistory(PathBuf::from(path)),
+        ("/resume", Some(id)) => UIAction::ResumeSession(id.to_string()),
+        ("/retry" | "/regenerate", model) => UIAction::Regenerate(model.map(str::to_string)),
+        ("/clear", None) => UIAction::ClearHistory,
+        ("/quit" | "/exit", None) => UIAction::Quit,
+        ("/model" | "/template" | "/save" | "/resume", None) => {
+            UIAction::CommandError(format!("{} needs an argument", command))
+        }
+        ("/clear" | "/quit" | "/exit", Some(_)) => {
+            UIAction::CommandError(format!("{} takes no arguments", command))
+        }
+        _ => UIAction::CommandError(format!("Unknown command {} (type / to list commands)", command)),
+    }
+}
+
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 struct ThemeColors {
     primary: Color,
     secondary: Color,
@@ -121,83 +237,324 @@ struct ThemeColors {
     warning: Color,
     error: Color,
     info: Color,
+    highlight: Color,
+    /// Background behind inline code and code blocks
+    code_background: Color,
+}
+
+impl ThemeColors {
+    /// The theme registered under `name`, or the default theme for names
+    /// that aren't known
+    fn named(name: &str) -> Self {
+        match name {
+            "solarized" => Self::solarized(),
+            "monochrome" => Self::monochrome(),
+            "default" => Self::default_theme(),
+            other => {
+                tracing::warn!(theme = other, "Unknown UI theme, using the default");
+                Self::default_theme()
+            }
+        }
+    }
+
+    fn default_theme() -> Self {
+        Self {
+            primary: Color::Cyan,
+            secondary: Color::Blue,
+            accent: Color::Yellow,
+            background: Color::Black,
+            text: Color::White,
+            border: Color::Gray,
+            success: Color::Green,
+            warning: Color::Yellow,
+            error: Color::Red,
+            info: Color::Blue,
+            highlight: Color::Magenta,
+            code_background: Color::DarkGray,
+        }
+    }
+
+    fn solarized() -> Self {
+        Self {
+            primary: Color::Rgb(0x26, 0x8b, 0xd2),
+            secondary: Color::Rgb(0x2a, 0xa1, 0x98),
+            accent: Color::Rgb(0xb5, 0x89, 0x00),
+            background: Color::Rgb(0x00, 0x2b, 0x36),
+            text: Color::Rgb(0x83, 0x94, 0x96),
+            border: Color::Rgb(0x58, 0x6e, 0x75),
+            success: Color::Rgb(0x85, 0x99, 0x00),
+            warning: Color::Rgb(0xcb, 0x4b, 0x16),
+            error: Color::Rgb(0xdc, 0x32, 0x2f),
+            info: Color::Rgb(0x6c, 0x71, 0xc4),
+            highlight: Color::Rgb(0xd3, 0x36, 0x82),
+            code_background: Color::Rgb(0x07, 0x36, 0x42),
+        }
+    }
+
+    fn monochrome() -> Self {
+        Self {
+            primary: Color::White,
+            secondary: Color::Gray,
+            accent: Color::White,
+            background: Color::Black,
+            text: Color::White,
+            border: Color::DarkGray,
+            success: Color::Gray,
+            warning: Color::White,
+            error: Color::White,
+            info: Color::Gray,
+            highlight: Color::Gray,
+            code_background: Color::DarkGray,
+        }
+    }
+
+    fn high_contrast() -> Self {
+        Self {
+            primary: Color::White,
+            secondary: Color::Black,
+            accent: Color::White,
+            background: Color::Black,
+            text: Color::White,
+            border: Color::White,
+            success: Color::White,
+            warning: Color::White,
+            error: Color::White,
+            info: Color::White,
+            highlight: Color::White,
+            code_background: Color::Black,
+        }
+    }
+
+    fn role_color(&self, role: &MessageRole) -> Color {
+        match role {
+            MessageRole::User => self.primary,
+            MessageRole::Assistant => self.success,
+            MessageRole::System => self.warning,
+        }
+    }
 }
 
+/// Where the UI draws: stdout, or a sink when running headless
+type UIBackend = CrosstermBackend<Box<dyn io::Write + Send>>;
+
+/// Lines of a multi-line prompt shown before the input area scrolls
+const MAX_INPUT_LINES: usize = 5;
+
 pub struct TerminalUI {
-    terminal: Terminal<CrosstermBackend<io::Stdout>>,
+    terminal: Terminal<UIBackend>,
+    /// Whether `new` put the terminal into raw mode, so cleanup must undo it
+    raw_mode: bool,
     app_state: AppState,
     message_history: Vec<ChatMessage>,
     input_buffer: String,
+    /// Byte offset of the cursor in `input_buffer`, always on a char boundary
+    input_cursor: usize,
     scroll_offset: usize,
     current_streaming_content: String,
-    markdown_renderer: MarkdownRenderer,
+    current_reasoning_content: String,
     auto_scroll: bool,
     progress_animation_frame: usize,
     high_contrast_mode: bool,
+    theme: ThemeColors,
+    /// The session being chatted in; its messages live in `message_history`
+    session: Session,
+    sessions_dir: PathBuf,
     last_terminal_size: (u16, u16),
 }
 
+/// The assistant response currently being streamed
+struct InFlightResponse<'a> {
+    content: &'a str,
+    reasoning: &'a str,
+}
+
+/// Simple markdown and syntax highlighting without external dependencies,
+/// drawn in the colors of a UI theme
 pub struct MarkdownRenderer {
-    // Simple syntax highlighting without external dependencies
+    theme: ThemeColors,
+}
+
+impl Default for MarkdownRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MarkdownRenderer {
     pub fn new() -> Self {
-        Self {}
+        Self::with_theme(ThemeColors::default_theme())
+    }
+
+    fn with_theme(theme: ThemeColors) -> Self {
+        Self { theme }
+    }
+
+    /// Whether `content` has markdown worth rendering: code, emphasis,
+    /// headings, blockquotes or lists
+    pub fn has_markup(content: &str) -> bool {
+        content.contains(['`', '*', '_', '#', '>'])
+            || content.lines().any(|line| {
+                let line = line.trim_start();
+                line.starts_with("- ")
+                    || line.starts_with("+ ")
+                    || line
+                        .split_once(". ")
+                        .is_some_and(|(number, _)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+            })
+    }
+
+    fn heading_style(&self, level: HeadingLevel) -> Style {
+        let style = Style::default().add_modifier(Modifier::BOLD);
+        match level {
+            HeadingLevel::H1 => style.fg(self.theme.primary).add_modifier(Modifier::UNDERLINED),
+            HeadingLevel::H2 => style.fg(self.theme.primary),
+            _ => style.fg(self.theme.secondary),
+        }
+    }
+
+    /// Start a new line unless already at the start of one
+    fn line_break(spans: &mut Vec<Span<'_>>) {
+        if spans.last().is_some_and(|span| span.content != "\n") {
+            spans.push(Span::raw("\n"));
+        }
     }
 
-    pub fn render_to_spans(&self, content: &str) -> Vec<Span> {
+    fn quote_prefix(&self, spans: &mut Vec<Span<'_>>, quote_depth: usize) {
+        if quote_depth > 0 {
+            spans.push(Span::styled("│ ".repeat(quote_depth), Style::default().fg(self.theme.border)));
+        }
+    }
+
+    pub fn render_to_spans(&self, content: &str) -> Vec<Span<'_>> {
         let mut spans = Vec::new();
         let parser = Parser::new(content);
         let mut in_code_block = false;
         let mut code_language = String::new();
         let mut code_content = String::new();
+        // Depths rather than flags, so nested emphasis ends at the right tag
+        let mut strong_depth = 0usize;
+        let mut emphasis_depth = 0usize;
+        let mut quote_depth = 0usize;
+        let mut heading: Option<HeadingLevel> = None;
+        // The next number of each open list, or None for bullet lists
+        let mut lists: Vec<Option<u64>> = Vec::new();
+        // Set after a list marker, whose paragraph continues on the same line
+        let mut item_started = false;
 
         for event in parser {
+            if !matches!(event, MarkdownEvent::Start(Tag::Paragraph)) {
+                item_started = false;
+            }
+            let mut emphasis = Modifier::empty();
+            if strong_depth > 0 {
+                emphasis |= Modifier::BOLD;
+            }
+            if emphasis_depth > 0 {
+                emphasis |= Modifier::ITALIC;
+            }
+
             match event {
                 MarkdownEvent::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) => {
                     in_code_block = true;
                     code_language = lang.to_string();
                     code_content.clear();
                 }
-                MarkdownEvent::End(Tag::CodeBlock(_)) => {
-                    if in_code_block {
-                        spans.extend(self.highlight_code(&code_content, &code_language));
-                        in_code_block = false;
-                    }
+                MarkdownEvent::End(Tag::CodeBlock(_)) if in_code_block => {
+                    spans.extend(self.highlight_code(&code_content, &code_language));
+                    in_code_block = false;
                 }
                 MarkdownEvent::Text(text) => {
                     if in_code_block {
                         code_content.push_str(&text);
                     } else {
-                        spans.push(Span::raw(text.to_string()));
+                        let mut style = heading.map_or_else(Style::default, |level| self.heading_style(level)).add_modifier(emphasis);
+                        if quote_depth > 0 {
+                            style = style.add_modifier(Modifier::DIM);
+                        }
+                        spans.push(Span::styled(text.to_string(), style));
                     }
                 }
                 MarkdownEvent::Code(code) => {
                     spans.push(Span::styled(
                         code.to_string(),
-                        Style::default().fg(Color::Yellow).bg(Color::DarkGray)
+                        Style::default().fg(self.theme.accent).bg(self.theme.code_background).add_modifier(emphasis)
                     ));
                 }
                 MarkdownEvent::Start(Tag::Strong) => {
-                    // We'll handle this in a more sophisticated way later
+                    strong_depth += 1;
                 }
                 MarkdownEvent::End(Tag::Strong) => {
-                    // We'll handle this in a more sophisticated way later
+                    strong_depth = strong_depth.saturating_sub(1);
                 }
                 MarkdownEvent::Start(Tag::Emphasis) => {
-                    // We'll handle this in a more sophisticated way later
+                    emphasis_depth += 1;
                 }
                 MarkdownEvent::End(Tag::Emphasis) => {
-                    // We'll handle this in a more sophisticated way later
+                    emphasis_depth = emphasis_depth.saturating_sub(1);
+                }
+                MarkdownEvent::Start(Tag::Heading(level, _, _)) => {
+                    Self::line_break(&mut spans);
+                    spans.push(Span::styled(format!("{} ", "#".repeat(level as usize)), self.heading_style(level)));
+                    heading = Some(level);
+                }
+                MarkdownEvent::End(Tag::Heading(..)) => {
+                    heading = None;
+                    Self::line_break(&mut spans);
+                }
+                MarkdownEvent::Start(Tag::Paragraph) if item_started => {
+                    item_started = false;
+                }
+                MarkdownEvent::Start(Tag::Paragraph) => {
+                    Self::line_break(&mut spans);
+                    self.quote_prefix(&mut spans, quote_depth);
+                }
+                MarkdownEvent::End(Tag::Paragraph) => {
+                    Self::line_break(&mut spans);
+                }
+                MarkdownEvent::Start(Tag::BlockQuote) => {
+                    quote_depth += 1;
+                }
+                MarkdownEvent::End(Tag::BlockQuote) => {
+                    quote_depth = quote_depth.saturating_sub(1);
+                }
+                MarkdownEvent::Start(Tag::List(start)) => {
+                    Self::line_break(&mut spans);
+                    lists.push(start);
+                }
+                MarkdownEvent::End(Tag::List(_)) => {
+                    lists.pop();
+                }
+                MarkdownEvent::Start(Tag::Item) => {
+                    Self::line_break(&mut spans);
+                    self.quote_prefix(&mut spans, quote_depth);
+                    let indent = "  ".repeat(lists.len().saturating_sub(1));
+                    let marker = match lists.last_mut() {
+                        Some(Some(number)) => {
+                            let marker = format!("{}{}. ", indent, number);
+                            *number += 1;
+                            marker
+                        }
+                        _ => format!("{}• ", indent),
+                    };
+                    spans.push(Span::styled(marker, Style::default().fg(self.theme.accent)));
+                    item_started = true;
+                }
+                MarkdownEvent::End(Tag::Item) => {
+                    Self::line_break(&mut spans);
                 }
                 MarkdownEvent::SoftBreak | MarkdownEvent::HardBreak => {
                     spans.push(Span::raw("\n"));
+                    self.quote_prefix(&mut spans, quote_depth);
                 }
                 _ => {}
             }
         }
 
+        // Block ends leave a trailing line break that isn't part of the text
+        if spans.last().is_some_and(|span| span.content == "\n") {
+            spans.pop();
+        }
+
         if spans.is_empty() {
             spans.push(Span::raw(content.to_string()));
         }
@@ -205,40 +562,41 @@ impl MarkdownRenderer {
         spans
     }
 
-    fn highlight_code(&self, code: &str, language: &str) -> Vec<Span> {
+    fn highlight_code(&self, code: &str, language: &str) -> Vec<Span<'_>> {
         let mut spans = Vec::new();
         
         // Add code block header
         spans.push(Span::styled(
             format!("```{}\n", language),
-            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+            Style::default().fg(self.theme.primary).add_modifier(Modifier::BOLD)
         ));
 
-        // Simple syntax highlighting based on language
+        // Languages without a keyword highlighter are colored as a whole
         let code_color = match language.to_lowercase().as_str() {
-            "rust" | "rs" => Color::Red,
-            "python" | "py" => Color::Blue,
-            "javascript" | "js" | "typescript" | "ts" => Color::Yellow,
-            "json" => Color::Green,
-            "html" | "xml" => Color::Magenta,
-            "css" => Color::Cyan,
-            "bash" | "sh" | "shell" => Color::Gray,
-            _ => Color::White,
+            "html" | "xml" => self.theme.highlight,
+            "css" => self.theme.primary,
+            _ => self.theme.text,
         };
 
         // Apply basic highlighting
+        let language = language.to_lowercase();
         for line in code.lines() {
-            // Simple keyword highlighting for common languages
-            if language.to_lowercase().as_str() == "rust" {
-                spans.extend(self.highlight_rust_line(line));
-            } else if language.to_lowercase().as_str() == "python" {
-                spans.extend(self.highlight_python_line(line));
-            } else {
-                // Default: just color the whole line
-                spans.push(Span::styled(
-                    line.to_string(),
-                    Style::default().fg(code_color).bg(Color::DarkGray)
-                ));
+            // Keyword highlighting for common languages
+            match language.as_str() {
+                "rust" | "rs" => spans.extend(self.highlight_rust_line(line)),
+                "python" | "py" => spans.extend(self.highlight_python_line(line)),
+                "go" | "golang" => spans.extend(self.highlight_go_line(line)),
+                "javascript" | "js" | "typescript" | "ts" => spans.extend(self.highlight_javascript_line(line)),
+                "c" | "h" | "cpp" | "c++" | "cc" | "hpp" => spans.extend(self.highlight_c_line(line)),
+                "json" => spans.extend(self.highlight_json_line(line)),
+                "bash" | "sh" | "shell" | "zsh" => spans.extend(self.highlight_shell_line(line)),
+                _ => {
+                    // Default: just color the whole line
+                    spans.push(Span::styled(
+                        line.to_string(),
+                        Style::default().fg(code_color).bg(self.theme.code_background)
+                    ));
+                }
             }
             spans.push(Span::raw("\n"));
         }
@@ -246,131 +604,169 @@ impl MarkdownRenderer {
         // Add code block footer
         spans.push(Span::styled(
             "```\n",
-            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+            Style::default().fg(self.theme.primary).add_modifier(Modifier::BOLD)
         ));
 
         spans
     }
 
-    fn highlight_rust_line(&self, line: &str) -> Vec<Span> {
-        let mut spans = Vec::new();
+    fn highlight_rust_line(&self, line: &str) -> Vec<Span<'_>> {
         let keywords = ["fn", "let", "mut", "pub", "struct", "impl", "use", "mod", "if", "else", "match", "for", "while", "loop"];
-        
-        let mut current_word = String::new();
-        let mut in_string = false;
-        let mut chars = line.chars().peekable();
-        
-        while let Some(ch) = chars.next() {
-            if ch == '"' && !in_string {
-                in_string = true;
-                if !current_word.is_empty() {
-                    spans.push(self.style_word(&current_word, &keywords));
-                    current_word.clear();
-                }
-                current_word.push(ch);
-            } else if ch == '"' && in_string {
-                in_string = false;
-                current_word.push(ch);
-                spans.push(Span::styled(current_word.clone(), Style::default().fg(Color::Green).bg(Color::DarkGray)));
-                current_word.clear();
-            } else if in_string {
-                current_word.push(ch);
-            } else if ch.is_whitespace() || "(){}[];,".contains(ch) {
-                if !current_word.is_empty() {
-                    spans.push(self.style_word(&current_word, &keywords));
-                    current_word.clear();
-                }
-                spans.push(Span::styled(ch.to_string(), Style::default().fg(Color::White).bg(Color::DarkGray)));
-            } else {
-                current_word.push(ch);
-            }
-        }
-        
-        if !current_word.is_empty() {
-            spans.push(self.style_word(&current_word, &keywords));
-        }
-        
-        spans
+        self.highlight_line(line, &keywords, &['"'], "(){}[];,")
     }
 
-    fn highlight_python_line(&self, line: &str) -> Vec<Span> {
-        let mut spans = Vec::new();
+    fn highlight_python_line(&self, line: &str) -> Vec<Span<'_>> {
         let keywords = ["def", "class", "if", "else", "elif", "for", "while", "try", "except", "import", "from", "return", "yield"];
-        
+        self.highlight_line(line, &keywords, &['"', '\''], "(){}[];,:")
+    }
+
+    fn highlight_go_line(&self, line: &str) -> Vec<Span<'_>> {
+        let keywords = [
+            "package", "import", "func", "var", "const", "type", "struct", "interface", "map", "chan",
+            "if", "else", "for", "range", "switch", "case", "default", "return", "go", "defer", "select",
+        ];
+        self.highlight_line(line, &keywords, &['"', '\'', '`'], "(){}[];,:")
+    }
+
+    fn highlight_javascript_line(&self, line: &str) -> Vec<Span<'_>> {
+        let keywords = [
+            "function", "const", "let", "var", "class", "extends", "import", "export", "from", "return",
+            "if", "else", "for", "while", "switch", "case", "new", "async", "await", "interface", "type",
+        ];
+        self.highlight_line(line, &keywords, &['"', '\'', '`'], "(){}[];,:")
+    }
+
+    fn highlight_c_line(&self, line: &str) -> Vec<Span<'_>> {
+        let keywords = [
+            "int", "char", "void", "float", "double", "long", "unsigned", "const", "static", "struct",
+            "class", "namespace", "template", "return", "if", "else", "for", "while", "switch", "case",
+            "#include", "#define",
+        ];
+        self.highlight_line(line, &keywords, &['"', '\''], "(){}[];,")
+    }
+
+    fn highlight_json_line(&self, line: &str) -> Vec<Span<'_>> {
+        self.highlight_line(line, &["true", "false", "null"], &['"'], "{}[],:")
+    }
+
+    fn highlight_shell_line(&self, line: &str) -> Vec<Span<'_>> {
+        let keywords = [
+            "if", "then", "else", "elif", "fi", "for", "in", "do", "done", "while", "case", "esac",
+            "function", "export", "local", "return",
+        ];
+        self.highlight_line(line, &keywords, &['"', '\''], "(){}[];|&")
+    }
+
+    /// Split a line into keyword, number, string and plain spans. Strings open
+    /// with any of `quotes` and close with the same quote.
+    fn highlight_line(&self, line: &str, keywords: &[&str], quotes: &[char], delimiters: &str) -> Vec<Span<'_>> {
+        let mut spans = Vec::new();
+
         let mut current_word = String::new();
-        let mut in_string = false;
-        let mut chars = line.chars().peekable();
-        
-        while let Some(ch) = chars.next() {
-            if (ch == '"' || ch == '\'') && !in_string {
-                in_string = true;
+        let mut open_quote: Option<char> = None;
+        for ch in line.chars() {
+            if open_quote.is_none() && quotes.contains(&ch) {
+                open_quote = Some(ch);
                 if !current_word.is_empty() {
-                    spans.push(self.style_word(&current_word, &keywords));
+                    spans.push(self.style_word(&current_word, keywords));
                     current_word.clear();
                 }
                 current_word.push(ch);
-            } else if (ch == '"' || ch == '\'') && in_string {
-                in_string = false;
+            } else if open_quote == Some(ch) {
+                open_quote = None;
                 current_word.push(ch);
-                spans.push(Span::styled(current_word.clone(), Style::default().fg(Color::Green).bg(Color::DarkGray)));
+                spans.push(Span::styled(current_word.clone(), self.code_style(self.theme.success)));
                 current_word.clear();
-            } else if in_string {
+            } else if open_quote.is_some() {
                 current_word.push(ch);
-            } else if ch.is_whitespace() || "(){}[];,:".contains(ch) {
+            } else if ch.is_whitespace() || delimiters.contains(ch) {
                 if !current_word.is_empty() {
-                    spans.push(self.style_word(&current_word, &keywords));
+                    spans.push(self.style_word(&current_word, keywords));
                     current_word.clear();
                 }
-                spans.push(Span::styled(ch.to_string(), Style::default().fg(Color::White).bg(Color::DarkGray)));
+                spans.push(Span::styled(ch.to_string(), self.code_style(self.theme.text)));
             } else {
                 current_word.push(ch);
             }
         }
-        
+
         if !current_word.is_empty() {
-            spans.push(self.style_word(&current_word, &keywords));
+            spans.push(self.style_word(&current_word, keywords));
         }
-        
+
         spans
     }
 
-    fn style_word(&self, word: &str, keywords: &[&str]) -> Span {
+    fn style_word(&self, word: &str, keywords: &[&str]) -> Span<'_> {
         if keywords.contains(&word) {
-            Span::styled(word.to_string(), Style::default().fg(Color::Magenta).bg(Color::DarkGray).add_modifier(Modifier::BOLD))
-        } else if word.chars().all(|c| c.is_ascii_digit()) {
-            Span::styled(word.to_string(), Style::default().fg(Color::Yellow).bg(Color::DarkGray))
+            Span::styled(word.to_string(), self.code_style(self.theme.highlight).add_modifier(Modifier::BOLD))
+        } else if word.starts_with(|c: char| c.is_ascii_digit()) && word.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            Span::styled(word.to_string(), self.code_style(self.theme.accent))
         } else {
-            Span::styled(word.to_string(), Style::default().fg(Color::White).bg(Color::DarkGray))
+            Span::styled(word.to_string(), self.code_style(self.theme.text))
         }
     }
+
+    fn code_style(&self, color: Color) -> Style {
+        Style::default().fg(color).bg(self.theme.code_background)
+    }
 }
 
 impl TerminalUI {
-    pub fn new() -> Result<Self, UIError> {
+    /// Take over the terminal, drawing with the configured theme and saving
+    /// sessions to the configured directory
+    pub fn new(config: &UIConfig) -> Result<Self, UIError> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-        let backend = CrosstermBackend::new(stdout);
+        let backend = CrosstermBackend::new(Box::new(stdout) as Box<dyn io::Write + Send>);
         let terminal = Terminal::new(backend)?;
 
         let initial_size = terminal.size()?;
-        
-        Ok(Self {
+        let mut ui = Self::with_terminal(terminal, true, initial_size);
+        ui.theme = ThemeColors::named(&config.theme);
+        ui.sessions_dir = config.sessions_dir.clone();
+        Ok(ui)
+    }
+
+    /// A UI that draws into a fixed-size buffer and never touches the real
+    /// terminal, so its input handling can be driven without a tty
+    pub fn headless() -> Result<Self, UIError> {
+        let area = Rect::new(0, 0, 80, 24);
+        let backend = CrosstermBackend::new(Box::new(io::sink()) as Box<dyn io::Write + Send>);
+        let terminal = Terminal::with_options(backend, TerminalOptions { viewport: Viewport::Fixed(area) })?;
+        Ok(Self::with_terminal(terminal, false, area))
+    }
+
+    fn with_terminal(terminal: Terminal<UIBackend>, raw_mode: bool, initial_size: Rect) -> Self {
+        Self {
             terminal,
+            raw_mode,
             app_state: AppState::default(),
             message_history: Vec::new(),
             input_buffer: String::new(),
+            input_cursor: 0,
             scroll_offset: 0,
             current_streaming_content: String::new(),
-            markdown_renderer: MarkdownRenderer::new(),
+            current_reasoning_content: String::new(),
             auto_scroll: true,
             progress_animation_frame: 0,
             high_contrast_mode: false,
+            theme: ThemeColors::default_theme(),
+            session: Session::new(""),
+            sessions_dir: UIConfig::default().sessions_dir,
             last_terminal_size: (initial_size.width, initial_size.height),
-        })
+        }
     }
 
-    pub async fn run(&mut self, mut stream_receiver: mpsc::UnboundedReceiver<StreamToken>) -> Result<(), UIError> {
+    /// Run the event loop until the user quits. Each submitted message sends
+    /// the whole conversation on `prompt_sender`, and the reply is expected
+    /// back as tokens on `stream_receiver`, ending with a complete token.
+    pub async fn run(
+        &mut self,
+        prompt_sender: mpsc::UnboundedSender<Prompt>,
+        mut stream_receiver: mpsc::UnboundedReceiver<StreamToken>,
+    ) -> Result<(), UIError> {
         loop {
             self.render_frame()?;
 
@@ -378,27 +774,9 @@ impl TerminalUI {
             if event::poll(std::time::Duration::from_millis(50))? {
                 match event::read()? {
                     Event::Key(key) => {
-                        match self.handle_input(key) {
-                            UIAction::Quit => break,
-                            UIAction::SendMessage(msg) => {
-                                self.add_message(ChatMessage {
-                                    role: MessageRole::User,
-                                    content: msg,
-                                    timestamp: chrono::Utc::now(),
-                                    model: self.app_state.current_model.clone(),
-                                    template_used: self.app_state.active_template.clone(),
-                                    cached: false,
-                                });
-                                self.input_buffer.clear();
-                            }
-                            UIAction::ClearHistory => {
-                                self.message_history.clear();
-                                self.scroll_offset = 0;
-                            }
-                            UIAction::ChangeModel(model) => {
-                                self.app_state.current_model = model;
-                            }
-                            _ => {}
+                        let action = self.handle_input(key);
+                        if !self.apply_action(action, &prompt_sender) {
+                            break;
                         }
                     }
                     Event::Resize(width, height) => {
@@ -436,13 +814,174 @@ impl TerminalUI {
         Ok(())
     }
 
+    fn insert_char(&mut self, c: char) {
+        self.input_buffer.insert(self.input_cursor, c);
+        self.input_cursor += c.len_utf8();
+    }
+
+    /// Byte offset of the char before the cursor, if any
+    fn previous_boundary(&self) -> Option<usize> {
+        self.input_buffer[..self.input_cursor].char_indices().next_back().map(|(i, _)| i)
+    }
+
+    /// Carry out an action from `handle_input`. Returns false once the UI
+    /// should quit.
+    pub fn apply_action(&mut self, action: UIAction, prompt_sender: &mpsc::UnboundedSender<Prompt>) -> bool {
+        match action {
+            UIAction::Quit => return false,
+            // Hold new input until the current reply has finished
+            UIAction::SendMessage(_) | UIAction::Regenerate(_) if self.app_state.is_streaming => {}
+            UIAction::SendMessage(msg) => {
+                self.add_message(ChatMessage {
+                    role: MessageRole::User,
+                    content: msg,
+                    timestamp: chrono::Utc::now(),
+                    model: self.app_state.current_model.clone(),
+                    template_used: self.app_state.active_template.clone(),
+                    cached: false,
+                    reasoning: None,
+                });
+                self.input_buffer.clear();
+                self.input_cursor = 0;
+                self.send_conversation(prompt_sender);
+            }
+            UIAction::Regenerate(model) => {
+                // Everything after the last user message is the answer being replaced
+                match self.message_history.iter().rposition(|m| m.role == MessageRole::User) {
+                    Some(last_user) => {
+                        self.message_history.truncate(last_user + 1);
+                        if let Some(model) = model {
+                            self.app_state.current_model = model.clone();
+                            self.app_state.selected_model = Some(model);
+                        }
+                        self.send_conversation(prompt_sender);
+                    }
+                    None => self.add_notice("There is no message to answer again".to_string()),
+                }
+            }
+            UIAction::ClearHistory => {
+                self.message_history.clear();
+                self.scroll_offset = 0;
+            }
+            UIAction::ChangeModel(model) => {
+                self.add_notice(format!("Using model '{}'", model));
+                self.app_state.current_model = model.clone();
+                self.app_state.selected_model = Some(model);
+            }
+            UIAction::LoadTemplate(name) => {
+                self.add_notice(format!("Using template '{}'", name));
+                self.app_state.active_template = Some(name);
+            }
+            UIAction::SaveHistory(path) => {
+                let notice = match self.save_transcript(&path) {
+                    Ok(()) => format!("Saved {} messages to {}", self.message_history.len(), path.display()),
+                    Err(e) => format!("Could not save to {}: {}", path.display(), e),
+                };
+                self.add_notice(notice);
+            }
+            UIAction::ResumeSession(id) => {
+                let notice = match self.save_session().and_then(|_| Session::load(&self.sessions_dir, &id)) {
+                    Ok(session) => {
+                        let count = session.messages.len();
+                        self.resume(session);
+                        format!("Resumed session {} ({} messages)", id, count)
+                    }
+                    Err(e) => format!("Could not resume session {}: {}", id, e),
+                };
+                self.add_notice(notice);
+            }
+            UIAction::CommandError(reason) => self.add_notice(reason),
+            UIAction::None => {}
+        }
+        true
+    }
+
+    /// Continue `session`: its messages replace the history and its model
+    /// becomes the current one. Replies come from the backend's default model
+    /// until another is picked with `/model`.
+    pub fn resume(&mut self, session: Session) {
+        self.message_history = session.messages.clone();
+        self.app_state.current_model = session.model.clone();
+        self.app_state.selected_model = None;
+        self.scroll_offset = self.message_history.len();
+        self.session = Session { messages: Vec::new(), ..session };
+    }
+
+    /// The current session, including the messages so far
+    pub fn session(&self) -> Session {
+        Session {
+            model: self.app_state.current_model.clone(),
+            messages: self.message_history.clone(),
+            ..self.session.clone()
+        }
+    }
+
+    /// Save the current session unless nothing has been said yet
+    pub fn save_session(&self) -> Result<Option<PathBuf>, SessionError> {
+        if self.message_history.is_empty() {
+            return Ok(None);
+        }
+        self.session().save(&self.sessions_dir).map(Some)
+    }
+
+    fn send_conversation(&mut self, prompt_sender: &mpsc::UnboundedSender<Prompt>) {
+        let prompt = Prompt {
+            messages: self.conversation(),
+            model: self.app_state.selected_model.clone(),
+            template: self.app_state.active_template.clone(),
+        };
+        if prompt_sender.send(prompt).is_ok() {
+            self.app_state.is_streaming = true;
+        } else {
+            self.add_notice("No backend is listening for messages".to_string());
+        }
+    }
+
+    /// Show a message from the UI itself; it is never sent to the backend
+    fn add_notice(&mut self, content: String) {
+        self.add_message(ChatMessage {
+            role: MessageRole::System,
+            content,
+            timestamp: chrono::Utc::now(),
+            model: self.app_state.current_model.clone(),
+            template_used: None,
+            cached: false,
+            reasoning: None,
+        });
+    }
+
+    /// Write the conversation as Markdown
+    fn save_transcript(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, export_markdown(&self.message_history))
+    }
+
+    /// The chat so far as backend messages. System notices from the UI
+    /// itself are left out.
+    fn conversation(&self) -> Vec<Message> {
+        self.message_history
+            .iter()
+            .filter_map(|message| {
+                let role = match message.role {
+                    MessageRole::User => "user",
+                    MessageRole::Assistant => "assistant",
+                    MessageRole::System => return None,
+                };
+                Some(Message {
+                    role: role.to_string(),
+                    content: message.content.clone(),
+                    images: None,
+                })
+            })
+            .collect()
+    }
+
     pub fn handle_input(&mut self, key: KeyEvent) -> UIAction {
         match key.code {
             // Quit commands
-            KeyCode::Char('q') if key.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
+            KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 UIAction::Quit
             }
-            KeyCode::Char('c') if key.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 UIAction::Quit
             }
             KeyCode::Esc => {
@@ -450,31 +989,62 @@ impl TerminalUI {
             }
             
             // Clear history
-            KeyCode::Char('l') if key.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) => {
+            KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 UIAction::ClearHistory
             }
             
-            // Send message
+            // Shift+Enter or Alt+Enter starts a new line; not every terminal
+            // reports Shift with Enter, so Alt+Enter is the reliable one
+            KeyCode::Enter if key.modifiers.intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) => {
+                self.insert_char('\n');
+                UIAction::None
+            }
+
+            // Send message, or run a slash command; "//" sends a literal "/"
             KeyCode::Enter => {
-                if !self.input_buffer.trim().is_empty() {
-                    UIAction::SendMessage(self.input_buffer.clone())
-                } else {
+                let input = self.input_buffer.trim();
+                if input.is_empty() || input == "/" {
                     UIAction::None
+                } else if let Some(literal) = self.input_buffer.strip_prefix("//") {
+                    UIAction::SendMessage(format!("/{}", literal))
+                } else if input.starts_with('/') {
+                    let action = parse_command(input);
+                    self.input_buffer.clear();
+                    self.input_cursor = 0;
+                    action
+                } else {
+                    UIAction::SendMessage(self.input_buffer.clone())
                 }
             }
             
             // Input editing
             KeyCode::Backspace => {
-                self.input_buffer.pop();
+                if let Some(previous) = self.previous_boundary() {
+                    self.input_buffer.remove(previous);
+                    self.input_cursor = previous;
+                }
                 UIAction::None
             }
             KeyCode::Delete => {
-                // For now, just treat as backspace
-                self.input_buffer.pop();
+                if self.input_cursor < self.input_buffer.len() {
+                    self.input_buffer.remove(self.input_cursor);
+                }
                 UIAction::None
             }
             KeyCode::Char(c) => {
-                self.input_buffer.push(c);
+                self.insert_char(c);
+                UIAction::None
+            }
+            KeyCode::Left => {
+                if let Some(previous) = self.previous_boundary() {
+                    self.input_cursor = previous;
+                }
+                UIAction::None
+            }
+            KeyCode::Right => {
+                if let Some(c) = self.input_buffer[self.input_cursor..].chars().next() {
+                    self.input_cursor += c.len_utf8();
+                }
                 UIAction::None
             }
             
@@ -500,14 +1070,23 @@ impl TerminalUI {
                 self.scroll_offset = (self.scroll_offset + 10).min(max_scroll);
                 UIAction::None
             }
-            KeyCode::Home => {
+            KeyCode::Home if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 self.scroll_offset = 0;
                 UIAction::None
             }
-            KeyCode::End => {
+            KeyCode::End if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 self.scroll_offset = self.message_history.len().saturating_sub(1);
                 UIAction::None
             }
+            // Home/End move within the current input line
+            KeyCode::Home => {
+                self.input_cursor = self.input_buffer[..self.input_cursor].rfind('\n').map_or(0, |i| i + 1);
+                UIAction::None
+            }
+            KeyCode::End => {
+                self.input_cursor += self.input_buffer[self.input_cursor..].find('\n').unwrap_or(self.input_buffer.len() - self.input_cursor);
+                UIAction::None
+            }
             
             // Model switching (F1-F4 for quick model selection)
             KeyCode::F(1) => UIAction::ChangeModel("llama3.2".to_string()),
@@ -535,9 +1114,15 @@ impl TerminalUI {
         let app_state = self.app_state.clone();
         let message_history = self.message_history.clone();
         let input_buffer = self.input_buffer.clone();
+        let input_cursor = self.input_cursor;
         let current_streaming_content = self.current_streaming_content.clone();
+        let current_reasoning_content = self.current_reasoning_content.clone();
         let progress_indicator = self.get_progress_indicator();
         let high_contrast = self.high_contrast_mode;
+        let theme = self.active_theme();
+        let renderer = MarkdownRenderer::with_theme(theme.clone());
+        let scroll_offset = self.scroll_offset;
+        let mut first_visible = scroll_offset;
         
         // Update animation frame for smooth progress indicator
         if self.app_state.is_streaming {
@@ -562,11 +1147,13 @@ impl TerminalUI {
                     Constraint::Length(2),  // Compact input
                 ]
             } else {
-                // Full layout for normal terminals
+                // Full layout for normal terminals; the input grows with
+                // multi-line prompts up to a few lines
+                let input_lines = input_buffer.split('\n').count().min(MAX_INPUT_LINES) as u16;
                 vec![
                     Constraint::Length(3),  // Status bar
                     Constraint::Min(0),     // Chat history
-                    Constraint::Length(3),  // Input area
+                    Constraint::Length(input_lines + 2),  // Input area
                 ]
             };
 
@@ -575,48 +1162,29 @@ impl TerminalUI {
                 .constraints(constraints)
                 .split(size);
 
-            Self::render_status_bar_static(f, chunks[0], &app_state, progress_indicator, high_contrast);
-            Self::render_chat_history_with_renderer(f, chunks[1], &message_history, &current_streaming_content, &self.markdown_renderer, high_contrast, progress_indicator);
-            Self::render_input_area_static(f, chunks[2], &input_buffer, high_contrast);
+            Self::render_status_bar_static(f, chunks[0], &app_state, progress_indicator, high_contrast, &theme);
+            let in_flight = InFlightResponse {
+                content: &current_streaming_content,
+                reasoning: &current_reasoning_content,
+            };
+            first_visible = Self::render_chat_history_with_renderer(f, chunks[1], &message_history, in_flight, &renderer, &theme, progress_indicator, scroll_offset);
+            Self::render_input_area_static(f, chunks[2], &input_buffer, input_cursor, &theme);
+            Self::render_command_popup(f, chunks[1], &input_buffer, &theme);
         })?;
 
+        // Keep the offset within what can actually be shown, so scrolling
+        // back up takes effect on the first key press
+        self.scroll_offset = first_visible;
+
         Ok(())
     }
 
-    fn render_status_bar_static(f: &mut Frame, area: Rect, app_state: &AppState, progress_indicator: &str, high_contrast: bool) {
+    fn render_status_bar_static(f: &mut Frame, area: Rect, app_state: &AppState, progress_indicator: &str, high_contrast: bool, theme: &ThemeColors) {
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
             .split(area);
 
-        let theme = if high_contrast {
-            ThemeColors {
-                primary: Color::White,
-                secondary: Color::Black,
-                accent: Color::White,
-                background: Color::Black,
-                text: Color::White,
-                border: Color::White,
-                success: Color::White,
-                warning: Color::White,
-                error: Color::White,
-                info: Color::White,
-            }
-        } else {
-            ThemeColors {
-                primary: Color::Cyan,
-                secondary: Color::Blue,
-                accent: Color::Yellow,
-                background: Color::Black,
-                text: Color::White,
-                border: Color::Gray,
-                success: Color::Green,
-                warning: Color::Yellow,
-                error: Color::Red,
-                info: Color::Blue,
-            }
-        };
-
         // Main status info
         let status_text = format!(
             "Model: {} | Streaming: {} {} | Cache: {:.1}% hit rate ({} entries) | Template: {} | Mode: {}",
@@ -639,25 +1207,26 @@ impl TerminalUI {
         // Keyboard shortcuts
         let shortcuts = Paragraph::new("Ctrl+Q: Quit | Ctrl+L: Clear | F1-F4: Models | F5: Auto-scroll | F6: High contrast | ↑↓: Scroll")
             .block(Block::default().borders(Borders::ALL).title("Shortcuts"))
-            .style(Style::default().fg(if high_contrast { Color::White } else { Color::Gray }))
+            .style(Style::default().fg(theme.border))
             .wrap(Wrap { trim: true });
 
         f.render_widget(shortcuts, chunks[1]);
     }
 
-    fn render_chat_history_static(f: &mut Frame, area: Rect, message_history: &[ChatMessage], current_streaming_content: &str) {
-        Self::render_chat_history_with_renderer(f, area, message_history, current_streaming_content, &MarkdownRenderer::new(), false, "⚫");
-    }
-
+    /// Render the messages starting at `scroll_offset`, clamped so the last
+    /// page stays full, with a scrollbar when they don't all fit. Returns the
+    /// index of the first message shown.
+    #[allow(clippy::too_many_arguments)]
     fn render_chat_history_with_renderer(
         f: &mut Frame, 
         area: Rect, 
         message_history: &[ChatMessage], 
-        current_streaming_content: &str,
+        in_flight: InFlightResponse,
         renderer: &MarkdownRenderer,
-        high_contrast: bool,
-        progress_indicator: &str
-    ) {
+        theme: &ThemeColors,
+        progress_indicator: &str,
+        scroll_offset: usize,
+    ) -> usize {
         let messages: Vec<ListItem> = message_history
             .iter()
             .enumerate()
@@ -666,29 +1235,32 @@ impl TerminalUI {
                 let mut spans = vec![
                     Span::styled(
                         format!("[{}] {}: ", timestamp, msg.role.as_str()), 
-                        Style::default().fg(msg.role.color(high_contrast)).add_modifier(Modifier::BOLD)
+                        Style::default().fg(theme.role_color(&msg.role)).add_modifier(Modifier::BOLD)
                     )
                 ];
                 
                 // Add indicators
                 if msg.cached {
-                    spans.push(Span::styled("📋 ", Style::default().fg(if high_contrast { Color::White } else { Color::Blue })));
+                    spans.push(Span::styled("📋 ", Style::default().fg(theme.info)));
                 }
                 if msg.template_used.is_some() {
-                    spans.push(Span::styled("📝 ", Style::default().fg(if high_contrast { Color::White } else { Color::Magenta })));
+                    spans.push(Span::styled("📝 ", Style::default().fg(theme.highlight)));
+                }
+                if let Some(reasoning) = &msg.reasoning {
+                    spans.push(Self::reasoning_span(reasoning));
                 }
                 
                 // Render message content with markdown support
-                if msg.role == MessageRole::Assistant && (msg.content.contains("```") || msg.content.contains("`")) {
-                    // Use markdown rendering for assistant messages that might contain code
+                if msg.role == MessageRole::Assistant && MarkdownRenderer::has_markup(&msg.content) {
+                    // Use markdown rendering for assistant messages that might contain code or emphasis
                     let mut content_spans = renderer.render_to_spans(&msg.content);
                     spans.append(&mut content_spans);
                 } else {
                     // For user messages or simple text, just add as raw text but handle line breaks
-                    let content = if msg.content.len() > 200 {
-                        format!("{}...", &msg.content[..197])
-                    } else {
-                        msg.content.clone()
+                    // Cut on a char boundary; multi-byte text would panic on a byte index
+                    let content = match msg.content.char_indices().nth(197) {
+                        Some((cut, _)) if msg.content.chars().count() > 200 => format!("{}...", &msg.content[..cut]),
+                        _ => msg.content.clone(),
                     };
                     
                     // Handle line breaks in content
@@ -712,15 +1284,19 @@ impl TerminalUI {
 
         // Add current streaming content if any
         let mut all_messages = messages;
-        if !current_streaming_content.is_empty() {
+        let InFlightResponse { content: current_streaming_content, reasoning: current_reasoning_content } = in_flight;
+        if !current_streaming_content.is_empty() || !current_reasoning_content.is_empty() {
             let timestamp = chrono::Utc::now().format("%H:%M:%S");
             let mut streaming_spans = vec![
-                Span::styled(format!("[{}] Assistant: ", timestamp), Style::default().fg(if high_contrast { Color::White } else { Color::Green }).add_modifier(Modifier::BOLD)),
-                Span::styled(format!("{} ", progress_indicator), Style::default().fg(if high_contrast { Color::White } else { Color::Yellow })),
+                Span::styled(format!("[{}] Assistant: ", timestamp), Style::default().fg(theme.success).add_modifier(Modifier::BOLD)),
+                Span::styled(format!("{} ", progress_indicator), Style::default().fg(theme.accent)),
             ];
+            if !current_reasoning_content.is_empty() {
+                streaming_spans.push(Self::reasoning_span(current_reasoning_content));
+            }
             
-            // Apply markdown rendering to streaming content if it contains code
-            if current_streaming_content.contains("```") || current_streaming_content.contains("`") {
+            // Apply markdown rendering to streaming content if it contains code or emphasis
+            if MarkdownRenderer::has_markup(current_streaming_content) {
                 let mut content_spans = renderer.render_to_spans(current_streaming_content);
                 streaming_spans.append(&mut content_spans);
             } else {
@@ -737,62 +1313,145 @@ impl TerminalUI {
             &format!("Chat History ({} messages) - Markdown & syntax highlighting enabled", message_history.len())
         };
 
-        let messages_list = List::new(all_messages)
+        // Each message takes one row inside the borders
+        let visible_rows = area.height.saturating_sub(2) as usize;
+        let total = all_messages.len();
+        let max_first = total.saturating_sub(visible_rows);
+        let first_visible = scroll_offset.min(max_first);
+        let visible_messages: Vec<ListItem> = all_messages.into_iter().skip(first_visible).collect();
+
+        let messages_list = List::new(visible_messages)
             .block(Block::default().borders(Borders::ALL).title(title))
-            .style(Style::default().fg(if high_contrast { Color::White } else { Color::White }));
+            .style(Style::default().fg(theme.text));
 
         f.render_widget(messages_list, area);
+
+        // Nothing to scroll when everything fits. Otherwise scale the position
+        // so the thumb reaches the end of the track exactly at the last page.
+        if let Some(position) = (first_visible * total).checked_div(max_first) {
+            let mut scrollbar_state = ScrollbarState::new(total)
+                .viewport_content_length(visible_rows)
+                .position(position);
+            let scrollbar = Scrollbar::default()
+                .orientation(ScrollbarOrientation::VerticalRight)
+                .style(Style::default().fg(theme.border));
+            // Draw over the right border, between the corners
+            f.render_stateful_widget(scrollbar, area.inner(&Margin { vertical: 1, horizontal: 0 }), &mut scrollbar_state);
+        }
+
+        first_visible
+    }
+
+    /// List the slash commands matching a partly typed command, at the
+    /// bottom of `area` just above the input box
+    fn render_command_popup(f: &mut Frame, area: Rect, input_buffer: &str, theme: &ThemeColors) {
+        let suggestions = command_suggestions(input_buffer);
+        if suggestions.is_empty() {
+            return;
+        }
+
+        let height = (suggestions.len() as u16 + 2).min(area.height);
+        let width = 50.min(area.width);
+        let popup = Rect::new(area.x + 1, area.y + area.height - height, width, height);
+
+        let items: Vec<ListItem> = suggestions
+            .iter()
+            .map(|(usage, description)| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{:<18}", usage), Style::default().fg(theme.primary).add_modifier(Modifier::BOLD)),
+                    Span::styled(*description, Style::default().fg(theme.text)),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Commands")
+                .border_style(Style::default().fg(theme.accent)),
+        );
+        f.render_widget(Clear, popup);
+        f.render_widget(list, popup);
     }
 
-    fn render_input_area_static(f: &mut Frame, area: Rect, input_buffer: &str, high_contrast: bool) {
+    /// Reasoning is dimmed so it reads as separate from the answer
+    fn reasoning_span(reasoning: &str) -> Span<'static> {
+        Span::styled(
+            format!("💭 {} ", reasoning.trim()),
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM | Modifier::ITALIC),
+        )
+    }
+
+    fn render_input_area_static(f: &mut Frame, area: Rect, input_buffer: &str, input_cursor: usize, theme: &ThemeColors) {
         let title = if input_buffer.is_empty() {
-            "Input (Type your message and press Enter to send)"
+            "Input (Type your message and press Enter to send, Alt+Enter for a new line)"
         } else {
-            &format!("Input ({} chars) - Press Enter to send", input_buffer.len())
+            &format!("Input ({} chars) - Press Enter to send", input_buffer.chars().count())
         };
 
-        let text_color = if high_contrast { Color::White } else { Color::White };
-        let border_color = if high_contrast { Color::White } else { Color::Gray };
+        // Scroll the text so the cursor stays inside the borders
+        let (row, column) = Self::cursor_position(input_buffer, input_cursor);
+        let inner_width = area.width.saturating_sub(2).max(1);
+        let inner_height = area.height.saturating_sub(2).max(1);
+        let scroll = (
+            row.saturating_sub(inner_height - 1),
+            column.saturating_sub(inner_width - 1),
+        );
+
+        let text_color = theme.text;
+        let border_color = theme.border;
 
         let input = Paragraph::new(input_buffer)
             .block(Block::default()
                 .borders(Borders::ALL)
                 .title(title)
                 .border_style(Style::default().fg(border_color)))
-            .wrap(Wrap { trim: true })
+            .scroll(scroll)
             .style(Style::default().fg(text_color));
 
         f.render_widget(input, area);
 
-        // Set cursor position - handle wrapping for long input
-        let cursor_x = if input_buffer.len() as u16 + 1 < area.width - 2 {
-            area.x + input_buffer.len() as u16 + 1
-        } else {
-            area.x + (area.width - 2)
-        };
+        f.set_cursor(area.x + 1 + column - scroll.1, area.y + 1 + row - scroll.0);
+    }
 
-        f.set_cursor(cursor_x, area.y + 1);
+    /// Row and column of the cursor within the input, in chars
+    fn cursor_position(input_buffer: &str, input_cursor: usize) -> (u16, u16) {
+        let before = &input_buffer[..input_cursor];
+        let row = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count();
+        (row as u16, column as u16)
     }
 
     pub fn update_streaming_content(&mut self, token: StreamToken) {
+        let buffer = match token.kind {
+            TokenKind::Answer => &mut self.current_streaming_content,
+            TokenKind::Reasoning => &mut self.current_reasoning_content,
+        };
+        buffer.push_str(&token.content);
+
         if token.is_complete {
             // Streaming is complete, add the final message
+            let content = std::mem::take(&mut self.current_streaming_content);
+            let reasoning = std::mem::take(&mut self.current_reasoning_content);
             self.add_message(ChatMessage {
                 role: MessageRole::Assistant,
-                content: self.current_streaming_content.clone(),
+                content,
                 timestamp: chrono::Utc::now(),
                 model: self.app_state.current_model.clone(),
                 template_used: self.app_state.active_template.clone(),
                 cached: false,
+                reasoning: (!reasoning.is_empty()).then_some(reasoning),
             });
-            self.current_streaming_content.clear();
             self.app_state.is_streaming = false;
         } else {
-            // Accumulate streaming content
-            self.current_streaming_content.push_str(&token.content);
             self.app_state.is_streaming = true;
             // Update animation frame for progress indicator
             self.progress_animation_frame = (self.progress_animation_frame + 1) % 4;
+            // Follow the reply as it grows below the last message
+            if self.auto_scroll {
+                self.scroll_offset = self.message_history.len();
+            }
         }
     }
 
@@ -810,36 +1469,6 @@ impl TerminalUI {
         }
     }
 
-    fn get_theme_colors(&self) -> ThemeColors {
-        if self.high_contrast_mode {
-            ThemeColors {
-                primary: Color::White,
-                secondary: Color::Black,
-                accent: Color::White,
-                background: Color::Black,
-                text: Color::White,
-                border: Color::White,
-                success: Color::White,
-                warning: Color::White,
-                error: Color::White,
-                info: Color::White,
-            }
-        } else {
-            ThemeColors {
-                primary: Color::Cyan,
-                secondary: Color::Blue,
-                accent: Color::Yellow,
-                background: Color::Black,
-                text: Color::White,
-                border: Color::Gray,
-                success: Color::Green,
-                warning: Color::Yellow,
-                error: Color::Red,
-                info: Color::Blue,
-            }
-        }
-    }
-
     pub fn add_message(&mut self, message: ChatMessage) {
         self.message_history.push(message);
         // Auto-scroll to bottom if enabled
@@ -852,7 +1481,24 @@ impl TerminalUI {
         self.app_state = state;
     }
 
+    /// High-contrast mode overrides the configured theme
+    fn active_theme(&self) -> ThemeColors {
+        if self.high_contrast_mode {
+            ThemeColors::high_contrast()
+        } else {
+            self.theme.clone()
+        }
+    }
+
+    pub fn message_history(&self) -> &[ChatMessage] {
+        &self.message_history
+    }
+
     fn cleanup(&mut self) -> Result<(), UIError> {
+        if !self.raw_mode {
+            return Ok(());
+        }
+        self.raw_mode = false;
         disable_raw_mode()?;
         execute!(
             self.terminal.backend_mut(),
@@ -868,4 +1514,415 @@ impl Drop for TerminalUI {
     fn drop(&mut self) {
         let _ = self.cleanup();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, buffer::Buffer};
+
+    fn message(content: &str) -> ChatMessage {
+        ChatMessage {
+            role: MessageRole::User,
+            content: content.to_string(),
+            timestamp: chrono::Utc::now(),
+            model: "test".to_string(),
+            template_used: None,
+            cached: false,
+            reasoning: None,
+        }
+    }
+
+    fn row_text(buffer: &Buffer, y: u16) -> String {
+        (0..buffer.area.width).map(|x| buffer.get(x, y).symbol.as_str()).collect()
+    }
+
+    fn column_text(buffer: &Buffer, x: u16) -> String {
+        (0..buffer.area.height).map(|y| buffer.get(x, y).symbol.as_str()).collect()
+    }
+
+    /// Render `history` into a 60x6 buffer, which has four message rows
+    fn render_buffer(history: &[ChatMessage], scroll_offset: usize) -> (usize, Buffer) {
+        let mut terminal = Terminal::new(TestBackend::new(60, 6)).unwrap();
+        let renderer = MarkdownRenderer::new();
+        let mut first_visible = 0;
+        terminal
+            .draw(|f| {
+                let in_flight = InFlightResponse { content: "", reasoning: "" };
+                first_visible = TerminalUI::render_chat_history_with_renderer(
+                    f, f.size(), history, in_flight, &renderer, &ThemeColors::default_theme(), "", scroll_offset,
+                );
+            })
+            .unwrap();
+        (first_visible, terminal.backend().buffer().clone())
+    }
+
+    /// The first visible message index and the first message row
+    fn render_history(history: &[ChatMessage], scroll_offset: usize) -> (usize, String) {
+        let (first_visible, buffer) = render_buffer(history, scroll_offset);
+        (first_visible, row_text(&buffer, 1))
+    }
+
+    #[test]
+    fn test_chat_history_scrolls_to_offset() {
+        let history: Vec<ChatMessage> = (0..10).map(|i| message(&format!("message {}", i))).collect();
+
+        let (first, row) = render_history(&history, 0);
+        assert_eq!(first, 0);
+        assert!(row.contains("message 0"), "{}", row);
+
+        let (first, row) = render_history(&history, 3);
+        assert_eq!(first, 3);
+        assert!(row.contains("message 3"), "{}", row);
+
+        // Offsets past the end keep the last page full
+        let (first, row) = render_history(&history, 9);
+        assert_eq!(first, 6);
+        assert!(row.contains("message 6"), "{}", row);
+    }
+
+    #[test]
+    fn test_scrollbar_shown_only_when_history_overflows() {
+        let history: Vec<ChatMessage> = (0..10).map(|i| message(&format!("message {}", i))).collect();
+
+        let (_, buffer) = render_buffer(&history, 0);
+        assert_eq!(column_text(&buffer, 59), "┐▲█║▼┘");
+
+        let (_, buffer) = render_buffer(&history, 9);
+        assert_eq!(column_text(&buffer, 59), "┐▲║█▼┘");
+
+        // Everything fits, so the plain border is drawn
+        let (_, buffer) = render_buffer(&history[..3], 0);
+        assert_eq!(column_text(&buffer, 59), "┐││││┘");
+    }
+
+    #[test]
+    fn test_long_multibyte_messages_are_truncated() {
+        // 150 chars but 300 bytes: short enough to show in full
+        let (_, row) = render_history(&[message(&"é".repeat(150))], 0);
+        assert!(row.contains("ééé"), "{}", row);
+
+        // Byte 197 falls inside a character here
+        let (_, row) = render_history(&[message(&"日本".repeat(150))], 0);
+        assert!(row.contains("日"), "{}", row);
+    }
+
+    fn press(ui: &mut TerminalUI, code: KeyCode) -> UIAction {
+        ui.handle_input(KeyEvent::from(code))
+    }
+
+    fn type_text(ui: &mut TerminalUI, text: &str) {
+        for c in text.chars() {
+            press(ui, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn test_cursor_movement_and_mid_string_editing() {
+        let mut ui = TerminalUI::headless().unwrap();
+        type_text(&mut ui, "helo");
+
+        press(&mut ui, KeyCode::Left);
+        type_text(&mut ui, "l");
+        assert_eq!(ui.input_buffer, "hello");
+        assert_eq!(ui.input_cursor, 4);
+
+        press(&mut ui, KeyCode::Home);
+        press(&mut ui, KeyCode::Delete);
+        assert_eq!(ui.input_buffer, "ello");
+        type_text(&mut ui, "j");
+        assert_eq!(ui.input_buffer, "jello");
+
+        press(&mut ui, KeyCode::Right);
+        press(&mut ui, KeyCode::Backspace);
+        assert_eq!(ui.input_buffer, "jllo");
+        assert_eq!(ui.input_cursor, 1);
+
+        press(&mut ui, KeyCode::End);
+        type_text(&mut ui, "é!");
+        press(&mut ui, KeyCode::Left);
+        press(&mut ui, KeyCode::Left);
+        press(&mut ui, KeyCode::Backspace);
+        assert_eq!(ui.input_buffer, "jllé!");
+        press(&mut ui, KeyCode::Right);
+        assert_eq!(&ui.input_buffer[ui.input_cursor..], "!");
+    }
+
+    #[test]
+    fn test_alt_enter_inserts_newline_and_enter_sends() {
+        let mut ui = TerminalUI::headless().unwrap();
+        type_text(&mut ui, "first");
+        let action = ui.handle_input(KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT));
+        assert!(matches!(action, UIAction::None));
+        type_text(&mut ui, "second");
+        assert_eq!(ui.input_buffer, "first\nsecond");
+
+        // Home and End stay on the current line
+        press(&mut ui, KeyCode::Home);
+        assert_eq!(ui.input_cursor, "first\n".len());
+        assert_eq!(TerminalUI::cursor_position(&ui.input_buffer, ui.input_cursor), (1, 0));
+        press(&mut ui, KeyCode::Left);
+        press(&mut ui, KeyCode::Home);
+        assert_eq!(ui.input_cursor, 0);
+        press(&mut ui, KeyCode::End);
+        assert_eq!(TerminalUI::cursor_position(&ui.input_buffer, ui.input_cursor), (0, 5));
+
+        match press(&mut ui, KeyCode::Enter) {
+            UIAction::SendMessage(message) => assert_eq!(message, "first\nsecond"),
+            _ => panic!("Enter should send the message"),
+        }
+    }
+
+    #[test]
+    fn test_named_themes() {
+        assert_eq!(ThemeColors::named("default").primary, Color::Cyan);
+        assert_eq!(ThemeColors::named("solarized").primary, Color::Rgb(0x26, 0x8b, 0xd2));
+        assert_eq!(ThemeColors::named("monochrome").primary, Color::White);
+        // Unknown names fall back to the default theme
+        assert_eq!(ThemeColors::named("neon").primary, Color::Cyan);
+    }
+
+    #[test]
+    fn test_markdown_follows_theme() {
+        let theme = ThemeColors::solarized();
+        let renderer = MarkdownRenderer::with_theme(theme.clone());
+
+        let spans = renderer.render_to_spans("# Title\n\n- item `code`");
+        assert_eq!(spans[1].style.fg, Some(theme.primary));
+        let marker = spans.iter().find(|s| s.content == "• ").expect("list marker");
+        assert_eq!(marker.style.fg, Some(theme.accent));
+        let code = spans.iter().find(|s| s.content == "code").expect("inline code");
+        assert_eq!(code.style.bg, Some(theme.code_background));
+
+        let spans = renderer.render_to_spans("```rust\nfn main() {}\n```");
+        let keyword = spans.iter().find(|s| s.content == "fn").expect("keyword span");
+        assert_eq!(keyword.style.fg, Some(theme.highlight));
+    }
+
+    #[test]
+    fn test_render_bold_and_italic() {
+        let renderer = MarkdownRenderer::new();
+
+        let spans = renderer.render_to_spans("**hi**");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].content, "hi");
+        assert!(spans[0].style.add_modifier.contains(Modifier::BOLD));
+
+        // Bold nested inside italic carries both modifiers
+        let spans = renderer.render_to_spans("*a **b** c*");
+        let styles: Vec<(&str, Modifier)> = spans.iter().map(|s| (s.content.as_ref(), s.style.add_modifier)).collect();
+        assert_eq!(
+            styles,
+            vec![
+                ("a ", Modifier::ITALIC),
+                ("b", Modifier::ITALIC | Modifier::BOLD),
+                (" c", Modifier::ITALIC),
+            ]
+        );
+
+        let spans = renderer.render_to_spans("plain");
+        assert!(spans[0].style.add_modifier.is_empty());
+    }
+
+    fn contents(spans: &[Span<'_>]) -> Vec<String> {
+        spans.iter().map(|span| span.content.to_string()).collect()
+    }
+
+    #[test]
+    fn test_render_lists_with_markers() {
+        let renderer = MarkdownRenderer::new();
+
+        let spans = renderer.render_to_spans("- one\n- two");
+        assert_eq!(contents(&spans), vec!["• ", "one", "\n", "• ", "two"]);
+
+        let spans = renderer.render_to_spans("3. three\n4. four\n   - nested");
+        assert_eq!(
+            contents(&spans),
+            vec!["3. ", "three", "\n", "4. ", "four", "\n", "  • ", "nested"]
+        );
+
+        // Loose items keep their text on the marker's line
+        let spans = renderer.render_to_spans("- one\n\n- two");
+        assert_eq!(contents(&spans), vec!["• ", "one", "\n", "• ", "two"]);
+    }
+
+    #[test]
+    fn test_render_headings_and_blockquotes() {
+        let renderer = MarkdownRenderer::new();
+
+        let spans = renderer.render_to_spans("# Title\nbody");
+        assert_eq!(contents(&spans), vec!["# ", "Title", "\n", "body"]);
+        assert_eq!(spans[1].style.fg, Some(Color::Cyan));
+        assert!(spans[1].style.add_modifier.contains(Modifier::BOLD));
+        assert!(spans[3].style.add_modifier.is_empty());
+
+        let spans = renderer.render_to_spans("> quoted");
+        assert_eq!(contents(&spans), vec!["│ ", "quoted"]);
+        assert!(spans[1].style.add_modifier.contains(Modifier::DIM));
+    }
+
+    fn is_keyword(span: &Span<'_>) -> bool {
+        span.style.fg == Some(Color::Magenta) && span.style.add_modifier.contains(Modifier::BOLD)
+    }
+
+    #[test]
+    fn test_go_keywords_are_highlighted() {
+        let renderer = MarkdownRenderer::new();
+        let spans = renderer.render_to_spans("```go\npackage main\n\nfunc main() {\n\tfmt.Println(\"hi\", 42)\n}\n```");
+
+        let keywords: Vec<&str> = spans.iter().filter(|s| is_keyword(s)).map(|s| s.content.as_ref()).collect();
+        assert_eq!(keywords, vec!["package", "func"]);
+
+        let string = spans.iter().find(|s| s.content == "\"hi\"").expect("string literal span");
+        assert_eq!(string.style.fg, Some(Color::Green));
+        let number = spans.iter().find(|s| s.content == "42").expect("number span");
+        assert_eq!(number.style.fg, Some(Color::Yellow));
+    }
+
+    #[test]
+    fn test_other_languages_are_highlighted() {
+        let renderer = MarkdownRenderer::new();
+        let cases = [
+            ("ts", "export const x = `a b`;", "export"),
+            ("cpp", "static int count = 0;", "static"),
+            ("json", "{\"ok\": true, \"n\": 1.5}", "true"),
+            ("sh", "if [ -f x ]; then echo 'a b'; fi", "then"),
+        ];
+        for (language, code, keyword) in cases {
+            let spans = renderer.render_to_spans(&format!("```{}\n{}\n```", language, code));
+            assert!(spans.iter().any(|s| s.content == keyword && is_keyword(s)), "{} keyword in {}", keyword, language);
+        }
+
+        // Strings close on the quote that opened them
+        let spans = renderer.render_to_spans("```sh\necho \"it's\"\n```");
+        assert!(spans.iter().any(|s| s.content == "\"it's\""));
+    }
+
+    #[test]
+    fn test_slash_commands() {
+        assert!(matches!(parse_command("/model llama3"), UIAction::ChangeModel(name) if name == "llama3"));
+        assert!(matches!(parse_command("/template  code-review "), UIAction::LoadTemplate(name) if name == "code-review"));
+        assert!(matches!(parse_command("/save chat.txt"), UIAction::SaveHistory(path) if path == Path::new("chat.txt")));
+        assert!(matches!(parse_command("/clear"), UIAction::ClearHistory));
+        assert!(matches!(parse_command("/quit"), UIAction::Quit));
+        assert!(matches!(parse_command("/model"), UIAction::CommandError(_)));
+        assert!(matches!(parse_command("/bogus"), UIAction::CommandError(_)));
+
+        // Enter dispatches the command and clears the input
+        let mut ui = TerminalUI::headless().unwrap();
+        type_text(&mut ui, "/model llama3");
+        assert!(matches!(press(&mut ui, KeyCode::Enter), UIAction::ChangeModel(name) if name == "llama3"));
+        assert!(ui.input_buffer.is_empty());
+
+        // A doubled slash sends the text as a message
+        type_text(&mut ui, "//etc is a path");
+        assert!(matches!(press(&mut ui, KeyCode::Enter), UIAction::SendMessage(text) if text == "/etc is a path"));
+    }
+
+    #[test]
+    fn test_command_suggestions() {
+        assert_eq!(command_suggestions("/").len(), SLASH_COMMANDS.len());
+        assert_eq!(command_suggestions("/mo"), vec![SLASH_COMMANDS[0]]);
+        assert!(command_suggestions("/model llama3").is_empty());
+        assert!(command_suggestions("hello").is_empty());
+    }
+
+    #[test]
+    fn test_save_command_writes_transcript() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.md");
+        let (sender, _receiver) = mpsc::unbounded_channel();
+
+        let mut ui = TerminalUI::headless().unwrap();
+        ui.add_message(message("hello there"));
+        assert!(ui.apply_action(UIAction::SaveHistory(path.clone()), &sender));
+
+        let transcript = std::fs::read_to_string(&path).unwrap();
+        assert!(transcript.contains("## User"));
+        assert!(transcript.contains("hello there"));
+        assert!(ui.message_history().last().unwrap().content.starts_with("Saved 1 messages"));
+    }
+
+    #[test]
+    fn test_export_markdown() {
+        let mut answer = message("Use this:\n\n```rust\nfn main() {}\n```");
+        answer.role = MessageRole::Assistant;
+        answer.template_used = Some("code-review".to_string());
+        answer.cached = true;
+        let markdown = export_markdown(&[message("How do I start?"), answer]);
+
+        assert!(markdown.contains("## User\n"));
+        assert!(markdown.contains("How do I start?"));
+        assert!(markdown.contains("## Assistant\n"));
+        assert!(markdown.contains("```rust\nfn main() {}\n```"));
+        assert!(markdown.contains("model: test · template: code-review · cached"));
+        assert!(markdown.find("## User") < markdown.find("## Assistant"));
+    }
+
+    #[test]
+    fn test_model_and_template_commands_apply_to_prompts() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut ui = TerminalUI::headless().unwrap();
+
+        ui.apply_action(UIAction::SendMessage("first".to_string()), &sender);
+        let prompt = receiver.try_recv().unwrap();
+        assert_eq!(prompt.model, None);
+        assert_eq!(prompt.template, None);
+        ui.app_state.is_streaming = false;
+
+        ui.apply_action(parse_command("/model codellama"), &sender);
+        ui.apply_action(parse_command("/template code-review"), &sender);
+        ui.apply_action(UIAction::SendMessage("second".to_string()), &sender);
+        let prompt = receiver.try_recv().unwrap();
+        assert_eq!(prompt.model.as_deref(), Some("codellama"));
+        assert_eq!(prompt.template.as_deref(), Some("code-review"));
+        assert_eq!(ui.app_state.current_model, "codellama");
+    }
+
+    #[test]
+    fn test_retry_replaces_last_answer() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut ui = TerminalUI::headless().unwrap();
+        let mut answer = message("first answer");
+        answer.role = MessageRole::Assistant;
+        ui.add_message(message("question"));
+        ui.add_message(answer);
+
+        assert!(ui.apply_action(parse_command("/retry mistral"), &sender));
+
+        let prompt = receiver.try_recv().unwrap();
+        assert_eq!(prompt.model.as_deref(), Some("mistral"));
+        assert_eq!(prompt.messages.len(), 1);
+        assert_eq!(prompt.messages[0].content, "question");
+        assert_eq!(ui.message_history().len(), 1);
+
+        ui.update_streaming_content(StreamToken {
+            content: "second answer".to_string(),
+            is_complete: true,
+            kind: TokenKind::Answer,
+            metadata: None,
+        });
+        let contents: Vec<&str> = ui.message_history().iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["question", "second answer"]);
+    }
+
+    #[test]
+    fn test_resume_command_restores_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut saved = Session::new("codellama");
+        saved.messages = vec![message("earlier question")];
+        saved.save(dir.path()).unwrap();
+        let (sender, _receiver) = mpsc::unbounded_channel();
+
+        let mut ui = TerminalUI::headless().unwrap();
+        ui.sessions_dir = dir.path().to_path_buf();
+        ui.add_message(message("current chat"));
+        assert!(ui.apply_action(parse_command(&format!("/resume {}", saved.id)), &sender));
+
+        assert_eq!(ui.app_state.current_model, "codellama");
+        assert_eq!(ui.message_history()[0].content, "earlier question");
+        assert_eq!(ui.session().id, saved.id);
+        // The chat that was open is saved rather than lost
+        assert_eq!(Session::list(dir.path()).unwrap().len(), 2);
+    }
+}